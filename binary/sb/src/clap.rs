@@ -1,3 +1,4 @@
+// just sbk
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
@@ -6,15 +7,101 @@ use clap::{Parser, Subcommand};
 pub struct ClapArgs {
     #[arg(short, long, env("SB_WORKSPACE"))]
     pub workspace: Option<String>,
+    /// Path of the sbk binary
+    #[arg(long, env("SB_SBK_PATH"), default_value = "sbk")]
+    pub sbk: PathBuf,
+    /// Path of the sbc binary
+    #[arg(long, env("SB_SBC_PATH"), default_value = "sbc")]
+    pub sbc: PathBuf,
     #[command(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    Config { config: PathBuf },
+    Config {
+        config: PathBuf,
+    },
     Start,
-    Reload { config: Option<PathBuf> },
+    Reload {
+        config: Option<PathBuf>,
+    },
     Shutdown,
-    Status,
+    Status {
+        /// Keep printing state updates until the kernel stops
+        #[arg(short, long)]
+        watch: bool,
+    },
+    /// Build the flows and TLS of a service config without applying it
+    Validate {
+        config: PathBuf,
+    },
+    /// Show what a service config would change on the running kernel
+    Diff {
+        config: PathBuf,
+    },
+    /// List the service configs the kernel persisted, newest first
+    Saved,
+    /// Apply a persisted service config again, by default the one before the running config
+    Rollback {
+        version: Option<String>,
+    },
+    /// Plugins of the running kernel
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommands,
+    },
+    /// Http node and filter classes of the running kernel
+    Class {
+        #[command(subcommand)]
+        command: ClassCommands,
+    },
+    /// Controller tools, run through sbc
+    Controller {
+        #[command(subcommand)]
+        command: ControllerCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ClassCommands {
+    /// List the classes, plugin classes included
+    List,
+    /// Print the JSON Schema of the config of a class
+    Schema {
+        /// e.g. `std.reverse-proxy`
+        id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PluginCommands {
+    /// List the loaded plugins and their classes
+    List,
+    /// Load a plugin into the kernel, replacing the plugin of the same name. Flows use its
+    /// classes from the next config update
+    Push {
+        name: String,
+        /// A shared library, or a `.wasm` component
+        artifact: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ControllerCommands {
+    Storage {
+        #[command(subcommand)]
+        command: StorageCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StorageCommands {
+    /// Copy every object from the storage of one controller config to another's
+    Migrate {
+        #[arg(long)]
+        from: PathBuf,
+        #[arg(long)]
+        to: PathBuf,
+    },
 }
@@ -1,21 +1,65 @@
 use std::{path::PathBuf, process::ExitStatus};
 
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use switchboard_file_resolver::FileResolver;
+use switchboard_kernel_control::kernel::{
+    GetCurrentConfigRequest, GetCurrentStateRequest, GetKernelInfoRequest, InstanceType,
+    ListClassesRequest, ListPluginsRequest, ListSavedConfigsRequest, PluginKind, PushPluginRequest,
+    RestoreSavedConfigRequest, ShutdownRequest, WatchStatusRequest,
+    kernel_service_client::KernelServiceClient, list_saved_configs_response, push_plugin_response,
+    restore_saved_config_response, shutdown_response,
+};
+use switchboard_link_or_value::LinkOrValue;
+use switchboard_model::{
+    ServiceConfig,
+    diff::ConfigDiff,
+    kernel::{KernelInfo, KernelState, UDS_DEFAULT_PATH},
+};
 use tokio::fs;
 
-use crate::{Context, clap::Commands};
+use crate::{
+    Context,
+    clap::{ClassCommands, Commands, ControllerCommands, PluginCommands, StorageCommands},
+};
+
+const SHUTDOWN_REASON: &str = "requested by sb shutdown";
 
 impl Context {
     pub async fn exec(&self, commands: Commands) -> crate::Result<ExitStatus> {
         match commands {
             Commands::Config { config } => self.config(Some(config)).await,
             Commands::Reload { config } => self.reload(config).await,
-            Commands::Shutdown => self.kill().await,
+            Commands::Shutdown => self.shutdown().await,
             Commands::Start => self.start().await,
-            _ => return Err(crate::Error::Unimplemented),
+            Commands::Status { watch } => self.status(watch).await,
+            Commands::Validate { config } => self.validate(config).await,
+            Commands::Diff { config } => self.diff(config).await,
+            Commands::Saved => self.saved().await,
+            Commands::Rollback { version } => self.rollback(version).await,
+            Commands::Plugin {
+                command: PluginCommands::List,
+            } => self.list_plugins().await,
+            Commands::Plugin {
+                command: PluginCommands::Push { name, artifact },
+            } => self.push_plugin(name, artifact).await,
+            Commands::Class {
+                command: ClassCommands::List,
+            } => self.list_classes().await,
+            Commands::Class {
+                command: ClassCommands::Schema { id },
+            } => self.class_schema(id).await,
+            Commands::Controller {
+                command:
+                    ControllerCommands::Storage {
+                        command: StorageCommands::Migrate { from, to },
+                    },
+            } => self.migrate_storage(from, to).await,
         }
     }
     async fn start(&self) -> crate::Result<ExitStatus> {
         let task = tokio::process::Command::new(&self.sbk_path)
+            .arg(self.workspace.kernel_config_path())
             .kill_on_drop(false)
             .spawn()?;
         if let Some(pid) = task.id() {
@@ -27,13 +71,209 @@ impl Context {
         self.reset_service_config(config_path).await?;
         Ok(ExitStatus::default())
     }
-    async fn kill(&self) -> crate::Result<ExitStatus> {
-        let pid = self.read_pid().await?;
-        let mut kill_task = tokio::process::Command::new("kill")
-            .arg("-9")
-            .arg(pid)
-            .spawn()?;
-        let status = kill_task.wait().await?;
+    async fn status(&self, watch: bool) -> crate::Result<ExitStatus> {
+        let mut client = self.connect_kernel().await?;
+        let info = client.get_kernel_info(GetKernelInfoRequest {}).await?;
+        print_info(&KernelInfo::from(info.into_inner()));
+        let state = client.get_current_state(GetCurrentStateRequest {}).await?;
+        print_state(&KernelState::try_from(state.into_inner())?);
+        if watch {
+            let mut stream = client
+                .watch_status(WatchStatusRequest {})
+                .await?
+                .into_inner();
+            // the kernel ends the stream once it is stopped
+            while let Some(state) = stream.next().await {
+                print_state(&KernelState::try_from(state?)?);
+            }
+        }
+        Ok(ExitStatus::default())
+    }
+    async fn shutdown(&self) -> crate::Result<ExitStatus> {
+        let mut client = self.connect_kernel().await?;
+        let response = client
+            .shutdown(ShutdownRequest {
+                reason: SHUTDOWN_REASON.to_string(),
+            })
+            .await?
+            .into_inner();
+        match response.result {
+            Some(shutdown_response::Result::Success(_)) => Ok(ExitStatus::default()),
+            Some(shutdown_response::Result::Error(error_stack)) => {
+                Err(crate::Error::Kernel(error_stack.into()))
+            }
+            None => Err(crate::Error::Grpc(tonic::Status::internal(
+                "Kernel returned empty result on shutdown",
+            ))),
+        }
+    }
+    async fn validate(&self, config_path: PathBuf) -> crate::Result<ExitStatus> {
+        // sbk registers every provider and plugin, so it can build the config exactly as it would run
+        let status = tokio::process::Command::new(&self.sbk_path)
+            .arg(self.workspace.kernel_config_path())
+            .arg("--validate")
+            .arg(config_path)
+            .status()
+            .await?;
+        Ok(status)
+    }
+    async fn diff(&self, config_path: PathBuf) -> crate::Result<ExitStatus> {
+        let candidate = switchboard_model::resolve::file_style::fetch_config(
+            LinkOrValue::Link(config_path),
+            &FileResolver,
+        )
+        .await?;
+        let mut client = self.connect_kernel().await?;
+        let current = client
+            .get_current_config(GetCurrentConfigRequest {})
+            .await?
+            .into_inner();
+        let (current, _): (ServiceConfig, _) =
+            bincode::decode_from_slice(&current.config, bincode::config::standard())?;
+        println!("base:    {}", current.digest_sha256_base64());
+        println!("target:  {}", candidate.digest_sha256_base64());
+        let diff = ConfigDiff::between(&current, &candidate);
+        if diff.is_empty() {
+            println!("no changes");
+        } else {
+            print!("{diff}");
+        }
+        Ok(ExitStatus::default())
+    }
+    async fn saved(&self) -> crate::Result<ExitStatus> {
+        let mut client = self.connect_kernel().await?;
+        let response = client
+            .list_saved_configs(ListSavedConfigsRequest {})
+            .await?
+            .into_inner();
+        match response.result {
+            Some(list_saved_configs_response::Result::Success(saved)) => {
+                for config in saved.configs {
+                    println!("{}  {}", config.version, config.saved_at);
+                }
+                Ok(ExitStatus::default())
+            }
+            Some(list_saved_configs_response::Result::Error(error_stack)) => {
+                Err(crate::Error::Kernel(error_stack.into()))
+            }
+            None => Err(crate::Error::Grpc(tonic::Status::internal(
+                "Kernel returned empty result on list saved configs",
+            ))),
+        }
+    }
+    async fn rollback(&self, version: Option<String>) -> crate::Result<ExitStatus> {
+        let mut client = self.connect_kernel().await?;
+        let response = client
+            .restore_saved_config(RestoreSavedConfigRequest { version })
+            .await?
+            .into_inner();
+        match response.result {
+            Some(restore_saved_config_response::Result::Success(version)) => {
+                println!("restored: {version}");
+                Ok(ExitStatus::default())
+            }
+            Some(restore_saved_config_response::Result::Error(error_stack)) => {
+                Err(crate::Error::Kernel(error_stack.into()))
+            }
+            None => Err(crate::Error::Grpc(tonic::Status::internal(
+                "Kernel returned empty result on rollback",
+            ))),
+        }
+    }
+    async fn list_plugins(&self) -> crate::Result<ExitStatus> {
+        let mut client = self.connect_kernel().await?;
+        let plugins = client
+            .list_plugins(ListPluginsRequest {})
+            .await?
+            .into_inner();
+        println!("generation: {}", plugins.generation);
+        for plugin in plugins.plugins {
+            println!(
+                "{}  {}  (generation {})",
+                plugin.name, plugin.path, plugin.generation
+            );
+            for class in plugin.classes {
+                println!("  {class}");
+            }
+        }
+        Ok(ExitStatus::default())
+    }
+    async fn list_classes(&self) -> crate::Result<ExitStatus> {
+        let mut client = self.connect_kernel().await?;
+        let classes = client
+            .list_classes(ListClassesRequest {})
+            .await?
+            .into_inner();
+        println!("generation: {}", classes.generation);
+        for class in classes.classes {
+            let kind = match class.instance_type() {
+                InstanceType::Node => "node",
+                InstanceType::Filter => "filter",
+            };
+            println!("{}  {kind}  {}", class.id, class.version);
+        }
+        Ok(ExitStatus::default())
+    }
+    async fn class_schema(&self, id: String) -> crate::Result<ExitStatus> {
+        let mut client = self.connect_kernel().await?;
+        let classes = client
+            .list_classes(ListClassesRequest {})
+            .await?
+            .into_inner();
+        let class = classes
+            .classes
+            .into_iter()
+            .find(|class| class.id == id)
+            .ok_or(crate::Error::ClassNotFound(id))?;
+        println!("{}", class.config_schema);
+        Ok(ExitStatus::default())
+    }
+    async fn push_plugin(&self, name: String, path: PathBuf) -> crate::Result<ExitStatus> {
+        let artifact = fs::read(&path).await?;
+        let kind = if path.extension().is_some_and(|ext| ext == "wasm") {
+            PluginKind::Wasm
+        } else {
+            PluginKind::Native
+        };
+        let sha256 = Sha256::digest(&artifact)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let mut client = self.connect_kernel().await?;
+        let response = client
+            .push_plugin(PushPluginRequest {
+                name,
+                kind: kind.into(),
+                artifact,
+                sha256,
+            })
+            .await?
+            .into_inner();
+        match response.result {
+            Some(push_plugin_response::Result::Success(plugin)) => {
+                println!("loaded: {} (generation {})", plugin.name, plugin.generation);
+                for class in plugin.classes {
+                    println!("  {class}");
+                }
+                Ok(ExitStatus::default())
+            }
+            Some(push_plugin_response::Result::Error(error_stack)) => {
+                Err(crate::Error::Kernel(error_stack.into()))
+            }
+            None => Err(crate::Error::Grpc(tonic::Status::internal(
+                "Kernel returned empty result on plugin push",
+            ))),
+        }
+    }
+    async fn migrate_storage(&self, from: PathBuf, to: PathBuf) -> crate::Result<ExitStatus> {
+        // storage backends live in the controller, so sbc does the copying
+        let status = tokio::process::Command::new(&self.sbc_path)
+            .args(["storage", "migrate", "--from"])
+            .arg(from)
+            .arg("--to")
+            .arg(to)
+            .status()
+            .await?;
         Ok(status)
     }
     async fn reload(&self, config_path: Option<PathBuf>) -> crate::Result<ExitStatus> {
@@ -72,4 +312,50 @@ impl Context {
         let pid = fs::read_to_string(self.workspace.pid_file()).await?;
         Ok(pid)
     }
+    /// Socket path from `controller.listen.uds` of the workspace kernel config.
+    async fn kernel_socket_path(&self) -> crate::Result<PathBuf> {
+        let file = fs::read_to_string(self.workspace.kernel_config_path()).await?;
+        let toml_ast = toml_edit::Document::parse(file)?.into_mut();
+        let uds = toml_ast
+            .get("controller")
+            .and_then(|controller| controller.get("listen"))
+            .and_then(|listen| listen.get("uds"))
+            .ok_or(crate::Error::UdsListenerNotConfigured)?;
+        let path = uds
+            .get("path")
+            .and_then(|path| path.as_str())
+            .unwrap_or(UDS_DEFAULT_PATH);
+        Ok(PathBuf::from(path))
+    }
+    async fn connect_kernel(
+        &self,
+    ) -> crate::Result<KernelServiceClient<tonic::transport::Channel>> {
+        let path = self.kernel_socket_path().await?;
+        let channel = switchboard_kernel_control::uds::connect_uds(path).await?;
+        Ok(KernelServiceClient::new(channel))
+    }
+}
+
+fn print_info(info: &KernelInfo) {
+    println!("kernel:  {} ({})", info.name, info.id);
+    if let Some(description) = &info.description {
+        println!("         {description}");
+    }
+    println!("version: {} (build {})", info.meta.version, info.meta.build);
+    if !info.labels.is_empty() {
+        let labels = info
+            .labels
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>();
+        println!("labels:  {}", labels.join(","));
+    }
+}
+
+fn print_state(state: &KernelState) {
+    println!(
+        "state:   {:?} since {}",
+        state.kind,
+        state.since.to_rfc3339()
+    );
 }
@@ -1,3 +1,4 @@
+use switchboard_model::error::ErrorStack;
 use tokio::io;
 
 #[derive(thiserror::Error, Debug)]
@@ -7,6 +8,30 @@ pub enum Error {
     
     #[error("TOML error")]
     Toml(#[from] toml_edit::TomlError),
+
+    #[error("Kernel connection error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+
+    #[error("Kernel request error: {0}")]
+    Grpc(#[from] tonic::Status),
+
+    #[error("Kernel state parse error: {0}")]
+    StateParse(#[from] switchboard_kernel_control::TryFromProtoKernelStateError),
+
+    #[error("Kernel error: {0}")]
+    Kernel(ErrorStack),
+
+    #[error("Config file error: {0}")]
+    ConfigFile(#[from] switchboard_model::resolve::file_style::ResolveConfigFileError),
+
+    #[error("Kernel config decode error: {0}")]
+    ConfigDecode(#[from] bincode::error::DecodeError),
+
+    #[error("Kernel uds listener is not configured, add [controller.listen.uds] to kernel config")]
+    UdsListenerNotConfigured,
+
+    #[error("Class `{0}` not found")]
+    ClassNotFound(String),
     
     #[error("Unimplemented")]
     Unimplemented
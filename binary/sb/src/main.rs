@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::ExitCode};
 
 use ::clap::Parser;
 
@@ -14,13 +14,28 @@ pub use error::Result;
 
 pub struct Context {
     sbk_path: PathBuf,
+    sbc_path: PathBuf,
     workspace: Workspace,
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let args = clap::ClapArgs::parse();
-    
-    println!("{args:?}")
+    let workspace_dir = args
+        .workspace
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let context = Context {
+        sbk_path: args.sbk,
+        sbc_path: args.sbc,
+        workspace: Workspace::new(workspace_dir),
+    };
+    match context.exec(args.command).await {
+        Ok(status) if status.success() => ExitCode::SUCCESS,
+        Ok(status) => ExitCode::from(status.code().unwrap_or(1) as u8),
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
 }
-// just sbk
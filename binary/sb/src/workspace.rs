@@ -1,6 +1,5 @@
 use std::path::PathBuf;
 
-use tokio::fs::ReadDir;
 // sb workspace organization
 // 
 // 
@@ -11,6 +10,10 @@ pub struct Workspace {
 
 
 impl Workspace {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
     pub fn kernel_config_path(&self) -> PathBuf {
         self.dir.join("kernel.toml")
     }
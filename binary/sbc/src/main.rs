@@ -1,7 +1,11 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
-use switchboard_controller::{config::ControllerConfig, run::RunMode};
+use switchboard_controller::{
+    config::ControllerConfig,
+    run::RunMode,
+    storage::{create_storage, migrate},
+};
 
 #[derive(clap::Parser)]
 pub struct CliArgs {
@@ -13,6 +17,22 @@ pub struct CliArgs {
 #[derive(clap::Subcommand)]
 pub enum CliSubCommand {
     Start(CliSubCommandConfig),
+    /// Manage the controller storage
+    #[clap(subcommand)]
+    Storage(CliStorageCommand),
+}
+
+#[derive(clap::Subcommand)]
+pub enum CliStorageCommand {
+    /// Copy every object from the storage of one controller config to another's
+    Migrate {
+        /// Controller config whose `[storage]` is read
+        #[clap(long)]
+        from: PathBuf,
+        /// Controller config whose `[storage]` is written
+        #[clap(long)]
+        to: PathBuf,
+    },
 }
 
 #[derive(clap::Args)]
@@ -26,37 +46,48 @@ pub struct CliSubCommandConfig {
     k8s: bool,
 }
 
-pub async fn retrieve_controller_config(
-    args: &CliArgs,
+pub async fn read_controller_config(
+    path: &Path,
 ) -> Result<ControllerConfig, Box<dyn std::error::Error>> {
-    match &args.command {
-        CliSubCommand::Start(cmd) => {
-            let path = &cmd.config;
-            let config_str = tokio::fs::read_to_string(path).await?;
-            let config: ControllerConfig = toml::from_str(&config_str)?;
-            Ok(config)
-        }
-    }
+    let config_str = tokio::fs::read_to_string(path).await?;
+    let config: ControllerConfig = toml::from_str(&config_str)?;
+    Ok(config)
 }
-pub fn is_in_k8s(args: &CliArgs) -> bool {
-    match &args.command {
-        CliSubCommand::Start(cmd) => cmd.k8s,
+
+async fn migrate_storage(from: &Path, to: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let from = read_controller_config(from).await?.storage;
+    let to = read_controller_config(to).await?.storage;
+    if from == to {
+        return Err("source and target storage are the same".into());
     }
+    let from = create_storage(&from).await?;
+    let to = create_storage(&to).await?;
+    let report = migrate::migrate_storage(from.as_ref(), to.as_ref()).await?;
+    println!("copied:  {}", report.copied);
+    println!("skipped: {}", report.skipped);
+    Ok(())
 }
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::DEBUG)
         .init();
     let args = CliArgs::parse();
-    let controller_config = retrieve_controller_config(&args).await?;
+    let cmd = match args.command {
+        CliSubCommand::Start(cmd) => cmd,
+        CliSubCommand::Storage(CliStorageCommand::Migrate { from, to }) => {
+            return migrate_storage(&from, &to).await;
+        }
+    };
+    let controller_config = read_controller_config(&cmd.config).await?;
     // fs load switchboard config
     tracing::debug!("Controller config: {:?}", controller_config);
     // let sb_config = {
     //     let path = &controller_config.resolve.fs.path;
     // };
     let context = switchboard_controller::ControllerContext::new(controller_config).await?;
-    let run_mode = if is_in_k8s(&args) {
+    let run_mode = if cmd.k8s {
         RunMode::K8s
     } else {
         RunMode::Standalone
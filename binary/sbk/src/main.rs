@@ -1,20 +1,59 @@
 use std::path::PathBuf;
+mod plugin;
 mod register;
 use clap::Parser;
-use switchboard_kernel::{KernelContext, config::KernelConfig};
+use std::process::ExitCode;
+use switchboard_kernel::{KernelContext, config::KernelConfig, model::error::ErrorStack};
 
 #[derive(clap::Parser)]
 pub struct CliArgs {
     config: PathBuf,
+    /// Build the given service config without applying it, then exit.
+    #[arg(long)]
+    validate: Option<PathBuf>,
+    /// Write the http classes with the JSON Schemas of their configs to the given file, then exit.
+    #[arg(long)]
+    dump_class_schemas: Option<PathBuf>,
 }
 
-pub async fn retrieve_kernel_config() -> Result<KernelConfig, Box<dyn std::error::Error>> {
-    let args = CliArgs::parse();
-    let path = args.config;
+pub async fn retrieve_kernel_config(
+    path: &PathBuf,
+) -> Result<KernelConfig, Box<dyn std::error::Error>> {
     let config_str = tokio::fs::read_to_string(path).await?;
     let config: KernelConfig = toml::from_str(&config_str)?;
     Ok(config)
 }
+
+pub async fn validate_service_config(context: &KernelContext, path: PathBuf) -> ExitCode {
+    let config = match context.fetch_config_from_file(path).await {
+        Ok(config) => config,
+        Err(e) => {
+            eprint!("{}", ErrorStack::from_std(e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let report = context.validate_config(&config).await;
+    eprint!("{report}");
+    if report.is_valid() {
+        println!("config is valid");
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+pub async fn dump_class_schemas(
+    context: &KernelContext,
+    path: PathBuf,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let classes = context.list_classes().await;
+    tokio::fs::write(&path, serde_json::to_vec_pretty(&classes)?).await?;
+    println!(
+        "{} class schemas written to {}",
+        classes.classes.len(),
+        path.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
 #[cfg(unix)]
 pub async fn listen_reload_config_signal(
     context: KernelContext,
@@ -23,18 +62,21 @@ pub async fn listen_reload_config_signal(
     loop {
         reload_signal.recv().await;
         tracing::info!("SIGHUP received, reloading kernel config",);
-        if let Some(config) = context.fetch_config_locally().await? {
-            context.update_config(config).await?;
-        }
+        context.reload_config_locally().await?;
     }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt()
-        .with_env_filter("debug,switchboard-http=trace")
-        .init();
-    let kernel_config = retrieve_kernel_config().await?;
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let args = CliArgs::parse();
+    if args.validate.is_some() || args.dump_class_schemas.is_some() {
+        tracing_subscriber::fmt().with_env_filter("warn").init();
+    } else {
+        tracing_subscriber::fmt()
+            .with_env_filter("debug,switchboard-http=trace")
+            .init();
+    }
+    let kernel_config = retrieve_kernel_config(&args.config).await?;
 
     tracing::debug!("Starting kernel with config: {:?}", kernel_config);
     let context = KernelContext::new(kernel_config);
@@ -51,14 +93,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         });
     }
     register::register_prelude(&context).await;
+    if let Some(path) = args.validate {
+        return Ok(validate_service_config(&context, path).await);
+    }
+    if let Some(path) = args.dump_class_schemas {
+        return dump_class_schemas(&context, path).await;
+    }
     tracing::info!("Kernel starting up...");
     context.startup().await?;
     tracing::info!("Kernel startup complete");
     tracing::info!("Kernel running, press Ctrl+C to exit");
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to install Ctrl+C signal handler");
-    tracing::info!("Ctrl+C signal received, shutting down...");
+    tokio::select! {
+        signal = tokio::signal::ctrl_c() => {
+            signal.expect("failed to install Ctrl+C signal handler");
+            tracing::info!("Ctrl+C signal received, shutting down...");
+        }
+        _ = context.shutdown_requested() => {
+            tracing::info!("Shutdown requested, shutting down...");
+        }
+    }
     context.shutdown().await;
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
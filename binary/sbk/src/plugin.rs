@@ -0,0 +1,113 @@
+//! Loads plugins pushed by the controller into new generations of the http class registry.
+
+use std::sync::{Arc, OnceLock};
+
+use futures::future::BoxFuture;
+use switchboard_http::instance::class::{
+    plugin::{LoadedPlugin, PluginLibrary},
+    registry::ClassRegistry,
+    wasm::{WasmLimits, WasmPluginError, WasmRuntime},
+};
+use switchboard_kernel::{
+    model::kernel::{ClassInfo, ClassList, PluginInfo, PluginKind, PluginList},
+    plugin::{PluginArtifact, PluginHost, PluginHostError},
+};
+use tokio::sync::RwLock;
+
+pub struct HttpPluginHost {
+    pub class_registry: Arc<RwLock<ClassRegistry>>,
+    pub wasm_limits: WasmLimits,
+    /// Set up on the first wasm plugin.
+    pub wasm_runtime: OnceLock<WasmRuntime>,
+}
+
+fn plugin_info(plugin: &LoadedPlugin) -> PluginInfo {
+    PluginInfo {
+        name: plugin.name.clone(),
+        path: plugin.path.to_string_lossy().into_owned(),
+        sha256: plugin.sha256.clone(),
+        generation: plugin.generation,
+        classes: plugin.classes.iter().map(ToString::to_string).collect(),
+    }
+}
+
+impl HttpPluginHost {
+    fn wasm_runtime(&self) -> Result<WasmRuntime, WasmPluginError> {
+        if let Some(runtime) = self.wasm_runtime.get() {
+            return Ok(runtime.clone());
+        }
+        let runtime = WasmRuntime::new(self.wasm_limits.clone())?;
+        Ok(self.wasm_runtime.get_or_init(|| runtime).clone())
+    }
+
+    async fn load_artifact(&self, artifact: PluginArtifact) -> Result<PluginInfo, PluginHostError> {
+        let plugin = LoadedPlugin::new(&artifact.name, &artifact.path).with_sha256(artifact.sha256);
+        let path = artifact.path;
+        // loading runs library initializers or compiles the component, keep it off the runtime
+        let generation = match artifact.kind {
+            PluginKind::Native => {
+                // a replaced plugin must be unloaded once unused, rust abi plugins can't be
+                let library =
+                    tokio::task::spawn_blocking(move || unsafe { PluginLibrary::load(path) })
+                        .await??
+                        .remove_on_unload()
+                        .require_unloadable()?;
+                ClassRegistry::next_generation(&self.class_registry, |registry| {
+                    registry.register_native_plugin(plugin, Arc::new(library))
+                })
+                .await?
+            }
+            PluginKind::Wasm => {
+                let runtime = self.wasm_runtime()?;
+                let wasm_plugin = tokio::task::spawn_blocking(move || runtime.load(path)).await??;
+                ClassRegistry::next_generation(&self.class_registry, |registry| {
+                    registry.register_named_wasm_plugin(plugin, Arc::new(wasm_plugin))
+                })
+                .await?
+            }
+        };
+        let class_registry = self.class_registry.read().await;
+        let loaded = class_registry
+            .plugins
+            .get(&artifact.name)
+            .map(plugin_info)
+            .ok_or("plugin missing from the class registry after loading")?;
+        tracing::info!(
+            plugin = %loaded.name,
+            generation,
+            classes = ?loaded.classes,
+            "loaded http plugin, flows pick it up on the next config commit"
+        );
+        Ok(loaded)
+    }
+}
+
+impl PluginHost for HttpPluginHost {
+    fn load(&self, artifact: PluginArtifact) -> BoxFuture<'_, Result<PluginInfo, PluginHostError>> {
+        Box::pin(self.load_artifact(artifact))
+    }
+    fn list(&self) -> BoxFuture<'_, PluginList> {
+        Box::pin(async move {
+            let class_registry = self.class_registry.read().await;
+            PluginList {
+                generation: class_registry.generation,
+                plugins: class_registry.plugins.values().map(plugin_info).collect(),
+            }
+        })
+    }
+    fn classes(&self) -> BoxFuture<'_, ClassList> {
+        Box::pin(async move {
+            let class_registry = self.class_registry.read().await;
+            let mut classes = class_registry
+                .class_data
+                .values()
+                .map(|class| ClassInfo::from(class.data.clone()))
+                .collect::<Vec<_>>();
+            classes.sort_by(|a, b| a.id.cmp(&b.id));
+            ClassList {
+                generation: class_registry.generation,
+                classes,
+            }
+        })
+    }
+}
@@ -1,18 +1,37 @@
-use std::{ffi::OsStr, path::PathBuf};
+use std::{
+    ffi::OsStr,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
 
-use switchboard_http::HttpProvider;
+use switchboard_http::{
+    HttpProvider,
+    instance::class::{
+        plugin::PluginLibrary,
+        registry::ClassRegistry,
+        wasm::{WasmLimits, WasmRuntime},
+    },
+};
 use switchboard_kernel::KernelContext;
 use switchboard_pf::PortForwardProvider;
 use switchboard_socks5::Socks5Provider;
 use switchboard_tcp::TcpProvider;
 use switchboard_uds::UdsProvider;
 use switchboard_web_interface::WebInterfaceProvider;
+
+use crate::plugin::HttpPluginHost;
 #[cfg(target_os = "linux")]
 const LIB_EXT: &str = "so";
 #[cfg(target_os = "macos")]
 const LIB_EXT: &str = "dynlib";
 #[cfg(target_os = "windows")]
 const LIB_EXT: &str = "dll";
+const WASM_EXT: &str = "wasm";
+fn is_plugin_file(path: &std::path::Path) -> bool {
+    path.is_file()
+        && (path.extension() == Some(OsStr::new(LIB_EXT))
+            || path.extension() == Some(OsStr::new(WASM_EXT)))
+}
 pub async fn register_prelude(context: &KernelContext) {
     context.register_service(Socks5Provider).await;
     context
@@ -36,30 +55,122 @@ pub async fn register_prelude(context: &KernelContext) {
                 };
                 while let Ok(Some(file)) = dir.next_entry().await {
                     let file_path = file.path();
-                    if file_path.is_file() && file.path().extension() == Some(OsStr::new(LIB_EXT)) {
+                    if is_plugin_file(&file_path) {
                         tracing::debug!("collect http plugin lib {file_path:?}");
                         file_collection.push(file_path);
                     }
                 }
             } else {
                 let lib = PathBuf::from(lib);
-                if lib.is_file() && lib.extension() == Some(OsStr::new(LIB_EXT)) {
+                if is_plugin_file(&lib) {
                     tracing::debug!("collect http plugin lib {lib:?}");
                     file_collection.push(lib.clone());
                 }
             }
         }
+        let (wasm_files, file_collection): (Vec<_>, Vec<_>) = file_collection
+            .into_iter()
+            .partition(|path| path.extension() == Some(OsStr::new(WASM_EXT)));
+        // plugins which failed to load, the loaded ones are listed by the class registry
+        let mut failed_plugins = vec![];
         unsafe {
             for lib_path in file_collection {
-                if let Ok(lib) = libloading::Library::new(&lib_path).inspect_err(|e| {
-                    tracing::error!("fail to load dynamic lib http plugin {lib_path:?}: {e}")
-                }) {
-                    rust_dyn_libs.push(lib);
+                match PluginLibrary::load(&lib_path) {
+                    Ok(lib) => rust_dyn_libs.push(Arc::new(lib)),
+                    Err(e) => {
+                        tracing::error!("fail to load dynamic lib http plugin {lib_path:?}: {e}");
+                        failed_plugins.push(serde_json::json!({
+                            "path": lib_path,
+                            "loaded": false,
+                            "error": e.to_string(),
+                        }));
+                    }
+                }
+            }
+        }
+        let wasm_limits = match &context.kernel_config.provider.http.wasm {
+            Some(limits) => limits.clone().deserialize_into().unwrap_or_else(|e| {
+                tracing::error!("invalid http wasm plugin limits, using defaults: {e}");
+                WasmLimits::default()
+            }),
+            None => WasmLimits::default(),
+        };
+        let wasm_runtime = OnceLock::new();
+        let mut wasm_plugins = vec![];
+        if !wasm_files.is_empty() {
+            match WasmRuntime::new(wasm_limits.clone()) {
+                Ok(runtime) => {
+                    for wasm_path in wasm_files {
+                        match runtime.load(&wasm_path) {
+                            Ok(plugin) => wasm_plugins.push(plugin.into()),
+                            Err(e) => {
+                                tracing::error!("fail to load wasm http plugin {wasm_path:?}: {e}");
+                                failed_plugins.push(serde_json::json!({
+                                    "path": wasm_path,
+                                    "loaded": false,
+                                    "error": e.to_string(),
+                                }));
+                            }
+                        }
+                    }
+                    let _ = wasm_runtime.set(runtime);
                 }
+                Err(e) => tracing::error!("fail to set up wasm plugin runtime: {e}"),
             }
         }
+        let provider = HttpProvider {
+            rust_dyn_libs,
+            wasm_plugins,
+        };
+        // the registry is built once, with the plugins of the first provider asking for it
+        let class_registry = ClassRegistry::global(&provider);
+        #[cfg(feature = "script")]
+        switchboard_deno::script::register_script_classes(&mut *class_registry.write().await);
+        context.register_service(provider).await;
         context
-            .register_service(HttpProvider { rust_dyn_libs })
+            .set_plugin_host(HttpPluginHost {
+                class_registry: class_registry.clone(),
+                wasm_limits,
+                wasm_runtime,
+            })
+            .await;
+        context
+            .register_admin_section("http/classes", move || {
+                let class_registry = class_registry.clone();
+                async move {
+                    let class_registry = class_registry.read().await;
+                    let mut classes = class_registry
+                        .class_data
+                        .values()
+                        .map(|class| &class.data)
+                        .collect::<Vec<_>>();
+                    classes.sort_by_key(|class| class.id.to_string());
+                    serde_json::to_value(classes).unwrap_or_default()
+                }
+            })
+            .await;
+        let failed_plugins = Arc::new(failed_plugins);
+        let plugins_context = context.clone();
+        context
+            .register_admin_section("plugins", move || {
+                let context = plugins_context.clone();
+                let failed_plugins = failed_plugins.clone();
+                async move {
+                    let mut plugins = context
+                        .list_plugins()
+                        .await
+                        .plugins
+                        .into_iter()
+                        .map(|plugin| {
+                            let mut plugin = serde_json::to_value(plugin).unwrap_or_default();
+                            plugin["loaded"] = true.into();
+                            plugin
+                        })
+                        .collect::<Vec<_>>();
+                    plugins.extend(failed_plugins.iter().cloned());
+                    serde_json::Value::Array(plugins)
+                }
+            })
             .await;
     }
     context.register_service(UdsProvider).await;
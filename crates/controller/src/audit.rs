@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use switchboard_model::{Indexed, PageQuery, PagedList};
+
+use crate::{
+    ControllerContext,
+    storage::{KnownObject, ListObjectQuery, ObjectFilter, StorageError, StorageObjectDescriptor},
+};
+
+/// Storage id every audit record is saved under, each call is a new revision.
+pub const AUDIT_LOG_ID: &str = "audit_log";
+
+/// Ids only the controller itself writes, the storage api and `storage://` links refuse them so
+/// the audit log can't be read below admin or rewritten at all.
+pub const RESERVED_STORAGE_IDS: &[&str] = &[AUDIT_LOG_ID];
+
+pub fn is_reserved_storage_id(id: &str) -> bool {
+    RESERVED_STORAGE_IDS.contains(&id)
+}
+
+/// A mutating call to the controller api, recorded whether it was allowed or not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub at: DateTime<Utc>,
+    /// Authenticated caller, `None` when the api has no auth configured or the caller
+    /// failed to authenticate.
+    pub principal: Option<String>,
+    pub role: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+}
+
+impl KnownObject for AuditRecord {
+    fn data_type() -> &'static str {
+        "AuditRecord"
+    }
+}
+
+impl ControllerContext {
+    pub async fn record_audit(&self, record: AuditRecord) -> Option<StorageObjectDescriptor> {
+        match self.save_known_object(AUDIT_LOG_ID, record).await {
+            Ok(descriptor) => Some(descriptor),
+            Err(e) => {
+                tracing::warn!("Failed to record audit log: {}", e);
+                None
+            }
+        }
+    }
+
+    pub async fn list_audit_log(
+        &self,
+        page: PageQuery,
+    ) -> Result<PagedList<AuditRecord>, crate::Error> {
+        let list = self
+            .storage
+            .list_objects(ListObjectQuery {
+                filter: ObjectFilter {
+                    data_type: Some(AuditRecord::data_type().to_string()),
+                    id: Some(AUDIT_LOG_ID.to_string()),
+                    ..Default::default()
+                },
+                page,
+            })
+            .await?;
+        let mut items = Vec::with_capacity(list.items.len());
+        for item in list.items {
+            let Some(object) = self.storage.get_object(&item.data.descriptor).await? else {
+                continue;
+            };
+            let record = object
+                .data
+                .deserialize_into::<AuditRecord>()
+                .map_err(StorageError::DeserializationError)?;
+            items.push(Indexed::new(item.id, record));
+        }
+        Ok(PagedList {
+            items,
+            next_cursor: list.next_cursor,
+        })
+    }
+}
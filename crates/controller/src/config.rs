@@ -39,6 +39,9 @@ pub struct ControllerConfig {
     pub storage: StorageProvider,
     #[serde(default)]
     pub file_browser: FileBrowserConfig,
+    /// Elect a leader among controllers sharing kernels, every controller leads when unset.
+    #[serde(default)]
+    pub leader_election: Option<crate::leader::LeaderElectionConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
@@ -65,8 +68,82 @@ impl Default for FileBrowserConfig {
 pub struct KernelConfig {
     pub discovery: KernelDiscoveryConfig,
     // pub connect: KernelConnectConfig,
-    // default to be empty, meaning no authentication
-    // pub psk: Base64Bytes,
+    /// Default to be empty, meaning no authentication.
+    pub auth: KernelAuthConfig,
+    /// Accept kernels dialing in, for kernels the controller can't reach.
+    pub reverse: Option<KernelReverseConfig>,
+    /// Deployment groups, each selecting kernels by label and receiving its own config.
+    pub groups: Vec<crate::kernel::group::DeploymentGroupConfig>,
+}
+
+/// How the controller authenticates itself to kernels reached by `grpc://` addresses.
+/// # Example
+/// ```toml
+/// [kernel.auth.psk]
+/// id = "2025-01"
+/// key = "c2VjcmV0LWtleS1ieXRlcw=="
+/// [kernel.auth.tls]
+/// ca = "/etc/switchboard/kernel-ca.pem"
+/// cert = "/etc/switchboard/controller.pem"
+/// key = "/etc/switchboard/controller.key"
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct KernelAuthConfig {
+    pub psk: Option<KernelPskConfig>,
+    pub tls: Option<KernelTlsConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+pub struct KernelPskConfig {
+    pub id: String,
+    /// Base64 encoded key bytes.
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+pub struct KernelTlsConfig {
+    /// CA which signed the kernel certificates.
+    pub ca: PathBuf,
+    /// Client certificate chain and key, for kernels requiring mutual TLS.
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    /// Name to verify kernel certificates against, defaults to the host of the kernel address.
+    pub server_name: Option<String>,
+}
+
+/// Listener for kernels opening a reverse control channel, see `controller.reverse` in the
+/// kernel config. Kernels sign the tunnel request with their key in `psk`, or with
+/// `kernel.auth.psk` when there are none.
+/// # Example
+/// ```toml
+/// [kernel.reverse]
+/// bind = "0.0.0.0:9100"
+/// [kernel.reverse.tls]
+/// cert = "/etc/switchboard/controller.pem"
+/// key = "/etc/switchboard/controller.key"
+/// client_ca = "/etc/switchboard/kernel-ca.pem"
+/// [[kernel.reverse.psk]]
+/// id = "edge-1"
+/// key = "c2VjcmV0LWtleS1ieXRlcw=="
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+pub struct KernelReverseConfig {
+    pub bind: std::net::SocketAddr,
+    pub tls: Option<KernelReverseTlsConfig>,
+    /// Per kernel keys, the `id` of a key is the kernel id it connects as. The shared
+    /// `kernel.auth.psk` lets any kernel holding it connect under any id.
+    #[serde(default)]
+    pub psk: Vec<KernelPskConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+pub struct KernelReverseTlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Require kernels to present a client certificate signed by this CA, and issued for their
+    /// kernel id as a dns name.
+    pub client_ca: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
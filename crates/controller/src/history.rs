@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use switchboard_model::{
+    HumanReadableServiceConfig, Indexed, PageQuery, PagedList, ServiceConfig, diff::ConfigDiff,
+};
+
+use crate::{
+    ControllerContext,
+    kernel::KernelAddr,
+    link_resolver::Link,
+    storage::{KnownObject, ListObjectQuery, ObjectFilter, StorageError, StorageObjectDescriptor},
+};
+
+/// Storage id every applied config record is saved under, each apply is a new revision.
+pub const APPLIED_CONFIG_HISTORY_ID: &str = "applied_config_history";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AppliedConfigSource {
+    Update,
+    /// Applied by the kubernetes gateway reconciler.
+    Kubernetes,
+    Rollout {
+        rollout_id: String,
+    },
+    Rollback {
+        from_revision: String,
+    },
+}
+
+/// A config the controller applied to its kernels, with the outcome.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedConfigRecord {
+    pub config_version: String,
+    /// Deployment group the config was applied to, every kernel when unset.
+    #[serde(default)]
+    pub group: Option<String>,
+    pub author: Option<String>,
+    pub applied_at: DateTime<Utc>,
+    pub kernels: Vec<KernelAddr>,
+    pub succeeded: bool,
+    pub source: AppliedConfigSource,
+    /// The rollout report, as returned by the api that applied the config.
+    pub report: serde_json::Value,
+    pub config: ServiceConfig,
+}
+
+impl KnownObject for AppliedConfigRecord {
+    fn data_type() -> &'static str {
+        "AppliedConfigRecord"
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedConfigSummary {
+    pub revision: String,
+    pub config_version: String,
+    pub group: Option<String>,
+    pub author: Option<String>,
+    pub applied_at: DateTime<Utc>,
+    pub kernels: Vec<KernelAddr>,
+    pub succeeded: bool,
+    pub source: AppliedConfigSource,
+}
+
+impl AppliedConfigSummary {
+    fn new(revision: String, record: AppliedConfigRecord) -> Self {
+        Self {
+            revision,
+            config_version: record.config_version,
+            group: record.group,
+            author: record.author,
+            applied_at: record.applied_at,
+            kernels: record.kernels,
+            succeeded: record.succeeded,
+            source: record.source,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedConfigDetail {
+    #[serde(flatten)]
+    pub summary: AppliedConfigSummary,
+    pub report: serde_json::Value,
+    pub config: HumanReadableServiceConfig<Link>,
+}
+
+impl AppliedConfigDetail {
+    pub fn new(revision: String, mut record: AppliedConfigRecord) -> Self {
+        let report = std::mem::take(&mut record.report);
+        let config = HumanReadableServiceConfig::from_standard(record.config.clone());
+        Self {
+            summary: AppliedConfigSummary::new(revision, record),
+            report,
+            config,
+        }
+    }
+}
+
+impl AppliedConfigRecord {
+    pub fn new(
+        config: ServiceConfig,
+        author: Option<String>,
+        kernels: Vec<KernelAddr>,
+        succeeded: bool,
+        source: AppliedConfigSource,
+        report: &impl Serialize,
+    ) -> Self {
+        Self {
+            config_version: config.digest_sha256_base64(),
+            group: None,
+            author,
+            applied_at: Utc::now(),
+            kernels,
+            succeeded,
+            source,
+            report: serde_json::to_value(report).unwrap_or_default(),
+            config,
+        }
+    }
+    pub fn with_group(mut self, group: Option<String>) -> Self {
+        self.group = group;
+        self
+    }
+}
+
+impl ControllerContext {
+    /// Append a record to the applied config history, failures are logged, not returned,
+    /// since the config has already reached the kernels.
+    pub async fn record_applied_config(
+        &self,
+        record: AppliedConfigRecord,
+    ) -> Option<StorageObjectDescriptor> {
+        match self
+            .save_known_object(APPLIED_CONFIG_HISTORY_ID, record)
+            .await
+        {
+            Ok(descriptor) => Some(descriptor),
+            Err(e) => {
+                tracing::warn!("Failed to record applied config: {}", e);
+                None
+            }
+        }
+    }
+
+    pub async fn list_config_history(
+        &self,
+        page: PageQuery,
+    ) -> Result<PagedList<AppliedConfigSummary>, crate::Error> {
+        let list = self
+            .storage
+            .list_objects(ListObjectQuery {
+                filter: ObjectFilter {
+                    data_type: Some(AppliedConfigRecord::data_type().to_string()),
+                    id: Some(APPLIED_CONFIG_HISTORY_ID.to_string()),
+                    ..Default::default()
+                },
+                page,
+            })
+            .await?;
+        let mut items = Vec::with_capacity(list.items.len());
+        for item in list.items {
+            let revision = item.data.descriptor.revision;
+            let record = self.get_config_history(&revision).await?;
+            items.push(Indexed::new(
+                item.id,
+                AppliedConfigSummary::new(revision, record),
+            ));
+        }
+        Ok(PagedList {
+            items,
+            next_cursor: list.next_cursor,
+        })
+    }
+
+    pub async fn get_config_history(
+        &self,
+        revision: &str,
+    ) -> Result<AppliedConfigRecord, crate::Error> {
+        let descriptor = StorageObjectDescriptor {
+            id: APPLIED_CONFIG_HISTORY_ID.to_string(),
+            revision: revision.to_string(),
+        };
+        let object = self
+            .storage
+            .get_object(&descriptor)
+            .await?
+            .ok_or_else(|| crate::Error::ConfigHistoryNotFound(revision.to_string()))?;
+        object
+            .data
+            .deserialize_into::<AppliedConfigRecord>()
+            .map_err(|e| StorageError::DeserializationError(e).into())
+    }
+
+    /// Diff two history revisions, or a revision against the current config of its deployment
+    /// group when `to` is absent.
+    pub async fn diff_config_history(
+        &self,
+        from: &str,
+        to: Option<&str>,
+    ) -> Result<ConfigDiff, crate::Error> {
+        let old = self.get_config_history(from).await?;
+        let new = match to {
+            Some(to) => self.get_config_history(to).await?.config,
+            None => self
+                .current_config_of(old.group.as_deref())
+                .await
+                .ok_or(crate::Error::NoCurrentConfig)?,
+        };
+        Ok(ConfigDiff::between(&old.config, &new))
+    }
+
+    /// Re-apply a historical config through the prepare/commit transaction and record it,
+    /// to the deployment group it was applied to.
+    pub async fn rollback_config(
+        &self,
+        revision: &str,
+        author: Option<String>,
+    ) -> Result<crate::kernel::ConfigRolloutReport, crate::Error> {
+        let record = self.get_config_history(revision).await?;
+        let report = self
+            .update_group_config(record.config.clone(), record.group.as_deref())
+            .await?;
+        self.record_applied_config(
+            AppliedConfigRecord::new(
+                record.config,
+                author,
+                report.kernels(),
+                report.is_succeeded(),
+                AppliedConfigSource::Rollback {
+                    from_revision: revision.to_string(),
+                },
+                &report,
+            )
+            .with_group(record.group),
+        )
+        .await;
+        Ok(report)
+    }
+}
@@ -1,6 +1,10 @@
+mod audit;
+pub mod auth;
 mod file_browser;
+mod history;
 mod k8s;
 mod kernel_manager;
+mod leader;
 mod resolve;
 mod state;
 mod storage;
@@ -8,6 +12,7 @@ mod utils;
 
 use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use axum::response::IntoResponse as _;
 use http::HeaderValue;
@@ -22,6 +27,8 @@ use crate::ControllerContext;
 pub struct HttpInterfaceConfig {
     pub bind: SocketAddr,
     pub web_root: Option<PathBuf>,
+    /// Authentication and role checks for `/api`, every caller is treated as admin when unset.
+    pub auth: Option<auth::HttpAuthConfig>,
 }
 
 const DEFAULT_WEB_ROOT: &str = "./ui/controller-web/";
@@ -34,6 +41,7 @@ impl Default for HttpInterfaceConfig {
                 HTTP_CONTROLLER_DEFAULT_PORT,
             )),
             web_root: Some(PathBuf::from(DEFAULT_WEB_ROOT)),
+            auth: None,
         }
     }
 }
@@ -56,15 +64,39 @@ impl ControllerContext {
         }
     }
     pub fn build_axum_router(&self) -> axum::Router<()> {
+        let auth_config = self
+            .controller_config
+            .interface
+            .http
+            .as_ref()
+            .and_then(|config| config.auth.clone());
+        if auth_config.is_none() {
+            tracing::warn!("HTTP api authentication is not configured, every caller is admin");
+        }
+        let auth_state = auth::AuthLayerState {
+            authenticator: auth_config.map(|config| Arc::new(auth::HttpAuthenticator::new(config))),
+            controller_context: self.clone(),
+        };
         let api_router = axum::Router::new().nest(
             "/api",
             axum::Router::new()
+                .nest("/audit", audit::router())
                 .nest("/file_browser", file_browser::router())
+                .nest("/history", history::router())
                 .nest("/k8s", k8s::router())
                 .nest("/kernel_manager", kernel_manager::router())
                 .nest("/resolve", resolve::router())
                 .nest("/storage", storage::router())
-                .nest("/state", state::router()),
+                .nest("/state", state::router())
+                // runs after authentication, so unauthenticated callers don't learn the leader
+                .layer(axum::middleware::from_fn_with_state(
+                    self.clone(),
+                    leader::follower_guard,
+                ))
+                .layer(axum::middleware::from_fn_with_state(
+                    auth_state,
+                    auth::auth_middleware,
+                )),
         );
 
         let Some(web_root) = self
@@ -0,0 +1,22 @@
+use axum::{
+    extract::{Query, State},
+    response::Response,
+};
+use switchboard_model::{Cursor, PageQuery};
+
+use crate::interface::http::{HttpState, history::HistoryPageQuery};
+
+pub async fn list(
+    State(state): State<HttpState>,
+    Query(HistoryPageQuery { next, limit }): Query<HistoryPageQuery>,
+) -> Response {
+    let page = PageQuery {
+        cursor: Cursor::new(next),
+        limit,
+    };
+    super::result_to_json_response(state.controller_context.list_audit_log(page).await)
+}
+
+pub fn router() -> axum::Router<HttpState> {
+    axum::Router::new().route("/records", axum::routing::get(list))
+}
@@ -0,0 +1,442 @@
+//! Authentication and role based access control for the controller http api.
+
+use std::{path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use http::{HeaderMap, Method, StatusCode, header};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use switchboard_jwks::{JwksCache, JwksError, JwksSource};
+
+use crate::{ControllerContext, audit::AuditRecord};
+
+const DEFAULT_ROLES_CLAIM: &str = "roles";
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Read configs, kernel states, history and dry-run configs.
+    Viewer,
+    /// Also change stored objects and files.
+    Editor,
+    /// Also push configs to kernels, run rollouts and rollbacks.
+    Deployer,
+    /// Also read the audit log.
+    Admin,
+}
+
+impl Role {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "viewer" => Some(Role::Viewer),
+            "editor" => Some(Role::Editor),
+            "deployer" => Some(Role::Deployer),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Deployer => "deployer",
+            Role::Admin => "admin",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Http api authentication, every configured method is accepted.
+/// # Example
+/// ```toml
+/// [[interface.http.auth.tokens]]
+/// name = "ci"
+/// sha256 = "<hex sha256 of the token>"
+/// role = "deployer"
+/// [[interface.http.auth.basic]]
+/// username = "alice"
+/// password_hash = "$argon2id$v=19$m=19456,t=2,p=1$..."
+/// role = "admin"
+/// [interface.http.auth.oidc]
+/// issuer = "https://idp.example.com"
+/// audience = "switchboard"
+/// jwks_url = "https://idp.example.com/.well-known/jwks.json"
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct HttpAuthConfig {
+    pub tokens: Vec<ApiTokenConfig>,
+    pub basic: Vec<BasicUserConfig>,
+    pub oidc: Option<OidcConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+pub struct ApiTokenConfig {
+    pub name: String,
+    /// Hex encoded sha256 of the bearer token, the token itself is never stored.
+    pub sha256: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+pub struct BasicUserConfig {
+    pub username: String,
+    /// An argon2 PHC string or a bcrypt hash.
+    pub password_hash: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub audience: Option<String>,
+    pub jwks_url: Option<String>,
+    /// A local JWKS, used instead of `jwks_url`.
+    pub jwks_file: Option<PathBuf>,
+    /// Dot separated path of the claim holding role names, e.g. `realm_access.roles`.
+    #[serde(default = "default_roles_claim")]
+    pub roles_claim: String,
+    /// Role of valid tokens without a known role in their claims, they are rejected when unset.
+    pub default_role: Option<Role>,
+}
+
+fn default_roles_claim() -> String {
+    DEFAULT_ROLES_CLAIM.to_string()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpAuthError {
+    #[error("Missing credentials")]
+    MissingCredentials,
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("Token algorithm {0:?} is not allowed")]
+    DisallowedAlgorithm(Algorithm),
+    #[error("No key {0:?} in JWKS")]
+    UnknownKey(Option<String>),
+    #[error("Token carries no known role")]
+    NoRole,
+    #[error("Role {role} can't access this api, it needs {required}")]
+    Forbidden { role: Role, required: Role },
+    #[error("Neither jwks_url nor jwks_file is configured")]
+    NoJwks,
+    #[error(transparent)]
+    Jwks(#[from] JwksError),
+}
+
+impl HttpAuthError {
+    fn status(&self) -> StatusCode {
+        match self {
+            HttpAuthError::Forbidden { .. } | HttpAuthError::NoRole => StatusCode::FORBIDDEN,
+            HttpAuthError::NoJwks | HttpAuthError::Jwks(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+impl IntoResponse for HttpAuthError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let mut response =
+            axum::Json(switchboard_model::error::ErrorStack::from_std(self)).into_response();
+        *response.status_mut() = status;
+        if status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                http::HeaderValue::from_static("Basic realm=\"switchboard\", Bearer"),
+            );
+        }
+        response
+    }
+}
+
+/// Role an api call needs, `path` is relative to `/api`.
+pub fn required_role(method: &Method, path: &str) -> Role {
+    if path.starts_with("/audit") {
+        return Role::Admin;
+    }
+    if *method == Method::GET || *method == Method::HEAD {
+        return Role::Viewer;
+    }
+    match path {
+        // dry runs
+        "/kernel_manager/diff" | "/kernel_manager/validate" | "/resolve/service_config" => {
+            Role::Viewer
+        }
+        "/kernel_manager/kernels" | "/kernel_manager/refresh" | "/history/rollback" => {
+            Role::Deployer
+        }
+        path if path.starts_with("/kernel_manager/rollout") => Role::Deployer,
+        // loads native code into kernels
+        path if path.starts_with("/kernel_manager/plugins") => Role::Admin,
+        _ => Role::Editor,
+    }
+}
+
+/// Whether an api call changes anything, these are audited and rejected by followers.
+pub fn is_mutating(method: &Method, path: &str) -> bool {
+    required_role(method, path) > Role::Viewer && method != Method::GET && method != Method::HEAD
+}
+
+pub struct HttpAuthenticator {
+    config: HttpAuthConfig,
+    jwks: Option<JwksCache>,
+}
+
+impl HttpAuthenticator {
+    pub fn new(config: HttpAuthConfig) -> Self {
+        let jwks = config
+            .oidc
+            .as_ref()
+            .and_then(|oidc| match (&oidc.jwks_file, &oidc.jwks_url) {
+                (Some(path), _) => Some(JwksSource::File(path.clone())),
+                (None, Some(url)) => Some(JwksSource::Url(url.clone())),
+                (None, None) => None,
+            })
+            .map(JwksCache::new);
+        Self { config, jwks }
+    }
+
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, HttpAuthError> {
+        let authorization = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(HttpAuthError::MissingCredentials)?;
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            self.authenticate_bearer(token.trim()).await
+        } else if let Some(credentials) = authorization.strip_prefix("Basic ") {
+            self.authenticate_basic(credentials.trim()).await
+        } else {
+            Err(HttpAuthError::InvalidCredentials)
+        }
+    }
+
+    async fn authenticate_bearer(&self, token: &str) -> Result<Principal, HttpAuthError> {
+        let digest = hex::encode(Sha256::digest(token.as_bytes()));
+        if let Some(config) = self
+            .config
+            .tokens
+            .iter()
+            .find(|config| config.sha256.eq_ignore_ascii_case(&digest))
+        {
+            return Ok(Principal {
+                name: config.name.clone(),
+                role: config.role,
+            });
+        }
+        match &self.config.oidc {
+            Some(oidc) => self.authenticate_jwt(oidc, token).await,
+            None => Err(HttpAuthError::InvalidCredentials),
+        }
+    }
+
+    async fn authenticate_basic(&self, credentials: &str) -> Result<Principal, HttpAuthError> {
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(credentials)
+            .map_err(|_| HttpAuthError::InvalidCredentials)?;
+        let decoded = String::from_utf8(decoded).map_err(|_| HttpAuthError::InvalidCredentials)?;
+        let (username, password) = decoded
+            .split_once(':')
+            .ok_or(HttpAuthError::InvalidCredentials)?;
+        let user = self
+            .config
+            .basic
+            .iter()
+            .find(|user| user.username == username)
+            .ok_or(HttpAuthError::InvalidCredentials)?;
+        let hash = user.password_hash.clone();
+        let password = password.to_string();
+        // password hashes are slow on purpose, keep them off the async workers
+        let verified = tokio::task::spawn_blocking(move || verify_password(&hash, &password))
+            .await
+            .unwrap_or(false);
+        if !verified {
+            return Err(HttpAuthError::InvalidCredentials);
+        }
+        Ok(Principal {
+            name: user.username.clone(),
+            role: user.role,
+        })
+    }
+
+    async fn authenticate_jwt(
+        &self,
+        oidc: &OidcConfig,
+        token: &str,
+    ) -> Result<Principal, HttpAuthError> {
+        let header = jsonwebtoken::decode_header(token)?;
+        // keys come from the JWKS, never accept a shared secret chosen by the token
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(HttpAuthError::DisallowedAlgorithm(header.alg));
+        }
+        let jwks = self
+            .jwks
+            .as_ref()
+            .ok_or(HttpAuthError::NoJwks)?
+            .get(header.kid.as_deref())
+            .await?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or_else(|| HttpAuthError::UnknownKey(header.kid.clone()))?;
+        if let Some(key_algorithm) = jwk.common.key_algorithm
+            && key_algorithm.to_string() != format!("{:?}", header.alg)
+        {
+            return Err(HttpAuthError::DisallowedAlgorithm(header.alg));
+        }
+        let key = DecodingKey::from_jwk(jwk)?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&oidc.issuer]);
+        // jsonwebtoken only checks `iss` and `aud` when the token carries them
+        let mut required_claims = vec!["exp", "iss"];
+        match &oidc.audience {
+            Some(audience) => {
+                validation.set_audience(&[audience]);
+                required_claims.push("aud");
+            }
+            None => validation.validate_aud = false,
+        }
+        validation.set_required_spec_claims(&required_claims);
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &key, &validation)?.claims;
+        let name = claims
+            .get("sub")
+            .and_then(|sub| sub.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let role = claim_role(&claims, &oidc.roles_claim)
+            .or(oidc.default_role)
+            .ok_or(HttpAuthError::NoRole)?;
+        Ok(Principal { name, role })
+    }
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    if hash.starts_with("$2") {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
+    use argon2::PasswordVerifier;
+    argon2::PasswordHash::new(hash).is_ok_and(|hash| {
+        argon2::Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Highest known role named by the claim at the dot separated `path`.
+fn claim_role(claims: &serde_json::Value, path: &str) -> Option<Role> {
+    let claim = path
+        .split('.')
+        .try_fold(claims, |value, segment| value.get(segment))?;
+    match claim {
+        serde_json::Value::String(name) => Role::parse(name),
+        serde_json::Value::Array(names) => names
+            .iter()
+            .filter_map(|name| name.as_str().and_then(Role::parse))
+            .max(),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthLayerState {
+    pub authenticator: Option<Arc<HttpAuthenticator>>,
+    pub controller_context: ControllerContext,
+}
+
+/// Authenticates the caller, checks the role the route needs, and audits mutating calls.
+pub async fn auth_middleware(
+    State(state): State<AuthLayerState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let required = required_role(&method, &path);
+    let mutating = is_mutating(&method, &path);
+    let mut principal = None;
+    if let Some(authenticator) = &state.authenticator {
+        let checked = match authenticator.authenticate(request.headers()).await {
+            Ok(authenticated) => {
+                let role = authenticated.role;
+                principal = Some(authenticated);
+                if role >= required {
+                    Ok(())
+                } else {
+                    Err(HttpAuthError::Forbidden { role, required })
+                }
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = checked {
+            tracing::warn!(%method, path, "Rejected api call: {}", e);
+            let response = e.into_response();
+            if mutating {
+                audit(
+                    &state,
+                    principal.as_ref(),
+                    &method,
+                    &path,
+                    response.status(),
+                )
+                .await;
+            }
+            return response;
+        }
+    }
+    if let Some(principal) = &principal {
+        request.extensions_mut().insert(principal.clone());
+    }
+    let response = next.run(request).await;
+    if mutating {
+        audit(
+            &state,
+            principal.as_ref(),
+            &method,
+            &path,
+            response.status(),
+        )
+        .await;
+    }
+    response
+}
+
+async fn audit(
+    state: &AuthLayerState,
+    principal: Option<&Principal>,
+    method: &Method,
+    path: &str,
+    status: StatusCode,
+) {
+    state
+        .controller_context
+        .record_audit(AuditRecord {
+            at: chrono::Utc::now(),
+            principal: principal.map(|principal| principal.name.clone()),
+            role: principal.map(|principal| principal.role.to_string()),
+            method: method.to_string(),
+            path: path.to_string(),
+            status: status.as_u16(),
+        })
+        .await;
+}
@@ -0,0 +1,109 @@
+use axum::{
+    Extension, Json,
+    extract::{Query, State},
+    response::Response,
+};
+use switchboard_model::{Cursor, PageQuery};
+
+use crate::{
+    history::AppliedConfigDetail,
+    interface::http::{HttpState, auth::Principal},
+};
+
+#[derive(Debug, serde::Deserialize)]
+pub struct HistoryPageQuery {
+    #[serde(default)]
+    pub next: Option<String>,
+    pub limit: usize,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct RevisionQuery {
+    pub revision: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DiffHistoryQuery {
+    pub from: String,
+    /// Defaults to the config the controller currently applies.
+    pub to: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct RollbackRequest {
+    pub revision: String,
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+pub async fn list(
+    State(state): State<HttpState>,
+    Query(HistoryPageQuery { next, limit }): Query<HistoryPageQuery>,
+) -> Response {
+    let page = PageQuery {
+        cursor: Cursor::new(next),
+        limit,
+    };
+    super::result_to_json_response(state.controller_context.list_config_history(page).await)
+}
+
+pub async fn show(
+    State(state): State<HttpState>,
+    Query(RevisionQuery { revision }): Query<RevisionQuery>,
+) -> Response {
+    let process = async {
+        let record = state
+            .controller_context
+            .get_config_history(&revision)
+            .await?;
+        crate::Result::Ok(AppliedConfigDetail::new(revision, record))
+    };
+    super::result_to_json_response(process.await)
+}
+
+pub async fn diff(
+    State(state): State<HttpState>,
+    Query(DiffHistoryQuery { from, to }): Query<DiffHistoryQuery>,
+) -> Response {
+    super::result_to_json_response(
+        state
+            .controller_context
+            .diff_config_history(&from, to.as_deref())
+            .await,
+    )
+}
+
+/// Re-apply a historical revision to every kernel with the all-or-nothing transaction.
+pub async fn rollback(
+    State(state): State<HttpState>,
+    principal: Option<Extension<Principal>>,
+    Json(RollbackRequest { revision, author }): Json<RollbackRequest>,
+) -> Response {
+    let process = async {
+        if state
+            .controller_context
+            .run_mode
+            .read()
+            .await
+            .is_some_and(|m| m.is_k8s())
+        {
+            return Err(crate::Error::InKubernetesCluster);
+        }
+        state
+            .controller_context
+            .rollback_config(
+                &revision,
+                author.or_else(|| principal.map(|Extension(principal)| principal.name)),
+            )
+            .await
+    };
+    super::result_to_json_response(process.await)
+}
+
+pub fn router() -> axum::Router<HttpState> {
+    axum::Router::new()
+        .route("/records", axum::routing::get(list))
+        .route("/record", axum::routing::get(show))
+        .route("/diff", axum::routing::get(diff))
+        .route("/rollback", axum::routing::post(rollback))
+}
@@ -1,17 +1,69 @@
 use std::collections::BTreeMap;
 
-use axum::{Json, extract::State, response::Response};
+use axum::{
+    Extension, Json,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    response::Response,
+};
 use switchboard_link_or_value::LinkOrValue;
-use switchboard_model::{SerdeValue, kernel::KernelConnectionAndState};
+use switchboard_model::{SerdeValue, kernel::PluginKind, label::LabelSelector};
 
-use crate::{interface::http::HttpState, kernel::KernelAddr, link_resolver::Link};
+use crate::{
+    history::{AppliedConfigRecord, AppliedConfigSource},
+    interface::http::{HttpState, auth::Principal},
+    kernel::{PluginUpload, rollout::ProgressiveRolloutPolicy},
+    link_resolver::Link,
+};
+
+/// Kernels refuse larger plugin artifacts.
+const MAX_PLUGIN_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct KernelFilterQuery {
+    /// Only kernels whose labels match, e.g. `role=edge,region in (eu,us)`.
+    #[serde(default)]
+    pub selector: Option<LabelSelector>,
+    /// Only kernels of this deployment group.
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct GroupQuery {
+    #[serde(default)]
+    pub group: Option<String>,
+}
 
 pub async fn get_kernel_states(
     State(state): State<HttpState>,
-) -> Json<BTreeMap<KernelAddr, KernelConnectionAndState>> {
-    let kernel_manager = state.controller_context.kernel_manager.read().await;
-    let states = kernel_manager.get_kernel_states().await;
-    Json(states)
+    Query(KernelFilterQuery { selector, group }): Query<KernelFilterQuery>,
+) -> Response {
+    let process = async move {
+        let group = group
+            .as_deref()
+            .map(|group| state.controller_context.deployment_group(group))
+            .transpose()?;
+        let kernel_manager = state.controller_context.kernel_manager.read().await;
+        let mut states = kernel_manager.get_kernel_states().await;
+        for selector in group
+            .map(|group| &group.selector)
+            .into_iter()
+            .chain(&selector)
+        {
+            let selected = kernel_manager.select_kernels(selector);
+            states.retain(|addr, _| selected.contains(addr));
+        }
+        Ok::<_, crate::Error>(states)
+    };
+    super::result_to_json_response(process.await)
+}
+
+/// Every deployment group with the kernels it selects.
+pub async fn list_groups(State(state): State<HttpState>) -> Response {
+    super::result_to_json_response(Ok::<_, crate::Error>(
+        state.controller_context.list_deployment_groups().await,
+    ))
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -26,22 +78,37 @@ pub enum UpdateConfigRequest {
     },
 }
 
-pub async fn update_config(
-    State(state): State<HttpState>,
-    Json(request): Json<UpdateConfigRequest>,
-) -> Response {
-    let process = async move {
-        // check if we are in k8s mode
-        if state
-            .controller_context
-            .run_mode
-            .read()
-            .await
-            .is_some_and(|m| m.is_k8s())
-        {
-            return Err(crate::Error::InKubernetesCluster);
-        }
-        let standard_config = match request {
+#[derive(Debug, serde::Deserialize)]
+pub struct GatedUpdateConfigRequest {
+    #[serde(flatten)]
+    pub request: UpdateConfigRequest,
+    /// Kernel address to the `base_version` of a reviewed diff, the update is
+    /// rejected if any connected kernel no longer runs that version.
+    #[serde(default)]
+    pub base_versions: Option<BTreeMap<String, String>>,
+    /// Who applied the config, kept in the config history.
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Deployment group to apply the config to, every kernel when unset.
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct GroupUpdateConfigRequest {
+    #[serde(flatten)]
+    pub request: UpdateConfigRequest,
+    /// Deployment group to check the config against, every kernel when unset.
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+impl UpdateConfigRequest {
+    async fn into_standard_config(
+        self,
+        state: &HttpState,
+    ) -> Result<switchboard_model::ServiceConfig, crate::Error> {
+        let standard_config = match self {
             UpdateConfigRequest::NewConfig { new_config } => {
                 let resolver = state.controller_context.clone().link_resolver();
                 switchboard_model::resolve::file_style::fetch_config(new_config, &resolver).await?
@@ -57,15 +124,260 @@ pub async fn update_config(
                     .await?
             }
         };
-        let results = state
+        Ok(standard_config)
+    }
+}
+
+pub async fn update_config(
+    State(state): State<HttpState>,
+    principal: Option<Extension<Principal>>,
+    Json(GatedUpdateConfigRequest {
+        request,
+        base_versions,
+        author,
+        group,
+    }): Json<GatedUpdateConfigRequest>,
+) -> Response {
+    let process = async move {
+        // check if we are in k8s mode
+        if state
+            .controller_context
+            .run_mode
+            .read()
+            .await
+            .is_some_and(|m| m.is_k8s())
+        {
+            return Err(crate::Error::InKubernetesCluster);
+        }
+        let standard_config = request.into_standard_config(&state).await?;
+        if let Some(base_versions) = &base_versions {
+            state
+                .controller_context
+                .check_base_versions(base_versions, group.as_deref())
+                .await?;
+        }
+        let report = state
+            .controller_context
+            .update_group_config(standard_config.clone(), group.as_deref())
+            .await?;
+        state
             .controller_context
-            .update_config(standard_config)
+            .record_applied_config(
+                AppliedConfigRecord::new(
+                    standard_config,
+                    author.or_else(|| principal.map(|Extension(principal)| principal.name)),
+                    report.kernels(),
+                    report.is_succeeded(),
+                    AppliedConfigSource::Update,
+                    &report,
+                )
+                .with_group(group),
+            )
             .await;
+        Ok::<_, crate::Error>(report)
+    };
+    super::result_to_json_response(process.await)
+}
+
+/// Dry-run a config on every connected kernel of the group and return their validation reports.
+pub async fn validate_config(
+    State(state): State<HttpState>,
+    Json(GroupUpdateConfigRequest { request, group }): Json<GroupUpdateConfigRequest>,
+) -> Response {
+    let process = async move {
+        let standard_config = request.into_standard_config(&state).await?;
+        let results = state
+            .controller_context
+            .validate_config(standard_config, group.as_deref())
+            .await?;
         Ok::<_, crate::Error>(results)
     };
     super::result_to_json_response(process.await)
 }
 
+/// Diff the current config of every connected kernel of the group against a candidate config.
+pub async fn diff_config(
+    State(state): State<HttpState>,
+    Json(GroupUpdateConfigRequest { request, group }): Json<GroupUpdateConfigRequest>,
+) -> Response {
+    let process = async move {
+        let standard_config = request.into_standard_config(&state).await?;
+        let results = state
+            .controller_context
+            .diff_config(standard_config, group.as_deref())
+            .await?;
+        Ok::<_, crate::Error>(results)
+    };
+    super::result_to_json_response(process.await)
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct RolloutRequest {
+    #[serde(flatten)]
+    pub request: GatedUpdateConfigRequest,
+    #[serde(default)]
+    pub policy: ProgressiveRolloutPolicy,
+}
+
+/// Start a progressive rollout, answering with its initial progress.
+pub async fn start_rollout(
+    State(state): State<HttpState>,
+    principal: Option<Extension<Principal>>,
+    Json(RolloutRequest { request, policy }): Json<RolloutRequest>,
+) -> Response {
+    let process = async move {
+        if state
+            .controller_context
+            .run_mode
+            .read()
+            .await
+            .is_some_and(|m| m.is_k8s())
+        {
+            return Err(crate::Error::InKubernetesCluster);
+        }
+        let standard_config = request.request.into_standard_config(&state).await?;
+        if let Some(base_versions) = &request.base_versions {
+            state
+                .controller_context
+                .check_base_versions(base_versions, request.group.as_deref())
+                .await?;
+        }
+        state
+            .controller_context
+            .start_rollout(
+                standard_config,
+                policy,
+                request
+                    .author
+                    .or_else(|| principal.map(|Extension(principal)| principal.name)),
+                request.group,
+            )
+            .await
+    };
+    super::result_to_json_response(process.await)
+}
+
+pub async fn get_rollout(
+    State(state): State<HttpState>,
+    Query(GroupQuery { group }): Query<GroupQuery>,
+) -> Response {
+    let process = async move {
+        let rollout = state
+            .controller_context
+            .get_rollout(group.as_deref())
+            .await
+            .ok_or(crate::Error::NoRollout)?;
+        Ok::<_, crate::Error>(rollout.progress().await)
+    };
+    super::result_to_json_response(process.await)
+}
+
+async fn set_rollout_paused(state: HttpState, group: Option<String>, paused: bool) -> Response {
+    let process = async move {
+        let rollout = state
+            .controller_context
+            .get_rollout(group.as_deref())
+            .await
+            .ok_or(crate::Error::NoRollout)?;
+        rollout.set_paused(paused).await;
+        Ok::<_, crate::Error>(rollout.progress().await)
+    };
+    super::result_to_json_response(process.await)
+}
+
+pub async fn pause_rollout(
+    State(state): State<HttpState>,
+    Query(GroupQuery { group }): Query<GroupQuery>,
+) -> Response {
+    set_rollout_paused(state, group, true).await
+}
+
+pub async fn resume_rollout(
+    State(state): State<HttpState>,
+    Query(GroupQuery { group }): Query<GroupQuery>,
+) -> Response {
+    set_rollout_paused(state, group, false).await
+}
+
+/// Abort the rollout, rolling back every kernel it already updated.
+pub async fn abort_rollout(
+    State(state): State<HttpState>,
+    Query(GroupQuery { group }): Query<GroupQuery>,
+) -> Response {
+    let process = async move {
+        let rollout = state
+            .controller_context
+            .get_rollout(group.as_deref())
+            .await
+            .ok_or(crate::Error::NoRollout)?;
+        rollout.abort();
+        Ok::<_, crate::Error>(rollout.progress().await)
+    };
+    super::result_to_json_response(process.await)
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PushPluginQuery {
+    pub kind: PluginKind,
+    /// Hex sha256 of the artifact, checked by every kernel.
+    pub sha256: String,
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+/// Push the plugin artifact in the body to every connected kernel of the group. Flows use its
+/// classes once the next config is committed.
+pub async fn push_plugin(
+    State(state): State<HttpState>,
+    Path(name): Path<String>,
+    Query(PushPluginQuery {
+        kind,
+        sha256,
+        group,
+    }): Query<PushPluginQuery>,
+    artifact: Bytes,
+) -> Response {
+    let process = async move {
+        let plugin = PluginUpload {
+            name,
+            kind,
+            artifact,
+            sha256,
+        };
+        state
+            .controller_context
+            .push_plugin(plugin, group.as_deref())
+            .await
+    };
+    super::result_to_json_response(process.await)
+}
+
+/// The plugins loaded by every connected kernel of the group.
+pub async fn list_plugins(
+    State(state): State<HttpState>,
+    Query(GroupQuery { group }): Query<GroupQuery>,
+) -> Response {
+    super::result_to_json_response(
+        state
+            .controller_context
+            .list_plugins(group.as_deref())
+            .await,
+    )
+}
+
+/// The http classes of every connected kernel of the group, with their config schemas.
+pub async fn list_classes(
+    State(state): State<HttpState>,
+    Query(GroupQuery { group }): Query<GroupQuery>,
+) -> Response {
+    super::result_to_json_response(
+        state
+            .controller_context
+            .list_classes(group.as_deref())
+            .await,
+    )
+}
+
 pub async fn refresh_kernels(State(state): State<HttpState>) -> Response {
     super::result_to_json_response(state.controller_context.refresh_kernels().await)
 }
@@ -76,5 +388,21 @@ pub fn router() -> axum::Router<HttpState> {
             "/kernels",
             axum::routing::get(get_kernel_states).put(update_config),
         )
+        .route("/groups", axum::routing::get(list_groups))
+        .route("/diff", axum::routing::post(diff_config))
+        .route("/validate", axum::routing::post(validate_config))
         .route("/refresh", axum::routing::post(refresh_kernels))
+        .route(
+            "/rollout",
+            axum::routing::get(get_rollout).post(start_rollout),
+        )
+        .route("/rollout/pause", axum::routing::post(pause_rollout))
+        .route("/rollout/resume", axum::routing::post(resume_rollout))
+        .route("/rollout/abort", axum::routing::post(abort_rollout))
+        .route("/plugins", axum::routing::get(list_plugins))
+        .route("/classes", axum::routing::get(list_classes))
+        .route(
+            "/plugins/{name}",
+            axum::routing::put(push_plugin).layer(DefaultBodyLimit::max(MAX_PLUGIN_BYTES)),
+        )
 }
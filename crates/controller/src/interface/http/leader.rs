@@ -0,0 +1,32 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::StatusCode;
+
+use crate::ControllerContext;
+
+/// Followers serve reads only, calls changing anything have to go to the leader.
+pub async fn follower_guard(
+    State(context): State<ControllerContext>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !super::auth::is_mutating(request.method(), request.uri().path()) {
+        return next.run(request).await;
+    }
+    let leadership = context.leadership().await;
+    if leadership.is_leader {
+        return next.run(request).await;
+    }
+    let error = crate::Error::NotLeader(leadership.leader);
+    tracing::warn!(method = %request.method(), path = request.uri().path(), "{}", error);
+    let mut response =
+        axum::Json(switchboard_model::error::ErrorStack::from_std(error)).into_response();
+    response
+        .headers_mut()
+        .append("x-error-stack", http::HeaderValue::from_static(""));
+    *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    response
+}
@@ -1,22 +1,35 @@
-use axum::{Json, extract::State};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
 
 use switchboard_model::HumanReadableServiceConfig;
 
-use crate::{interface::http::HttpState, link_resolver::Link};
+use crate::{
+    interface::http::{HttpState, kernel_manager::GroupQuery},
+    leader::LeadershipState,
+    link_resolver::Link,
+};
 
+/// The config applied to every kernel, or to the deployment group in `?group=`.
 pub async fn get_current_config(
     State(state): State<HttpState>,
+    Query(GroupQuery { group }): Query<GroupQuery>,
 ) -> Json<Option<HumanReadableServiceConfig<Link>>> {
     let config = state
         .controller_context
-        .current_config
-        .read()
+        .current_config_of(group.as_deref())
         .await
-        .as_ref()
-        .map(|config| <HumanReadableServiceConfig<Link>>::from_standard(config.clone()));
+        .map(<HumanReadableServiceConfig<Link>>::from_standard);
     Json(config)
 }
 
+pub async fn get_leadership(State(state): State<HttpState>) -> Json<LeadershipState> {
+    Json(state.controller_context.leadership().await)
+}
+
 pub fn router() -> axum::Router<HttpState> {
-    axum::Router::new().route("/current_config", axum::routing::get(get_current_config))
+    axum::Router::new()
+        .route("/current_config", axum::routing::get(get_current_config))
+        .route("/leadership", axum::routing::get(get_leadership))
 }
@@ -7,6 +7,7 @@ use chrono::{DateTime, Utc};
 use switchboard_model::FlattenPageQueryWithFilter;
 
 use crate::{
+    audit::{RESERVED_STORAGE_IDS, is_reserved_storage_id},
     interface::http::HttpState,
     storage::{JsonInterpreter, ListObjectQuery, ObjectFilter, StorageObjectDescriptor},
 };
@@ -29,11 +30,20 @@ pub enum BatchDeleteStorageObjectRequest {
     },
 }
 
+/// The audit log is read through `/audit` and only written by the controller.
+fn check_id(id: &str) -> crate::Result<()> {
+    if is_reserved_storage_id(id) {
+        return Err(crate::Error::ReservedStorageId(id.to_string()));
+    }
+    Ok(())
+}
+
 pub async fn get(
     State(state): State<HttpState>,
     Query(params): Query<StorageObjectDescriptor>,
 ) -> Response {
     let process = async {
+        check_id(&params.id)?;
         let obj = state
             .controller_context
             .storage()
@@ -53,6 +63,7 @@ pub async fn delete(
     Query(params): Query<StorageObjectDescriptor>,
 ) -> Response {
     let process = async {
+        check_id(&params.id)?;
         state
             .controller_context
             .storage()
@@ -70,6 +81,9 @@ pub async fn batch_delete(
     let process = async {
         match request {
             BatchDeleteStorageObjectRequest::ByDescriptors { descriptors } => {
+                for descriptor in &descriptors {
+                    check_id(&descriptor.id)?;
+                }
                 state
                     .controller_context
                     .storage()
@@ -77,6 +91,7 @@ pub async fn batch_delete(
                     .await?;
             }
             BatchDeleteStorageObjectRequest::ById { id } => {
+                check_id(&id)?;
                 state
                     .controller_context
                     .storage()
@@ -94,6 +109,7 @@ pub async fn save(
     Json(request): Json<SaveStorageObjectRequest>,
 ) -> Response {
     let process = async {
+        check_id(&request.id)?;
         let data = JsonInterpreter::decode(request.data, &request.date_type)?;
         let descriptor = state
             .controller_context
@@ -143,8 +159,15 @@ pub async fn list(
         latest_only: filter.latest_only.map(|v| v.into()),
         created_before: filter.created_before,
         created_after: filter.created_after,
+        exclude_ids: RESERVED_STORAGE_IDS
+            .iter()
+            .map(|id| id.to_string())
+            .collect(),
     };
     let process = async {
+        if let Some(id) = &filter.id {
+            check_id(id)?;
+        }
         let list = state
             .controller_context
             .storage()
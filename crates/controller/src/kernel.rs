@@ -8,13 +8,20 @@ use std::{
 
 pub use discovery::*;
 mod connection;
+pub mod group;
 pub mod grpc_client;
+pub mod reverse;
+pub mod rollout;
 pub use connection::*;
 use futures::FutureExt;
 use serde::Serialize;
 use switchboard_model::{
+    diff::ConfigDiff,
     error::{ErrorStack, ResultObject},
-    kernel::{KernelConnectionAndState, KernelInfoAndState},
+    kernel::{
+        ClassList, KernelConnectionAndState, KernelInfoAndState, PluginInfo, PluginKind, PluginList,
+    },
+    validation::ValidationReport,
 };
 
 const ROLLOUT_ABORT_REASON: &str = "all_or_nothing rollout failed";
@@ -22,6 +29,8 @@ const ROLLOUT_ABORT_REASON: &str = "all_or_nothing rollout failed";
 pub enum KernelAddr {
     Uds(Arc<std::path::Path>),
     Grpc(Arc<str>),
+    /// A kernel which dialed in, by its kernel id.
+    Reverse(Arc<str>),
 }
 
 impl Display for KernelAddr {
@@ -29,6 +38,7 @@ impl Display for KernelAddr {
         match self {
             KernelAddr::Uds(path) => write!(f, "unix://{}", path.display()),
             KernelAddr::Grpc(addr) => write!(f, "{}", addr),
+            KernelAddr::Reverse(id) => write!(f, "reverse://{}", id),
         }
     }
 }
@@ -51,6 +61,7 @@ impl FromStr for KernelAddr {
                     std::path::PathBuf::from(path).as_path().into(),
                 )),
                 "http" | "https" | "grpc" => Ok(KernelAddr::Grpc(path.into())),
+                "reverse" => Ok(KernelAddr::Reverse(path.into())),
                 _ => Err(KernelAddrParseError::UnknownFormat {
                     format: schema.to_string(),
                 }),
@@ -81,20 +92,32 @@ pub enum KernelAddrParseError {
     InvalidTcpAddress(#[from] std::net::AddrParseError),
 }
 
+/// A plugin artifact pushed to kernels.
+#[derive(Debug)]
+pub struct PluginUpload {
+    pub name: String,
+    pub kind: PluginKind,
+    pub artifact: bytes::Bytes,
+    /// Hex sha256 of the artifact, checked by every kernel.
+    pub sha256: String,
+}
+
 pub struct KernelManager {
     kernels: HashMap<KernelAddr, KernelHandle>,
+    auth: Arc<crate::config::KernelAuthConfig>,
 }
 
 impl Default for KernelManager {
     fn default() -> Self {
-        Self::new()
+        Self::new(Default::default())
     }
 }
 
 impl KernelManager {
-    pub fn new() -> Self {
+    pub fn new(auth: crate::config::KernelAuthConfig) -> Self {
         Self {
             kernels: HashMap::new(),
+            auth: Arc::new(auth),
         }
     }
     pub async fn get_kernel_states(&self) -> BTreeMap<KernelAddr, KernelConnectionAndState> {
@@ -139,7 +162,7 @@ impl KernelManager {
     pub async fn add_new_kernel(&mut self, kernel: DiscoveredKernel) {
         let addr = kernel.addr.clone();
         tracing::debug!(?kernel, "Adding new kernel at addr: {:?}", kernel.addr);
-        let Ok(conn) = KernelGrpcConnection::connect(addr.clone())
+        let Ok(conn) = KernelGrpcConnection::connect(addr.clone(), &self.auth)
             .await
             .inspect_err(|e| tracing::error!(?kernel, "cannot connect to addr {addr}: {e}"))
         else {
@@ -150,6 +173,25 @@ impl KernelManager {
         self.kernels
             .insert(addr.clone(), KernelHandle::new_connected(addr, conn));
     }
+    /// Track a kernel which dialed in, replacing an older connection of the same kernel.
+    pub fn add_connected_kernel(&mut self, conn: KernelGrpcConnection) {
+        let addr = conn.addr.as_ref().clone();
+        self.kernels
+            .insert(addr.clone(), KernelHandle::new_connected(addr, conn));
+    }
+    /// Forget the kernel connected through `conn_addr`, unless it has reconnected since.
+    pub fn remove_connection(&mut self, conn_addr: &Arc<KernelAddr>) {
+        let is_current =
+            self.kernels
+                .get(conn_addr.as_ref())
+                .is_some_and(|handle| match &handle.state {
+                    KernelHandleState::Connected(conn) => Arc::ptr_eq(&conn.addr, conn_addr),
+                    KernelHandleState::Disconnected => false,
+                });
+        if is_current {
+            self.kernels.remove(conn_addr.as_ref());
+        }
+    }
     pub async fn remove_kernel(&mut self, addr: &KernelAddr) {
         let handle = self.kernels.remove(addr);
         if let Some(handle) = handle
@@ -168,6 +210,17 @@ impl KernelManager {
             }
         }
     }
+    /// Tracked kernels out of `kernel_addrs`, unknown addresses are skipped.
+    fn selected<'a>(
+        &'a self,
+        kernel_addrs: &'a [KernelAddr],
+    ) -> impl Iterator<Item = (&'a KernelAddr, &'a KernelHandle)> {
+        kernel_addrs
+            .iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|addr| self.kernels.get(addr).map(|kernel| (addr, kernel)))
+    }
     pub async fn update_config(
         &self,
         new_config: switchboard_model::ServiceConfig,
@@ -190,6 +243,74 @@ impl KernelManager {
         task_set.join_all().await.into_iter().collect()
     }
 
+    /// Dry-run a configuration on the selected kernels which are connected.
+    ///
+    /// # Errors
+    /// Each element may contain transport errors; config problems are in the report.
+    pub async fn validate_config(
+        &self,
+        new_config: switchboard_model::ServiceConfig,
+        kernel_addrs: &[KernelAddr],
+    ) -> Vec<(
+        KernelAddr,
+        Result<ValidationReport, KernelGrpcConnectionError>,
+    )> {
+        let mut task_set = tokio::task::JoinSet::new();
+        let new_config = Arc::new(new_config);
+        for (addr, kernel) in self.selected(kernel_addrs) {
+            if let Some(handle) = kernel.get_connected_handle() {
+                let addr = addr.clone();
+                let config = new_config.clone();
+                let mut handle = handle.clone();
+                task_set.spawn(async move {
+                    handle
+                        .validate_config(config.as_ref())
+                        .map(|result| (addr, result))
+                        .await
+                });
+            }
+        }
+        task_set.join_all().await.into_iter().collect()
+    }
+
+    /// Diff the current config of the selected connected kernels against a candidate config.
+    ///
+    /// # Errors
+    /// Each element may contain transport or config decode errors.
+    pub async fn diff_config(
+        &self,
+        new_config: switchboard_model::ServiceConfig,
+        kernel_addrs: &[KernelAddr],
+    ) -> Vec<(
+        KernelAddr,
+        Result<KernelConfigDiff, KernelGrpcConnectionError>,
+    )> {
+        let mut task_set = tokio::task::JoinSet::new();
+        let new_config = Arc::new(new_config);
+        let target_version = new_config.digest_sha256_base64();
+        for (addr, kernel) in self.selected(kernel_addrs) {
+            if let Some(handle) = kernel.get_connected_handle() {
+                let addr = addr.clone();
+                let config = new_config.clone();
+                let target_version = target_version.clone();
+                let mut handle = handle.clone();
+                task_set.spawn(async move {
+                    let result =
+                        handle
+                            .get_current_config()
+                            .await
+                            .map(|current| KernelConfigDiff {
+                                base_version: current.digest_sha256_base64(),
+                                target_version,
+                                diff: ConfigDiff::between(&current, &config),
+                            });
+                    (addr, result)
+                });
+            }
+        }
+        task_set.join_all().await.into_iter().collect()
+    }
+
     /// Prepare a configuration transaction on all tracked kernels.
     ///
     /// # Errors
@@ -345,6 +466,70 @@ impl KernelManager {
         results
     }
 
+    /// Push a plugin artifact to the selected kernels which are connected.
+    ///
+    /// # Errors
+    /// Each element may contain transport or kernel-side load errors.
+    pub async fn push_plugin(
+        &self,
+        plugin: Arc<PluginUpload>,
+        kernel_addrs: &[KernelAddr],
+    ) -> Vec<(KernelAddr, Result<PluginInfo, KernelGrpcConnectionError>)> {
+        let mut task_set = tokio::task::JoinSet::new();
+        for (addr, kernel) in self.selected(kernel_addrs) {
+            if let Some(handle) = kernel.get_connected_handle() {
+                let addr = addr.clone();
+                let plugin = plugin.clone();
+                let mut handle = handle.clone();
+                task_set.spawn(async move {
+                    let result = handle
+                        .push_plugin(&plugin.name, plugin.kind, &plugin.artifact, &plugin.sha256)
+                        .await;
+                    (addr, result)
+                });
+            }
+        }
+        task_set.join_all().await.into_iter().collect()
+    }
+
+    /// List the plugins loaded by the selected kernels which are connected.
+    ///
+    /// # Errors
+    /// Each element may contain transport errors.
+    pub async fn list_plugins(
+        &self,
+        kernel_addrs: &[KernelAddr],
+    ) -> Vec<(KernelAddr, Result<PluginList, KernelGrpcConnectionError>)> {
+        let mut task_set = tokio::task::JoinSet::new();
+        for (addr, kernel) in self.selected(kernel_addrs) {
+            if let Some(handle) = kernel.get_connected_handle() {
+                let addr = addr.clone();
+                let mut handle = handle.clone();
+                task_set.spawn(async move { (addr, handle.list_plugins().await) });
+            }
+        }
+        task_set.join_all().await.into_iter().collect()
+    }
+
+    /// List the http classes of the selected kernels which are connected.
+    ///
+    /// # Errors
+    /// Each element may contain transport errors.
+    pub async fn list_classes(
+        &self,
+        kernel_addrs: &[KernelAddr],
+    ) -> Vec<(KernelAddr, Result<ClassList, KernelGrpcConnectionError>)> {
+        let mut task_set = tokio::task::JoinSet::new();
+        for (addr, kernel) in self.selected(kernel_addrs) {
+            if let Some(handle) = kernel.get_connected_handle() {
+                let addr = addr.clone();
+                let mut handle = handle.clone();
+                task_set.spawn(async move { (addr, handle.list_classes().await) });
+            }
+        }
+        task_set.join_all().await.into_iter().collect()
+    }
+
     pub async fn shutdown_all(&mut self) {
         let addrs: Vec<KernelAddr> = self.kernels.keys().cloned().collect();
         for addr in addrs {
@@ -360,6 +545,15 @@ pub enum RolloutStatus {
     Failed { phase: &'static str },
 }
 
+/// Changes a candidate config would make on one kernel.
+#[derive(Debug, Clone, Serialize)]
+pub struct KernelConfigDiff {
+    /// Version of the config the kernel is running, use it to gate the update.
+    pub base_version: String,
+    pub target_version: String,
+    pub diff: ConfigDiff,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ConfigRolloutReport {
     pub transaction_id: String,
@@ -373,6 +567,19 @@ pub struct ConfigRolloutReport {
     pub rollback_commit_results: Vec<(KernelAddr, ResultObject<()>)>,
     pub rollback_abort_results: Vec<(KernelAddr, ResultObject<()>)>,
 }
+
+impl ConfigRolloutReport {
+    pub fn is_succeeded(&self) -> bool {
+        matches!(self.status, RolloutStatus::Succeeded)
+    }
+    /// Kernels the transaction was sent to.
+    pub fn kernels(&self) -> Vec<KernelAddr> {
+        self.prepare_results
+            .iter()
+            .map(|(addr, _)| addr.clone())
+            .collect()
+    }
+}
 #[derive(Clone)]
 pub struct KernelHandle {
     pub addr: KernelAddr,
@@ -5,7 +5,8 @@ use futures::StreamExt;
 use switchboard_kernel_control::kernel::{GetKernelInfoRequest, WatchStatusRequest};
 use switchboard_model::{
     error::ErrorStack,
-    kernel::{KernelInfo, KernelState},
+    kernel::{ClassList, KernelInfo, KernelState, PluginInfo, PluginKind, PluginList},
+    validation::ValidationReport,
 };
 
 #[derive(Clone)]
@@ -36,13 +37,31 @@ pub enum KernelGrpcConnectionError {
     CommitConfigError(ErrorStack),
     #[error("Kernel abort config error: {0}")]
     AbortConfigError(ErrorStack),
+    #[error("Kernel push plugin error: {0}")]
+    PushPluginError(ErrorStack),
+    #[error("Kernel config decode error: {0}")]
+    ConfigDecodeError(#[from] bincode::error::DecodeError),
+    #[error("Kernel config in unsupported format {0}")]
+    UnsupportedConfigFormat(String),
     #[error("Kernel state parse error: {0}")]
     StateParseError(#[from] switchboard_kernel_control::TryFromProtoKernelStateError),
+    #[error("Kernel auth config error: {0}")]
+    AuthConfigError(#[from] crate::kernel::grpc_client::KernelAuthConfigError),
 }
 
 impl KernelGrpcConnection {
-    pub async fn connect(addr: KernelAddr) -> Result<Self, KernelGrpcConnectionError> {
-        let mut client = addr.connect_grpc().await?;
+    pub async fn connect(
+        addr: KernelAddr,
+        auth: &crate::config::KernelAuthConfig,
+    ) -> Result<Self, KernelGrpcConnectionError> {
+        let client = addr.connect_grpc(auth).await?;
+        Self::from_client(addr, client).await
+    }
+    /// Wrap an already connected client, e.g. one over a reverse tunnel.
+    pub async fn from_client(
+        addr: KernelAddr,
+        mut client: KernelGrpcClient,
+    ) -> Result<Self, KernelGrpcConnectionError> {
         let addr = Arc::new(addr);
         let info = {
             let response = client.get_kernel_info(GetKernelInfoRequest {}).await?;
@@ -171,6 +190,48 @@ impl KernelGrpcConnection {
         }
     }
 
+    /// Fetch the config the kernel is currently running.
+    ///
+    /// # Errors
+    /// Returns an error when the gRPC request fails or the config can't be decoded.
+    pub async fn get_current_config(
+        &mut self,
+    ) -> Result<switchboard_model::ServiceConfig, KernelGrpcConnectionError> {
+        let response = self
+            .client
+            .get_current_config(switchboard_kernel_control::kernel::GetCurrentConfigRequest {})
+            .await?
+            .into_inner();
+        if response.format != CONFIG_FORMAT_BINCODE {
+            return Err(KernelGrpcConnectionError::UnsupportedConfigFormat(
+                response.format,
+            ));
+        }
+        let (config, _) =
+            bincode::decode_from_slice(&response.config, bincode::config::standard())?;
+        Ok(config)
+    }
+
+    /// Dry-run a configuration on kernel and collect every error and warning.
+    ///
+    /// # Errors
+    /// Returns an error when the config can't be encoded or the gRPC request fails.
+    pub async fn validate_config(
+        &mut self,
+        new_config: &switchboard_model::ServiceConfig,
+    ) -> Result<ValidationReport, KernelGrpcConnectionError> {
+        let version = new_config.digest_sha256_base64();
+        let config_bytes = bincode::encode_to_vec(new_config, bincode::config::standard())
+            .map_err(|e| tonic::Status::internal(format!("Config encode error: {}", e)))?;
+        let request = switchboard_kernel_control::kernel::ValidateConfigRequest {
+            format: CONFIG_FORMAT_BINCODE.to_string(),
+            config: config_bytes,
+            version,
+        };
+        let response = self.client.validate_config(request).await?.into_inner();
+        Ok(response.into())
+    }
+
     /// Commit a prepared configuration transaction on kernel.
     ///
     /// # Errors
@@ -230,4 +291,63 @@ impl KernelGrpcConnection {
             )),
         }
     }
+
+    /// Push a plugin artifact to the kernel, which checks it against `sha256` and loads it.
+    ///
+    /// # Errors
+    /// Returns an error when the gRPC request fails or kernel refuses the plugin.
+    pub async fn push_plugin(
+        &mut self,
+        name: &str,
+        kind: PluginKind,
+        artifact: &[u8],
+        sha256: &str,
+    ) -> Result<PluginInfo, KernelGrpcConnectionError> {
+        let request = switchboard_kernel_control::kernel::PushPluginRequest {
+            name: name.to_string(),
+            kind: switchboard_kernel_control::kernel::PluginKind::from(kind).into(),
+            artifact: artifact.to_vec(),
+            sha256: sha256.to_string(),
+        };
+        let response = self.client.push_plugin(request).await?.into_inner();
+        match response.result {
+            Some(switchboard_kernel_control::kernel::push_plugin_response::Result::Success(
+                plugin,
+            )) => Ok(plugin.into()),
+            Some(switchboard_kernel_control::kernel::push_plugin_response::Result::Error(
+                error_stack,
+            )) => Err(KernelGrpcConnectionError::PushPluginError(
+                error_stack.into(),
+            )),
+            None => Err(KernelGrpcConnectionError::GrpcRequestError(
+                tonic::Status::internal("Kernel returned empty result on plugin push"),
+            )),
+        }
+    }
+
+    /// List the plugins loaded by the kernel.
+    ///
+    /// # Errors
+    /// Returns an error when the gRPC request fails.
+    pub async fn list_plugins(&mut self) -> Result<PluginList, KernelGrpcConnectionError> {
+        let response = self
+            .client
+            .list_plugins(switchboard_kernel_control::kernel::ListPluginsRequest {})
+            .await?
+            .into_inner();
+        Ok(response.into())
+    }
+
+    /// List the http classes of the kernel with the JSON Schemas of their configs.
+    ///
+    /// # Errors
+    /// Returns an error when the gRPC request fails.
+    pub async fn list_classes(&mut self) -> Result<ClassList, KernelGrpcConnectionError> {
+        let response = self
+            .client
+            .list_classes(switchboard_kernel_control::kernel::ListClassesRequest {})
+            .await?
+            .into_inner();
+        Ok(response.into())
+    }
 }
@@ -1,4 +1,7 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
 
 use crate::{ControllerContext, kernel::uds::KernelDiscoveryUdsConfig};
 use serde::{Deserialize, Serialize};
@@ -67,7 +70,7 @@ impl ControllerContext {
             let local_kernels = local::scan_local_kernels(local_dir).await?;
             kernels.extend(local_kernels);
         }
-        let k8s_kernels = k8s::scan_k8s_kernels().await?;
+        let k8s_kernels = k8s::scan_k8s_kernels(&self.controller_config.kernel.auth).await?;
         kernels.extend(k8s_kernels);
         #[cfg(target_family = "unix")]
         {
@@ -82,9 +85,11 @@ impl ControllerContext {
             .map(|k| k.addr.clone())
             .collect::<std::collections::HashSet<_>>();
         let mut kernel_manager = self.kernel_manager.write().await;
+        // reverse connected kernels are not discovered, they leave when their tunnel closes
         let existed_kernel_keys = kernel_manager
             .kernels
             .keys()
+            .filter(|addr| !matches!(addr, crate::kernel::KernelAddr::Reverse(_)))
             .cloned()
             .collect::<std::collections::HashSet<_>>();
         let mut deleted_kernels = existed_kernel_keys
@@ -101,18 +106,201 @@ impl ControllerContext {
         }
         Ok(())
     }
+    /// Dry-run a config on the connected kernels of `group`, or every connected kernel,
+    /// without touching live state.
+    pub async fn validate_config(
+        &self,
+        new_config: switchboard_model::ServiceConfig,
+        group: Option<&str>,
+    ) -> Result<
+        Vec<(
+            crate::kernel::KernelAddr,
+            ResultObject<switchboard_model::validation::ValidationReport>,
+        )>,
+        crate::Error,
+    > {
+        let kernel_addrs = self.target_kernels(group).await?;
+        Ok(self
+            .kernel_manager
+            .read()
+            .await
+            .validate_config(new_config, &kernel_addrs)
+            .await
+            .into_iter()
+            .map(|(addr, result)| (addr, ResultObject::from(result)))
+            .collect())
+    }
+    /// Diff the current config of the connected kernels of `group`, or every connected kernel,
+    /// against a candidate config.
+    pub async fn diff_config(
+        &self,
+        new_config: switchboard_model::ServiceConfig,
+        group: Option<&str>,
+    ) -> Result<
+        Vec<(
+            crate::kernel::KernelAddr,
+            ResultObject<crate::kernel::KernelConfigDiff>,
+        )>,
+        crate::Error,
+    > {
+        let kernel_addrs = self.target_kernels(group).await?;
+        Ok(self
+            .kernel_manager
+            .read()
+            .await
+            .diff_config(new_config, &kernel_addrs)
+            .await
+            .into_iter()
+            .map(|(addr, result)| (addr, ResultObject::from(result)))
+            .collect())
+    }
+    /// Push a plugin to the connected kernels of `group`, or every connected kernel. Flows use
+    /// its classes once the next config is committed.
+    pub async fn push_plugin(
+        &self,
+        plugin: crate::kernel::PluginUpload,
+        group: Option<&str>,
+    ) -> Result<
+        Vec<(
+            crate::kernel::KernelAddr,
+            ResultObject<switchboard_model::kernel::PluginInfo>,
+        )>,
+        crate::Error,
+    > {
+        let kernel_addrs = self.target_kernels(group).await?;
+        Ok(self
+            .kernel_manager
+            .read()
+            .await
+            .push_plugin(plugin.into(), &kernel_addrs)
+            .await
+            .into_iter()
+            .map(|(addr, result)| (addr, ResultObject::from(result)))
+            .collect())
+    }
+    /// List the plugins loaded by the connected kernels of `group`, or every connected kernel.
+    pub async fn list_plugins(
+        &self,
+        group: Option<&str>,
+    ) -> Result<
+        Vec<(
+            crate::kernel::KernelAddr,
+            ResultObject<switchboard_model::kernel::PluginList>,
+        )>,
+        crate::Error,
+    > {
+        let kernel_addrs = self.target_kernels(group).await?;
+        Ok(self
+            .kernel_manager
+            .read()
+            .await
+            .list_plugins(&kernel_addrs)
+            .await
+            .into_iter()
+            .map(|(addr, result)| (addr, ResultObject::from(result)))
+            .collect())
+    }
+    /// List the http classes and config schemas of the connected kernels of `group`, or every
+    /// connected kernel.
+    pub async fn list_classes(
+        &self,
+        group: Option<&str>,
+    ) -> Result<
+        Vec<(
+            crate::kernel::KernelAddr,
+            ResultObject<switchboard_model::kernel::ClassList>,
+        )>,
+        crate::Error,
+    > {
+        let kernel_addrs = self.target_kernels(group).await?;
+        Ok(self
+            .kernel_manager
+            .read()
+            .await
+            .list_classes(&kernel_addrs)
+            .await
+            .into_iter()
+            .map(|(addr, result)| (addr, ResultObject::from(result)))
+            .collect())
+    }
+    /// Make sure every connected kernel of `group` still runs the config version a diff was
+    /// reviewed against.
+    ///
+    /// # Errors
+    /// Returns an error when a kernel is not part of `base_versions`, or its config has changed.
+    pub async fn check_base_versions(
+        &self,
+        base_versions: &BTreeMap<String, String>,
+        group: Option<&str>,
+    ) -> Result<(), crate::Error> {
+        let kernel_addrs = self.target_kernels(group).await?;
+        let handles = {
+            let kernel_manager = self.kernel_manager.read().await;
+            kernel_addrs
+                .into_iter()
+                .filter_map(|addr| {
+                    kernel_manager
+                        .kernels
+                        .get(&addr)
+                        .and_then(|kernel| kernel.get_connected_handle())
+                        .map(|h| (addr, h))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (addr, mut handle) in handles {
+            let kernel = addr.to_string();
+            let expected = base_versions
+                .get(&kernel)
+                .ok_or_else(|| crate::Error::ConfigDiffMissingKernel(kernel.clone()))?;
+            let actual = handle.get_current_config().await?.digest_sha256_base64();
+            if &actual != expected {
+                return Err(crate::Error::StaleConfigDiff {
+                    kernel,
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+    /// Apply a config to every tracked kernel with the all-or-nothing transaction.
     pub async fn update_config(
         &self,
         new_config: switchboard_model::ServiceConfig,
     ) -> crate::kernel::ConfigRolloutReport {
-        let previous_config = self.current_config.read().await.clone();
+        let kernel_addrs = self.kernel_manager.read().await.all_kernels();
+        self.update_kernels_config(new_config, &kernel_addrs, None)
+            .await
+    }
+    /// Apply a config to the kernels of a deployment group, or every tracked kernel when
+    /// `group` is `None`, in a transaction of its own.
+    ///
+    /// # Errors
+    /// Returns an error when no group is named `group`.
+    pub async fn update_group_config(
+        &self,
+        new_config: switchboard_model::ServiceConfig,
+        group: Option<&str>,
+    ) -> Result<crate::kernel::ConfigRolloutReport, crate::Error> {
+        let kernel_addrs = self.target_kernels(group).await?;
+        Ok(self
+            .update_kernels_config(new_config, &kernel_addrs, group)
+            .await)
+    }
+    async fn update_kernels_config(
+        &self,
+        new_config: switchboard_model::ServiceConfig,
+        kernel_addrs: &[crate::kernel::KernelAddr],
+        group: Option<&str>,
+    ) -> crate::kernel::ConfigRolloutReport {
+        let previous_config = self.current_config_of(group).await;
         let transaction_id = Uuid::now_v7().to_string();
         let version = new_config.digest_sha256_base64();
         let prepare_raw = self
             .kernel_manager
             .read()
             .await
-            .prepare_config(&transaction_id, new_config.clone())
+            .prepare_config_for(&transaction_id, new_config.clone(), kernel_addrs)
             .await;
         let prepared_kernel_addrs = prepare_raw
             .iter()
@@ -154,7 +342,7 @@ impl ControllerContext {
             .kernel_manager
             .read()
             .await
-            .commit_config(&transaction_id, &version)
+            .commit_config_for(&transaction_id, &version, kernel_addrs)
             .await;
         let commit_results = commit_raw
             .into_iter()
@@ -262,10 +450,7 @@ impl ControllerContext {
                 rollback_abort_results,
             };
         }
-        {
-            let mut current_config_lock = self.current_config.write().await;
-            *current_config_lock = Some(new_config);
-        }
+        self.set_current_config(group, new_config).await;
         crate::kernel::ConfigRolloutReport {
             transaction_id,
             all_or_nothing: true,
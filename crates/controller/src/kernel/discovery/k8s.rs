@@ -44,7 +44,9 @@ fn build_service_endpoint(service_name: &str, namespace: &str, port: u16) -> Str
     format!("grpc://{service_name}.{namespace}.{K8S_SERVICE_DNS_SUFFIX}:{port}")
 }
 
-pub async fn scan_k8s_kernels() -> Result<HashMap<String, DiscoveredKernel>, KernelDiscoveryError> {
+pub async fn scan_k8s_kernels(
+    auth: &crate::config::KernelAuthConfig,
+) -> Result<HashMap<String, DiscoveredKernel>, KernelDiscoveryError> {
     let mut kernels = HashMap::new();
     let Some(client) = crate::utils::k8s::kube_client_if_in_cluster().await? else {
         return Ok(kernels);
@@ -69,7 +71,7 @@ pub async fn scan_k8s_kernels() -> Result<HashMap<String, DiscoveredKernel>, Ker
         let endpoint = build_service_endpoint(&service_name, &namespace, port);
         let addr = KernelAddr::Grpc(endpoint.clone().into());
 
-        let Ok(connection) = KernelGrpcConnection::connect(addr.clone())
+        let Ok(connection) = KernelGrpcConnection::connect(addr.clone(), auth)
             .await
             .inspect_err(|error| {
                 tracing::warn!(
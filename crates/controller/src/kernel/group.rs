@@ -0,0 +1,129 @@
+//! Deployment groups: kernels picked by a label selector, each group with its own config.
+
+use serde::{Deserialize, Serialize};
+use switchboard_model::{ServiceConfig, label::LabelSelector};
+
+use crate::{
+    ControllerContext,
+    kernel::{KernelAddr, KernelManager},
+};
+
+/// A set of kernels receiving the same config, picked by their labels.
+/// # Example
+/// ```toml
+/// [[kernel.groups]]
+/// name = "edge"
+/// selector = "role=edge,region in (eu,us)"
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+pub struct DeploymentGroupConfig {
+    pub name: String,
+    pub selector: LabelSelector,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeploymentGroupState {
+    #[serde(flatten)]
+    pub config: DeploymentGroupConfig,
+    /// Connected kernels matching the selector.
+    pub kernels: Vec<KernelAddr>,
+    /// Version of the last config applied to the group, if any.
+    pub config_version: Option<String>,
+}
+
+impl KernelManager {
+    /// Connected kernels whose labels match `selector`.
+    pub fn select_kernels(&self, selector: &LabelSelector) -> Vec<KernelAddr> {
+        let mut kernels = self
+            .kernels
+            .iter()
+            .filter(|(_, kernel)| {
+                kernel
+                    .get_connected_handle()
+                    .is_some_and(|conn| conn.info_cache.matches(selector))
+            })
+            .map(|(addr, _)| addr.clone())
+            .collect::<Vec<_>>();
+        kernels.sort();
+        kernels
+    }
+    /// Every tracked kernel, including disconnected ones.
+    pub fn all_kernels(&self) -> Vec<KernelAddr> {
+        let mut kernels = self.kernels.keys().cloned().collect::<Vec<_>>();
+        kernels.sort();
+        kernels
+    }
+}
+
+impl ControllerContext {
+    pub fn deployment_group(&self, name: &str) -> Result<&DeploymentGroupConfig, crate::Error> {
+        self.controller_config
+            .kernel
+            .groups
+            .iter()
+            .find(|group| group.name == name)
+            .ok_or_else(|| crate::Error::UnknownDeploymentGroup(name.to_string()))
+    }
+
+    /// Kernels a config for `group` goes to, every tracked kernel when `group` is `None`.
+    ///
+    /// # Errors
+    /// Returns an error when no group is named `group`.
+    pub async fn target_kernels(
+        &self,
+        group: Option<&str>,
+    ) -> Result<Vec<KernelAddr>, crate::Error> {
+        let kernel_manager = self.kernel_manager.read().await;
+        match group {
+            Some(group) => {
+                Ok(kernel_manager.select_kernels(&self.deployment_group(group)?.selector))
+            }
+            None => Ok(kernel_manager.all_kernels()),
+        }
+    }
+
+    pub async fn list_deployment_groups(&self) -> Vec<DeploymentGroupState> {
+        let kernel_manager = self.kernel_manager.read().await;
+        let group_configs = self.group_configs.read().await;
+        self.controller_config
+            .kernel
+            .groups
+            .iter()
+            .map(|group| DeploymentGroupState {
+                config: group.clone(),
+                kernels: kernel_manager.select_kernels(&group.selector),
+                config_version: group_configs
+                    .get(&group.name)
+                    .map(ServiceConfig::digest_sha256_base64),
+            })
+            .collect()
+    }
+
+    /// The config last applied to `group`, falling back to the one applied to every kernel.
+    pub async fn current_config_of(&self, group: Option<&str>) -> Option<ServiceConfig> {
+        if let Some(group) = group
+            && let Some(config) = self.group_configs.read().await.get(group)
+        {
+            return Some(config.clone());
+        }
+        self.current_config.read().await.clone()
+    }
+
+    pub(crate) async fn set_current_config(&self, group: Option<&str>, config: ServiceConfig) {
+        match group {
+            Some(group) => {
+                self.group_configs
+                    .write()
+                    .await
+                    .insert(group.to_string(), config);
+            }
+            None => {
+                // every kernel runs this config now, including the grouped ones
+                self.group_configs.write().await.clear();
+                *self.current_config.write().await = Some(config);
+            }
+        }
+    }
+}
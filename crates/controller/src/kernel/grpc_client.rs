@@ -1,21 +1,96 @@
-use switchboard_kernel_control::kernel::kernel_service_client::KernelServiceClient;
+use std::sync::Arc;
+
+use base64::Engine;
+use switchboard_kernel_control::{
+    auth::{PskClientInterceptor, PskKey},
+    kernel::kernel_service_client::KernelServiceClient,
+    tls::{ClientConfigError, ConnectTlsError, build_client_config},
+};
+use tonic::{service::interceptor::InterceptedService, transport::Channel};
+
+use crate::{
+    ControllerContext,
+    config::{KernelAuthConfig, KernelPskConfig, KernelTlsConfig},
+    kernel::KernelAddr,
+};
+pub type KernelGrpcClient = KernelServiceClient<InterceptedService<Channel, PskClientInterceptor>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KernelAuthConfigError {
+    #[error("Invalid base64 psk key: {0}")]
+    InvalidPskKey(#[from] base64::DecodeError),
+    #[error("Tls config error: {0}")]
+    ClientConfig(#[from] ClientConfigError),
+    #[error("Tls connect error: {0}")]
+    Connect(#[from] ConnectTlsError),
+}
+
+impl KernelPskConfig {
+    pub(crate) fn decode(&self) -> Result<PskKey, KernelAuthConfigError> {
+        Ok(PskKey {
+            id: self.id.clone(),
+            key: base64::engine::general_purpose::STANDARD.decode(&self.key)?,
+        })
+    }
+}
+
+impl KernelAuthConfig {
+    fn psk_interceptor(&self) -> Result<PskClientInterceptor, KernelAuthConfigError> {
+        Ok(PskClientInterceptor {
+            key: self.psk.as_ref().map(KernelPskConfig::decode).transpose()?,
+        })
+    }
+}
+
+impl KernelTlsConfig {
+    fn build_client_config(&self) -> Result<Arc<rustls::ClientConfig>, ClientConfigError> {
+        build_client_config(&self.ca, self.cert.as_deref(), self.key.as_deref())
+    }
+}
 
-use crate::{ControllerContext, kernel::KernelAddr};
-pub type KernelGrpcClient = KernelServiceClient<tonic::transport::Channel>;
 impl KernelAddr {
     pub async fn connect_grpc(
         &self,
-    ) -> Result<KernelServiceClient<tonic::transport::Channel>, tonic::transport::Error> {
+        auth: &KernelAuthConfig,
+    ) -> Result<KernelGrpcClient, super::KernelGrpcConnectionError> {
         tracing::info!("Connecting to kernel at {:?}", self);
-        let endpoint = match self {
-            KernelAddr::Uds(path) => tonic::transport::Endpoint::from_shared(
-                path.as_os_str().as_encoded_bytes().to_vec(),
-            )?,
-            KernelAddr::Grpc(url) => tonic::transport::Endpoint::from_shared(url.to_string())?,
+        let channel = match self {
+            // the uds listener is protected by file permissions, not by controller auth
+            #[cfg(unix)]
+            KernelAddr::Uds(path) => switchboard_kernel_control::uds::connect_uds(path).await?,
+            #[cfg(not(unix))]
+            KernelAddr::Uds(path) => {
+                tonic::transport::Endpoint::from_shared(
+                    path.as_os_str().as_encoded_bytes().to_vec(),
+                )?
+                .connect()
+                .await?
+            }
+            // only reachable through the tunnel the kernel opened
+            KernelAddr::Reverse(_) => {
+                return Err(super::KernelGrpcConnectionError::KernelNotConnected);
+            }
+            KernelAddr::Grpc(url) => match &auth.tls {
+                Some(tls) => switchboard_kernel_control::tls::connect_tls(
+                    url,
+                    tls.build_client_config()
+                        .map_err(KernelAuthConfigError::from)?,
+                    tls.server_name.as_deref(),
+                )
+                .await
+                .map_err(KernelAuthConfigError::from)?,
+                None => {
+                    tonic::transport::Endpoint::from_shared(url.to_string())?
+                        .connect()
+                        .await?
+                }
+            },
+        };
+        let interceptor = match self {
+            KernelAddr::Uds(_) | KernelAddr::Reverse(_) => PskClientInterceptor::default(),
+            KernelAddr::Grpc(_) => auth.psk_interceptor()?,
         };
-        let channel = endpoint.connect().await?;
-        let client = <KernelServiceClient<tonic::transport::Channel>>::new(channel);
-        Ok(client)
+        Ok(KernelServiceClient::with_interceptor(channel, interceptor))
     }
 }
 impl ControllerContext {}
@@ -0,0 +1,331 @@
+//! Kernels dialing in over `ControllerService.Tunnel`, for kernels the controller can't reach.
+//!
+//! The tunnel carries a plain `KernelService` connection, so a reverse connected kernel is
+//! driven by the [`KernelManager`](super::KernelManager) like any discovered one.
+
+use std::{
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use futures::{Stream, StreamExt, TryStreamExt};
+use hyper_util::rt::TokioIo;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use switchboard_kernel_control::{
+    auth::{
+        DEFAULT_MAX_CLOCK_SKEW_SECS, PskClientInterceptor, PskKey, PskKeyId, PskServerInterceptor,
+    },
+    kernel::{
+        TunnelFrame,
+        controller_service_server::{ControllerService, ControllerServiceServer},
+        kernel_service_client::KernelServiceClient,
+    },
+    tls::cert_is_issued_for,
+    tunnel::{KERNEL_ID_METADATA, tunnel},
+};
+use tokio::io::DuplexStream;
+use tokio_util::sync::CancellationToken;
+use tonic::{
+    Request, Response, Status, Streaming,
+    service::interceptor::InterceptedService,
+    transport::server::{TcpConnectInfo, TlsConnectInfo},
+};
+use tracing::Instrument;
+
+use crate::{
+    ControllerContext,
+    config::{KernelAuthConfig, KernelPskConfig, KernelReverseConfig, KernelReverseTlsConfig},
+    kernel::{KernelAddr, KernelGrpcConnection, grpc_client::KernelAuthConfigError},
+};
+
+/// The uri is required by tonic but ignored by the connector, every request goes to the tunnel.
+const TUNNEL_PLACEHOLDER_URI: &str = "http://[::]:0";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReverseListenerError {
+    #[error("Failed to bind kernel reverse listener: {0}")]
+    Bind(#[source] std::io::Error),
+    #[error("Kernel auth config error: {0}")]
+    Auth(#[from] KernelAuthConfigError),
+    #[error("Failed to read {path:?}: {source}")]
+    ReadPem {
+        path: PathBuf,
+        #[source]
+        source: rustls::pki_types::pem::Error,
+    },
+    #[error("Tls config error: {0}")]
+    Rustls(#[from] rustls::Error),
+    #[error("Client certificate verifier error: {0}")]
+    ClientVerifier(#[from] rustls::server::VerifierBuilderError),
+    #[error("Pre-shared keys need the kernel reverse listener to have tls configured")]
+    PskWithoutTls,
+}
+
+impl KernelReverseConfig {
+    /// Accepts tunnels signed with a per kernel key, or with the key the controller signs its
+    /// requests with when there are none.
+    fn psk_server_interceptor(
+        &self,
+        auth: &KernelAuthConfig,
+    ) -> Result<PskServerInterceptor, KernelAuthConfigError> {
+        let keys: Result<Arc<[PskKey]>, _> = if self.psk.is_empty() {
+            auth.psk.iter().map(KernelPskConfig::decode).collect()
+        } else {
+            self.psk.iter().map(KernelPskConfig::decode).collect()
+        };
+        Ok(PskServerInterceptor::new(
+            keys?,
+            DEFAULT_MAX_CLOCK_SKEW_SECS,
+        ))
+    }
+}
+
+impl KernelReverseTlsConfig {
+    fn build_acceptor(&self) -> Result<tokio_rustls::TlsAcceptor, ReverseListenerError> {
+        let read_error = |path: &PathBuf| {
+            let path = path.clone();
+            move |source| ReverseListenerError::ReadPem { path, source }
+        };
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca {
+            Some(path) => {
+                let mut roots = rustls::RootCertStore::empty();
+                for cert in CertificateDer::pem_file_iter(path).map_err(read_error(path))? {
+                    roots.add(cert.map_err(read_error(path))?)?;
+                }
+                let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(
+                    roots.into(),
+                    provider,
+                )
+                .build()?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let certs = CertificateDer::pem_file_iter(&self.cert)
+            .map_err(read_error(&self.cert))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(read_error(&self.cert))?;
+        let key = PrivateKeyDer::from_pem_file(&self.key).map_err(read_error(&self.key))?;
+        let mut config = builder.with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+pub struct ReverseListenerHandle {
+    ct: CancellationToken,
+    task: tokio::task::JoinHandle<Result<(), tonic::transport::Error>>,
+}
+
+impl ReverseListenerHandle {
+    pub async fn cancel(self) {
+        self.ct.cancel();
+        match self.task.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::error!("Kernel reverse listener exited with error: {}", e),
+            Err(e) => tracing::error!("Kernel reverse listener task join error: {}", e),
+        }
+    }
+}
+
+struct ControllerServiceImpl {
+    context: ControllerContext,
+    /// Tunnels are signed with per kernel keys, whose id must be the kernel id.
+    psk_per_kernel: bool,
+    /// Kernels present a client certificate, which must be issued for the kernel id.
+    client_cert: bool,
+}
+
+impl ControllerServiceImpl {
+    /// Check the kernel id a tunnel claims against the credentials it was opened with.
+    fn check_kernel_id<T>(&self, request: &Request<T>, kernel_id: &str) -> Result<(), Status> {
+        if self.psk_per_kernel
+            && request
+                .extensions()
+                .get::<PskKeyId>()
+                .is_none_or(|PskKeyId(id)| id != kernel_id)
+        {
+            return Err(Status::permission_denied(format!(
+                "kernel {kernel_id} signed the tunnel with another kernel's key"
+            )));
+        }
+        if self.client_cert {
+            let certs = request
+                .extensions()
+                .get::<TlsConnectInfo<TcpConnectInfo>>()
+                .and_then(TlsConnectInfo::peer_certs)
+                .ok_or_else(|| Status::unauthenticated("missing client certificate"))?;
+            if !certs
+                .first()
+                .is_some_and(|cert| cert_is_issued_for(cert, kernel_id))
+            {
+                return Err(Status::permission_denied(format!(
+                    "client certificate is not issued for kernel {kernel_id}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl ControllerService for ControllerServiceImpl {
+    type TunnelStream = Pin<Box<dyn Stream<Item = Result<TunnelFrame, Status>> + Send>>;
+    async fn tunnel(
+        &self,
+        request: Request<Streaming<TunnelFrame>>,
+    ) -> Result<Response<Self::TunnelStream>, Status> {
+        let kernel_id = request
+            .metadata()
+            .get(KERNEL_ID_METADATA)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| Status::invalid_argument("missing kernel id"))?
+            .to_string();
+        self.check_kernel_id(&request, &kernel_id)
+            .inspect_err(|e| tracing::warn!("Rejected kernel reverse tunnel: {}", e.message()))?;
+        let (io, outbound, inbound) = tunnel();
+        let closed = inbound.forward(request.into_inner());
+        let context = self.context.clone();
+        // the kernel only answers once this response is streaming, so connect in the background
+        tokio::spawn(async move {
+            context
+                .serve_reverse_kernel(KernelAddr::Reverse(kernel_id.into()), io, closed)
+                .await
+        });
+        Ok(Response::new(Box::pin(outbound.map(Ok))))
+    }
+}
+
+impl ControllerContext {
+    pub(crate) async fn spawn_reverse_listener(&self) -> Result<(), ReverseListenerError> {
+        let Some(config) = self.controller_config.kernel.reverse.clone() else {
+            return Ok(());
+        };
+        let auth = &self.controller_config.kernel.auth;
+        let interceptor = config.psk_server_interceptor(auth)?;
+        if !interceptor.keys.is_empty() && config.tls.is_none() {
+            return Err(ReverseListenerError::PskWithoutTls);
+        }
+        let tls_acceptor = config
+            .tls
+            .as_ref()
+            .map(KernelReverseTlsConfig::build_acceptor)
+            .transpose()?;
+        let client_cert = config
+            .tls
+            .as_ref()
+            .is_some_and(|tls| tls.client_ca.is_some());
+        if interceptor.keys.is_empty() && !client_cert {
+            tracing::warn!(
+                "Kernel reverse listener on {} accepts unauthenticated kernels, configure `kernel.reverse.psk` or `kernel.reverse.tls.client_ca`",
+                config.bind
+            );
+        } else if config.psk.is_empty() && !client_cert {
+            tracing::warn!(
+                "Kernels on the reverse listener {} share `kernel.auth.psk` and can connect under any kernel id, configure `kernel.reverse.psk` or `kernel.reverse.tls.client_ca`",
+                config.bind
+            );
+        }
+        let listener = tokio::net::TcpListener::bind(config.bind)
+            .await
+            .map_err(ReverseListenerError::Bind)?;
+        let service = InterceptedService::new(
+            ControllerServiceServer::new(ControllerServiceImpl {
+                context: self.clone(),
+                psk_per_kernel: !config.psk.is_empty(),
+                client_cert,
+            }),
+            interceptor,
+        );
+        let ct = CancellationToken::new();
+        let shutdown = ct.child_token().cancelled_owned();
+        let span = tracing::info_span!("kernel-reverse-listener", bind = %config.bind);
+        tracing::info!("Kernel reverse listener on {}", config.bind);
+        let incoming = tokio_stream::wrappers::TcpListenerStream::new(listener);
+        let task = match tls_acceptor {
+            Some(tls) => {
+                let incoming = incoming.and_then(move |stream| {
+                    let tls = tls.clone();
+                    async move {
+                        tls.accept(stream).await.inspect_err(|e| {
+                            tracing::warn!("Failed to accept kernel reverse tls connection: {}", e)
+                        })
+                    }
+                });
+                tokio::spawn(
+                    tonic::transport::Server::builder()
+                        .add_service(service)
+                        .serve_with_incoming_shutdown(incoming, shutdown)
+                        .instrument(span),
+                )
+            }
+            None => tokio::spawn(
+                tonic::transport::Server::builder()
+                    .add_service(service)
+                    .serve_with_incoming_shutdown(incoming, shutdown)
+                    .instrument(span),
+            ),
+        };
+        *self.reverse_listener.write().await = Some(ReverseListenerHandle { ct, task });
+        Ok(())
+    }
+
+    pub(crate) async fn cancel_reverse_listener(&self) {
+        if let Some(handle) = self.reverse_listener.write().await.take() {
+            handle.cancel().await;
+        }
+    }
+
+    /// Track the kernel behind a tunnel until the tunnel closes.
+    async fn serve_reverse_kernel(
+        &self,
+        addr: KernelAddr,
+        io: DuplexStream,
+        closed: tokio::task::JoinHandle<()>,
+    ) {
+        // a tunnel holds a single connection, reconnecting means the kernel dials in again
+        let io = Arc::new(Mutex::new(Some(io)));
+        let channel = tonic::transport::Endpoint::from_static(TUNNEL_PLACEHOLDER_URI)
+            .connect_with_connector(tower::service_fn(move |_| {
+                let io = io.lock().ok().and_then(|mut io| io.take());
+                async move {
+                    io.map(TokioIo::new).ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::NotConnected,
+                            "reverse tunnel is closed",
+                        )
+                    })
+                }
+            }))
+            .await;
+        let client = channel.map(|channel| {
+            KernelServiceClient::with_interceptor(channel, PskClientInterceptor::default())
+        });
+        let conn = match client {
+            Ok(client) => KernelGrpcConnection::from_client(addr.clone(), client).await,
+            Err(e) => Err(e.into()),
+        };
+        let conn = match conn {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::error!(%addr, "Failed to connect reverse tunneled kernel: {}", e);
+                closed.abort();
+                return;
+            }
+        };
+        let conn_addr = conn.addr.clone();
+        tracing::info!(%addr, info = ?conn.info_cache, "Kernel connected through reverse tunnel");
+        self.kernel_manager.write().await.add_connected_kernel(conn);
+        let _ = closed.await;
+        tracing::info!(%addr, "Kernel reverse tunnel closed");
+        self.kernel_manager
+            .write()
+            .await
+            .remove_connection(&conn_addr);
+    }
+}
@@ -0,0 +1,674 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt, Empty, Limited};
+use serde::{Deserialize, Serialize};
+use switchboard_model::{ServiceConfig, error::ResultObject, kernel::KernelStateKind};
+use tokio::sync::{RwLock, watch};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    ControllerContext,
+    history::{AppliedConfigRecord, AppliedConfigSource},
+    kernel::KernelAddr,
+};
+
+const ROLLBACK_REASON_ABORTED: &str = "rollout aborted";
+const ERROR_RATE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The endpoint answers a single number, anything longer is not an error rate.
+const MAX_ERROR_RATE_BYTES: usize = 1024;
+
+/// Update kernels batch by batch: a canary first, then percentage batches,
+/// baking and checking gates after each one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProgressiveRolloutPolicy {
+    /// Number of kernels in the first, canary batch.
+    pub canary: usize,
+    /// Share of all kernels updated by each batch after the canary, in percent.
+    pub batch_percent: u8,
+    /// Time to wait after each batch before checking its gates.
+    pub bake_secs: u64,
+    pub gate: RolloutGate,
+    /// Roll every updated kernel back to its previous config when a batch fails.
+    pub auto_rollback: bool,
+}
+
+impl Default for ProgressiveRolloutPolicy {
+    fn default() -> Self {
+        Self {
+            canary: 1,
+            batch_percent: 25,
+            bake_secs: 30,
+            gate: RolloutGate::default(),
+            auto_rollback: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RolloutGate {
+    /// Require every updated kernel to be connected and running the target version.
+    pub health: bool,
+    pub error_rate: Option<ErrorRateGate>,
+}
+
+impl Default for RolloutGate {
+    fn default() -> Self {
+        Self {
+            health: true,
+            error_rate: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorRateGate {
+    /// An `http://` url answering with the current error rate as a plain number, e.g. `0.02`.
+    pub url: String,
+    pub max: f64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ErrorRateGateError {
+    #[error("invalid error rate url: {0}")]
+    InvalidUrl(#[from] http::uri::InvalidUri),
+    #[error("error rate request failed: {0}")]
+    Request(#[from] hyper_util::client::legacy::Error),
+    #[error("error rate response body error: {0}")]
+    Body(Box<dyn std::error::Error + Send + Sync>),
+    #[error("error rate endpoint didn't answer within {0:?}")]
+    Timeout(Duration),
+    #[error("error rate endpoint answered {0}")]
+    Status(http::StatusCode),
+    #[error("error rate response is not a number: {0:?}")]
+    NotANumber(String),
+}
+
+impl ErrorRateGate {
+    async fn fetch(&self) -> Result<f64, ErrorRateGateError> {
+        let client =
+            hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+                .build_http::<Empty<Bytes>>();
+        let uri = self.url.parse()?;
+        let body = tokio::time::timeout(ERROR_RATE_TIMEOUT, async {
+            let response = client.get(uri).await?;
+            if !response.status().is_success() {
+                return Err(ErrorRateGateError::Status(response.status()));
+            }
+            Limited::new(response.into_body(), MAX_ERROR_RATE_BYTES)
+                .collect()
+                .await
+                .map_err(ErrorRateGateError::Body)
+        })
+        .await
+        .map_err(|_| ErrorRateGateError::Timeout(ERROR_RATE_TIMEOUT))??
+        .to_bytes();
+        let text = String::from_utf8_lossy(&body).trim().to_string();
+        text.parse()
+            .map_err(|_| ErrorRateGateError::NotANumber(text))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RolloutState {
+    Running { batch: usize },
+    Baking { batch: usize, until: DateTime<Utc> },
+    Succeeded,
+    RolledBack { reason: String },
+    Failed { reason: String },
+}
+
+impl RolloutState {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            RolloutState::Succeeded | RolloutState::RolledBack { .. } | RolloutState::Failed { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatchReport {
+    pub transaction_id: String,
+    pub prepare_results: Vec<(KernelAddr, ResultObject<()>)>,
+    pub commit_results: Vec<(KernelAddr, ResultObject<()>)>,
+    pub abort_results: Vec<(KernelAddr, ResultObject<()>)>,
+    pub gate_failures: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloutProgress {
+    pub id: String,
+    /// Deployment group the rollout targets, every kernel when unset.
+    pub group: Option<String>,
+    pub target_version: String,
+    pub author: Option<String>,
+    pub policy: ProgressiveRolloutPolicy,
+    pub state: RolloutState,
+    /// Paused rollouts finish their current batch and wait before the next one.
+    pub paused: bool,
+    pub batches: Vec<Vec<KernelAddr>>,
+    pub reports: Vec<BatchReport>,
+    pub rollback_results: Vec<(KernelAddr, ResultObject<()>)>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub struct RolloutHandle {
+    progress: Arc<RwLock<RolloutProgress>>,
+    pause: watch::Sender<bool>,
+    ct: CancellationToken,
+}
+
+impl RolloutHandle {
+    pub async fn progress(&self) -> RolloutProgress {
+        self.progress.read().await.clone()
+    }
+    pub async fn is_finished(&self) -> bool {
+        self.progress.read().await.state.is_finished()
+    }
+    pub async fn set_paused(&self, paused: bool) {
+        self.pause.send_replace(paused);
+        self.progress.write().await.paused = paused;
+    }
+    /// Stop the rollout and roll back the kernels updated so far.
+    pub fn abort(&self) {
+        self.ct.cancel();
+    }
+}
+
+/// Split kernels into a canary batch followed by batches of `batch_percent` of all kernels.
+pub fn plan_batches(
+    mut kernels: Vec<KernelAddr>,
+    policy: &ProgressiveRolloutPolicy,
+) -> Vec<Vec<KernelAddr>> {
+    let total = kernels.len();
+    let mut batches = Vec::new();
+    if total == 0 {
+        return batches;
+    }
+    let canary = policy.canary.clamp(1, total);
+    let rest = kernels.split_off(canary);
+    batches.push(kernels);
+    let batch_size = (total * usize::from(policy.batch_percent.clamp(1, 100))).div_ceil(100);
+    batches.extend(rest.chunks(batch_size.max(1)).map(<[_]>::to_vec));
+    batches
+}
+
+#[derive(Debug)]
+enum BatchOutcome {
+    Done,
+    Failed(String),
+    Aborted,
+}
+
+impl BatchOutcome {
+    /// The state a rollout ends in, and whether the kernels it updated are rolled back.
+    fn finished_state(self, auto_rollback: bool) -> (RolloutState, bool) {
+        match self {
+            BatchOutcome::Done => (RolloutState::Succeeded, false),
+            BatchOutcome::Failed(reason) if auto_rollback => {
+                (RolloutState::RolledBack { reason }, true)
+            }
+            BatchOutcome::Failed(reason) => (RolloutState::Failed { reason }, false),
+            BatchOutcome::Aborted => (
+                RolloutState::RolledBack {
+                    reason: ROLLBACK_REASON_ABORTED.to_string(),
+                },
+                true,
+            ),
+        }
+    }
+}
+
+impl ControllerContext {
+    /// Start a progressive rollout in the background, to the kernels of `group` or to
+    /// every connected kernel.
+    ///
+    /// # Errors
+    /// Returns an error when a running rollout shares a kernel with this one, when the
+    /// group is unknown, or when the current config of a kernel can't be fetched for rollback.
+    pub async fn start_rollout(
+        &self,
+        new_config: ServiceConfig,
+        policy: ProgressiveRolloutPolicy,
+        author: Option<String>,
+        group: Option<String>,
+    ) -> Result<RolloutProgress, crate::Error> {
+        let kernel_addrs = self.target_kernels(group.as_deref()).await?;
+        let handles = {
+            let kernel_manager = self.kernel_manager.read().await;
+            kernel_addrs
+                .iter()
+                .filter_map(|addr| {
+                    kernel_manager
+                        .kernels
+                        .get(addr)
+                        .and_then(|kernel| kernel.get_connected_handle())
+                        .map(|h| (addr.clone(), h))
+                })
+                .collect::<Vec<_>>()
+        };
+        // snapshot every kernel's config, so each can be rolled back to what it ran, before
+        // taking the rollouts lock so a slow kernel doesn't hold up the other groups
+        let mut previous_configs = BTreeMap::new();
+        for (addr, mut handle) in handles {
+            let config = handle.get_current_config().await?;
+            previous_configs.insert(addr, config);
+        }
+        let mut rollouts = self.rollouts.write().await;
+        for existing in rollouts.values() {
+            let existing = existing.progress().await;
+            if !existing.state.is_finished()
+                && existing
+                    .batches
+                    .iter()
+                    .flatten()
+                    .any(|addr| kernel_addrs.contains(addr))
+            {
+                return Err(crate::Error::RolloutInProgress(existing.id));
+            }
+        }
+        let batches = plan_batches(previous_configs.keys().cloned().collect(), &policy);
+        let progress = RolloutProgress {
+            id: Uuid::now_v7().to_string(),
+            group: group.clone(),
+            target_version: new_config.digest_sha256_base64(),
+            author,
+            policy,
+            state: RolloutState::Running { batch: 0 },
+            paused: false,
+            batches,
+            reports: Vec::new(),
+            rollback_results: Vec::new(),
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        let (pause, pause_receiver) = watch::channel(false);
+        let handle = RolloutHandle {
+            progress: Arc::new(RwLock::new(progress.clone())),
+            pause,
+            ct: CancellationToken::new(),
+        };
+        tokio::spawn(self.clone().run_rollout(
+            handle.clone(),
+            pause_receiver,
+            new_config,
+            previous_configs,
+        ));
+        rollouts.insert(group, handle);
+        Ok(progress)
+    }
+
+    async fn run_rollout(
+        self,
+        handle: RolloutHandle,
+        mut pause: watch::Receiver<bool>,
+        new_config: ServiceConfig,
+        previous_configs: BTreeMap<KernelAddr, ServiceConfig>,
+    ) {
+        let (batches, target_version, policy) = {
+            let progress = handle.progress.read().await;
+            (
+                progress.batches.clone(),
+                progress.target_version.clone(),
+                progress.policy.clone(),
+            )
+        };
+        let mut updated = Vec::new();
+        let mut outcome = BatchOutcome::Done;
+        for (index, batch) in batches.iter().enumerate() {
+            tokio::select! {
+                _ = handle.ct.cancelled() => {
+                    outcome = BatchOutcome::Aborted;
+                    break;
+                }
+                _ = pause.wait_for(|paused| !paused) => {}
+            }
+            handle.progress.write().await.state = RolloutState::Running { batch: index };
+            let mut report = BatchReport {
+                transaction_id: Uuid::now_v7().to_string(),
+                ..Default::default()
+            };
+            outcome = self
+                .rollout_batch(
+                    &handle,
+                    &mut report,
+                    &mut updated,
+                    batch,
+                    &new_config,
+                    &target_version,
+                    &policy,
+                    index,
+                )
+                .await;
+            handle.progress.write().await.reports.push(report);
+            if !matches!(outcome, BatchOutcome::Done) {
+                break;
+            }
+        }
+        let (state, rollback) = outcome.finished_state(policy.auto_rollback);
+        if rollback {
+            self.rollback_kernels(&handle, &updated, &previous_configs)
+                .await;
+        } else if matches!(state, RolloutState::Succeeded) {
+            let group = handle.progress.read().await.group.clone();
+            self.set_current_config(group.as_deref(), new_config.clone())
+                .await;
+        }
+        tracing::info!(?state, "Rollout finished");
+        let progress = {
+            let mut progress = handle.progress.write().await;
+            progress.state = state;
+            progress.finished_at = Some(Utc::now());
+            progress.clone()
+        };
+        self.record_applied_config(
+            AppliedConfigRecord::new(
+                new_config,
+                progress.author.clone(),
+                progress.batches.concat(),
+                matches!(progress.state, RolloutState::Succeeded),
+                AppliedConfigSource::Rollout {
+                    rollout_id: progress.id.clone(),
+                },
+                &progress,
+            )
+            .with_group(progress.group.clone()),
+        )
+        .await;
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn rollout_batch(
+        &self,
+        handle: &RolloutHandle,
+        report: &mut BatchReport,
+        updated: &mut Vec<KernelAddr>,
+        batch: &[KernelAddr],
+        new_config: &ServiceConfig,
+        target_version: &str,
+        policy: &ProgressiveRolloutPolicy,
+        index: usize,
+    ) -> BatchOutcome {
+        let transaction_id = report.transaction_id.clone();
+        let prepare_raw = self
+            .kernel_manager
+            .read()
+            .await
+            .prepare_config_for(&transaction_id, new_config.clone(), batch)
+            .await;
+        let prepared = prepare_raw
+            .iter()
+            .filter_map(|(addr, result)| result.as_ref().ok().map(|_| addr.clone()))
+            .collect::<Vec<_>>();
+        report.prepare_results = prepare_raw
+            .into_iter()
+            .map(|(addr, result)| (addr, ResultObject::from(result)))
+            .collect();
+        if prepared.len() != batch.len() {
+            report.abort_results = self.abort_batch(&transaction_id, &prepared).await;
+            return BatchOutcome::Failed(format!("batch {index} failed to prepare"));
+        }
+        let commit_raw = self
+            .kernel_manager
+            .read()
+            .await
+            .commit_config_for(&transaction_id, target_version, batch)
+            .await;
+        updated.extend(
+            commit_raw
+                .iter()
+                .filter_map(|(addr, result)| result.as_ref().ok().map(|_| addr.clone())),
+        );
+        report.commit_results = commit_raw
+            .into_iter()
+            .map(|(addr, result)| (addr, ResultObject::from(result)))
+            .collect();
+        if report
+            .commit_results
+            .iter()
+            .any(|(_, result)| matches!(result, ResultObject::Error(_)))
+        {
+            report.abort_results = self.abort_batch(&transaction_id, &prepared).await;
+            return BatchOutcome::Failed(format!("batch {index} failed to commit"));
+        }
+        let bake = Duration::from_secs(policy.bake_secs);
+        handle.progress.write().await.state = RolloutState::Baking {
+            batch: index,
+            until: Utc::now() + bake,
+        };
+        tokio::select! {
+            _ = handle.ct.cancelled() => return BatchOutcome::Aborted,
+            _ = tokio::time::sleep(bake) => {}
+        }
+        report.gate_failures = tokio::select! {
+            _ = handle.ct.cancelled() => return BatchOutcome::Aborted,
+            failures = self.check_rollout_gate(&policy.gate, updated, target_version) => failures,
+        };
+        if report.gate_failures.is_empty() {
+            BatchOutcome::Done
+        } else {
+            BatchOutcome::Failed(format!("batch {index} failed its gate"))
+        }
+    }
+
+    async fn abort_batch(
+        &self,
+        transaction_id: &str,
+        prepared: &[KernelAddr],
+    ) -> Vec<(KernelAddr, ResultObject<()>)> {
+        self.kernel_manager
+            .read()
+            .await
+            .abort_config_for(transaction_id, prepared)
+            .await
+            .into_iter()
+            .map(|(addr, result)| (addr, ResultObject::from(result)))
+            .collect()
+    }
+
+    /// Check the gate against every kernel updated so far, returning the failures.
+    async fn check_rollout_gate(
+        &self,
+        gate: &RolloutGate,
+        updated: &[KernelAddr],
+        target_version: &str,
+    ) -> Vec<String> {
+        let mut failures = Vec::new();
+        if gate.health {
+            for addr in updated {
+                let handle = self
+                    .kernel_manager
+                    .read()
+                    .await
+                    .kernels
+                    .get(addr)
+                    .and_then(|kernel| kernel.get_connected_handle());
+                let Some(mut handle) = handle else {
+                    failures.push(format!("kernel {addr} is not connected"));
+                    continue;
+                };
+                match handle.get_current_state().await {
+                    Ok(state) => match state.kind {
+                        KernelStateKind::Running { config_version }
+                            if config_version == target_version => {}
+                        kind => failures.push(format!("kernel {addr} is unhealthy: {kind:?}")),
+                    },
+                    Err(e) => failures.push(format!("kernel {addr} state error: {e}")),
+                }
+            }
+        }
+        if let Some(error_rate) = &gate.error_rate {
+            match error_rate.fetch().await {
+                Ok(rate) if rate <= error_rate.max => {}
+                Ok(rate) => failures.push(format!("error rate {rate} is above {}", error_rate.max)),
+                Err(e) => failures.push(e.to_string()),
+            }
+        }
+        failures
+    }
+
+    /// Put every updated kernel back on the config it ran before the rollout.
+    async fn rollback_kernels(
+        &self,
+        handle: &RolloutHandle,
+        updated: &[KernelAddr],
+        previous_configs: &BTreeMap<KernelAddr, ServiceConfig>,
+    ) {
+        let mut results = Vec::new();
+        for addr in updated {
+            let Some(config) = previous_configs.get(addr) else {
+                continue;
+            };
+            let transaction_id = format!("{}-rollback", Uuid::now_v7());
+            let version = config.digest_sha256_base64();
+            let kernel_manager = self.kernel_manager.read().await;
+            let targets = std::slice::from_ref(addr);
+            let prepared = kernel_manager
+                .prepare_config_for(&transaction_id, config.clone(), targets)
+                .await;
+            let result = match prepared.into_iter().next() {
+                Some((_, Ok(()))) => kernel_manager
+                    .commit_config_for(&transaction_id, &version, targets)
+                    .await
+                    .into_iter()
+                    .next()
+                    .map(|(_, result)| result),
+                Some((_, Err(e))) => Some(Err(e)),
+                None => None,
+            };
+            if let Some(result) = result {
+                results.push((addr.clone(), ResultObject::from(result)));
+            }
+        }
+        handle.progress.write().await.rollback_results = results;
+    }
+
+    /// The latest rollout to `group`, or to every kernel when `group` is `None`.
+    pub async fn get_rollout(&self, group: Option<&str>) -> Option<RolloutHandle> {
+        self.rollouts
+            .read()
+            .await
+            .get(&group.map(str::to_string))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn kernels(count: usize) -> Vec<KernelAddr> {
+        (0..count)
+            .map(|i| KernelAddr::Grpc(format!("http://kernel-{i}:8080").into()))
+            .collect()
+    }
+
+    fn batch_sizes(count: usize, canary: usize, batch_percent: u8) -> Vec<usize> {
+        let policy = ProgressiveRolloutPolicy {
+            canary,
+            batch_percent,
+            ..Default::default()
+        };
+        plan_batches(kernels(count), &policy)
+            .iter()
+            .map(Vec::len)
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_batches() {
+        assert!(batch_sizes(0, 1, 25).is_empty());
+        assert_eq!(batch_sizes(1, 1, 25), [1]);
+        assert_eq!(batch_sizes(10, 1, 25), [1, 3, 3, 3]);
+        assert_eq!(batch_sizes(10, 2, 50), [2, 5, 3]);
+        // the canary is at least one kernel and at most all of them
+        assert_eq!(batch_sizes(4, 0, 100), [1, 3]);
+        assert_eq!(batch_sizes(4, 10, 25), [4]);
+        // a zero percent batch still makes progress
+        assert_eq!(batch_sizes(3, 1, 0), [1, 1, 1]);
+        // every kernel is planned exactly once, in order
+        let policy = ProgressiveRolloutPolicy::default();
+        assert_eq!(plan_batches(kernels(7), &policy).concat(), kernels(7));
+    }
+
+    #[test]
+    fn test_finished_state() {
+        let (state, rollback) = BatchOutcome::Done.finished_state(true);
+        assert!(matches!(state, RolloutState::Succeeded) && !rollback);
+
+        let failed = || BatchOutcome::Failed("batch 1 failed its gate".to_string());
+        let (state, rollback) = failed().finished_state(true);
+        assert!(rollback);
+        assert!(
+            matches!(state, RolloutState::RolledBack { reason } if reason == "batch 1 failed its gate")
+        );
+        let (state, rollback) = failed().finished_state(false);
+        assert!(!rollback);
+        assert!(matches!(state, RolloutState::Failed { .. }));
+
+        // an abort always rolls back, whatever the policy
+        let (state, rollback) = BatchOutcome::Aborted.finished_state(false);
+        assert!(rollback);
+        assert!(
+            matches!(state, RolloutState::RolledBack { reason } if reason == ROLLBACK_REASON_ABORTED)
+        );
+    }
+
+    #[test]
+    fn test_state_is_finished() {
+        assert!(!RolloutState::Running { batch: 0 }.is_finished());
+        assert!(
+            !RolloutState::Baking {
+                batch: 1,
+                until: Utc::now()
+            }
+            .is_finished()
+        );
+        assert!(RolloutState::Succeeded.is_finished());
+        assert!(
+            RolloutState::Failed {
+                reason: String::new()
+            }
+            .is_finished()
+        );
+        assert_eq!(
+            serde_json::to_value(RolloutState::Running { batch: 2 }).unwrap(),
+            serde_json::json!({ "state": "running", "batch": 2 })
+        );
+    }
+
+    /// Answer a single request with `body`, returning the url to fetch it from.
+    async fn serve_once(body: Vec<u8>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/error_rate", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 1024]).await.unwrap();
+            let head = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", body.len());
+            stream.write_all(head.as_bytes()).await.unwrap();
+            let _ = stream.write_all(&body).await;
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_error_rate_fetch() {
+        let gate = |url| ErrorRateGate { url, max: 0.05 };
+        let rate = gate(serve_once(b"0.02\n".to_vec()).await).fetch().await;
+        assert!(matches!(rate, Ok(rate) if (rate - 0.02).abs() < f64::EPSILON));
+        // a body longer than a number is refused before it is read to the end
+        let long = vec![b'0'; MAX_ERROR_RATE_BYTES + 1];
+        let rate = gate(serve_once(long).await).fetch().await;
+        assert!(matches!(rate, Err(ErrorRateGateError::Body(_))));
+    }
+}
@@ -0,0 +1,397 @@
+//! Leader election between controllers sharing kernels.
+//!
+//! Only the leader runs the k8s reconcilers and changes kernel configs, followers keep
+//! discovering kernels and serve the read-only part of the http api.
+
+use std::{collections::VecDeque, time::Duration};
+
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::MicroTime;
+use kube::api::{ObjectMeta, PostParams};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::ControllerContext;
+
+/// Transitions kept for the state api.
+const MAX_TRANSITIONS: usize = 32;
+
+/// Leader election between controllers, a k8s `Lease` in k8s mode and a storage lease otherwise.
+/// # Example
+/// ```toml
+/// [leader_election]
+/// lease_name = "switchboard-controller"
+/// lease_duration_secs = 15
+/// renew_interval_secs = 5
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+#[serde(default)]
+pub struct LeaderElectionConfig {
+    /// Defaults to `HOSTNAME`, which is the pod name in k8s, or a random id.
+    pub identity: Option<String>,
+    pub lease_name: String,
+    /// Namespace of the k8s `Lease`, defaults to the controller's namespace.
+    pub namespace: Option<String>,
+    pub lease_duration_secs: u64,
+    pub renew_interval_secs: u64,
+}
+
+impl Default for LeaderElectionConfig {
+    fn default() -> Self {
+        Self {
+            identity: None,
+            lease_name: "switchboard-controller".to_string(),
+            namespace: None,
+            lease_duration_secs: 15,
+            renew_interval_secs: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderElectionBackend {
+    /// No leader election configured, this controller always leads.
+    Disabled,
+    Kubernetes,
+    Storage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LeadershipTransition {
+    pub at: DateTime<Utc>,
+    pub leader: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LeadershipState {
+    pub identity: String,
+    pub backend: LeaderElectionBackend,
+    pub is_leader: bool,
+    /// Current holder of the lease, as last seen.
+    pub leader: Option<String>,
+    pub since: DateTime<Utc>,
+    pub transitions: VecDeque<LeadershipTransition>,
+}
+
+impl LeadershipState {
+    pub fn new(identity: String, backend: LeaderElectionBackend) -> Self {
+        let is_leader = backend == LeaderElectionBackend::Disabled;
+        Self {
+            leader: is_leader.then(|| identity.clone()),
+            identity,
+            backend,
+            is_leader,
+            since: Utc::now(),
+            transitions: VecDeque::new(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LeaderElectionError {
+    #[error("Storage lease error: {0}")]
+    Storage(#[from] crate::storage::StorageError),
+    #[error("Kubernetes lease error: {0}")]
+    Kubernetes(#[from] kube::Error),
+}
+
+pub struct LeaderElectionHandle {
+    ct: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+impl LeaderElectionHandle {
+    /// Stop campaigning, stepping down and releasing the lease if held.
+    pub async fn cancel(self) {
+        self.ct.cancel();
+        let _ = self.task.await;
+    }
+}
+
+pub fn default_identity() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .filter(|hostname| !hostname.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::now_v7().to_string())
+}
+
+enum LeaseBackend {
+    Kubernetes { api: kube::Api<Lease> },
+    Storage,
+}
+
+impl ControllerContext {
+    pub async fn leadership(&self) -> LeadershipState {
+        self.leadership.read().await.clone()
+    }
+
+    pub async fn is_leader(&self) -> bool {
+        self.leadership.read().await.is_leader
+    }
+
+    /// Start campaigning for leadership, the k8s `Lease` backend is used when `k8s_client` is
+    /// given.
+    pub(crate) async fn spawn_leader_election(
+        &self,
+        config: LeaderElectionConfig,
+        k8s_client: Option<kube::Client>,
+    ) -> Result<(), crate::Error> {
+        let backend = match k8s_client {
+            Some(client) => {
+                let namespace = match &config.namespace {
+                    Some(namespace) => namespace.clone(),
+                    None => crate::utils::k8s::current_namespace()
+                        .await?
+                        .unwrap_or_else(|| crate::DEFAULT_NAMESPACE.to_string()),
+                };
+                LeaseBackend::Kubernetes {
+                    api: kube::Api::namespaced(client, &namespace),
+                }
+            }
+            None => LeaseBackend::Storage,
+        };
+        let identity = config.identity.clone().unwrap_or_else(default_identity);
+        *self.leadership.write().await = LeadershipState::new(
+            identity.clone(),
+            match backend {
+                LeaseBackend::Kubernetes { .. } => LeaderElectionBackend::Kubernetes,
+                LeaseBackend::Storage => LeaderElectionBackend::Storage,
+            },
+        );
+        tracing::info!(
+            identity,
+            lease = config.lease_name,
+            "Campaigning for leadership"
+        );
+        let ct = CancellationToken::new();
+        let context = self.clone();
+        let task_ct = ct.clone();
+        let task = tokio::spawn(async move {
+            context
+                .run_leader_election(config, identity, backend, task_ct)
+                .await
+        });
+        *self.leader_election.write().await = Some(LeaderElectionHandle { ct, task });
+        Ok(())
+    }
+
+    pub(crate) async fn cancel_leader_election(&self) {
+        if let Some(handle) = self.leader_election.write().await.take() {
+            handle.cancel().await;
+        }
+    }
+
+    async fn run_leader_election(
+        &self,
+        config: LeaderElectionConfig,
+        identity: String,
+        backend: LeaseBackend,
+        ct: CancellationToken,
+    ) {
+        let lease_duration = Duration::from_secs(config.lease_duration_secs);
+        let renew_interval = Duration::from_secs(config.renew_interval_secs);
+        let mut last_renewed: Option<tokio::time::Instant> = None;
+        loop {
+            let attempt_at = tokio::time::Instant::now();
+            match backend
+                .try_acquire(self, &config.lease_name, &identity, lease_duration)
+                .await
+            {
+                Ok(holder) => {
+                    if holder.as_deref() == Some(identity.as_str()) {
+                        last_renewed = Some(attempt_at);
+                    }
+                    self.set_leader(holder).await;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to renew leader lease: {}", e);
+                    // without a renewal we can't tell whether another controller took over
+                    let expired = last_renewed.is_none_or(|at| at.elapsed() >= lease_duration);
+                    if expired && self.is_leader().await {
+                        self.set_leader(None).await;
+                    }
+                }
+            }
+            tokio::select! {
+                _ = ct.cancelled() => break,
+                _ = tokio::time::sleep(renew_interval) => {}
+            }
+        }
+        if self.is_leader().await {
+            self.set_leader(None).await;
+            if let Err(e) = backend.release(self, &config.lease_name, &identity).await {
+                tracing::warn!("Failed to release leader lease: {}", e);
+            }
+        }
+    }
+
+    /// Record the lease holder, starting or stopping leader-only work when this controller
+    /// gains or loses leadership.
+    async fn set_leader(&self, leader: Option<String>) {
+        let (was_leader, is_leader) = {
+            let mut state = self.leadership.write().await;
+            if state.leader == leader {
+                return;
+            }
+            let was_leader = state.is_leader;
+            state.is_leader = leader.as_deref() == Some(state.identity.as_str());
+            state.leader = leader.clone();
+            let now = Utc::now();
+            state.since = now;
+            if state.transitions.len() == MAX_TRANSITIONS {
+                state.transitions.pop_front();
+            }
+            state.transitions.push_back(LeadershipTransition {
+                at: now,
+                leader: leader.clone(),
+            });
+            (was_leader, state.is_leader)
+        };
+        tracing::info!(leader = ?leader, "Leadership changed");
+        if is_leader && !was_leader {
+            self.on_elected().await;
+        } else if was_leader && !is_leader {
+            self.on_deposed().await;
+        }
+    }
+
+    async fn on_elected(&self) {
+        tracing::info!("Elected as leader");
+        if self.run_mode.read().await.is_some_and(|mode| mode.is_k8s())
+            && let Err(e) = self.spawn_k8s_runtime().await
+        {
+            tracing::error!("Failed to start k8s runtime after election: {}", e);
+        }
+    }
+
+    async fn on_deposed(&self) {
+        tracing::warn!("Lost leadership");
+        if let Err(e) = self.cancel_k8s_runtime().await {
+            tracing::error!("Failed to stop k8s runtime after losing leadership: {}", e);
+        }
+        let rollouts = self
+            .rollouts
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        for rollout in rollouts {
+            if !rollout.is_finished().await {
+                tracing::warn!("Aborting rollout, this controller is no longer the leader");
+                rollout.abort();
+            }
+        }
+    }
+}
+
+impl LeaseBackend {
+    /// Take or renew the lease, returning its holder afterwards.
+    async fn try_acquire(
+        &self,
+        context: &ControllerContext,
+        name: &str,
+        identity: &str,
+        lease_duration: Duration,
+    ) -> Result<Option<String>, LeaderElectionError> {
+        match self {
+            LeaseBackend::Storage => {
+                let lease = context
+                    .storage
+                    .acquire_lease(name, identity, lease_duration)
+                    .await?;
+                Ok(Some(lease.holder))
+            }
+            LeaseBackend::Kubernetes { api } => {
+                try_acquire_k8s_lease(api, name, identity, lease_duration).await
+            }
+        }
+    }
+
+    async fn release(
+        &self,
+        context: &ControllerContext,
+        name: &str,
+        identity: &str,
+    ) -> Result<(), LeaderElectionError> {
+        match self {
+            LeaseBackend::Storage => Ok(context.storage.release_lease(name, identity).await?),
+            LeaseBackend::Kubernetes { api } => {
+                let Some(mut lease) = api.get_opt(name).await? else {
+                    return Ok(());
+                };
+                let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+                if spec.holder_identity.as_deref() != Some(identity) {
+                    return Ok(());
+                }
+                spec.holder_identity = None;
+                api.replace(name, &PostParams::default(), &lease).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn try_acquire_k8s_lease(
+    api: &kube::Api<Lease>,
+    name: &str,
+    identity: &str,
+    lease_duration: Duration,
+) -> Result<Option<String>, LeaderElectionError> {
+    let now = Utc::now();
+    let duration_secs = lease_duration.as_secs() as i32;
+    let Some(mut lease) = api.get_opt(name).await? else {
+        let lease = Lease {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            spec: Some(LeaseSpec {
+                holder_identity: Some(identity.to_string()),
+                lease_duration_seconds: Some(duration_secs),
+                acquire_time: Some(MicroTime(now)),
+                renew_time: Some(MicroTime(now)),
+                lease_transitions: Some(0),
+                ..Default::default()
+            }),
+        };
+        return match api.create(&PostParams::default(), &lease).await {
+            Ok(_) => Ok(Some(identity.to_string())),
+            // another controller created it first
+            Err(kube::Error::Api(response)) if response.code == 409 => Ok(api
+                .get_opt(name)
+                .await?
+                .and_then(|lease| lease.spec)
+                .and_then(|spec| spec.holder_identity)),
+            Err(e) => Err(e.into()),
+        };
+    };
+    let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+    let holder = spec.holder_identity.clone();
+    let held_by_other = holder.as_deref().is_some_and(|holder| holder != identity);
+    let expired = match (&spec.renew_time, spec.lease_duration_seconds) {
+        (Some(renewed), Some(duration)) => {
+            renewed.0 + chrono::Duration::seconds(duration as i64) < now
+        }
+        _ => true,
+    };
+    if held_by_other && !expired {
+        return Ok(holder);
+    }
+    if holder.as_deref() != Some(identity) {
+        spec.acquire_time = Some(MicroTime(now));
+        spec.lease_transitions = Some(spec.lease_transitions.unwrap_or(0) + 1);
+    }
+    spec.holder_identity = Some(identity.to_string());
+    spec.renew_time = Some(MicroTime(now));
+    spec.lease_duration_seconds = Some(duration_secs);
+    // the resource version in the metadata makes this fail if someone else wrote in between
+    match api.replace(name, &PostParams::default(), &lease).await {
+        Ok(_) => Ok(Some(identity.to_string())),
+        Err(kube::Error::Api(response)) if response.code == 409 => Ok(holder),
+        Err(e) => Err(e.into()),
+    }
+}
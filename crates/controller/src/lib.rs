@@ -1,9 +1,12 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 use tokio::sync::RwLock;
+pub mod audit;
 pub mod config;
 pub mod dir;
+pub mod history;
 pub mod interface;
 pub mod kernel;
+pub mod leader;
 pub mod link_resolver;
 pub mod resolve;
 pub mod resource;
@@ -20,39 +23,68 @@ pub struct ControllerContext {
     pub storage: storage::SharedStorage,
     pub resolve: Arc<resolve::ServiceConfigResolverRegistry>,
     pub current_config: Arc<RwLock<Option<switchboard_model::ServiceConfig>>>,
+    /// Configs applied to deployment groups, by group name.
+    pub group_configs: Arc<RwLock<BTreeMap<String, switchboard_model::ServiceConfig>>>,
     pub scan_task: Arc<RwLock<Option<kernel::ScanTaskHandle>>>,
     pub k8s_runtime: Arc<RwLock<Option<run::k8s::K8sRuntimeHandle>>>,
     pub k8s_apply_status: Arc<RwLock<Option<run::k8s::K8sApplyStatus>>>,
     pub run_mode: Arc<RwLock<Option<run::RunMode>>>,
+    /// The latest rollout of each deployment group, `None` for rollouts to every kernel.
+    pub rollouts: Arc<RwLock<BTreeMap<Option<String>, kernel::rollout::RolloutHandle>>>,
+    pub leadership: Arc<RwLock<leader::LeadershipState>>,
+    pub leader_election: Arc<RwLock<Option<leader::LeaderElectionHandle>>>,
+    pub reverse_listener: Arc<RwLock<Option<kernel::reverse::ReverseListenerHandle>>>,
 }
 
 impl ControllerContext {
     pub async fn new(controller_config: config::ControllerConfig) -> Result<Self> {
         let this = Self {
             storage: storage::create_storage(&controller_config.storage).await?,
-            kernel_manager: Arc::new(RwLock::new(kernel::KernelManager::new())),
+            kernel_manager: Arc::new(RwLock::new(kernel::KernelManager::new(
+                controller_config.kernel.auth.clone(),
+            ))),
             interface_manager: Arc::new(RwLock::new(interface::InterfaceManager::default())),
             controller_config: controller_config.into(),
             resolve: resolve::ServiceConfigResolverRegistry::prelude().into(),
             current_config: Arc::new(RwLock::new(None)),
+            group_configs: Arc::new(RwLock::new(BTreeMap::new())),
             scan_task: Arc::new(RwLock::new(None)),
             k8s_runtime: Arc::new(RwLock::new(None)),
             k8s_apply_status: Arc::new(RwLock::new(None)),
             run_mode: Arc::new(RwLock::new(None)),
+            rollouts: Arc::new(RwLock::new(BTreeMap::new())),
+            leadership: Arc::new(RwLock::new(leader::LeadershipState::new(
+                leader::default_identity(),
+                leader::LeaderElectionBackend::Disabled,
+            ))),
+            leader_election: Arc::new(RwLock::new(None)),
+            reverse_listener: Arc::new(RwLock::new(None)),
         };
         Ok(this)
     }
     pub async fn startup(&self, run_mode: run::RunMode) -> Result<()> {
+        *self.run_mode.write().await = Some(run_mode);
         self.start_up_all_interfaces().await?;
         self.refresh_kernels().await?;
         self.spawn_scan_task().await;
-        if run_mode.is_k8s() {
-            self.spawn_k8s_runtime().await?;
+        self.spawn_reverse_listener().await?;
+        match self.controller_config.leader_election.clone() {
+            // the k8s runtime starts once this controller is elected
+            Some(config) => {
+                let k8s_client = if run_mode.is_k8s() {
+                    utils::k8s::kube_client_if_in_cluster().await?
+                } else {
+                    None
+                };
+                self.spawn_leader_election(config, k8s_client).await?;
+            }
+            None if run_mode.is_k8s() => self.spawn_k8s_runtime().await?,
+            None => {}
         }
-        *self.run_mode.write().await = Some(run_mode);
         Ok(())
     }
     pub async fn shutdown(&self) -> Result<()> {
+        self.cancel_leader_election().await;
         self.cancel_k8s_runtime().await?;
         self.cancel_scan_task().await;
         // shutdown all kernel connections
@@ -60,6 +92,8 @@ impl ControllerContext {
             let mut manager = self.kernel_manager.write().await;
             manager.shutdown_all().await;
         }
+        // after the kernel connections are gone, so no tunnel keeps the listener draining
+        self.cancel_reverse_listener().await;
         Ok(())
     }
 }
@@ -73,6 +107,8 @@ pub enum Error {
     KernelConnectionError(#[from] crate::kernel::KernelGrpcConnectionError),
     #[error("Startup http interface error: {0}")]
     StartupHttpInterfaceError(#[source] std::io::Error),
+    #[error("Kernel reverse listener error: {0}")]
+    ReverseListenerError(#[from] crate::kernel::reverse::ReverseListenerError),
 
     #[error("Kubernetes client error: {0}")]
     KubernetesClientError(#[from] kube::Error),
@@ -104,9 +140,40 @@ pub enum Error {
     #[error("File browser path not allowed: {0}")]
     FileBrowserPathNotAllowed(std::path::PathBuf),
 
+    #[error("Storage id {0} is reserved for the controller")]
+    ReservedStorageId(String),
+
     #[error("Controller is not running in kubernetes cluster")]
     NotInKubernetesCluster,
 
     #[error("Controller is running in kubernetes cluster")]
     InKubernetesCluster,
+
+    #[error("Kernel {0} was not part of the reviewed config diff")]
+    ConfigDiffMissingKernel(String),
+
+    #[error("Kernel {kernel} config changed since the diff, expected {expected}, got {actual}")]
+    StaleConfigDiff {
+        kernel: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Rollout {0} is still in progress")]
+    RolloutInProgress(String),
+
+    #[error("No rollout has been started")]
+    NoRollout,
+
+    #[error("Deployment group {0} is not configured")]
+    UnknownDeploymentGroup(String),
+
+    #[error("Config history revision {0} not found")]
+    ConfigHistoryNotFound(String),
+
+    #[error("Controller has not applied any config yet")]
+    NoCurrentConfig,
+
+    #[error("Controller is a follower, the leader is {}", .0.as_deref().unwrap_or("unknown"))]
+    NotLeader(Option<String>),
 }
@@ -5,6 +5,7 @@ use switchboard_file_resolver::FileResolver;
 use switchboard_link_or_value::Resolver;
 use switchboard_model::SerdeValue;
 
+use crate::audit::is_reserved_storage_id;
 use crate::storage::{InvalidStorageObjectDescriptor, StorageError, StorageObjectDescriptor};
 
 #[derive(Clone)]
@@ -58,6 +59,9 @@ impl Resolver<Link, String> for ControllerLinkResolver {
                 Ok(string)
             }
             Link::Storage(storage_object_descriptor) => {
+                if is_reserved_storage_id(&storage_object_descriptor.id) {
+                    return Err(LinkResolveError::NotFound { link });
+                }
                 let storage = &self.context.storage;
                 let object = storage
                     .get_object(storage_object_descriptor)
@@ -89,6 +93,9 @@ impl Resolver<Link, SerdeValue> for ControllerLinkResolver {
                 .await
                 .map_err(Into::into),
             Link::Storage(storage_object_descriptor) => {
+                if is_reserved_storage_id(&storage_object_descriptor.id) {
+                    return Err(LinkResolveError::NotFound { link });
+                }
                 let storage = &self.context.storage;
                 let object = storage
                     .get_object(storage_object_descriptor)
@@ -117,6 +117,7 @@ impl ServiceBuilder {
             let listener = Listener {
                 bind,
                 description: Some(format!("listener on {port} for {gateway_name}")),
+                limits: Default::default(),
             };
             let mut route = TcpRoute {
                 bind,
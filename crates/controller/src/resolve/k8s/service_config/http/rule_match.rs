@@ -118,6 +118,7 @@ impl super::HttpGatewayBuilder {
                         method: k8s_match.method.as_ref().map(k8s_method_to_http_method),
                         headers,
                         queries,
+                        ..Default::default()
                     }
                 };
                 let tree_key = match match_type {
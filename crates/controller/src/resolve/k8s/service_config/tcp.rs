@@ -91,6 +91,7 @@ impl ServiceBuilder {
                                 "tls passthrough listener {} for {}",
                                 listener_name, gateway_name
                             )),
+                            limits: Default::default(),
                         },
                     );
                     self.config.tcp_routes.insert(
@@ -203,6 +204,7 @@ impl ServiceBuilder {
                                 "tls terminate listener {} for {}",
                                 listener_name, gateway_name
                             )),
+                            limits: Default::default(),
                         },
                     );
                     self.config.tcp_routes.insert(
@@ -118,8 +118,18 @@ async fn apply_rebuild_all(context: &ControllerContext, event_tx: &mpsc::Sender<
         }
     }
 
-    let report = context.update_config(standard_config).await;
+    let report = context.update_config(standard_config.clone()).await;
     let apply_succeeded = matches!(report.status, crate::kernel::RolloutStatus::Succeeded);
+    context
+        .record_applied_config(crate::history::AppliedConfigRecord::new(
+            standard_config,
+            None,
+            report.kernels(),
+            apply_succeeded,
+            crate::history::AppliedConfigSource::Kubernetes,
+            &report,
+        ))
+        .await;
     if apply_succeeded {
         tracing::info!(digest = %digest, transaction_id = %report.transaction_id, "k8s apply succeeded");
     } else {
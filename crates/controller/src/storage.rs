@@ -10,12 +10,24 @@ use switchboard_model::{
 };
 
 use crate::{ControllerContext, link_resolver::Link};
+pub mod filesystem;
+pub mod migrate;
+pub mod postgres;
 pub mod surrealdb_local;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum StorageProvider {
+    /// Embedded SurrealDB, only usable by a single controller.
     Local { db_file: std::path::PathBuf },
+    /// A PostgreSQL database which several controllers can share.
+    Postgres {
+        url: String,
+        #[serde(default)]
+        pool_size: Option<usize>,
+    },
+    /// One file per object revision under `dir`.
+    Filesystem { dir: std::path::PathBuf },
 }
 
 impl Default for StorageProvider {
@@ -97,6 +109,10 @@ pub enum StorageError {
     DeserializationError(#[source] SerdeValueError),
     #[error("Digest mismatch: expected {expected}, found {found}")]
     DigestMismatch { expected: String, found: String },
+    #[error("Invalid object id {0:?}")]
+    InvalidId(String),
+    #[error("Invalid revision {0:?}, expected a hex sha256 digest")]
+    InvalidRevision(String),
 }
 pub fn encode_object(object: SerdeValue) -> Result<(String, Vec<u8>), StorageError> {
     use sha2::{Digest, Sha256};
@@ -129,6 +145,13 @@ pub fn decode_object(bytes: &[u8], digest: &str) -> Result<SerdeValue, StorageEr
     Ok(object)
 }
 
+/// A named lease held until `expires_at`, the storage side of controller leader election.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct StorageLease {
+    pub holder: String,
+    pub expires_at: DateTime<Utc>,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Default)]
 pub struct ObjectFilter {
     pub data_type: Option<String>,
@@ -137,6 +160,9 @@ pub struct ObjectFilter {
     pub latest_only: Option<bool>,
     pub created_before: Option<DateTime<Utc>>,
     pub created_after: Option<DateTime<Utc>>,
+    /// Ids left out of the list, applied before paging so every page stays full.
+    #[serde(default)]
+    pub exclude_ids: Vec<String>,
 }
 
 pub struct StorageObjectValueStyle {
@@ -169,6 +195,13 @@ pub trait Storage: Send + Sync + 'static {
         object: SerdeValue,
     ) -> impl Future<Output = Result<StorageObjectDescriptor, StorageError>> + Send;
 
+    /// Store an already encoded object keeping its descriptor and meta, used to copy objects
+    /// between storages.
+    fn import_object(
+        &self,
+        object: StorageObject,
+    ) -> impl Future<Output = Result<StorageObjectDescriptor, StorageError>> + Send;
+
     fn get_object(
         &self,
         descriptor: &StorageObjectDescriptor,
@@ -193,6 +226,22 @@ pub trait Storage: Send + Sync + 'static {
         &self,
         id: &str,
     ) -> impl Future<Output = Result<(), StorageError>> + Send;
+
+    /// Atomically take or renew lease `name` for `holder` for `ttl`, unless another holder's
+    /// lease has not expired yet. Returns the lease as it is after the attempt.
+    fn acquire_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl: std::time::Duration,
+    ) -> impl Future<Output = Result<StorageLease, StorageError>> + Send;
+
+    /// Give up lease `name` if `holder` still holds it.
+    fn release_lease(
+        &self,
+        name: &str,
+        holder: &str,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send;
 }
 
 pub trait DynamicStorage: Send + Sync + 'static {
@@ -202,6 +251,10 @@ pub trait DynamicStorage: Send + Sync + 'static {
         data_type: &'a str,
         object: SerdeValue,
     ) -> BoxFuture<'a, Result<StorageObjectDescriptor, StorageError>>;
+    fn import_object(
+        &self,
+        object: StorageObject,
+    ) -> BoxFuture<'_, Result<StorageObjectDescriptor, StorageError>>;
     fn list_objects(
         &self,
         query: ListObjectQuery,
@@ -223,6 +276,19 @@ pub trait DynamicStorage: Send + Sync + 'static {
         &'a self,
         id: &'a str,
     ) -> BoxFuture<'a, Result<(), StorageError>>;
+
+    fn acquire_lease<'a>(
+        &'a self,
+        name: &'a str,
+        holder: &'a str,
+        ttl: std::time::Duration,
+    ) -> BoxFuture<'a, Result<StorageLease, StorageError>>;
+
+    fn release_lease<'a>(
+        &'a self,
+        name: &'a str,
+        holder: &'a str,
+    ) -> BoxFuture<'a, Result<(), StorageError>>;
 }
 
 impl<S: Storage> DynamicStorage for S {
@@ -235,6 +301,13 @@ impl<S: Storage> DynamicStorage for S {
         Box::pin(self.save_object(id, data_type, object))
     }
 
+    fn import_object(
+        &self,
+        object: StorageObject,
+    ) -> BoxFuture<'_, Result<StorageObjectDescriptor, StorageError>> {
+        Box::pin(self.import_object(object))
+    }
+
     fn list_objects(
         &self,
         query: ListObjectQuery,
@@ -269,6 +342,23 @@ impl<S: Storage> DynamicStorage for S {
     ) -> BoxFuture<'a, Result<(), StorageError>> {
         Box::pin(self.delete_all_objects_by_id(id))
     }
+
+    fn acquire_lease<'a>(
+        &'a self,
+        name: &'a str,
+        holder: &'a str,
+        ttl: std::time::Duration,
+    ) -> BoxFuture<'a, Result<StorageLease, StorageError>> {
+        Box::pin(self.acquire_lease(name, holder, ttl))
+    }
+
+    fn release_lease<'a>(
+        &'a self,
+        name: &'a str,
+        holder: &'a str,
+    ) -> BoxFuture<'a, Result<(), StorageError>> {
+        Box::pin(self.release_lease(name, holder))
+    }
 }
 
 pub type SharedStorage = Arc<dyn DynamicStorage>;
@@ -302,14 +392,20 @@ mod impl_static_storage_object {
     derive_local_type!(HttpConfig);
 }
 
-pub(crate) async fn create_storage(
-    provider: &StorageProvider,
-) -> Result<SharedStorage, StorageError> {
+pub async fn create_storage(provider: &StorageProvider) -> Result<SharedStorage, StorageError> {
     match provider {
         StorageProvider::Local { db_file } => {
             let storage = surrealdb_local::SurrealRocksDbStorage::new(db_file.as_ref()).await?;
             Ok(Arc::new(storage))
         }
+        StorageProvider::Postgres { url, pool_size } => {
+            let storage = postgres::PostgresStorage::new(url, *pool_size).await?;
+            Ok(Arc::new(storage))
+        }
+        StorageProvider::Filesystem { dir } => {
+            let storage = filesystem::FilesystemStorage::new(dir).await?;
+            Ok(Arc::new(storage))
+        }
     }
 }
 
@@ -0,0 +1,484 @@
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use switchboard_model::{Cursor, Indexed, PagedList, SerdeValue};
+
+use crate::storage::{
+    ListObjectQuery, ObjectFilter, Storage, StorageError, StorageLease, StorageMeta, StorageObject,
+    StorageObjectDescriptor, StorageObjectValueStyle, StorageObjectWithoutData, decode_object,
+    encode_object,
+};
+
+const PROVIDER: &str = "Filesystem";
+const OBJECT_FILE_EXTENSION: &str = "json";
+const HEADER_FILE_SUFFIX: &str = ".meta.json";
+/// Escaped ids never start with a dot, so this can't clash with an object dir.
+const LEASE_DIR: &str = ".leases";
+/// A lease lock file older than this was left behind by a crashed controller.
+const STALE_LEASE_LOCK: std::time::Duration = std::time::Duration::from_secs(30);
+const LEASE_LOCK_ATTEMPTS: usize = 40;
+const LEASE_LOCK_RETRY: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// One json file per object revision at `<dir>/<id>/<revision>.json`, with its header alone in
+/// `<revision>.meta.json` so listing never reads the data.
+///
+/// Files are written once per save and never edited in place, so the directory can be kept in
+/// git or synced between controllers. `seq` is a time ordered uuid, the latest revision of an id
+/// is the one with the highest `seq`.
+pub struct FilesystemStorage {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ObjectFileHeader {
+    seq: String,
+    id: String,
+    revision: String,
+    data_type: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ObjectFile {
+    #[serde(flatten)]
+    header: ObjectFileHeader,
+    /// Base64 of the `encode_object` bytes.
+    data: String,
+}
+
+fn storage_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> StorageError {
+    StorageError::StorageError {
+        source: Box::new(e),
+        provider: PROVIDER,
+    }
+}
+
+/// Keeps ids usable as a single path segment on every platform.
+fn escape_id(id: &str) -> String {
+    let mut escaped = String::with_capacity(id.len());
+    for (index, byte) in id.bytes().enumerate() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => escaped.push(byte as char),
+            b'.' if index > 0 => escaped.push('.'),
+            _ => escaped.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    escaped
+}
+
+/// Revisions are the hex sha256 `encode_object` gives, anything else could name a path outside
+/// the object dir.
+fn check_revision(revision: &str) -> Result<(), StorageError> {
+    if revision.len() == 64 && revision.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidRevision(revision.to_string()))
+    }
+}
+
+impl FilesystemStorage {
+    pub async fn new(dir: &Path) -> Result<Self, StorageError> {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(storage_error)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// An empty id would be the storage dir itself, leases included.
+    fn object_dir(&self, id: &str) -> Result<PathBuf, StorageError> {
+        if id.is_empty() {
+            return Err(StorageError::InvalidId(id.to_string()));
+        }
+        Ok(self.dir.join(escape_id(id)))
+    }
+
+    fn object_path(&self, descriptor: &StorageObjectDescriptor) -> Result<PathBuf, StorageError> {
+        check_revision(&descriptor.revision)?;
+        Ok(self
+            .object_dir(&descriptor.id)?
+            .join(format!("{}.{OBJECT_FILE_EXTENSION}", descriptor.revision)))
+    }
+
+    fn header_path(&self, descriptor: &StorageObjectDescriptor) -> Result<PathBuf, StorageError> {
+        check_revision(&descriptor.revision)?;
+        Ok(self
+            .object_dir(&descriptor.id)?
+            .join(format!("{}{HEADER_FILE_SUFFIX}", descriptor.revision)))
+    }
+
+    async fn write(
+        &self,
+        descriptor: &StorageObjectDescriptor,
+        meta: &StorageMeta,
+        data: &[u8],
+    ) -> Result<(), StorageError> {
+        let file = ObjectFile {
+            header: ObjectFileHeader {
+                seq: uuid::Uuid::now_v7().to_string(),
+                id: descriptor.id.clone(),
+                revision: descriptor.revision.clone(),
+                data_type: meta.data_type.clone(),
+                created_at: meta.created_at,
+            },
+            data: base64::engine::general_purpose::STANDARD.encode(data),
+        };
+        let dir = self.object_dir(&descriptor.id)?;
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(storage_error)?;
+        let content = serde_json::to_vec_pretty(&file).map_err(storage_error)?;
+        write_atomic(&dir, &self.object_path(descriptor)?, content).await?;
+        // the header makes the revision visible to lists, so it goes after the data
+        let header = serde_json::to_vec_pretty(&file.header).map_err(storage_error)?;
+        write_atomic(&dir, &self.header_path(descriptor)?, header).await
+    }
+
+    /// Remove a revision, its header first so lists stop showing it before the data is gone.
+    /// Returns whether the revision existed.
+    async fn remove(&self, descriptor: &StorageObjectDescriptor) -> Result<bool, StorageError> {
+        match tokio::fs::remove_file(self.header_path(descriptor)?).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(storage_error(e)),
+        }
+        match tokio::fs::remove_file(self.object_path(descriptor)?).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+
+    async fn read(&self, path: &Path) -> Result<Option<ObjectFile>, StorageError> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes).map_err(storage_error)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+
+    /// Headers of every revision of one object dir, oldest first, without reading their data.
+    async fn read_object_dir(&self, dir: &Path) -> Result<Vec<ObjectFileHeader>, StorageError> {
+        let mut headers = Vec::new();
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(headers),
+            Err(e) => return Err(storage_error(e)),
+        };
+        while let Some(entry) = entries.next_entry().await.map_err(storage_error)? {
+            let path = entry.path();
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(HEADER_FILE_SUFFIX)
+            {
+                continue;
+            }
+            let bytes = match tokio::fs::read(&path).await {
+                Ok(bytes) => bytes,
+                // deleted while listing
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(storage_error(e)),
+            };
+            headers
+                .push(serde_json::from_slice::<ObjectFileHeader>(&bytes).map_err(storage_error)?);
+        }
+        headers.sort_by(|a, b| a.seq.cmp(&b.seq));
+        Ok(headers)
+    }
+
+    async fn object_dirs(&self, id: Option<&str>) -> Result<Vec<PathBuf>, StorageError> {
+        if let Some(id) = id {
+            return Ok(vec![self.object_dir(id)?]);
+        }
+        let mut dirs = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.dir)
+            .await
+            .map_err(storage_error)?;
+        while let Some(entry) = entries.next_entry().await.map_err(storage_error)? {
+            if entry.file_type().await.map_err(storage_error)?.is_dir()
+                && !entry.file_name().to_string_lossy().starts_with('.')
+            {
+                dirs.push(entry.path());
+            }
+        }
+        Ok(dirs)
+    }
+
+    fn lease_path(&self, name: &str) -> PathBuf {
+        self.dir
+            .join(LEASE_DIR)
+            .join(format!("{}.{OBJECT_FILE_EXTENSION}", escape_id(name)))
+    }
+
+    async fn read_lease(&self, path: &Path) -> Result<Option<StorageLease>, StorageError> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes).map_err(storage_error)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+}
+
+/// Exclusive lock on one lease file, held while it is read and rewritten.
+struct LeaseLock {
+    path: PathBuf,
+}
+
+impl LeaseLock {
+    async fn acquire(lease_path: &Path) -> Result<Self, StorageError> {
+        if let Some(parent) = lease_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(storage_error)?;
+        }
+        let path = lease_path.with_extension("lock");
+        for _ in 0..LEASE_LOCK_ATTEMPTS {
+            match tokio::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .await
+            {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = tokio::fs::metadata(&path)
+                        .await
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > STALE_LEASE_LOCK);
+                    if stale {
+                        let _ = tokio::fs::remove_file(&path).await;
+                    } else {
+                        tokio::time::sleep(LEASE_LOCK_RETRY).await;
+                    }
+                }
+                Err(e) => return Err(storage_error(e)),
+            }
+        }
+        Err(storage_error(std::io::Error::new(
+            std::io::ErrorKind::WouldBlock,
+            format!("lease lock {} is busy", path.display()),
+        )))
+    }
+}
+
+impl Drop for LeaseLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Write through a temp file, so readers never see a half written file.
+async fn write_atomic(dir: &Path, path: &Path, content: Vec<u8>) -> Result<(), StorageError> {
+    let temp = dir.join(format!(".{}.tmp", uuid::Uuid::now_v7()));
+    tokio::fs::write(&temp, content)
+        .await
+        .map_err(storage_error)?;
+    tokio::fs::rename(&temp, path).await.map_err(storage_error)
+}
+
+fn matches(filter: &ObjectFilter, header: &ObjectFileHeader) -> bool {
+    filter
+        .data_type
+        .as_ref()
+        .is_none_or(|data_type| *data_type == header.data_type)
+        && filter
+            .revision
+            .as_ref()
+            .is_none_or(|revision| *revision == header.revision)
+        && filter
+            .created_after
+            .is_none_or(|after| header.created_at >= after)
+        && filter
+            .created_before
+            .is_none_or(|before| header.created_at <= before)
+}
+
+impl Storage for FilesystemStorage {
+    async fn save_object(
+        &self,
+        name: &str,
+        data_type: &str,
+        object: SerdeValue,
+    ) -> Result<StorageObjectDescriptor, StorageError> {
+        let (revision, data) = encode_object(object)?;
+        let descriptor = StorageObjectDescriptor {
+            id: name.to_string(),
+            revision,
+        };
+        let meta = StorageMeta {
+            data_type: data_type.to_string(),
+            created_at: Utc::now(),
+        };
+        self.write(&descriptor, &meta, &data).await?;
+        Ok(descriptor)
+    }
+
+    async fn import_object(
+        &self,
+        object: StorageObject,
+    ) -> Result<StorageObjectDescriptor, StorageError> {
+        decode_object(&object.data, &object.descriptor.revision)?;
+        self.write(&object.descriptor, &object.meta, &object.data)
+            .await?;
+        Ok(object.descriptor)
+    }
+
+    async fn get_object(
+        &self,
+        descriptor: &StorageObjectDescriptor,
+    ) -> Result<Option<StorageObjectValueStyle>, StorageError> {
+        let Some(file) = self.read(&self.object_path(descriptor)?).await? else {
+            return Ok(None);
+        };
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(&file.data)
+            .map_err(storage_error)?;
+        let value = decode_object(&data, &file.header.revision)?;
+        Ok(Some(StorageObjectValueStyle {
+            descriptor: StorageObjectDescriptor {
+                id: file.header.id,
+                revision: file.header.revision,
+            },
+            meta: StorageMeta {
+                data_type: file.header.data_type,
+                created_at: file.header.created_at,
+            },
+            data: value,
+        }))
+    }
+
+    async fn list_objects(
+        &self,
+        query: ListObjectQuery,
+    ) -> Result<PagedList<StorageObjectWithoutData>, StorageError> {
+        let ListObjectQuery { filter, page } = query;
+        let excluded = filter
+            .exclude_ids
+            .iter()
+            .map(|id| self.object_dir(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut headers = Vec::new();
+        for dir in self.object_dirs(filter.id.as_deref()).await? {
+            if excluded.contains(&dir) {
+                continue;
+            }
+            let mut revisions = self.read_object_dir(&dir).await?;
+            if filter.latest_only.unwrap_or(false) {
+                revisions = revisions.pop().into_iter().collect();
+            }
+            headers.extend(revisions.into_iter().filter(|header| {
+                matches(&filter, header)
+                    && page
+                        .cursor
+                        .next
+                        .as_ref()
+                        .is_none_or(|cursor| header.seq > *cursor)
+            }));
+        }
+        headers.sort_by(|a, b| a.seq.cmp(&b.seq));
+        headers.truncate(page.limit);
+        let items = headers
+            .into_iter()
+            .map(|header| {
+                Indexed::new(
+                    header.seq,
+                    StorageObjectWithoutData {
+                        descriptor: StorageObjectDescriptor {
+                            id: header.id,
+                            revision: header.revision,
+                        },
+                        meta: StorageMeta {
+                            data_type: header.data_type,
+                            created_at: header.created_at,
+                        },
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let next_cursor = Cursor::new(items.last().map(|item| item.id.clone()));
+        Ok(PagedList {
+            items,
+            next_cursor: Some(next_cursor),
+        })
+    }
+
+    async fn delete_object(
+        &self,
+        descriptor: &StorageObjectDescriptor,
+    ) -> Result<Option<StorageObjectDescriptor>, StorageError> {
+        if self.remove(descriptor).await? {
+            Ok(Some(descriptor.clone()))
+        } else {
+            Err(StorageError::ObjectNotFound {
+                descriptor: descriptor.clone(),
+            })
+        }
+    }
+
+    async fn batch_delete_objects(
+        &self,
+        descriptors: Vec<StorageObjectDescriptor>,
+    ) -> Result<(), StorageError> {
+        for descriptor in descriptors {
+            self.remove(&descriptor).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_all_objects_by_id(&self, id: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_dir_all(self.object_dir(id)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+
+    async fn acquire_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl: std::time::Duration,
+    ) -> Result<StorageLease, StorageError> {
+        let path = self.lease_path(name);
+        let _lock = LeaseLock::acquire(&path).await?;
+        let now = Utc::now();
+        if let Some(current) = self.read_lease(&path).await?
+            && current.holder != holder
+            && current.expires_at > now
+        {
+            return Ok(current);
+        }
+        let lease = StorageLease {
+            holder: holder.to_string(),
+            expires_at: now + ttl,
+        };
+        let content = serde_json::to_vec_pretty(&lease).map_err(storage_error)?;
+        let temp = path.with_extension("tmp");
+        tokio::fs::write(&temp, content)
+            .await
+            .map_err(storage_error)?;
+        tokio::fs::rename(&temp, &path)
+            .await
+            .map_err(storage_error)?;
+        Ok(lease)
+    }
+
+    async fn release_lease(&self, name: &str, holder: &str) -> Result<(), StorageError> {
+        let path = self.lease_path(name);
+        let _lock = LeaseLock::acquire(&path).await?;
+        if self
+            .read_lease(&path)
+            .await?
+            .is_some_and(|current| current.holder == holder)
+        {
+            tokio::fs::remove_file(&path).await.map_err(storage_error)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use serde::Serialize;
+use switchboard_model::{Cursor, PageQuery};
+
+use crate::storage::{
+    DynamicStorage, ListObjectQuery, ObjectFilter, StorageError, StorageObject, encode_object,
+};
+
+const MIGRATE_PAGE_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MigrateReport {
+    pub copied: usize,
+    /// Revisions the target already had, so running a migration twice copies nothing new.
+    pub skipped: usize,
+}
+
+/// Copy every object revision from `from` to `to`, oldest first, so the latest revision of each
+/// id stays the latest in `to`.
+pub async fn migrate_storage(
+    from: &dyn DynamicStorage,
+    to: &dyn DynamicStorage,
+) -> Result<MigrateReport, StorageError> {
+    let mut report = MigrateReport::default();
+    let mut cursor = Cursor::empty();
+    loop {
+        let page = from
+            .list_objects(ListObjectQuery {
+                filter: ObjectFilter::default(),
+                page: PageQuery {
+                    cursor,
+                    limit: MIGRATE_PAGE_SIZE,
+                },
+            })
+            .await?;
+        if page.items.is_empty() {
+            break;
+        }
+        for item in page.items {
+            let descriptor = item.data.descriptor;
+            if to.get_object(&descriptor).await?.is_some() {
+                report.skipped += 1;
+                continue;
+            }
+            let Some(object) = from.get_object(&descriptor).await? else {
+                // deleted since it was listed
+                continue;
+            };
+            let (revision, data) = encode_object(object.data)?;
+            if revision != descriptor.revision {
+                return Err(StorageError::DigestMismatch {
+                    expected: descriptor.revision,
+                    found: revision,
+                });
+            }
+            to.import_object(StorageObject {
+                descriptor,
+                meta: object.meta,
+                data,
+            })
+            .await?;
+            report.copied += 1;
+        }
+        match page.next_cursor {
+            Some(next) if !next.is_empty() => cursor = next,
+            _ => break,
+        }
+    }
+    tracing::info!(
+        copied = report.copied,
+        skipped = report.skipped,
+        "Storage migration finished"
+    );
+    Ok(report)
+}
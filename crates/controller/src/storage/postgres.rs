@@ -0,0 +1,353 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+use switchboard_model::{Cursor, Indexed, PagedList, SerdeValue};
+use tokio_postgres::Row;
+
+use crate::storage::{
+    ListObjectQuery, Storage, StorageError, StorageLease, StorageMeta, StorageObject,
+    StorageObjectDescriptor, StorageObjectValueStyle, StorageObjectWithoutData, decode_object,
+    encode_object,
+};
+
+const PROVIDER: &str = "PostgreSQL";
+const DEFAULT_POOL_SIZE: usize = 8;
+
+/// Objects in a PostgreSQL table, shared by every controller pointing at the same database.
+///
+/// `seq` orders the revisions, the latest revision of an id is the one with the highest `seq`.
+pub struct PostgresStorage {
+    pool: Pool,
+}
+
+fn storage_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> StorageError {
+    StorageError::StorageError {
+        source: Box::new(e),
+        provider: PROVIDER,
+    }
+}
+
+impl PostgresStorage {
+    /// `url` is a `postgres://` url or a libpq style `key=value` string, tls is used unless
+    /// `sslmode=disable`.
+    pub async fn new(url: &str, pool_size: Option<usize>) -> Result<Self, StorageError> {
+        let config: tokio_postgres::Config = url.parse().map_err(storage_error)?;
+        let manager_config = ManagerConfig {
+            recycling_method: RecyclingMethod::Fast,
+        };
+        let manager = match config.get_ssl_mode() {
+            tokio_postgres::config::SslMode::Disable => {
+                Manager::from_config(config, tokio_postgres::NoTls, manager_config)
+            }
+            _ => Manager::from_config(config, tls_connector()?, manager_config),
+        };
+        let pool = Pool::builder(manager)
+            .max_size(pool_size.unwrap_or(DEFAULT_POOL_SIZE))
+            .build()
+            .map_err(storage_error)?;
+        let this = Self { pool };
+        this.ensure_initialized().await?;
+        Ok(this)
+    }
+
+    pub async fn ensure_initialized(&self) -> Result<(), StorageError> {
+        let client = self.pool.get().await.map_err(storage_error)?;
+        client
+            .batch_execute(include_str!("postgres/define.sql"))
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    /// Store a revision once per id. Importing an existing revision again leaves it as it is,
+    /// saving one again makes it the latest revision like the other storages do.
+    async fn insert(
+        &self,
+        descriptor: &StorageObjectDescriptor,
+        meta: &StorageMeta,
+        data: &[u8],
+        on_conflict: OnConflict,
+    ) -> Result<(), StorageError> {
+        let statement = match on_conflict {
+            OnConflict::Keep => {
+                "INSERT INTO storage_object (object_id, revision, data_type, created_at, data) \
+                 VALUES ($1, $2, $3, $4, $5) ON CONFLICT (object_id, revision) DO NOTHING"
+            }
+            OnConflict::MakeLatest => {
+                "INSERT INTO storage_object (object_id, revision, data_type, created_at, data) \
+                 VALUES ($1, $2, $3, $4, $5) ON CONFLICT (object_id, revision) DO UPDATE \
+                 SET seq = nextval(pg_get_serial_sequence('storage_object', 'seq')), \
+                 data_type = EXCLUDED.data_type, created_at = EXCLUDED.created_at"
+            }
+        };
+        let client = self.pool.get().await.map_err(storage_error)?;
+        client
+            .execute(
+                statement,
+                &[
+                    &descriptor.id,
+                    &descriptor.revision,
+                    &meta.data_type,
+                    &meta.created_at,
+                    &data,
+                ],
+            )
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+}
+
+/// What storing a revision the id already has does.
+#[derive(Debug, Clone, Copy)]
+enum OnConflict {
+    Keep,
+    MakeLatest,
+}
+
+fn tls_connector() -> Result<tokio_postgres_rustls::MakeRustlsConnect, StorageError> {
+    let mut roots = rustls::RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    for cert in native.certs {
+        // skip system certificates rustls can't parse instead of refusing to start
+        let _ = roots.add(cert);
+    }
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(storage_error)?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    Ok(tokio_postgres_rustls::MakeRustlsConnect::new(config))
+}
+
+fn row_descriptor(row: &Row) -> StorageObjectDescriptor {
+    StorageObjectDescriptor {
+        id: row.get("object_id"),
+        revision: row.get("revision"),
+    }
+}
+
+fn row_meta(row: &Row) -> StorageMeta {
+    StorageMeta {
+        data_type: row.get("data_type"),
+        created_at: row.get::<_, DateTime<Utc>>("created_at"),
+    }
+}
+
+impl Storage for PostgresStorage {
+    async fn save_object(
+        &self,
+        name: &str,
+        data_type: &str,
+        object: SerdeValue,
+    ) -> Result<StorageObjectDescriptor, StorageError> {
+        let (revision, data) = encode_object(object)?;
+        let descriptor = StorageObjectDescriptor {
+            id: name.to_string(),
+            revision,
+        };
+        let meta = StorageMeta {
+            data_type: data_type.to_string(),
+            created_at: Utc::now(),
+        };
+        self.insert(&descriptor, &meta, &data, OnConflict::MakeLatest)
+            .await?;
+        Ok(descriptor)
+    }
+
+    async fn import_object(
+        &self,
+        object: StorageObject,
+    ) -> Result<StorageObjectDescriptor, StorageError> {
+        decode_object(&object.data, &object.descriptor.revision)?;
+        self.insert(
+            &object.descriptor,
+            &object.meta,
+            &object.data,
+            OnConflict::Keep,
+        )
+        .await?;
+        Ok(object.descriptor)
+    }
+
+    async fn get_object(
+        &self,
+        descriptor: &StorageObjectDescriptor,
+    ) -> Result<Option<StorageObjectValueStyle>, StorageError> {
+        let client = self.pool.get().await.map_err(storage_error)?;
+        let row = client
+            .query_opt(
+                "SELECT object_id, revision, data_type, created_at, data FROM storage_object \
+                 WHERE object_id = $1 AND revision = $2 ORDER BY seq DESC LIMIT 1",
+                &[&descriptor.id, &descriptor.revision],
+            )
+            .await
+            .map_err(storage_error)?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let descriptor = row_descriptor(&row);
+        let data: Vec<u8> = row.get("data");
+        let value = decode_object(&data, &descriptor.revision)?;
+        Ok(Some(StorageObjectValueStyle {
+            meta: row_meta(&row),
+            descriptor,
+            data: value,
+        }))
+    }
+
+    async fn list_objects(
+        &self,
+        query: ListObjectQuery,
+    ) -> Result<PagedList<StorageObjectWithoutData>, StorageError> {
+        let ListObjectQuery { filter, page } = query;
+        let cursor = page
+            .cursor
+            .next
+            .as_deref()
+            .map(str::parse::<i64>)
+            .transpose()
+            .map_err(storage_error)?;
+        let latest_only = filter.latest_only.unwrap_or(false);
+        let limit = page.limit as i64;
+        let client = self.pool.get().await.map_err(storage_error)?;
+        let rows = client
+            .query(
+                "SELECT seq, object_id, revision, data_type, created_at FROM storage_object o \
+                 WHERE (NOT $1 OR seq = (SELECT max(seq) FROM storage_object l WHERE l.object_id = o.object_id)) \
+                 AND ($2::text IS NULL OR data_type = $2) \
+                 AND ($3::text IS NULL OR object_id = $3) \
+                 AND ($4::text IS NULL OR revision = $4) \
+                 AND ($5::timestamptz IS NULL OR created_at >= $5) \
+                 AND ($6::timestamptz IS NULL OR created_at <= $6) \
+                 AND ($7::bigint IS NULL OR seq > $7) \
+                 AND NOT (object_id = ANY($9)) \
+                 ORDER BY seq ASC LIMIT $8",
+                &[
+                    &latest_only,
+                    &filter.data_type,
+                    &filter.id,
+                    &filter.revision,
+                    &filter.created_after,
+                    &filter.created_before,
+                    &cursor,
+                    &limit,
+                    &filter.exclude_ids,
+                ],
+            )
+            .await
+            .map_err(storage_error)?;
+        let items = rows
+            .iter()
+            .map(|row| {
+                Indexed::new(
+                    row.get::<_, i64>("seq").to_string(),
+                    StorageObjectWithoutData {
+                        descriptor: row_descriptor(row),
+                        meta: row_meta(row),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let next_cursor = Cursor::new(items.last().map(|item| item.id.clone()));
+        Ok(PagedList {
+            items,
+            next_cursor: Some(next_cursor),
+        })
+    }
+
+    async fn delete_object(
+        &self,
+        descriptor: &StorageObjectDescriptor,
+    ) -> Result<Option<StorageObjectDescriptor>, StorageError> {
+        let client = self.pool.get().await.map_err(storage_error)?;
+        let deleted = client
+            .execute(
+                "DELETE FROM storage_object WHERE object_id = $1 AND revision = $2",
+                &[&descriptor.id, &descriptor.revision],
+            )
+            .await
+            .map_err(storage_error)?;
+        if deleted == 0 {
+            return Err(StorageError::ObjectNotFound {
+                descriptor: descriptor.clone(),
+            });
+        }
+        Ok(Some(descriptor.clone()))
+    }
+
+    async fn batch_delete_objects(
+        &self,
+        descriptors: Vec<StorageObjectDescriptor>,
+    ) -> Result<(), StorageError> {
+        let (ids, revisions): (Vec<String>, Vec<String>) = descriptors
+            .into_iter()
+            .map(|descriptor| (descriptor.id, descriptor.revision))
+            .unzip();
+        let client = self.pool.get().await.map_err(storage_error)?;
+        client
+            .execute(
+                "DELETE FROM storage_object o USING unnest($1::text[], $2::text[]) AS d(object_id, revision) \
+                 WHERE o.object_id = d.object_id AND o.revision = d.revision",
+                &[&ids, &revisions],
+            )
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn delete_all_objects_by_id(&self, id: &str) -> Result<(), StorageError> {
+        let client = self.pool.get().await.map_err(storage_error)?;
+        client
+            .execute("DELETE FROM storage_object WHERE object_id = $1", &[&id])
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn acquire_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl: std::time::Duration,
+    ) -> Result<StorageLease, StorageError> {
+        let client = self.pool.get().await.map_err(storage_error)?;
+        // the database clock decides expiry, so controller clocks don't need to agree
+        client
+            .execute(
+                "INSERT INTO storage_lease (name, holder, expires_at) \
+                 VALUES ($1, $2, now() + $3::float8 * interval '1 second') \
+                 ON CONFLICT (name) DO UPDATE SET holder = excluded.holder, expires_at = excluded.expires_at \
+                 WHERE storage_lease.holder = excluded.holder OR storage_lease.expires_at < now()",
+                &[&name, &holder, &ttl.as_secs_f64()],
+            )
+            .await
+            .map_err(storage_error)?;
+        let row = client
+            .query_one(
+                "SELECT holder, expires_at FROM storage_lease WHERE name = $1",
+                &[&name],
+            )
+            .await
+            .map_err(storage_error)?;
+        Ok(StorageLease {
+            holder: row.get("holder"),
+            expires_at: row.get("expires_at"),
+        })
+    }
+
+    async fn release_lease(&self, name: &str, holder: &str) -> Result<(), StorageError> {
+        let client = self.pool.get().await.map_err(storage_error)?;
+        client
+            .execute(
+                "DELETE FROM storage_lease WHERE name = $1 AND holder = $2",
+                &[&name, &holder],
+            )
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+-- storage object table, one row per saved revision
+CREATE TABLE IF NOT EXISTS storage_object (
+    seq BIGSERIAL PRIMARY KEY,
+    object_id TEXT NOT NULL,
+    revision TEXT NOT NULL,
+    data_type TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    data BYTEA NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS index_descriptor ON storage_object (object_id, revision);
+CREATE INDEX IF NOT EXISTS index_latest ON storage_object (object_id, seq DESC);
+CREATE INDEX IF NOT EXISTS index_data_type ON storage_object (data_type);
+
+-- named leases for controller leader election
+CREATE TABLE IF NOT EXISTS storage_lease (
+    name TEXT PRIMARY KEY,
+    holder TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
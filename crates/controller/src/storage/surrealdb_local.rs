@@ -1,8 +1,8 @@
 use std::path::Path;
 
 use crate::storage::{
-    ListObjectQuery, Storage, StorageError, StorageMeta, StorageObject, StorageObjectDescriptor,
-    StorageObjectValueStyle, StorageObjectWithoutData, decode_object,
+    ListObjectQuery, Storage, StorageError, StorageLease, StorageMeta, StorageObject,
+    StorageObjectDescriptor, StorageObjectValueStyle, StorageObjectWithoutData, decode_object,
 };
 use chrono::Utc;
 use surrealdb::sql::Thing;
@@ -37,6 +37,23 @@ impl SurrealRocksDbStorage {
         self.client.query(sql).await.map_err(storage_error)?;
         Ok(())
     }
+    async fn insert(&self, object: StorageObject) -> Result<StorageObjectDescriptor, StorageError> {
+        let insert_result = self
+            .client
+            .query("fn::storage_object::save($content)")
+            .bind(("content", StorageObjectDb::from_storage_object(object)))
+            .await
+            .map_err(storage_error)?
+            .take(0)
+            .map_err(storage_error)?;
+        match insert_result {
+            Some(descriptor) => Ok(descriptor),
+            None => Err(StorageError::StorageError {
+                source: "Failed to retrieve inserted config ID".into(),
+                provider: PROVIDER,
+            }),
+        }
+    }
 }
 
 fn storage_error(e: surrealdb::Error) -> StorageError {
@@ -115,33 +132,25 @@ impl Storage for SurrealRocksDbStorage {
     ) -> Result<StorageObjectDescriptor, StorageError> {
         let (revision, data) = super::encode_object(object)?;
         let now = Utc::now();
-        let model = StorageObjectDb {
+        self.insert(StorageObject {
             descriptor: StorageObjectDescriptor {
                 id: name.to_string(),
                 revision,
             },
-            meta: StorageMetaDb {
-                created_at: now.into(),
+            meta: StorageMeta {
+                created_at: now,
                 data_type: data_type.to_string(),
             },
-            data: data.into(),
-        };
-
-        let insert_result = self
-            .client
-            .query("fn::storage_object::save($content)")
-            .bind(("content", model))
-            .await
-            .map_err(storage_error)?
-            .take(0)
-            .map_err(storage_error)?;
-        match insert_result {
-            Some(descriptor) => Ok(descriptor),
-            None => Err(StorageError::StorageError {
-                source: "Failed to retrieve inserted config ID".into(),
-                provider: PROVIDER,
-            }),
-        }
+            data,
+        })
+        .await
+    }
+    async fn import_object(
+        &self,
+        object: StorageObject,
+    ) -> Result<StorageObjectDescriptor, StorageError> {
+        decode_object(&object.data, &object.descriptor.revision)?;
+        self.insert(object).await
     }
     async fn delete_object(
         &self,
@@ -174,6 +183,7 @@ impl Storage for SurrealRocksDbStorage {
             pub latest_only: Option<bool>,
             pub created_before: Option<surrealdb::sql::Datetime>,
             pub created_after: Option<surrealdb::sql::Datetime>,
+            pub exclude_ids: Vec<String>,
         }
 
         impl ObjectFilterDb {
@@ -185,6 +195,7 @@ impl Storage for SurrealRocksDbStorage {
                     latest_only: filter.latest_only,
                     created_before: filter.created_before.map(surrealdb::sql::Datetime::from),
                     created_after: filter.created_after.map(surrealdb::sql::Datetime::from),
+                    exclude_ids: filter.exclude_ids,
                 }
             }
         }
@@ -248,4 +259,47 @@ impl Storage for SurrealRocksDbStorage {
             .map_err(storage_error)?;
         Ok(())
     }
+
+    async fn acquire_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl: std::time::Duration,
+    ) -> Result<StorageLease, StorageError> {
+        #[derive(Debug, serde::Deserialize)]
+        struct StorageLeaseDb {
+            holder: String,
+            expires_at: surrealdb::sql::Datetime,
+        }
+        let lease: Option<StorageLeaseDb> = self
+            .client
+            .query("fn::storage_lease::acquire($name, $holder, $ttl)")
+            .bind(("name", name.to_string()))
+            .bind(("holder", holder.to_string()))
+            .bind(("ttl", surrealdb::sql::Duration::from(ttl)))
+            .await
+            .map_err(storage_error)?
+            .take(0)
+            .map_err(storage_error)?;
+        let Some(lease) = lease else {
+            return Err(StorageError::StorageError {
+                source: "Failed to read lease after acquiring it".into(),
+                provider: PROVIDER,
+            });
+        };
+        Ok(StorageLease {
+            holder: lease.holder,
+            expires_at: lease.expires_at.into(),
+        })
+    }
+
+    async fn release_lease(&self, name: &str, holder: &str) -> Result<(), StorageError> {
+        self.client
+            .query("fn::storage_lease::release($name, $holder)")
+            .bind(("name", name.to_string()))
+            .bind(("holder", holder.to_string()))
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
 }
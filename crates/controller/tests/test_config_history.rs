@@ -0,0 +1,170 @@
+use switchboard_controller::{
+    ControllerContext,
+    config::ControllerConfig,
+    history::{AppliedConfigRecord, AppliedConfigSource, AppliedConfigSummary},
+    storage::StorageProvider,
+};
+use switchboard_model::{Cursor, PageQuery, ServiceConfig, diff::ChangeKind, tcp_route::TcpRoute};
+
+fn config(ports: &[u16]) -> ServiceConfig {
+    ServiceConfig {
+        tcp_routes: ports
+            .iter()
+            .map(|port| {
+                let bind = std::net::SocketAddr::from(([127, 0, 0, 1], *port));
+                (
+                    bind,
+                    TcpRoute {
+                        bind,
+                        service: "web".to_string(),
+                        tls: None,
+                    },
+                )
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+async fn list_history(context: &ControllerContext) -> Vec<AppliedConfigSummary> {
+    let mut summaries = Vec::new();
+    let mut cursor = Cursor::empty();
+    loop {
+        let page = context
+            .list_config_history(PageQuery::with_limit(2).with_cursor(cursor))
+            .await
+            .unwrap();
+        if page.items.is_empty() {
+            return summaries;
+        }
+        summaries.extend(page.items.into_iter().map(|item| item.data));
+        assert!(summaries.len() <= 10, "history listed a record twice");
+        cursor = page.next_cursor.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_config_history() -> switchboard_controller::Result<()> {
+    const DB_PATH: &str = "tmp/test_config_history.db";
+    if tokio::fs::try_exists(DB_PATH).await.unwrap() {
+        tokio::fs::remove_dir_all(DB_PATH).await.unwrap();
+    }
+    let context = ControllerContext::new(ControllerConfig {
+        storage: StorageProvider::Local {
+            db_file: DB_PATH.into(),
+        },
+        ..Default::default()
+    })
+    .await?;
+    assert!(list_history(&context).await.is_empty());
+
+    // record two applies, the second one to a deployment group
+    let first = config(&[8080]);
+    let second = config(&[8080, 8443]);
+    let first_revision = context
+        .record_applied_config(AppliedConfigRecord::new(
+            first.clone(),
+            Some("alice".to_string()),
+            Vec::new(),
+            true,
+            AppliedConfigSource::Update,
+            &(),
+        ))
+        .await
+        .unwrap()
+        .revision;
+    let second_revision = context
+        .record_applied_config(
+            AppliedConfigRecord::new(
+                second.clone(),
+                None,
+                Vec::new(),
+                false,
+                AppliedConfigSource::Kubernetes,
+                &(),
+            )
+            .with_group(Some("edge".to_string())),
+        )
+        .await
+        .unwrap()
+        .revision;
+    let history = list_history(&context).await;
+    assert_eq!(history.len(), 2);
+    assert!(
+        history
+            .iter()
+            .any(|record| record.revision == first_revision)
+    );
+    assert!(
+        history
+            .iter()
+            .any(|record| record.revision == second_revision)
+    );
+
+    // show
+    let record = context.get_config_history(&first_revision).await?;
+    assert_eq!(record.config, first);
+    assert_eq!(record.config_version, first.digest_sha256_base64());
+    assert_eq!(record.author.as_deref(), Some("alice"));
+    assert!(record.succeeded && record.group.is_none());
+    assert!(matches!(record.source, AppliedConfigSource::Update));
+    let record = context.get_config_history(&second_revision).await?;
+    assert_eq!(record.group.as_deref(), Some("edge"));
+    assert!(!record.succeeded);
+    assert!(matches!(
+        context.get_config_history("no-such-revision").await,
+        Err(switchboard_controller::Error::ConfigHistoryNotFound(_))
+    ));
+
+    // diff between revisions
+    let diff = context
+        .diff_config_history(&first_revision, Some(&second_revision))
+        .await?;
+    assert_eq!(diff.tcp_routes.len(), 1);
+    assert_eq!(diff.tcp_routes[0].key, "127.0.0.1:8443");
+    assert_eq!(diff.tcp_routes[0].kind, ChangeKind::Added);
+    assert!(
+        context
+            .diff_config_history(&first_revision, Some(&first_revision))
+            .await?
+            .is_empty()
+    );
+    // nothing was applied through this controller yet
+    assert!(matches!(
+        context.diff_config_history(&first_revision, None).await,
+        Err(switchboard_controller::Error::NoCurrentConfig)
+    ));
+
+    // rollback re-applies the recorded config and records the rollback itself
+    let report = context
+        .rollback_config(&first_revision, Some("bob".to_string()))
+        .await?;
+    assert!(report.is_succeeded());
+    assert_eq!(context.current_config_of(None).await, Some(first.clone()));
+    assert!(
+        context
+            .diff_config_history(&first_revision, None)
+            .await?
+            .is_empty()
+    );
+    // the edge group has no config of its own, so it is compared with the current one
+    let diff = context.diff_config_history(&second_revision, None).await?;
+    assert_eq!(diff.tcp_routes.len(), 1);
+    assert_eq!(diff.tcp_routes[0].kind, ChangeKind::Removed);
+
+    let history = list_history(&context).await;
+    assert_eq!(history.len(), 3);
+    let rollback = history
+        .iter()
+        .find(|record| {
+            matches!(
+                &record.source,
+                AppliedConfigSource::Rollback { from_revision } if *from_revision == first_revision
+            )
+        })
+        .unwrap();
+    assert_eq!(rollback.author.as_deref(), Some("bob"));
+    assert_eq!(rollback.config_version, first.digest_sha256_base64());
+    assert!(rollback.succeeded);
+    Ok(())
+}
@@ -219,3 +219,339 @@ async fn test_db_storage() -> switchboard_controller::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_filesystem_storage_paths() -> switchboard_controller::Result<()> {
+    use switchboard_controller::ControllerContext;
+    use switchboard_controller::storage::{StorageError, StorageObjectDescriptor, StorageProvider};
+    const DATA_TYPE: &str = "test-data";
+    const DIR: &str = "tmp/test_filesystem_storage_paths";
+    if tokio::fs::try_exists(DIR).await.unwrap() {
+        tokio::fs::remove_dir_all(DIR).await.unwrap();
+    }
+    let context = ControllerContext::new(ControllerConfig {
+        storage: StorageProvider::Filesystem { dir: DIR.into() },
+        ..Default::default()
+    })
+    .await?;
+    let lease = context
+        .storage()
+        .acquire_lease("leader", "controller-a", std::time::Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(lease.holder, "controller-a");
+    let descriptor = context
+        .storage()
+        .save_object("test-object", DATA_TYPE, value!({ "version": 1 }))
+        .await
+        .unwrap();
+    // listing reads the small header files, never the data
+    let data_file = format!("{DIR}/test-object/{}.json", descriptor.revision);
+    let data = tokio::fs::read(&data_file).await.unwrap();
+    tokio::fs::write(&data_file, "not json").await.unwrap();
+    let listed = context
+        .storage()
+        .list_objects(ListObjectQuery {
+            filter: ObjectFilter::default(),
+            page: PageQuery::with_limit(10),
+        })
+        .await
+        .unwrap();
+    assert_eq!(listed.items.len(), 1);
+    tokio::fs::write(&data_file, data).await.unwrap();
+    // a file outside the storage dir, which traversing revisions would reach
+    tokio::fs::write("tmp/test_filesystem_storage_outside.json", "{}")
+        .await
+        .unwrap();
+
+    // revisions other than hex digests never reach the filesystem
+    for revision in [
+        "../../test_filesystem_storage_outside",
+        "../test-object/..",
+        "",
+        "not-a-digest",
+    ] {
+        let traversal = StorageObjectDescriptor {
+            id: "test-object".into(),
+            revision: revision.into(),
+        };
+        assert!(matches!(
+            context.storage().get_object(&traversal).await,
+            Err(StorageError::InvalidRevision(_))
+        ));
+        assert!(matches!(
+            context.storage().delete_object(&traversal).await,
+            Err(StorageError::InvalidRevision(_))
+        ));
+        assert!(
+            context
+                .storage()
+                .batch_delete_objects(vec![traversal])
+                .await
+                .is_err()
+        );
+    }
+    assert!(
+        tokio::fs::try_exists("tmp/test_filesystem_storage_outside.json")
+            .await
+            .unwrap()
+    );
+
+    // an empty id would be the storage dir itself
+    assert!(matches!(
+        context.storage().delete_all_objects_by_id("").await,
+        Err(StorageError::InvalidId(_))
+    ));
+    assert!(matches!(
+        context
+            .storage()
+            .save_object("", DATA_TYPE, value!({ "version": 1 }))
+            .await,
+        Err(StorageError::InvalidId(_))
+    ));
+    let empty_id = StorageObjectDescriptor {
+        id: String::new(),
+        revision: descriptor.revision.clone(),
+    };
+    assert!(matches!(
+        context.storage().get_object(&empty_id).await,
+        Err(StorageError::InvalidId(_))
+    ));
+
+    // dots and slashes in ids stay inside their own object dir
+    context
+        .storage()
+        .delete_all_objects_by_id("..")
+        .await
+        .unwrap();
+    context
+        .storage()
+        .delete_all_objects_by_id("../test-object")
+        .await
+        .unwrap();
+    assert!(context.storage().get_object(&descriptor).await?.is_some());
+    let lease = context
+        .storage()
+        .acquire_lease("leader", "controller-b", std::time::Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(lease.holder, "controller-a");
+    Ok(())
+}
+
+/// Walk every page of `filter`, `limit` objects at a time.
+async fn list_all_pages(
+    context: &switchboard_controller::ControllerContext,
+    filter: ObjectFilter,
+    limit: usize,
+) -> Vec<switchboard_controller::storage::StorageObjectDescriptor> {
+    let mut descriptors = Vec::new();
+    let mut cursor = switchboard_model::Cursor::empty();
+    loop {
+        let page = context
+            .storage()
+            .list_objects(ListObjectQuery {
+                filter: filter.clone(),
+                page: PageQuery::with_limit(limit).with_cursor(cursor),
+            })
+            .await
+            .unwrap();
+        assert!(page.items.len() <= limit);
+        if page.items.is_empty() {
+            return descriptors;
+        }
+        descriptors.extend(page.items.into_iter().map(|item| item.data.descriptor));
+        // a cursor which doesn't advance would loop forever
+        assert!(
+            descriptors.len() <= 20,
+            "pagination returned an object twice"
+        );
+        cursor = page.next_cursor.unwrap();
+    }
+}
+
+/// Following `next_cursor` visits every object once. The surrealdb list used to compare the
+/// cursor with `<` while ordering ascending, so the second page repeated the first one.
+#[tokio::test]
+async fn test_storage_pagination() -> switchboard_controller::Result<()> {
+    use std::collections::HashSet;
+    use switchboard_controller::ControllerContext;
+    use switchboard_controller::storage::StorageProvider;
+    const DATA_TYPE: &str = "test-data";
+    const DB_PATH: &str = "tmp/test_storage_pagination.db";
+    const DIR: &str = "tmp/test_storage_pagination";
+    for path in [DB_PATH, DIR] {
+        if tokio::fs::try_exists(path).await.unwrap() {
+            tokio::fs::remove_dir_all(path).await.unwrap();
+        }
+    }
+    for storage in [
+        StorageProvider::Local {
+            db_file: DB_PATH.into(),
+        },
+        StorageProvider::Filesystem { dir: DIR.into() },
+    ] {
+        let context = ControllerContext::new(ControllerConfig {
+            storage,
+            ..Default::default()
+        })
+        .await?;
+        let mut saved = Vec::new();
+        let mut latest = Vec::new();
+        for index in 0..5 {
+            for version in 1..=2 {
+                let descriptor = context
+                    .storage()
+                    .save_object(
+                        &format!("page-object-{index}"),
+                        DATA_TYPE,
+                        value!({ "version": version }),
+                    )
+                    .await
+                    .unwrap();
+                saved.push(descriptor);
+            }
+            latest.extend(saved.last().cloned());
+        }
+        for limit in [1, 2, 3, 10] {
+            let listed = list_all_pages(
+                &context,
+                ObjectFilter {
+                    data_type: Some(DATA_TYPE.into()),
+                    ..Default::default()
+                },
+                limit,
+            )
+            .await;
+            assert_eq!(listed.len(), saved.len());
+            assert_eq!(
+                listed.into_iter().collect::<HashSet<_>>(),
+                saved.iter().cloned().collect::<HashSet<_>>()
+            );
+            let listed_latest = list_all_pages(
+                &context,
+                ObjectFilter {
+                    data_type: Some(DATA_TYPE.into()),
+                    latest_only: Some(true),
+                    ..Default::default()
+                },
+                limit,
+            )
+            .await;
+            assert_eq!(listed_latest.len(), latest.len());
+            assert_eq!(
+                listed_latest.into_iter().collect::<HashSet<_>>(),
+                latest.iter().cloned().collect::<HashSet<_>>()
+            );
+        }
+        // excluded ids are left out by the storage, before paging
+        let listed = list_all_pages(
+            &context,
+            ObjectFilter {
+                data_type: Some(DATA_TYPE.into()),
+                exclude_ids: vec!["page-object-0".into()],
+                ..Default::default()
+            },
+            3,
+        )
+        .await;
+        assert_eq!(listed.len(), saved.len() - 2);
+        assert!(
+            listed
+                .iter()
+                .all(|descriptor| descriptor.id != "page-object-0")
+        );
+    }
+    Ok(())
+}
+
+/// A lease is held by one controller until it expires or is released, for every backend
+/// leader election can run on.
+#[tokio::test]
+async fn test_storage_lease() -> switchboard_controller::Result<()> {
+    use std::time::Duration;
+    use switchboard_controller::ControllerContext;
+    use switchboard_controller::storage::StorageProvider;
+    const DB_PATH: &str = "tmp/test_storage_lease.db";
+    const DIR: &str = "tmp/test_storage_lease";
+    for path in [DB_PATH, DIR] {
+        if tokio::fs::try_exists(path).await.unwrap() {
+            tokio::fs::remove_dir_all(path).await.unwrap();
+        }
+    }
+    for storage in [
+        StorageProvider::Local {
+            db_file: DB_PATH.into(),
+        },
+        StorageProvider::Filesystem { dir: DIR.into() },
+    ] {
+        let context = ControllerContext::new(ControllerConfig {
+            storage,
+            ..Default::default()
+        })
+        .await?;
+        let storage = context.storage();
+        let ttl = Duration::from_secs(60);
+
+        let lease = storage.acquire_lease("leader", "a", ttl).await.unwrap();
+        assert_eq!(lease.holder, "a");
+        // another controller sees the current holder and doesn't take over
+        assert_eq!(
+            storage
+                .acquire_lease("leader", "b", ttl)
+                .await
+                .unwrap()
+                .holder,
+            "a"
+        );
+        // renewing extends the lease
+        let renewed = storage.acquire_lease("leader", "a", ttl).await.unwrap();
+        assert_eq!(renewed.holder, "a");
+        assert!(renewed.expires_at >= lease.expires_at);
+        // leases are independent by name
+        assert_eq!(
+            storage
+                .acquire_lease("other", "b", ttl)
+                .await
+                .unwrap()
+                .holder,
+            "b"
+        );
+
+        // only the holder can release
+        storage.release_lease("leader", "b").await.unwrap();
+        assert_eq!(
+            storage
+                .acquire_lease("leader", "b", ttl)
+                .await
+                .unwrap()
+                .holder,
+            "a"
+        );
+        storage.release_lease("leader", "a").await.unwrap();
+        assert_eq!(
+            storage
+                .acquire_lease("leader", "b", ttl)
+                .await
+                .unwrap()
+                .holder,
+            "b"
+        );
+
+        // an expired lease is taken over
+        storage
+            .acquire_lease("short", "a", Duration::from_millis(50))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            storage
+                .acquire_lease("short", "b", ttl)
+                .await
+                .unwrap()
+                .holder,
+            "b"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use switchboard_controller::{
+    ControllerContext,
+    config::ControllerConfig,
+    history::AppliedConfigSource,
+    kernel::rollout::{ProgressiveRolloutPolicy, RolloutState},
+    storage::StorageProvider,
+};
+use switchboard_model::{PageQuery, ServiceConfig, tcp_route::TcpRoute};
+
+/// A rollout without kernels has no batches, it still finishes, becomes the current config
+/// and is recorded in the history.
+#[tokio::test]
+async fn test_rollout_without_kernels() -> switchboard_controller::Result<()> {
+    const DB_PATH: &str = "tmp/test_rollout.db";
+    if tokio::fs::try_exists(DB_PATH).await.unwrap() {
+        tokio::fs::remove_dir_all(DB_PATH).await.unwrap();
+    }
+    let context = ControllerContext::new(ControllerConfig {
+        storage: StorageProvider::Local {
+            db_file: DB_PATH.into(),
+        },
+        ..Default::default()
+    })
+    .await?;
+    let bind = std::net::SocketAddr::from(([127, 0, 0, 1], 8080));
+    let config = ServiceConfig {
+        tcp_routes: [(
+            bind,
+            TcpRoute {
+                bind,
+                service: "web".to_string(),
+                tls: None,
+            },
+        )]
+        .into(),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        context
+            .start_rollout(
+                config.clone(),
+                ProgressiveRolloutPolicy::default(),
+                None,
+                Some("edge".to_string()),
+            )
+            .await,
+        Err(switchboard_controller::Error::UnknownDeploymentGroup(_))
+    ));
+
+    let started = context
+        .start_rollout(
+            config.clone(),
+            ProgressiveRolloutPolicy::default(),
+            Some("alice".to_string()),
+            None,
+        )
+        .await?;
+    assert!(started.batches.is_empty());
+    assert!(matches!(started.state, RolloutState::Running { batch: 0 }));
+    assert_eq!(started.target_version, config.digest_sha256_base64());
+
+    let handle = context.get_rollout(None).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !handle.is_finished().await {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("rollout didn't finish");
+    let progress = handle.progress().await;
+    assert_eq!(progress.id, started.id);
+    assert!(matches!(progress.state, RolloutState::Succeeded));
+    assert!(progress.finished_at.is_some());
+    assert_eq!(context.current_config_of(None).await, Some(config.clone()));
+
+    // the record is written right after the state is, give it a moment
+    let mut recorded = None;
+    for _ in 0..100 {
+        let history = context
+            .list_config_history(PageQuery::with_limit(10))
+            .await?;
+        recorded = history
+            .items
+            .into_iter()
+            .map(|item| item.data)
+            .find(|record| {
+                matches!(
+                    &record.source,
+                    AppliedConfigSource::Rollout { rollout_id } if *rollout_id == started.id
+                )
+            });
+        if recorded.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let recorded = recorded.expect("rollout wasn't recorded");
+    assert!(recorded.succeeded);
+    assert_eq!(recorded.author.as_deref(), Some("alice"));
+    assert_eq!(recorded.config_version, config.digest_sha256_base64());
+    Ok(())
+}
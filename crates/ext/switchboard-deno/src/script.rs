@@ -0,0 +1,734 @@
+//! `script` filter and `script-service` node classes, running a TypeScript or JavaScript
+//! handler per request.
+//!
+//! A script defines `onRequest` and optionally `onResponse` as top level functions:
+//!
+//! ```ts
+//! function onRequest(req) {
+//!     if (!req.headers["authorization"]) {
+//!         return { respond: { status: 401, body: "unauthorized" } };
+//!     }
+//!     req.headers["x-user"] = req.captures["user"] ?? "anonymous";
+//!     return { next: req };
+//! }
+//!
+//! function onResponse(res, req) {
+//!     res.headers["x-handled-by"] = "script";
+//!     return res;
+//! }
+//! ```
+//!
+//! `onRequest` returns nothing to pass the request on unchanged, `{ next: request }` to pass it
+//! on modified, or `{ respond: response }` to answer right away. `onResponse` returns nothing or
+//! the modified response, whose body is only replaced if it sets one. Both may be `async`.
+//!
+//! Scripts run on a pool of worker threads, each with its own isolate, so global state is
+//! neither shared between workers nor kept for a particular client.
+
+mod worker;
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use deno_ast::{
+    EmitOptions, MediaType, ParseDiagnostic, ParseParams, SourceMapOption, TranspileError,
+    TranspileModuleOptions, TranspileOptions,
+};
+use deno_core::serde_v8;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use switchboard_http::{
+    DynRequest, DynResponse,
+    extension::captures::Captures,
+    flow::{
+        FlowContext,
+        filter::{FilterClass, FilterLike, Next},
+        node::NodeClass,
+        service::{Service, ServiceNode},
+    },
+    instance::class::{registry::ClassRegistry, replace_plugin_body},
+    utils::{error_response, one_or_many::OneOrMany},
+};
+use switchboard_model::services::http::ClassId;
+
+use worker::script_pool;
+
+pub const SCRIPT_FILTER_CLASS_ID: &str = "script";
+pub const SCRIPT_SERVICE_CLASS_ID: &str = "script-service";
+pub const ERR_SCRIPT: &str = "script";
+
+/// # Example
+/// ```toml
+/// [filter.auth]
+/// class = "script"
+/// config = { path = "scripts/auth.ts", timeout = "50ms" }
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct ScriptConfig {
+    /// Inline source, takes precedence over `path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub lang: ScriptLang,
+    /// Time limit of a single call, waiting for a free worker included.
+    #[serde(
+        with = "switchboard_http::utils::duration_expr",
+        default = "default_timeout"
+    )]
+    #[schemars(with = "switchboard_http::utils::duration_expr::DurationExpr")]
+    pub timeout: Duration,
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_millis(100)
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptLang {
+    #[default]
+    Typescript,
+    Javascript,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptConfigError {
+    #[error("either `source` or `path` must be set")]
+    MissingSource,
+    #[error("failed to read script {path:?}: {error}")]
+    Read {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("parse diagnostic: {0}")]
+    ParseDiagnostic(#[from] ParseDiagnostic),
+    #[error("transpile error: {0}")]
+    TranspileError(#[from] TranspileError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptError {
+    #[error("serde_v8 error: {0}")]
+    SerdeV8Error(#[from] serde_v8::Error),
+    #[error("js error: {0}")]
+    JsError(#[from] Box<deno_core::error::JsError>),
+    #[error("core error: {0}")]
+    CoreError(#[from] deno_core::error::CoreError),
+    #[error("script exceeded its time limit of {0:?}")]
+    Timeout(Duration),
+    #[error("script worker is gone")]
+    WorkerGone,
+    #[error("invalid result from script: {0}")]
+    InvalidResult(#[from] serde_json::Error),
+    #[error("invalid {0} from script")]
+    InvalidMessage(&'static str),
+    #[error("`onRequest` of a script service must respond")]
+    MissingResponse,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Hook {
+    OnRequest,
+    OnResponse,
+}
+
+/// A transpiled script, loaded into each worker isolate on its first call there.
+pub(crate) struct CompiledScript {
+    id: u64,
+    name: String,
+    code: String,
+    timeout: Duration,
+    /// Set once a worker loaded the script, so a filter without `onResponse` skips the call.
+    has_on_response: OnceLock<bool>,
+}
+
+impl Drop for CompiledScript {
+    fn drop(&mut self) {
+        script_pool().unload(self.id);
+    }
+}
+
+static NEXT_SCRIPT_ID: AtomicU64 = AtomicU64::new(0);
+
+impl CompiledScript {
+    fn compile(config: ScriptConfig) -> Result<Self, ScriptConfigError> {
+        let (name, source) = match (config.source, config.path) {
+            (Some(source), _) => ("script".to_string(), source),
+            (None, Some(path)) => {
+                let source =
+                    std::fs::read_to_string(&path).map_err(|error| ScriptConfigError::Read {
+                        path: path.clone(),
+                        error,
+                    })?;
+                (path.display().to_string(), source)
+            }
+            (None, None) => return Err(ScriptConfigError::MissingSource),
+        };
+        let code = match config.lang {
+            ScriptLang::Typescript => transpile_ts(&source)?,
+            ScriptLang::Javascript => source,
+        };
+        // a function scope per script, so scripts in the same isolate don't see each other
+        let code = format!(
+            "(() => {{\n{code}\n;return {{\n\
+             onRequest: typeof onRequest === \"function\" ? onRequest : undefined,\n\
+             onResponse: typeof onResponse === \"function\" ? onResponse : undefined,\n\
+             }};\n}})()"
+        );
+        Ok(Self {
+            id: NEXT_SCRIPT_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            code,
+            timeout: config.timeout,
+            has_on_response: OnceLock::new(),
+        })
+    }
+}
+
+fn transpile_ts(code: &str) -> Result<String, ScriptConfigError> {
+    let specifier = deno_core::ModuleSpecifier::parse("file:///script.ts")
+        .expect("should be valid module specifier");
+    let parsed = deno_ast::parse_script(ParseParams {
+        specifier,
+        text: code.into(),
+        media_type: MediaType::TypeScript,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })?;
+    let transpiled = parsed.transpile(
+        &TranspileOptions::default(),
+        &TranspileModuleOptions::default(),
+        &EmitOptions {
+            source_map: SourceMapOption::None,
+            ..Default::default()
+        },
+    )?;
+    Ok(transpiled.into_source().text)
+}
+
+type ScriptHeaders = HashMap<String, OneOrMany<String>>;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScriptRequest {
+    method: String,
+    uri: String,
+    headers: ScriptHeaders,
+    captures: HashMap<Arc<str>, Arc<str>>,
+    peer_addr: Option<String>,
+}
+
+/// A request passed on by `onRequest`, unset fields are kept.
+#[derive(Debug, Default, Deserialize)]
+struct RequestPatch {
+    method: Option<String>,
+    uri: Option<String>,
+    headers: Option<ScriptHeaders>,
+    captures: Option<HashMap<Arc<str>, Arc<str>>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ScriptResponse {
+    status: Option<u16>,
+    headers: Option<ScriptHeaders>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RequestAction {
+    Next(RequestPatch),
+    Respond(ScriptResponse),
+}
+
+fn to_script_headers(headers: &HeaderMap) -> ScriptHeaders {
+    let mut script_headers = ScriptHeaders::new();
+    for name in headers.keys() {
+        let mut values = headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok().map(str::to_string))
+            .collect::<Vec<_>>();
+        let value = match values.len() {
+            0 => continue,
+            1 => OneOrMany::One(values.remove(0)),
+            _ => OneOrMany::Many(values),
+        };
+        script_headers.insert(name.to_string(), value);
+    }
+    script_headers
+}
+
+fn from_script_headers(headers: ScriptHeaders) -> Result<HeaderMap, ScriptError> {
+    let mut header_map = HeaderMap::with_capacity(headers.len());
+    for (name, values) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| ScriptError::InvalidMessage("header name"))?;
+        for value in values {
+            let value = HeaderValue::from_str(&value)
+                .map_err(|_| ScriptError::InvalidMessage("header value"))?;
+            header_map.append(name.clone(), value);
+        }
+    }
+    Ok(header_map)
+}
+
+fn from_script_response(response: ScriptResponse) -> Result<DynResponse, ScriptError> {
+    let status = StatusCode::from_u16(response.status.unwrap_or(200))
+        .map_err(|_| ScriptError::InvalidMessage("status code"))?;
+    let mut headers = response
+        .headers
+        .map(from_script_headers)
+        .transpose()?
+        .unwrap_or_default();
+    let body = replace_plugin_body(&mut headers, response.body.unwrap_or_default());
+    let mut dyn_response = DynResponse::new(body);
+    *dyn_response.status_mut() = status;
+    *dyn_response.headers_mut() = headers;
+    Ok(dyn_response)
+}
+
+fn script_error_response(error: ScriptError) -> DynResponse {
+    tracing::error!("{}", error);
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, error, ERR_SCRIPT)
+}
+
+impl ScriptRequest {
+    fn new(parts: &http::request::Parts, ctx: &FlowContext) -> Self {
+        Self {
+            method: parts.method.to_string(),
+            uri: parts.uri.to_string(),
+            headers: to_script_headers(&parts.headers),
+            captures: parts
+                .extensions
+                .get::<Captures>()
+                .map(|captures| captures.captures.clone())
+                .unwrap_or_default(),
+            peer_addr: ctx
+                .connection_info
+                .as_ref()
+                .map(|info| info.peer_addr.to_string()),
+        }
+    }
+
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("script request should serialize")
+    }
+}
+
+/// Apply a request passed on by `onRequest` onto the original request parts.
+fn apply_request_patch(
+    parts: &mut http::request::Parts,
+    patch: RequestPatch,
+) -> Result<(), ScriptError> {
+    if let Some(method) = patch.method {
+        parts.method = method
+            .parse()
+            .map_err(|_| ScriptError::InvalidMessage("method"))?;
+    }
+    if let Some(uri) = patch.uri {
+        parts.uri = uri
+            .parse()
+            .map_err(|_| ScriptError::InvalidMessage("uri"))?;
+    }
+    if let Some(headers) = patch.headers {
+        parts.headers = from_script_headers(headers)?;
+    }
+    if let Some(captures) = patch.captures {
+        parts.extensions.insert(Captures { captures });
+    }
+    Ok(())
+}
+
+async fn on_request(
+    script: &Arc<CompiledScript>,
+    request: &ScriptRequest,
+) -> Result<Option<RequestAction>, ScriptError> {
+    let result = script_pool()
+        .call(script, Hook::OnRequest, vec![request.to_value()])
+        .await?;
+    Ok(serde_json::from_value(result)?)
+}
+
+pub struct ScriptFilter {
+    script: Arc<CompiledScript>,
+}
+
+impl ScriptFilter {
+    async fn on_response(
+        &self,
+        response: DynResponse,
+        request: &ScriptRequest,
+    ) -> Result<DynResponse, ScriptError> {
+        let (mut parts, body) = response.into_parts();
+        let script_response = ScriptResponse {
+            status: Some(parts.status.as_u16()),
+            headers: Some(to_script_headers(&parts.headers)),
+            body: None,
+        };
+        let result = script_pool()
+            .call(
+                &self.script,
+                Hook::OnResponse,
+                vec![serde_json::to_value(&script_response)?, request.to_value()],
+            )
+            .await?;
+        let Some(patch) = serde_json::from_value::<Option<ScriptResponse>>(result)? else {
+            return Ok(DynResponse::from_parts(parts, body));
+        };
+        if let Some(status) = patch.status {
+            parts.status = StatusCode::from_u16(status)
+                .map_err(|_| ScriptError::InvalidMessage("status code"))?;
+        }
+        if let Some(headers) = patch.headers {
+            parts.headers = from_script_headers(headers)?;
+        }
+        let body = match patch.body {
+            Some(new_body) => replace_plugin_body(&mut parts.headers, new_body),
+            None => body,
+        };
+        Ok(DynResponse::from_parts(parts, body))
+    }
+}
+
+impl FilterLike for ScriptFilter {
+    async fn call(
+        self: Arc<Self>,
+        req: DynRequest,
+        ctx: &mut FlowContext,
+        next: Next,
+    ) -> DynResponse {
+        let (mut parts, body) = req.into_parts();
+        let request = ScriptRequest::new(&parts, ctx);
+        match on_request(&self.script, &request).await {
+            Ok(None) => {}
+            Ok(Some(RequestAction::Next(patch))) => {
+                if let Err(e) = apply_request_patch(&mut parts, patch) {
+                    return script_error_response(e);
+                }
+            }
+            Ok(Some(RequestAction::Respond(response))) => {
+                return from_script_response(response).unwrap_or_else(script_error_response);
+            }
+            Err(e) => return script_error_response(e),
+        }
+        let response = next.call(DynRequest::from_parts(parts, body), ctx).await;
+        if self.script.has_on_response.get() == Some(&false) {
+            return response;
+        }
+        self.on_response(response, &request)
+            .await
+            .unwrap_or_else(script_error_response)
+    }
+}
+
+pub struct ScriptService {
+    script: Arc<CompiledScript>,
+}
+
+impl Service for ScriptService {
+    fn call<'c>(
+        &self,
+        req: DynRequest,
+        ctx: &'c mut FlowContext,
+    ) -> impl Future<Output = DynResponse> + Send + 'c {
+        let script = self.script.clone();
+        let (parts, _) = req.into_parts();
+        let request = ScriptRequest::new(&parts, ctx);
+        async move {
+            let result = match on_request(&script, &request).await {
+                Ok(Some(RequestAction::Respond(response))) => from_script_response(response),
+                Ok(_) => Err(ScriptError::MissingResponse),
+                Err(e) => Err(e),
+            };
+            result.unwrap_or_else(script_error_response)
+        }
+    }
+}
+
+pub struct ScriptFilterClass;
+
+impl FilterClass for ScriptFilterClass {
+    type Filter = ScriptFilter;
+    type Error = ScriptConfigError;
+    type Config = ScriptConfig;
+
+    fn id(&self) -> ClassId {
+        ClassId::std(SCRIPT_FILTER_CLASS_ID)
+    }
+
+    fn construct(&self, config: Self::Config) -> Result<Self::Filter, Self::Error> {
+        Ok(ScriptFilter {
+            script: Arc::new(CompiledScript::compile(config)?),
+        })
+    }
+}
+
+pub struct ScriptServiceClass;
+
+impl NodeClass for ScriptServiceClass {
+    type Node = ServiceNode<ScriptService>;
+    type Error = ScriptConfigError;
+    type Config = ScriptConfig;
+
+    fn id(&self) -> ClassId {
+        ClassId::std(SCRIPT_SERVICE_CLASS_ID)
+    }
+
+    fn construct(&self, config: Self::Config) -> Result<Self::Node, Self::Error> {
+        Ok(ServiceNode::new(ScriptService {
+            script: Arc::new(CompiledScript::compile(config)?),
+        }))
+    }
+}
+
+/// Register the `script` filter and `script-service` node classes.
+pub fn register_script_classes(registry: &mut ClassRegistry) {
+    registry.register_filter(ScriptFilterClass);
+    registry.register_node(ScriptServiceClass);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str, lang: ScriptLang) -> Arc<CompiledScript> {
+        Arc::new(
+            CompiledScript::compile(ScriptConfig {
+                source: Some(source.to_string()),
+                path: None,
+                lang,
+                timeout: Duration::from_secs(5),
+            })
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let config = |source: Option<&str>, path: Option<&str>| ScriptConfig {
+            source: source.map(str::to_string),
+            path: path.map(PathBuf::from),
+            lang: ScriptLang::Typescript,
+            timeout: default_timeout(),
+        };
+        assert!(matches!(
+            CompiledScript::compile(config(None, None)),
+            Err(ScriptConfigError::MissingSource)
+        ));
+        assert!(matches!(
+            CompiledScript::compile(config(None, Some("no/such/script.ts"))),
+            Err(ScriptConfigError::Read { .. })
+        ));
+        assert!(CompiledScript::compile(config(Some("function onRequest(req {"), None)).is_err());
+    }
+
+    #[test]
+    fn test_script_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-one", HeaderValue::from_static("1"));
+        headers.append("x-many", HeaderValue::from_static("a"));
+        headers.append("x-many", HeaderValue::from_static("b"));
+        let script_headers = to_script_headers(&headers);
+        assert!(matches!(&script_headers["x-one"], OneOrMany::One(value) if value == "1"));
+        assert!(
+            matches!(&script_headers["x-many"], OneOrMany::Many(values) if values == &["a", "b"])
+        );
+        assert_eq!(from_script_headers(script_headers).unwrap(), headers);
+
+        let invalid = |name: &str, value: &str| {
+            from_script_headers([(name.to_string(), OneOrMany::One(value.to_string()))].into())
+        };
+        assert!(matches!(
+            invalid("bad name", "v"),
+            Err(ScriptError::InvalidMessage("header name"))
+        ));
+        assert!(matches!(
+            invalid("x-bad", "a\nb"),
+            Err(ScriptError::InvalidMessage("header value"))
+        ));
+    }
+
+    #[test]
+    fn test_from_script_response() {
+        let response = from_script_response(ScriptResponse {
+            status: None,
+            headers: Some(
+                [
+                    ("x-script".to_string(), OneOrMany::One("yes".to_string())),
+                    (
+                        "content-length".to_string(),
+                        OneOrMany::One("100".to_string()),
+                    ),
+                ]
+                .into(),
+            ),
+            body: Some("hello".to_string()),
+        })
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-script"], "yes");
+        assert!(
+            response
+                .headers()
+                .get(http::header::CONTENT_LENGTH)
+                .is_none()
+        );
+        assert!(matches!(
+            from_script_response(ScriptResponse {
+                status: Some(1000),
+                ..Default::default()
+            }),
+            Err(ScriptError::InvalidMessage("status code"))
+        ));
+    }
+
+    #[test]
+    fn test_apply_request_patch() {
+        let (mut parts, _) = http::Request::builder()
+            .uri("/old")
+            .header("x-keep", "1")
+            .body(())
+            .unwrap()
+            .into_parts();
+        apply_request_patch(&mut parts, RequestPatch::default()).unwrap();
+        assert_eq!(parts.uri, "/old");
+        assert_eq!(parts.headers["x-keep"], "1");
+
+        apply_request_patch(
+            &mut parts,
+            RequestPatch {
+                method: Some("POST".to_string()),
+                uri: Some("/new".to_string()),
+                headers: Some([("x-new".to_string(), OneOrMany::One("2".to_string()))].into()),
+                captures: Some(HashMap::from([(Arc::from("user"), Arc::from("alice"))])),
+            },
+        )
+        .unwrap();
+        assert_eq!(parts.method, http::Method::POST);
+        assert_eq!(parts.uri, "/new");
+        assert!(parts.headers.get("x-keep").is_none());
+        assert_eq!(parts.headers["x-new"], "2");
+        let captures = parts.extensions.get::<Captures>().unwrap();
+        assert_eq!(
+            captures.captures.get("user").map(|user| &**user),
+            Some("alice")
+        );
+
+        assert!(matches!(
+            apply_request_patch(
+                &mut parts,
+                RequestPatch {
+                    method: Some("BAD METHOD".to_string()),
+                    ..Default::default()
+                }
+            ),
+            Err(ScriptError::InvalidMessage("method"))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_pool() {
+        let script = compile(
+            r#"
+            function onRequest(req: { headers: Record<string, string> }) {
+                if (!req.headers["authorization"]) {
+                    return { respond: { status: 401, body: "unauthorized" } };
+                }
+                return { next: { headers: { "x-user": "alice" } } };
+            }
+            "#,
+            ScriptLang::Typescript,
+        );
+        let request = |headers: &[(&str, &str)]| ScriptRequest {
+            method: "GET".to_string(),
+            uri: "/".to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), OneOrMany::One(value.to_string())))
+                .collect(),
+            captures: HashMap::new(),
+            peer_addr: None,
+        };
+        let Some(RequestAction::Respond(response)) =
+            on_request(&script, &request(&[])).await.unwrap()
+        else {
+            panic!("request without authorization wasn't refused");
+        };
+        assert_eq!(response.status, Some(401));
+        assert_eq!(response.body.as_deref(), Some("unauthorized"));
+        let Some(RequestAction::Next(patch)) =
+            on_request(&script, &request(&[("authorization", "token")]))
+                .await
+                .unwrap()
+        else {
+            panic!("authorized request wasn't passed on");
+        };
+        assert!(patch.headers.unwrap().contains_key("x-user"));
+        assert_eq!(script.has_on_response.get(), Some(&false));
+
+        // async handlers are awaited, a script without handlers passes everything on
+        let script = compile(
+            "async function onRequest(req) { return { respond: { status: 204 } }; }\n\
+             function onResponse(res) { return res; }",
+            ScriptLang::Javascript,
+        );
+        assert!(matches!(
+            on_request(&script, &request(&[])).await.unwrap(),
+            Some(RequestAction::Respond(ScriptResponse {
+                status: Some(204),
+                ..
+            }))
+        ));
+        assert_eq!(script.has_on_response.get(), Some(&true));
+        let script = compile("const unused = 1;", ScriptLang::Javascript);
+        assert!(on_request(&script, &request(&[])).await.unwrap().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_timeout() {
+        let script = Arc::new(
+            CompiledScript::compile(ScriptConfig {
+                source: Some("function onRequest(req) { while (true) {} }".to_string()),
+                path: None,
+                lang: ScriptLang::Javascript,
+                timeout: Duration::from_millis(50),
+            })
+            .unwrap(),
+        );
+        let request = ScriptRequest {
+            method: "GET".to_string(),
+            uri: "/".to_string(),
+            headers: ScriptHeaders::new(),
+            captures: HashMap::new(),
+            peer_addr: None,
+        };
+        assert!(matches!(
+            on_request(&script, &request).await,
+            Err(ScriptError::Timeout(_))
+        ));
+        // the worker was terminated, not stuck, and serves the next call
+        let script = compile(
+            "function onRequest(req) { return { respond: {} }; }",
+            ScriptLang::Javascript,
+        );
+        for _ in 0..8 {
+            assert!(matches!(
+                on_request(&script, &request).await,
+                Ok(Some(RequestAction::Respond(_)))
+            ));
+        }
+    }
+}
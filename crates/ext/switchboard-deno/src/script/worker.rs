@@ -0,0 +1,266 @@
+//! Script worker threads, each owning one isolate.
+//!
+//! A `JsRuntime` can't leave the thread it was created on, so calls are sent to the workers
+//! over channels, the same way the typescript config renderer works.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use deno_core::{JsRuntime, PollEventLoopOptions, RuntimeOptions, serde_v8, v8};
+
+use super::{CompiledScript, Hook, ScriptError};
+
+static SCRIPT_POOL: OnceLock<ScriptPool> = OnceLock::new();
+
+/// The pool shared by every script, spawned on first use with one worker per cpu.
+pub(crate) fn script_pool() -> &'static ScriptPool {
+    SCRIPT_POOL.get_or_init(|| {
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        ScriptPool::spawn(workers)
+    })
+}
+
+pub(crate) enum ScriptTask {
+    Call {
+        call_id: u64,
+        script: Arc<CompiledScript>,
+        hook: Hook,
+        args: Vec<serde_json::Value>,
+        result_sender: tokio::sync::oneshot::Sender<Result<serde_json::Value, ScriptError>>,
+    },
+    Unload {
+        script_id: u64,
+    },
+}
+
+/// The call a worker is running, so a caller whose time limit elapsed only terminates its own.
+#[derive(Default)]
+struct RunningCall {
+    call_id: Mutex<Option<u64>>,
+    isolate: OnceLock<v8::IsolateHandle>,
+}
+
+impl RunningCall {
+    fn start(&self, call_id: u64) {
+        *self.call_id.lock().unwrap_or_else(|e| e.into_inner()) = Some(call_id);
+    }
+    fn finish(&self) {
+        let mut running = self.call_id.lock().unwrap_or_else(|e| e.into_inner());
+        *running = None;
+        if let Some(isolate) = self.isolate.get() {
+            isolate.cancel_terminate_execution();
+        }
+    }
+    fn terminate(&self, call_id: u64) {
+        let running = self.call_id.lock().unwrap_or_else(|e| e.into_inner());
+        if *running == Some(call_id)
+            && let Some(isolate) = self.isolate.get()
+        {
+            isolate.terminate_execution();
+        }
+    }
+}
+
+struct WorkerHandle {
+    task_sender: tokio::sync::mpsc::UnboundedSender<ScriptTask>,
+    running: Arc<RunningCall>,
+}
+
+pub(crate) struct ScriptPool {
+    workers: Vec<WorkerHandle>,
+    next_worker: AtomicUsize,
+    next_call_id: AtomicU64,
+}
+
+impl ScriptPool {
+    fn spawn(size: usize) -> Self {
+        let workers = (0..size.max(1))
+            .map(|index| {
+                let (task_sender, task_receiver) = tokio::sync::mpsc::unbounded_channel();
+                let running = Arc::new(RunningCall::default());
+                let worker_running = running.clone();
+                std::thread::Builder::new()
+                    .name(format!("sbk-script-{index}"))
+                    .spawn(move || ScriptWorker::run(task_receiver, worker_running))
+                    .expect("fail to spawn sbk-script thread");
+                WorkerHandle {
+                    task_sender,
+                    running,
+                }
+            })
+            .collect();
+        Self {
+            workers,
+            next_worker: AtomicUsize::new(0),
+            next_call_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Call `hook` of `script` on the next worker. The time limit covers the wait for the
+    /// worker as well, a call still running when it elapses is terminated.
+    pub(crate) async fn call(
+        &self,
+        script: &Arc<CompiledScript>,
+        hook: Hook,
+        args: Vec<serde_json::Value>,
+    ) -> Result<serde_json::Value, ScriptError> {
+        let worker =
+            &self.workers[self.next_worker.fetch_add(1, Ordering::Relaxed) % self.workers.len()];
+        let call_id = self.next_call_id.fetch_add(1, Ordering::Relaxed);
+        let (result_sender, result_receiver) = tokio::sync::oneshot::channel();
+        worker
+            .task_sender
+            .send(ScriptTask::Call {
+                call_id,
+                script: script.clone(),
+                hook,
+                args,
+                result_sender,
+            })
+            .map_err(|_| ScriptError::WorkerGone)?;
+        match tokio::time::timeout(script.timeout, result_receiver).await {
+            Ok(result) => result.map_err(|_| ScriptError::WorkerGone)?,
+            Err(_) => {
+                worker.running.terminate(call_id);
+                Err(ScriptError::Timeout(script.timeout))
+            }
+        }
+    }
+
+    /// Drop the loaded handlers of a script from every isolate.
+    pub(crate) fn unload(&self, script_id: u64) {
+        for worker in &self.workers {
+            let _ = worker.task_sender.send(ScriptTask::Unload { script_id });
+        }
+    }
+}
+
+struct Handlers {
+    on_request: Option<v8::Global<v8::Function>>,
+    on_response: Option<v8::Global<v8::Function>>,
+}
+
+struct ScriptWorker {
+    runtime: JsRuntime,
+    loaded: HashMap<u64, Handlers>,
+}
+
+fn get_function<'s>(
+    scope: &mut v8::PinScope<'s, '_>,
+    exports: v8::Local<'s, v8::Value>,
+    name: &str,
+) -> Option<v8::Global<v8::Function>> {
+    let exports = v8::Local::<v8::Object>::try_from(exports).ok()?;
+    let key = v8::String::new(scope, name)?;
+    let value = exports.get(scope, key.into())?;
+    let function = v8::Local::<v8::Function>::try_from(value).ok()?;
+    Some(v8::Global::new(scope, function))
+}
+
+impl ScriptWorker {
+    fn run(
+        mut task_receiver: tokio::sync::mpsc::UnboundedReceiver<ScriptTask>,
+        running: Arc<RunningCall>,
+    ) {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("should build script worker runtime");
+        rt.block_on(async move {
+            let mut worker = ScriptWorker {
+                runtime: JsRuntime::new(RuntimeOptions::default()),
+                loaded: HashMap::new(),
+            };
+            let _ = running
+                .isolate
+                .set(worker.runtime.v8_isolate().thread_safe_handle());
+            while let Some(task) = task_receiver.recv().await {
+                match task {
+                    ScriptTask::Call {
+                        call_id,
+                        script,
+                        hook,
+                        args,
+                        result_sender,
+                    } => {
+                        // the caller already gave up waiting
+                        if result_sender.is_closed() {
+                            continue;
+                        }
+                        running.start(call_id);
+                        let result = worker.call(&script, hook, args).await;
+                        running.finish();
+                        let _ = result_sender.send(result);
+                    }
+                    ScriptTask::Unload { script_id } => {
+                        worker.loaded.remove(&script_id);
+                    }
+                }
+            }
+            tracing::debug!("script pool has been dropped, shutting down script worker");
+        });
+    }
+
+    fn load(&mut self, script: &CompiledScript) -> Result<(), ScriptError> {
+        if self.loaded.contains_key(&script.id) {
+            return Ok(());
+        }
+        let exports = self
+            .runtime
+            .execute_script(script.name.clone(), script.code.clone())?;
+        let handlers = {
+            deno_core::scope!(scope, self.runtime);
+            let exports = v8::Local::new(scope, exports);
+            Handlers {
+                on_request: get_function(scope, exports, "onRequest"),
+                on_response: get_function(scope, exports, "onResponse"),
+            }
+        };
+        let _ = script.has_on_response.set(handlers.on_response.is_some());
+        self.loaded.insert(script.id, handlers);
+        Ok(())
+    }
+
+    async fn call(
+        &mut self,
+        script: &CompiledScript,
+        hook: Hook,
+        args: Vec<serde_json::Value>,
+    ) -> Result<serde_json::Value, ScriptError> {
+        self.load(script)?;
+        let handlers = &self.loaded[&script.id];
+        let function = match hook {
+            Hook::OnRequest => &handlers.on_request,
+            Hook::OnResponse => &handlers.on_response,
+        };
+        let Some(function) = function else {
+            return Ok(serde_json::Value::Null);
+        };
+        let function = function.clone();
+        let args = {
+            deno_core::scope!(scope, self.runtime);
+            let mut v8_args = Vec::with_capacity(args.len());
+            for arg in &args {
+                let arg = serde_v8::to_v8(scope, arg)?;
+                v8_args.push(v8::Global::new(scope, arg));
+            }
+            v8_args
+        };
+        // a returned promise is awaited on this worker's event loop
+        let call = self.runtime.call_with_args(&function, &args);
+        let result = self
+            .runtime
+            .with_event_loop_promise(call, PollEventLoopOptions::default())
+            .await?;
+        deno_core::scope!(scope, self.runtime);
+        let result = v8::Local::new(scope, result);
+        Ok(serde_v8::from_v8(scope, result)?)
+    }
+}
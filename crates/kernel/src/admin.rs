@@ -0,0 +1,407 @@
+//! Local admin http api, for inspecting a running kernel.
+//!
+//! Off by default. It can force-close connections, so it only binds to a loopback address or a
+//! unix socket.
+
+use std::{collections::BTreeMap, net::SocketAddr, path::PathBuf, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use switchboard_model::{ListenerLimits, error::ErrorStack, kernel::KernelState};
+use tracing::Instrument;
+
+use crate::{
+    KernelContext,
+    switchboard::{
+        connection::{ConnectionFilter, ConnectionId, ConnectionInfo},
+        limit::LimitCounts,
+    },
+};
+
+/// # Example
+/// ```toml
+/// [admin]
+/// bind = "127.0.0.1:9901"
+/// uds = "/var/run/switchboard/kernel/admin.sock"
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct AdminConfig {
+    /// A loopback address, other addresses are refused.
+    pub bind: Option<SocketAddr>,
+    #[cfg(unix)]
+    pub uds: Option<PathBuf>,
+}
+
+/// Extra read-only data served by the admin api, e.g. classes of a service provider.
+pub type AdminSection = Arc<dyn Fn() -> BoxFuture<'static, serde_json::Value> + Send + Sync>;
+pub(crate) type AdminSections = BTreeMap<String, AdminSection>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdminError {
+    #[error("Admin section not found: {0}")]
+    SectionNotFound(String),
+    #[error("Connection not found: {0}")]
+    ConnectionNotFound(ConnectionId),
+    #[error("Kernel error: {0}")]
+    Kernel(#[from] crate::Error),
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            AdminError::SectionNotFound(_) | AdminError::ConnectionNotFound(_) => {
+                StatusCode::NOT_FOUND
+            }
+            AdminError::Kernel(crate::Error::EmptyConnectionFilter) => StatusCode::BAD_REQUEST,
+            AdminError::Kernel(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(ErrorStack::from_std(self))).into_response()
+    }
+}
+
+pub struct AdminListenerHandle {
+    ct: tokio_util::sync::CancellationToken,
+    join_set: tokio::task::JoinSet<std::io::Result<()>>,
+    uds_path: Option<PathBuf>,
+}
+
+impl AdminListenerHandle {
+    pub async fn shutdown(mut self) {
+        self.ct.cancel();
+        while let Some(res) = self.join_set.join_next().await {
+            match res {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::error!("Admin listener task joined with error: {}", e),
+                Err(e) => tracing::error!("Admin listener task join error: {}", e),
+            }
+        }
+        if let Some(path) = self.uds_path {
+            let _ = tokio::fs::remove_file(&path).await.inspect_err(|e| {
+                tracing::error!("Failed to remove admin uds socket {:?}: {}", path, e)
+            });
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigSummary {
+    /// The version controllers compare against, base64 of the sha256 digest.
+    pub version: String,
+    /// Hex sha256 digest of the bincode encoded config.
+    pub digest: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListenerInfo {
+    pub bind: SocketAddr,
+    pub service: Option<String>,
+    pub tls: Option<String>,
+    pub connections: usize,
+    pub limits: ListenerLimits,
+    /// How many connections each limit closed.
+    pub limits_reached: LimitCounts,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CloseConnectionsResponse {
+    pub closed: usize,
+}
+
+async fn get_state(State(context): State<KernelContext>) -> Json<KernelState> {
+    Json(context.get_state())
+}
+
+async fn get_config(State(context): State<KernelContext>) -> Json<ConfigSummary> {
+    let config = context.current_config.read().await;
+    let digest = config
+        .digest_sha256()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    Json(ConfigSummary {
+        version: config.digest_sha256_base64(),
+        digest,
+    })
+}
+
+async fn list_listeners(
+    State(context): State<KernelContext>,
+) -> Result<Json<Vec<ListenerInfo>>, AdminError> {
+    let switchboard = context.tcp_switchboard.read().await;
+    let handle = switchboard.handle()?;
+    let router = handle.get_current_router().await;
+    let counts = handle.connections.count_by_bind();
+    let mut listeners = handle
+        .tcp_listeners
+        .iter()
+        .map(|(bind, listener_task)| {
+            let route = router.routes.get(bind);
+            ListenerInfo {
+                bind: *bind,
+                service: route.map(|route| route.service.to_string()),
+                tls: route.and_then(|route| route.tls.as_deref().map(str::to_owned)),
+                connections: counts.get(bind).copied().unwrap_or_default(),
+                limits: *listener_task.limits.borrow(),
+                limits_reached: listener_task.limit_counters.counts(),
+            }
+        })
+        .collect::<Vec<_>>();
+    listeners.sort_by_key(|listener| listener.bind);
+    Ok(Json(listeners))
+}
+
+async fn list_connections(
+    State(context): State<KernelContext>,
+    Query(filter): Query<ConnectionFilter>,
+) -> Result<Json<Vec<ConnectionInfo>>, AdminError> {
+    Ok(Json(context.list_connections(&filter).await?))
+}
+
+async fn close_connections(
+    State(context): State<KernelContext>,
+    Json(filter): Json<ConnectionFilter>,
+) -> Result<Json<CloseConnectionsResponse>, AdminError> {
+    let closed = context.close_connections(&filter).await?;
+    Ok(Json(CloseConnectionsResponse { closed }))
+}
+
+async fn close_connection(
+    State(context): State<KernelContext>,
+    Path(id): Path<ConnectionId>,
+) -> Result<Json<CloseConnectionsResponse>, AdminError> {
+    if !context.close_connection(id).await? {
+        return Err(AdminError::ConnectionNotFound(id));
+    }
+    Ok(Json(CloseConnectionsResponse { closed: 1 }))
+}
+
+async fn list_sections(State(context): State<KernelContext>) -> Json<Vec<String>> {
+    Json(
+        context
+            .admin_sections
+            .read()
+            .await
+            .keys()
+            .cloned()
+            .collect(),
+    )
+}
+
+async fn get_section(
+    State(context): State<KernelContext>,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    let section = context
+        .admin_sections
+        .read()
+        .await
+        .get(&name)
+        .cloned()
+        .ok_or(AdminError::SectionNotFound(name))?;
+    Ok(Json(section().await))
+}
+
+fn admin_router(context: KernelContext) -> axum::Router {
+    axum::Router::new()
+        .route("/state", get(get_state))
+        .route("/config", get(get_config))
+        .route("/listeners", get(list_listeners))
+        .route("/connections", get(list_connections))
+        .route("/connections/close", post(close_connections))
+        .route("/connections/{id}/close", post(close_connection))
+        .route("/sections", get(list_sections))
+        .route("/sections/{*name}", get(get_section))
+        .with_state(context)
+}
+
+impl KernelContext {
+    /// Serve `section` at `/sections/{name}` of the admin api.
+    pub async fn register_admin_section<F, Fut>(&self, name: impl Into<String>, section: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = serde_json::Value> + Send + 'static,
+    {
+        let section: AdminSection = Arc::new(move || Box::pin(section()));
+        self.admin_sections
+            .write()
+            .await
+            .insert(name.into(), section);
+    }
+
+    pub async fn spawn_admin_listener(&self) -> Option<AdminListenerHandle> {
+        let config = self.kernel_config.admin.as_ref()?;
+        let ct = tokio_util::sync::CancellationToken::new();
+        let router = admin_router(self.clone());
+        let mut join_set = tokio::task::JoinSet::new();
+        'bind_tcp: {
+            let Some(addr) = config.bind else {
+                break 'bind_tcp;
+            };
+            if !addr.ip().is_loopback() {
+                tracing::error!(
+                    "Refusing to bind admin listener on non-loopback address {}, use a loopback address or `admin.uds`",
+                    addr
+                );
+                break 'bind_tcp;
+            }
+            let listener = match tokio::net::TcpListener::bind(addr).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    tracing::error!("Failed to bind admin listener on {}: {}", addr, e);
+                    break 'bind_tcp;
+                }
+            };
+            tracing::info!("Admin http api listening on {}", addr);
+            let span = tracing::info_span!("admin-http-listener", %addr);
+            let server = axum::serve(listener, router.clone())
+                .with_graceful_shutdown(ct.child_token().cancelled_owned());
+            join_set.spawn(server.into_future().instrument(span));
+        }
+        #[cfg(unix)]
+        let uds_path = 'bind_uds: {
+            let Some(path) = &config.uds else {
+                break 'bind_uds None;
+            };
+            if let Some(dir) = path.parent()
+                && let Err(e) = tokio::fs::create_dir_all(dir).await
+            {
+                tracing::error!("Failed to create admin uds socket dir {:?}: {}", dir, e);
+                break 'bind_uds None;
+            }
+            // a socket left by a previous kernel which didn't exit cleanly
+            if tokio::fs::try_exists(path).await.unwrap_or(false) {
+                let _ = tokio::fs::remove_file(path).await.inspect_err(|e| {
+                    tracing::warn!("Failed to remove stale admin uds socket {:?}: {}", path, e)
+                });
+            }
+            let listener = match tokio::net::UnixListener::bind(path) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    tracing::error!("Failed to bind admin uds listener on {:?}: {}", path, e);
+                    break 'bind_uds None;
+                }
+            };
+            tracing::info!("Admin http api listening on {:?}", path);
+            let span = tracing::info_span!("admin-uds-listener", path = %path.display());
+            let server = axum::serve(listener, router)
+                .with_graceful_shutdown(ct.child_token().cancelled_owned());
+            join_set.spawn(server.into_future().instrument(span));
+            Some(path.clone())
+        };
+        #[cfg(not(unix))]
+        let uds_path = None;
+        Some(AdminListenerHandle {
+            ct,
+            join_set,
+            uds_path,
+        })
+    }
+
+    pub async fn shutdown_admin_listener(&self) {
+        if let Some(handle) = self.admin_listener_handle.write().await.take() {
+            handle.shutdown().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_refuse_non_loopback() {
+        let context = KernelContext::new(crate::config::KernelConfig {
+            admin: Some(AdminConfig {
+                bind: Some(([0, 0, 0, 0], 0).into()),
+                #[cfg(unix)]
+                uds: None,
+            }),
+            ..Default::default()
+        });
+        let handle = context.spawn_admin_listener().await.unwrap();
+        assert!(handle.join_set.is_empty());
+        handle.shutdown().await;
+        // no admin config, no listener
+        let context = KernelContext::new(Default::default());
+        assert!(context.spawn_admin_listener().await.is_none());
+    }
+
+    #[cfg(unix)]
+    async fn request(
+        path: &std::path::Path,
+        method: &str,
+        uri: &str,
+        body: &str,
+    ) -> (u16, serde_json::Value) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut stream = tokio::net::UnixStream::connect(path).await.unwrap();
+        let request = format!(
+            "{method} {uri} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_admin_api() {
+        let path = std::env::temp_dir().join(format!(
+            "switchboard-kernel-admin-{}/admin.sock",
+            std::process::id()
+        ));
+        let context = KernelContext::new(crate::config::KernelConfig {
+            admin: Some(AdminConfig {
+                bind: None,
+                uds: Some(path.clone()),
+            }),
+            ..Default::default()
+        });
+        context
+            .register_admin_section("http/classes", || async { serde_json::json!(["std.cors"]) })
+            .await;
+        let handle = context.spawn_admin_listener().await.unwrap();
+        assert!(path.exists());
+
+        let (status, state) = request(&path, "GET", "/state", "").await;
+        assert_eq!(status, 200);
+        assert_eq!(state, serde_json::to_value(context.get_state()).unwrap());
+        let (status, config) = request(&path, "GET", "/config", "").await;
+        assert_eq!(status, 200);
+        let empty = switchboard_model::ServiceConfig::default();
+        assert_eq!(config["version"], empty.digest_sha256_base64());
+        assert_eq!(config["digest"].as_str().unwrap().len(), 64);
+
+        assert_eq!(
+            request(&path, "GET", "/sections", "").await,
+            (200, serde_json::json!(["http/classes"]))
+        );
+        assert_eq!(
+            request(&path, "GET", "/sections/http/classes", "").await,
+            (200, serde_json::json!(["std.cors"]))
+        );
+        assert_eq!(request(&path, "GET", "/sections/missing", "").await.0, 404);
+
+        // closing everything needs an explicit filter
+        assert_eq!(
+            request(&path, "POST", "/connections/close", "{}").await.0,
+            400
+        );
+        // the switchboard isn't running
+        assert_eq!(request(&path, "GET", "/connections", "").await.0, 500);
+
+        handle.shutdown().await;
+        assert!(!path.exists());
+    }
+}
@@ -9,6 +9,10 @@ pub struct KernelConfig {
     pub controller: crate::controller::ControllerConfig,
     pub provider: ProviderConfig,
     pub config: Option<LinkOrValue<PathBuf, SerdeValue>>,
+    /// Where applied configs are kept to restart without the controller.
+    pub persist: Option<crate::persist::PersistConfig>,
+    /// Local admin http api, off when unset.
+    pub admin: Option<crate::admin::AdminConfig>,
 }
 
 use switchboard_model::SerdeValue;
@@ -21,5 +25,10 @@ pub struct ProviderConfig {
 
 #[derive(Clone, Debug, Deserialize, Default)]
 pub struct HttpProviderConfig {
+    /// Rust plugin libraries and `.wasm` plugin components, `dir/*` takes every one in `dir`.
     pub plugins: Vec<String>,
+    /// Fuel, memory and body size limits of wasm plugins.
+    pub wasm: Option<SerdeValue>,
+    /// Where plugins pushed by the controller are written, pushes are refused when unset.
+    pub plugin_dir: Option<PathBuf>,
 }
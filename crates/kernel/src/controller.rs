@@ -1,5 +1,7 @@
+pub mod auth;
 pub mod grpc_service;
 pub mod listener;
+pub mod reverse;
 // to tell controller the existence of this instance.
 pub mod discovery;
 use serde::{Deserialize, Serialize};
@@ -11,6 +13,9 @@ pub struct ControllerConfig {
     pub state_report_interval: u32,
     pub listen: listener::ListenerConfig,
     pub discovery: discovery::DiscoveryConfig,
+    pub auth: auth::ControllerAuthConfig,
+    /// Dial out to a controller, for kernels the controller can't reach.
+    pub reverse: Option<reverse::ReverseConnectConfig>,
 }
 
 impl Default for ControllerConfig {
@@ -19,6 +24,8 @@ impl Default for ControllerConfig {
             state_report_interval: DEFAULT_STATE_REPORT_INTERVAL_SECS,
             listen: listener::ListenerConfig::default(),
             discovery: discovery::DiscoveryConfig::default(),
+            auth: auth::ControllerAuthConfig::default(),
+            reverse: None,
         }
     }
 }
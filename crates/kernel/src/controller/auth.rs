@@ -0,0 +1,114 @@
+use std::{path::PathBuf, sync::Arc};
+
+use base64::Engine;
+use rustls::pki_types::{CertificateDer, pem::PemObject};
+use serde::{Deserialize, Serialize};
+use switchboard_kernel_control::auth::{DEFAULT_MAX_CLOCK_SKEW_SECS, PskKey, PskServerInterceptor};
+
+/// Authentication of controllers connecting through the http listener.
+///
+/// The uds listener is not authenticated, access to it is controlled by the socket file
+/// permissions.
+/// # Example
+/// ```toml
+/// [controller.auth]
+/// client_ca = "/etc/switchboard/controller-ca.pem"
+/// [[controller.auth.psk]]
+/// id = "2025-01"
+/// key = "c2VjcmV0LWtleS1ieXRlcw=="
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ControllerAuthConfig {
+    /// Require controllers to present a client certificate signed by this CA, needs the http
+    /// listener to have `tls` configured.
+    pub client_ca: Option<PathBuf>,
+    /// Accepted pre-shared keys, list both the old and new key while rotating. Needs the http
+    /// listener to have `tls` configured, tokens are not sent in plaintext.
+    pub psk: Vec<PskKeyConfig>,
+    pub max_clock_skew_secs: u64,
+}
+
+impl Default for ControllerAuthConfig {
+    fn default() -> Self {
+        Self {
+            client_ca: None,
+            psk: Vec::new(),
+            max_clock_skew_secs: DEFAULT_MAX_CLOCK_SKEW_SECS,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PskKeyConfig {
+    pub id: String,
+    /// Base64 encoded key bytes.
+    pub key: String,
+}
+
+impl PskKeyConfig {
+    pub fn decode(&self) -> Result<PskKey, ControllerAuthError> {
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(&self.key)
+            .map_err(|source| ControllerAuthError::InvalidPskKey {
+                id: self.id.clone(),
+                source,
+            })?;
+        Ok(PskKey {
+            id: self.id.clone(),
+            key,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ControllerAuthError {
+    #[error("Invalid base64 psk key {id}: {source}")]
+    InvalidPskKey {
+        id: String,
+        #[source]
+        source: base64::DecodeError,
+    },
+    #[error("Failed to read client CA {path:?}: {source}")]
+    ReadClientCa {
+        path: PathBuf,
+        #[source]
+        source: rustls::pki_types::pem::Error,
+    },
+    #[error("Invalid client CA certificate: {0}")]
+    InvalidClientCa(#[from] rustls::Error),
+    #[error("Client CA needs the controller http listener to have tls configured")]
+    ClientCaWithoutTls,
+    #[error("Pre-shared keys need the controller http listener to have tls configured")]
+    PskWithoutTls,
+}
+
+impl ControllerAuthConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.client_ca.is_some() || !self.psk.is_empty()
+    }
+
+    pub fn build_interceptor(&self) -> Result<PskServerInterceptor, ControllerAuthError> {
+        let keys = self
+            .psk
+            .iter()
+            .map(PskKeyConfig::decode)
+            .collect::<Result<Arc<[_]>, ControllerAuthError>>()?;
+        Ok(PskServerInterceptor::new(keys, self.max_clock_skew_secs))
+    }
+
+    pub fn client_ca_roots(&self) -> Result<Option<rustls::RootCertStore>, ControllerAuthError> {
+        let Some(path) = &self.client_ca else {
+            return Ok(None);
+        };
+        let read_error = |source| ControllerAuthError::ReadClientCa {
+            path: path.clone(),
+            source,
+        };
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in CertificateDer::pem_file_iter(path).map_err(read_error)? {
+            roots.add(cert.map_err(read_error)?)?;
+        }
+        Ok(Some(roots))
+    }
+}
@@ -1,14 +1,18 @@
 use std::{future::ready, pin::Pin, time::Duration};
 
+use switchboard_kernel_control::auth::PskServerInterceptor;
 use switchboard_kernel_control::kernel::{
     kernel_service_server::{KernelService, KernelServiceServer},
     *,
 };
 use switchboard_model::ServiceConfig;
+use tonic::service::interceptor::InterceptedService;
 
-use crate::KernelContext;
+use crate::{KernelContext, switchboard::connection};
 
 const CONFIG_FORMAT_BINCODE: &str = "bincode";
+/// Pushed plugin artifacts are larger than the default limit of 4 MiB.
+const MAX_DECODING_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
 
 #[derive(Clone)]
 pub(crate) struct KernelServiceImpl {
@@ -26,6 +30,8 @@ impl KernelServiceImpl {
 pub(crate) struct StatusStream {
     kernel_context: KernelContext,
     interval: tokio::time::Interval,
+    // the stream ends after reporting `Stopped`, so graceful shutdown is not blocked by watchers
+    stopped: bool,
 }
 
 impl StatusStream {
@@ -39,6 +45,7 @@ impl StatusStream {
         Self {
             kernel_context: kernel_context.clone(),
             interval,
+            stopped: false,
         }
     }
 }
@@ -57,6 +64,37 @@ fn response_error_from_stack(
     }
 }
 
+fn connection_filter_or_status(
+    filter: Option<ConnectionFilter>,
+) -> Result<connection::ConnectionFilter, tonic::Status> {
+    let Some(filter) = filter else {
+        return Ok(connection::ConnectionFilter::default());
+    };
+    let bind = filter
+        .bind
+        .map(|bind| bind.parse())
+        .transpose()
+        .map_err(|e| tonic::Status::invalid_argument(format!("Invalid bind filter: {}", e)))?;
+    let peer = filter
+        .peer
+        .map(|peer| peer.parse())
+        .transpose()
+        .map_err(|e| tonic::Status::invalid_argument(format!("Invalid peer filter: {}", e)))?;
+    Ok(connection::ConnectionFilter {
+        service: filter.service,
+        bind,
+        peer,
+        sni: filter.sni,
+    })
+}
+
+fn status_from_kernel_error(error: crate::Error) -> tonic::Status {
+    match error {
+        crate::Error::EmptyConnectionFilter => tonic::Status::invalid_argument(error.to_string()),
+        error => tonic::Status::unavailable(error.to_string()),
+    }
+}
+
 fn decode_config_or_status(
     format: &str,
     config_data: &[u8],
@@ -98,31 +136,32 @@ impl tokio_stream::Stream for StatusStream {
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
         let this = self.get_mut();
-        if this
+        if this.stopped {
+            return std::task::Poll::Ready(None);
+        }
+        let changed = this
             .kernel_context
             .state_receiver
             .has_changed()
             .unwrap_or_else(|err| {
                 tracing::warn!("State channel status check failed: {}", err);
                 false
-            })
-        {
-            let state = this.kernel_context.get_state();
-            let status: KernelState = state.into();
-            return std::task::Poll::Ready(Some(Ok(status)));
-        } else {
-            // poll interval
-            match Pin::new(&mut this.interval).poll_tick(cx) {
-                std::task::Poll::Ready(_) => {
-                    let state = this.kernel_context.get_state();
-                    let status: KernelState = state.into();
-                    return std::task::Poll::Ready(Some(Ok(status)));
-                }
-                std::task::Poll::Pending => {
-                    return std::task::Poll::Pending;
-                }
-            }
+            });
+        // poll interval when nothing changed
+        if !changed && Pin::new(&mut this.interval).poll_tick(cx).is_pending() {
+            return std::task::Poll::Pending;
         }
+        let state = this
+            .kernel_context
+            .state_receiver
+            .borrow_and_update()
+            .clone();
+        this.stopped = matches!(
+            state.kind,
+            switchboard_model::kernel::KernelStateKind::Stopped
+        );
+        let status: KernelState = state.into();
+        std::task::Poll::Ready(Some(Ok(status)))
     }
 }
 
@@ -242,6 +281,39 @@ impl KernelService for KernelServiceImpl {
         })
     }
 
+    fn validate_config<'life0, 'async_trait>(
+        &'life0 self,
+        request: tonic::Request<ValidateConfigRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = std::result::Result<tonic::Response<ValidationReport>, tonic::Status>,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let request = request.into_inner();
+        let config = match decode_config_or_status(&request.format, &request.config) {
+            Ok(config) => config,
+            Err(status) => return Box::pin(ready(Err(status))),
+        };
+        // the version is optional for a dry run
+        if !request.version.is_empty()
+            && let Err(status) =
+                validate_version_or_status(&request.version, &config.digest_sha256_base64())
+        {
+            return Box::pin(ready(Err(status)));
+        }
+        Box::pin(async move {
+            let report = self.kernel_context.validate_config(&config).await;
+            Ok(tonic::Response::new(report.into()))
+        })
+    }
+
     fn commit_config<'life0, 'async_trait>(
         &'life0 self,
         request: tonic::Request<CommitConfigRequest>,
@@ -357,12 +429,322 @@ impl KernelService for KernelServiceImpl {
         let response = tonic::Response::new(proto_state);
         Box::pin(ready(Ok(response)))
     }
+
+    fn get_current_config<'life0, 'async_trait>(
+        &'life0 self,
+        _request: tonic::Request<GetCurrentConfigRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<Output = std::result::Result<tonic::Response<CurrentConfig>, tonic::Status>>
+                + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let config = self.kernel_context.current_config.read().await.clone();
+            let version = config.digest_sha256_base64();
+            let config = bincode::encode_to_vec(&config, bincode::config::standard())
+                .map_err(|e| tonic::Status::internal(format!("Config encode error: {}", e)))?;
+            Ok(tonic::Response::new(CurrentConfig {
+                format: CONFIG_FORMAT_BINCODE.to_string(),
+                version,
+                config,
+            }))
+        })
+    }
+
+    fn shutdown<'life0, 'async_trait>(
+        &'life0 self,
+        request: tonic::Request<ShutdownRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = std::result::Result<tonic::Response<ShutdownResponse>, tonic::Status>,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let request = request.into_inner();
+        tracing::info!(reason = %request.reason, "Shutdown requested through gRPC");
+        // the response is sent before the listener is torn down by the shutdown sequence
+        self.kernel_context.request_shutdown();
+        Box::pin(ready(Ok(tonic::Response::new(ShutdownResponse {
+            result: Some(shutdown_response::Result::Success(Empty {})),
+        }))))
+    }
+
+    fn list_saved_configs<'life0, 'async_trait>(
+        &'life0 self,
+        _request: tonic::Request<ListSavedConfigsRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = std::result::Result<
+                        tonic::Response<ListSavedConfigsResponse>,
+                        tonic::Status,
+                    >,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let result = match self.kernel_context.list_saved_configs().await {
+                Ok(saved) => list_saved_configs_response::Result::Success(SavedConfigs {
+                    configs: saved
+                        .into_iter()
+                        .map(|saved| SavedConfig {
+                            version: saved.version,
+                            saved_at: saved.saved_at.to_rfc2822(),
+                        })
+                        .collect(),
+                }),
+                Err(e) => list_saved_configs_response::Result::Error(
+                    switchboard_model::error::ErrorStack::from_std(e).into(),
+                ),
+            };
+            Ok(tonic::Response::new(ListSavedConfigsResponse {
+                result: Some(result),
+            }))
+        })
+    }
+
+    fn restore_saved_config<'life0, 'async_trait>(
+        &'life0 self,
+        request: tonic::Request<RestoreSavedConfigRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = std::result::Result<
+                        tonic::Response<RestoreSavedConfigResponse>,
+                        tonic::Status,
+                    >,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let request = request.into_inner();
+        Box::pin(async move {
+            let result = match self
+                .kernel_context
+                .restore_saved_config(request.version.as_deref())
+                .await
+            {
+                Ok(version) => restore_saved_config_response::Result::Success(version),
+                Err(e) => restore_saved_config_response::Result::Error(
+                    switchboard_model::error::ErrorStack::from_std(e).into(),
+                ),
+            };
+            Ok(tonic::Response::new(RestoreSavedConfigResponse {
+                result: Some(result),
+            }))
+        })
+    }
+
+    fn list_connections<'life0, 'async_trait>(
+        &'life0 self,
+        request: tonic::Request<ListConnectionsRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<Output = std::result::Result<tonic::Response<ConnectionList>, tonic::Status>>
+                + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let filter = match connection_filter_or_status(request.into_inner().filter) {
+            Ok(filter) => filter,
+            Err(status) => return Box::pin(ready(Err(status))),
+        };
+        Box::pin(async move {
+            let connections = self
+                .kernel_context
+                .list_connections(&filter)
+                .await
+                .map_err(status_from_kernel_error)?
+                .into_iter()
+                .map(|connection| Connection {
+                    id: connection.id,
+                    bind: connection.bind.to_string(),
+                    peer: connection.peer.to_string(),
+                    service: connection.service,
+                    tls: connection.tls,
+                    sni: connection.sni,
+                    started_at: connection.started_at.to_rfc2822(),
+                    bytes_received: connection.bytes_received,
+                    bytes_sent: connection.bytes_sent,
+                })
+                .collect();
+            Ok(tonic::Response::new(ConnectionList { connections }))
+        })
+    }
+
+    fn close_connections<'life0, 'async_trait>(
+        &'life0 self,
+        request: tonic::Request<CloseConnectionsRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = std::result::Result<
+                        tonic::Response<CloseConnectionsResponse>,
+                        tonic::Status,
+                    >,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let target = match request.into_inner().target {
+            Some(close_connections_request::Target::Id(id)) => Ok(id),
+            Some(close_connections_request::Target::Filter(filter)) => {
+                match connection_filter_or_status(Some(filter)) {
+                    Ok(filter) => Err(filter),
+                    Err(status) => return Box::pin(ready(Err(status))),
+                }
+            }
+            None => Err(connection::ConnectionFilter::default()),
+        };
+        Box::pin(async move {
+            let closed = match target {
+                Ok(id) => self
+                    .kernel_context
+                    .close_connection(id)
+                    .await
+                    .map(u64::from),
+                Err(filter) => self
+                    .kernel_context
+                    .close_connections(&filter)
+                    .await
+                    .map(|closed| closed as u64),
+            }
+            .map_err(status_from_kernel_error)?;
+            Ok(tonic::Response::new(CloseConnectionsResponse { closed }))
+        })
+    }
+
+    fn push_plugin<'life0, 'async_trait>(
+        &'life0 self,
+        request: tonic::Request<PushPluginRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = std::result::Result<
+                        tonic::Response<PushPluginResponse>,
+                        tonic::Status,
+                    >,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let request = request.into_inner();
+        let kind = match PluginKind::try_from(request.kind) {
+            Ok(kind) => kind,
+            Err(e) => {
+                return Box::pin(ready(Err(tonic::Status::invalid_argument(format!(
+                    "Invalid plugin kind: {}",
+                    e
+                )))));
+            }
+        };
+        Box::pin(async move {
+            let result = match self
+                .kernel_context
+                .push_plugin(
+                    request.name,
+                    kind.into(),
+                    &request.artifact,
+                    &request.sha256,
+                )
+                .await
+            {
+                Ok(plugin) => push_plugin_response::Result::Success(plugin.into()),
+                Err(e) => push_plugin_response::Result::Error(
+                    switchboard_model::error::ErrorStack::from_std(e).into(),
+                ),
+            };
+            Ok(tonic::Response::new(PushPluginResponse {
+                result: Some(result),
+            }))
+        })
+    }
+
+    fn list_plugins<'life0, 'async_trait>(
+        &'life0 self,
+        _request: tonic::Request<ListPluginsRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<Output = std::result::Result<tonic::Response<PluginList>, tonic::Status>>
+                + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let plugins = self.kernel_context.list_plugins().await;
+            Ok(tonic::Response::new(plugins.into()))
+        })
+    }
+
+    fn list_classes<'life0, 'async_trait>(
+        &'life0 self,
+        _request: tonic::Request<ListClassesRequest>,
+    ) -> Pin<
+        Box<
+            dyn Future<Output = std::result::Result<tonic::Response<ClassList>, tonic::Status>>
+                + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let classes = self.kernel_context.list_classes().await;
+            Ok(tonic::Response::new(classes.into()))
+        })
+    }
 }
 
 impl KernelContext {
     pub(crate) fn build_grpc_server(&self) -> KernelServiceServer<KernelServiceImpl> {
         let kernel_service = KernelServiceImpl::new(self);
         KernelServiceServer::new(kernel_service)
+            .max_decoding_message_size(MAX_DECODING_MESSAGE_SIZE)
+    }
+    pub(crate) fn build_authenticated_grpc_server(
+        &self,
+        interceptor: PskServerInterceptor,
+    ) -> InterceptedService<KernelServiceServer<KernelServiceImpl>, PskServerInterceptor> {
+        InterceptedService::new(self.build_grpc_server(), interceptor)
     }
     // pub(crate) fn build_health_grpc_service(&self) -> tonic_health::server::HealthService {
     //     let health_reporter = tonic_health::server::HealthReporter::new();
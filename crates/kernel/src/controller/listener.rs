@@ -6,15 +6,21 @@ use switchboard_file_resolver::FileResolver;
 use tracing::Instrument;
 
 pub mod http;
+#[cfg(unix)]
+pub mod uds;
 
 #[derive(Clone, Debug, Deserialize, Serialize, Default)]
 pub struct ListenerConfig {
     pub http: Option<http::HttpListenerConfig>,
+    #[cfg(unix)]
+    pub uds: Option<uds::UdsListenerConfig>,
 }
 
 pub struct ListenerHandle {
     pub ct: tokio_util::sync::CancellationToken,
     join_set: tokio::task::JoinSet<std::result::Result<(), tonic::transport::Error>>,
+    /// Socket file created by the uds listener, removed on shutdown.
+    uds_path: Option<std::path::PathBuf>,
 }
 
 pub enum ListenerHandleQuitReason {
@@ -35,6 +41,11 @@ impl ListenerHandle {
                 }
             }
         }
+        if let Some(path) = self.uds_path {
+            let _ = tokio::fs::remove_file(&path).await.inspect_err(|e| {
+                tracing::error!("Failed to remove controller uds socket {:?}: {}", path, e)
+            });
+        }
     }
 }
 
@@ -47,6 +58,38 @@ impl KernelContext {
         'bind_http: {
             if let Some(http_config) = &listener_config.http {
                 let addr: std::net::SocketAddr = (http_config.host, http_config.port).into();
+                // fail closed, a listener with broken auth config is not bound at all
+                let auth = &self.kernel_config.controller.auth;
+                let (interceptor, client_roots) =
+                    match (auth.build_interceptor(), auth.client_ca_roots()) {
+                        (Ok(_), Ok(Some(_))) if http_config.tls.is_none() => {
+                            tracing::error!(
+                                "Failed to setup controller http listener auth: {}",
+                                crate::controller::auth::ControllerAuthError::ClientCaWithoutTls
+                            );
+                            break 'bind_http;
+                        }
+                        // the token would be readable by anyone on the path to the kernel
+                        (Ok(_), Ok(_)) if http_config.tls.is_none() && !auth.psk.is_empty() => {
+                            tracing::error!(
+                                "Failed to setup controller http listener auth: {}",
+                                crate::controller::auth::ControllerAuthError::PskWithoutTls
+                            );
+                            break 'bind_http;
+                        }
+                        (Ok(interceptor), Ok(client_roots)) => (interceptor, client_roots),
+                        (Err(e), _) | (_, Err(e)) => {
+                            tracing::error!("Failed to setup controller http listener auth: {}", e);
+                            break 'bind_http;
+                        }
+                    };
+                if !auth.is_enabled() {
+                    tracing::warn!(
+                        "Controller http listener on {} accepts unauthenticated requests, configure `controller.auth`",
+                        addr
+                    );
+                }
+                let grpc_server = self.build_authenticated_grpc_server(interceptor);
                 let mut tls_acceptor = None;
                 if let Some(tls) = &http_config.tls {
                     let tls_resolver = tls
@@ -61,7 +104,7 @@ impl KernelContext {
                                 resolver: tls_resolver,
                                 options: tls_option,
                             };
-                            match crate::tls::build_tls_config(tls) {
+                            match crate::tls::build_tls_config_with_client_auth(tls, client_roots) {
                                 Ok(config) => {
                                     tls_acceptor = Some(tokio_rustls::TlsAcceptor::from(config));
                                 }
@@ -140,7 +183,61 @@ impl KernelContext {
                 }
             }
         }
-        ListenerHandle { ct, join_set }
+        #[cfg(unix)]
+        let uds_path = 'bind_uds: {
+            let Some(uds_config) = &listener_config.uds else {
+                break 'bind_uds None;
+            };
+            let path = &uds_config.path;
+            if let Some(dir) = path.parent()
+                && let Err(e) = tokio::fs::create_dir_all(dir).await
+            {
+                tracing::error!(
+                    "Failed to create controller uds socket dir {:?}: {}",
+                    dir,
+                    e
+                );
+                break 'bind_uds None;
+            }
+            // a socket left by a previous kernel which didn't exit cleanly
+            if tokio::fs::try_exists(path).await.unwrap_or(false) {
+                let _ = tokio::fs::remove_file(path).await.inspect_err(|e| {
+                    tracing::warn!(
+                        "Failed to remove stale controller uds socket {:?}: {}",
+                        path,
+                        e
+                    )
+                });
+            }
+            let listener = match tokio::net::UnixListener::bind(path) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to bind controller uds listener on {:?}: {}",
+                        path,
+                        e
+                    );
+                    break 'bind_uds None;
+                }
+            };
+            let incoming = tokio_stream::wrappers::UnixListenerStream::new(listener);
+            tracing::info!("Controller uds gRPC listening on {:?}", path);
+            let span = tracing::info_span!("controller-uds-listener", path = %path.display());
+            join_set.spawn(
+                tonic::transport::Server::builder()
+                    .add_service(grpc_server.clone())
+                    .serve_with_incoming_shutdown(incoming, ct.child_token().cancelled_owned())
+                    .instrument(span),
+            );
+            Some(path.clone())
+        };
+        #[cfg(not(unix))]
+        let uds_path = None;
+        ListenerHandle {
+            ct,
+            join_set,
+            uds_path,
+        }
     }
     pub async fn shutdown_controller_listener(&self) {
         if let Some(handle) = self.controller_listener_handle.write().await.take() {
@@ -152,3 +249,81 @@ impl KernelContext {
             .inspect_err(|e| tracing::error!("fail to unpublish discovery {e}"));
     }
 }
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use switchboard_kernel_control::kernel::{
+        GetCurrentStateRequest, ShutdownRequest, WatchStatusRequest,
+        kernel_service_client::KernelServiceClient, shutdown_response,
+    };
+    use switchboard_model::kernel::{KernelState, KernelStateKind};
+
+    use super::*;
+
+    /// What `sb status` and `sb shutdown` do over the local socket.
+    #[tokio::test]
+    async fn test_uds_status_and_shutdown() {
+        let path = std::env::temp_dir().join(format!(
+            "switchboard-kernel-controller-{}/kernel.sock",
+            std::process::id()
+        ));
+        let mut config = crate::config::KernelConfig::default();
+        config.controller.listen.uds = Some(uds::UdsListenerConfig { path: path.clone() });
+        let context = KernelContext::new(config);
+        let handle = context.spawn_controller_listener().await;
+        assert!(path.exists());
+
+        let channel = switchboard_kernel_control::uds::connect_uds(&path)
+            .await
+            .unwrap();
+        let mut client = KernelServiceClient::new(channel);
+        let state = client
+            .get_current_state(GetCurrentStateRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            KernelState::try_from(state).unwrap().kind,
+            context.get_state().kind
+        );
+
+        // the status stream ends once the kernel reports it stopped
+        let mut stream = client
+            .watch_status(WatchStatusRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        stream.next().await.unwrap().unwrap();
+        context.set_state(KernelState::new(KernelStateKind::Stopped));
+        let rest = tokio::time::timeout(Duration::from_secs(10), stream.collect::<Vec<_>>())
+            .await
+            .expect("status stream didn't end");
+        let last = rest.last().unwrap().clone().unwrap();
+        assert!(matches!(
+            KernelState::try_from(last).unwrap().kind,
+            KernelStateKind::Stopped
+        ));
+
+        let response = client
+            .shutdown(ShutdownRequest {
+                reason: "test".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(matches!(
+            response.result,
+            Some(shutdown_response::Result::Success(_))
+        ));
+        tokio::time::timeout(Duration::from_secs(10), context.shutdown_requested())
+            .await
+            .expect("shutdown wasn't requested");
+
+        drop(client);
+        handle.shutdown().await;
+        assert!(!path.exists());
+    }
+}
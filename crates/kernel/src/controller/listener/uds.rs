@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use switchboard_model::kernel::UDS_DEFAULT_PATH;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UdsListenerConfig {
+    pub path: PathBuf,
+}
+
+impl Default for UdsListenerConfig {
+    fn default() -> Self {
+        UdsListenerConfig {
+            path: PathBuf::from(UDS_DEFAULT_PATH),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Reverse control channel, the kernel dials the controller and serves `KernelService` through
+//! a `ControllerService.Tunnel` stream.
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use switchboard_kernel_control::{
+    auth::PskClientInterceptor,
+    kernel::controller_service_client::ControllerServiceClient,
+    tls::{ClientConfigError, ConnectTlsError, build_client_config},
+    tunnel::{KERNEL_ID_METADATA, tunnel},
+};
+use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    KernelContext,
+    controller::auth::{ControllerAuthError, PskKeyConfig},
+};
+
+/// Dial a controller and keep a control channel open, reconnecting when it drops.
+///
+/// The controller registers the kernel under its `info.id`, which should be unique.
+/// # Example
+/// ```toml
+/// [controller.reverse]
+/// endpoint = "https://controller.example.com:9100"
+/// [controller.reverse.tls]
+/// ca = "/etc/switchboard/controller-ca.pem"
+/// cert = "/etc/switchboard/kernel.pem"
+/// key = "/etc/switchboard/kernel.key"
+/// [controller.reverse.psk]
+/// id = "edge-1"
+/// key = "c2VjcmV0LWtleS1ieXRlcw=="
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReverseConnectConfig {
+    pub endpoint: String,
+    pub tls: Option<ReverseTlsConfig>,
+    /// Key to sign the tunnel request with. The controller checks it against `kernel.reverse.psk`,
+    /// where its id must be this kernel's `info.id`, or against the shared `kernel.auth.psk`.
+    pub psk: Option<PskKeyConfig>,
+    /// Allow an endpoint without `tls`. Anyone able to intercept the connection can then push
+    /// configs and plugins to this kernel.
+    #[serde(default)]
+    pub insecure: bool,
+    #[serde(default = "default_reconnect_interval_secs")]
+    pub reconnect_interval_secs: u64,
+}
+
+fn default_reconnect_interval_secs() -> u64 {
+    5
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReverseTlsConfig {
+    /// CA which signed the controller certificate.
+    pub ca: PathBuf,
+    /// Client certificate chain and key, for controllers requiring mutual TLS.
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    /// Name to verify the controller certificate against, defaults to the endpoint host.
+    pub server_name: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReverseConnectError {
+    #[error("Refusing a plaintext reverse endpoint, configure `tls` or set `insecure = true`")]
+    Plaintext,
+    #[error("Auth config error: {0}")]
+    Auth(#[from] ControllerAuthError),
+    #[error("Tls config error: {0}")]
+    ClientConfig(#[from] ClientConfigError),
+    #[error("Tls connect error: {0}")]
+    Tls(#[from] ConnectTlsError),
+    #[error("gRPC connection error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    #[error("gRPC request error: {0}")]
+    Status(#[from] tonic::Status),
+    #[error("Invalid kernel id for metadata: {0}")]
+    InvalidKernelId(#[from] tonic::metadata::errors::InvalidMetadataValue),
+}
+
+impl ReverseTlsConfig {
+    fn build_client_config(&self) -> Result<Arc<rustls::ClientConfig>, ClientConfigError> {
+        build_client_config(&self.ca, self.cert.as_deref(), self.key.as_deref())
+    }
+}
+
+pub struct ReverseConnectionHandle {
+    ct: CancellationToken,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ReverseConnectionHandle {
+    pub async fn shutdown(self) {
+        self.ct.cancel();
+        let _ = self
+            .task
+            .await
+            .inspect_err(|e| tracing::error!("Reverse control channel task join error: {}", e));
+    }
+}
+
+impl KernelContext {
+    pub fn spawn_reverse_connection(&self) -> Option<ReverseConnectionHandle> {
+        let config = self.kernel_config.controller.reverse.clone()?;
+        if config.tls.is_none() {
+            if !config.insecure {
+                tracing::error!(
+                    endpoint = config.endpoint,
+                    "Failed to start reverse control channel: {}",
+                    ReverseConnectError::Plaintext
+                );
+                return None;
+            }
+            tracing::error!(
+                endpoint = config.endpoint,
+                "Reverse control channel is plaintext and unauthenticated, anyone on the path to the controller can push configs and plugins"
+            );
+        }
+        let ct = CancellationToken::new();
+        let context = self.clone();
+        let task_ct = ct.clone();
+        let task =
+            tokio::spawn(async move { context.run_reverse_connection(config, task_ct).await });
+        Some(ReverseConnectionHandle { ct, task })
+    }
+
+    pub async fn shutdown_reverse_connection(&self) {
+        if let Some(handle) = self.reverse_connection_handle.write().await.take() {
+            handle.shutdown().await;
+        }
+    }
+
+    async fn run_reverse_connection(&self, config: ReverseConnectConfig, ct: CancellationToken) {
+        let reconnect_interval = Duration::from_secs(config.reconnect_interval_secs);
+        loop {
+            match self.connect_reverse_once(&config, &ct).await {
+                Ok(()) => {
+                    tracing::info!(endpoint = config.endpoint, "Reverse control channel closed")
+                }
+                Err(e) => tracing::warn!(
+                    endpoint = config.endpoint,
+                    "Reverse control channel failed: {}",
+                    e
+                ),
+            }
+            tokio::select! {
+                _ = ct.cancelled() => break,
+                _ = tokio::time::sleep(reconnect_interval) => {}
+            }
+        }
+    }
+
+    /// Open one tunnel and serve `KernelService` through it until either side closes it.
+    async fn connect_reverse_once(
+        &self,
+        config: &ReverseConnectConfig,
+        ct: &CancellationToken,
+    ) -> Result<(), ReverseConnectError> {
+        let channel = match &config.tls {
+            Some(tls) => {
+                switchboard_kernel_control::tls::connect_tls(
+                    &config.endpoint,
+                    tls.build_client_config()?,
+                    tls.server_name.as_deref(),
+                )
+                .await?
+            }
+            None => {
+                tonic::transport::Endpoint::from_shared(config.endpoint.clone())?
+                    .connect()
+                    .await?
+            }
+        };
+        let interceptor = PskClientInterceptor {
+            key: config.psk.as_ref().map(PskKeyConfig::decode).transpose()?,
+        };
+        let mut client = ControllerServiceClient::with_interceptor(channel, interceptor);
+        let (io, outbound, inbound) = tunnel();
+        let mut request = tonic::Request::new(outbound);
+        request
+            .metadata_mut()
+            .insert(KERNEL_ID_METADATA, self.kernel_config.info.id.parse()?);
+        let frames = client.tunnel(request).await?.into_inner();
+        let closed = inbound.forward(frames);
+        tracing::info!(
+            endpoint = config.endpoint,
+            "Reverse control channel connected"
+        );
+        // the incoming stream must not end, tonic shuts down once it does
+        let incoming =
+            tokio_stream::once(Ok::<_, std::io::Error>(io)).chain(tokio_stream::pending());
+        let serve = tonic::transport::Server::builder()
+            .add_service(self.build_grpc_server())
+            .serve_with_incoming_shutdown(incoming, ct.child_token().cancelled_owned());
+        tokio::select! {
+            result = serve => result?,
+            _ = closed => {}
+        }
+        Ok(())
+    }
+}
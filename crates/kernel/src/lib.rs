@@ -1,11 +1,24 @@
-use std::{collections::HashSet, sync::Arc, time::Duration};
+use std::{
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
+use persist::{ConfigSource, StartupConfig};
 use registry::Registry;
 use switchboard_file_resolver::FileResolver;
-use switchboard_model::kernel::{KernelState, KernelStateKind};
+use switchboard_model::{
+    error::ErrorStack,
+    kernel::{KernelState, KernelStateKind},
+    validation::{ValidationIssue, ValidationReport},
+};
 use switchboard_service::tcp::TcpListener;
+pub mod admin;
 pub mod config;
 pub mod controller;
+pub mod persist;
+pub mod plugin;
 pub mod registry;
 pub mod switchboard;
 pub mod tls;
@@ -43,6 +56,53 @@ pub enum Error {
 
     #[error("Publish discovery error: {0}")]
     PublishDiscoveryError(#[from] crate::controller::discovery::PublishError),
+
+    #[error("Invalid TLS config {name}: {source}")]
+    InvalidTls {
+        name: String,
+        #[source]
+        source: crate::tls::TlsBuildError,
+    },
+    #[error("Invalid TCP service {name}: {source}")]
+    InvalidTcpService {
+        name: String,
+        #[source]
+        source: crate::registry::RegistryError,
+    },
+    #[error("TCP route on {0} has no listener")]
+    UnboundTcpRoute(std::net::SocketAddr),
+    #[error("TCP listener on {0} needs a fixed port and a unicast address")]
+    InvalidTcpListener(SocketAddr),
+    #[error("TCP listener on {bind} takes the same address as the one on {other}")]
+    ConflictingTcpListener { bind: SocketAddr, other: SocketAddr },
+    #[error("TCP route on {bind} references unknown {kind} {name}")]
+    DanglingTcpRoute {
+        bind: std::net::SocketAddr,
+        kind: &'static str,
+        name: String,
+    },
+    #[error("Persist error: {0}")]
+    PersistError(#[from] crate::persist::PersistError),
+    #[error("config persistence is not configured")]
+    PersistNotConfigured,
+    #[error("saved config not found: {0}")]
+    SavedConfigNotFound(String),
+    #[error("no saved config older than the current one")]
+    NoPreviousSavedConfig,
+    #[error("refusing to close connections with an empty filter")]
+    EmptyConnectionFilter,
+    #[error("invalid plugin name {0:?}, expected [A-Za-z0-9._-]+")]
+    InvalidPluginName(String),
+    #[error("plugin checksum mismatch, expected {expected}, got {actual}")]
+    PluginChecksumMismatch { expected: String, actual: String },
+    #[error("plugin dir is not configured")]
+    PluginDirNotConfigured,
+    #[error("no plugin host is set up")]
+    PluginHostNotSet,
+    #[error("Write plugin error: {0}")]
+    WritePlugin(#[source] std::io::Error),
+    #[error("Load plugin error: {0}")]
+    LoadPlugin(#[source] crate::plugin::PluginHostError),
     // #[error("Config service error: {0}")]
     // ConfigError(C::Error),
 }
@@ -59,9 +119,16 @@ pub struct KernelContext {
     // pub(crate) controller_handle: Arc<RwLock<Option<controller::listener::ListenerHandle>>>,
     pub(crate) controller_listener_handle:
         Arc<RwLock<Option<controller::listener::ListenerHandle>>>,
+    pub(crate) reverse_connection_handle:
+        Arc<RwLock<Option<controller::reverse::ReverseConnectionHandle>>>,
     pub(crate) pending_config_transaction: Arc<RwLock<Option<PendingConfigTransaction>>>,
     /// The handle for discovery publication, which can be used to unpublish on shutdown.
     pub(crate) discovery_handle: Arc<RwLock<Option<controller::discovery::PublishHandle>>>,
+    /// Cancelled when a graceful shutdown is requested, e.g. by the `Shutdown` rpc.
+    pub(crate) shutdown_signal: tokio_util::sync::CancellationToken,
+    pub(crate) admin_listener_handle: Arc<RwLock<Option<admin::AdminListenerHandle>>>,
+    pub(crate) admin_sections: Arc<RwLock<admin::AdminSections>>,
+    pub(crate) plugin_host: Arc<RwLock<Option<plugin::SharedPluginHost>>>,
 }
 
 impl KernelContext {
@@ -73,11 +140,16 @@ impl KernelContext {
             current_config: Arc::new(RwLock::new(model::ServiceConfig::default())),
             // controller_handle: Arc::new(tokio::sync::RwLock::new(None)),
             controller_listener_handle: Arc::new(tokio::sync::RwLock::new(None)),
+            reverse_connection_handle: Arc::new(tokio::sync::RwLock::new(None)),
             pending_config_transaction: Arc::new(tokio::sync::RwLock::new(None)),
             tcp_switchboard: Arc::new(RwLock::new(TcpSwitchboard::new_halted())),
             state,
             state_receiver,
             discovery_handle: Arc::new(RwLock::new(None)),
+            shutdown_signal: tokio_util::sync::CancellationToken::new(),
+            admin_listener_handle: Arc::new(RwLock::new(None)),
+            admin_sections: Arc::new(RwLock::new(admin::AdminSections::new())),
+            plugin_host: Arc::new(RwLock::new(None)),
         }
     }
     pub fn get_state(&self) -> KernelState {
@@ -98,22 +170,55 @@ impl KernelContext {
             Ok(None)
         }
     }
+    pub async fn fetch_config_from_file(
+        &self,
+        path: std::path::PathBuf,
+    ) -> Result<model::ServiceConfig, Error> {
+        tracing::info!(
+            "Loading service config from file: {}",
+            path.to_string_lossy()
+        );
+        let config = crate::config::fetch_config(
+            switchboard_link_or_value::LinkOrValue::Link(path),
+            &FileResolver,
+        )
+        .await?;
+        Ok(config)
+    }
+    /// Apply the local config file again, it is saved as a local config and not as a push.
+    pub async fn reload_config_locally(&self) -> Result<(), Error> {
+        if let Some(config) = self.fetch_config_locally().await? {
+            self.apply_config(config, ConfigSource::Local).await?;
+        }
+        Ok(())
+    }
     pub async fn startup(&self) -> Result<(), Error> {
-        let service_config = self.fetch_config_locally().await?;
+        let startup_configs = self.startup_configs().await?;
         // start tcp switchboard
         {
             self.tcp_switchboard.write().await.ensure_running();
         }
-        // load startup config
+        // load startup config, falling back to the next one when it fails to load
         {
-            if let Some(sb_config) = service_config {
-                self.load_config(sb_config).await?;
+            let mut result = Ok(());
+            for startup_config in startup_configs {
+                result = self.load_startup_config(startup_config).await;
+                match &result {
+                    Ok(()) => break,
+                    Err(e) => tracing::error!("Failed to load startup config: {}", e),
+                }
             }
+            result?;
         }
         // listen controller requests
         {
             let listener_handle = self.spawn_controller_listener().await;
             *self.controller_listener_handle.write().await = Some(listener_handle);
+            *self.reverse_connection_handle.write().await = self.spawn_reverse_connection();
+        }
+        // listen admin requests
+        {
+            *self.admin_listener_handle.write().await = self.spawn_admin_listener().await;
         }
         // publish discovery
         {
@@ -155,12 +260,22 @@ impl KernelContext {
                 tcp_switchboard.remove_listener_task(bind_addr).await;
                 tracing::info!(%bind_addr, "Removed TCP listener");
             }
+            // update limits of kept listeners
+            for bind_addr in existed.intersection(&new_listeners) {
+                tcp_switchboard
+                    .update_listener_limits(bind_addr, sb_config.tcp_listeners[bind_addr].limits);
+            }
             // add new listeners
             for bind_addr in to_add {
                 match TcpListener::bind(*bind_addr).await {
                     Ok(tcp_listener) => {
                         tracing::info!(%bind_addr, "Adding TCP listener");
-                        tcp_switchboard.create_listener_task(tcp_listener).await?;
+                        tcp_switchboard
+                            .create_listener_task(
+                                tcp_listener,
+                                sb_config.tcp_listeners[bind_addr].limits,
+                            )
+                            .await?;
                         tracing::info!(%bind_addr, "Added TCP listener");
                     }
                     Err(e) => {
@@ -217,13 +332,157 @@ impl KernelContext {
         }
         Ok(())
     }
+    /// Dry-run a service config: build every TLS config and TCP service, check
+    /// routes and check listener addresses for conflicts, without binding them or
+    /// touching live state.
+    ///
+    /// Every problem is collected into the returned report.
+    pub async fn validate_config(&self, sb_config: &model::ServiceConfig) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for (tls_name, tls) in &sb_config.tls {
+            if let Err(source) = crate::tls::build_tls_config(tls.clone()) {
+                let error = Error::InvalidTls {
+                    name: tls_name.clone(),
+                    source,
+                };
+                report.errors.push(ValidationIssue::new(
+                    ["tls", tls_name],
+                    ErrorStack::from_std(error),
+                ));
+            }
+        }
+        for (service_name, service_config) in &sb_config.tcp_services {
+            match self.registry.validate_tcp_service(service_config).await {
+                Ok(service_report) => {
+                    let issue = |issue: switchboard_service::ValidationIssue| {
+                        let path = ["tcp_services", service_name, "config"]
+                            .into_iter()
+                            .map(str::to_owned)
+                            .chain(issue.path);
+                        ValidationIssue::new(path, ErrorStack::from_dyn(&*issue.error))
+                    };
+                    report
+                        .errors
+                        .extend(service_report.errors.into_iter().map(issue));
+                    report
+                        .warnings
+                        .extend(service_report.warnings.into_iter().map(issue));
+                }
+                Err(source) => {
+                    let error = Error::InvalidTcpService {
+                        name: service_name.clone(),
+                        source,
+                    };
+                    report.errors.push(ValidationIssue::new(
+                        ["tcp_services", service_name, "provider"],
+                        ErrorStack::from_std(error),
+                    ));
+                }
+            }
+        }
+        for (bind, route) in &sb_config.tcp_routes {
+            let bind_key = bind.to_string();
+            if !sb_config.tcp_services.contains_key(&route.service) {
+                let error = Error::DanglingTcpRoute {
+                    bind: *bind,
+                    kind: "service",
+                    name: route.service.clone(),
+                };
+                report.errors.push(ValidationIssue::new(
+                    ["tcp_routes", &bind_key, "service"],
+                    ErrorStack::from_std(error),
+                ));
+            }
+            if let Some(tls) = &route.tls
+                && !sb_config.tls.contains_key(tls)
+            {
+                let error = Error::DanglingTcpRoute {
+                    bind: *bind,
+                    kind: "tls",
+                    name: tls.clone(),
+                };
+                report.errors.push(ValidationIssue::new(
+                    ["tcp_routes", &bind_key, "tls"],
+                    ErrorStack::from_std(error),
+                ));
+            }
+            if !sb_config.tcp_listeners.contains_key(bind) {
+                report.warnings.push(ValidationIssue::new(
+                    ["tcp_routes", &bind_key],
+                    ErrorStack::from_std(Error::UnboundTcpRoute(*bind)),
+                ));
+            }
+        }
+        // listeners missing from the config are closed before new ones are bound, so only the
+        // listeners of the config itself can take each other's address
+        let binds = sb_config.tcp_listeners.keys().copied().collect::<Vec<_>>();
+        for (index, bind) in binds.iter().enumerate() {
+            let error = if !is_bindable(*bind) {
+                Error::InvalidTcpListener(*bind)
+            } else if let Some(other) = binds
+                .iter()
+                .take(index)
+                .find(|other| listeners_overlap(**other, *bind))
+            {
+                Error::ConflictingTcpListener {
+                    bind: *bind,
+                    other: *other,
+                }
+            } else {
+                continue;
+            };
+            report.errors.push(ValidationIssue::new(
+                ["tcp_listeners", &bind.to_string()],
+                ErrorStack::from_std(error),
+            ));
+        }
+        report
+    }
     pub fn set_state(&self, state: KernelState) {
         if let Err(err) = self.state.send(state) {
             tracing::warn!("Failed to publish kernel state update: {}", err);
         }
     }
 
+    async fn load_startup_config(&self, startup_config: StartupConfig) -> Result<(), Error> {
+        match startup_config {
+            StartupConfig::Saved(saved) => {
+                tracing::info!(
+                    version = %saved.version,
+                    saved_at = %saved.saved_at,
+                    "Restoring saved service config"
+                );
+                self.load_config(saved.config).await?;
+                self.set_state(KernelState::new(KernelStateKind::Running {
+                    config_version: saved.version,
+                }));
+            }
+            StartupConfig::Local(sb_config) => {
+                let persisted_config = self
+                    .kernel_config
+                    .persist
+                    .is_some()
+                    .then(|| sb_config.clone());
+                self.load_config(sb_config).await?;
+                if let Some(config) = persisted_config
+                    && let Err(e) = self.save_config(&config, ConfigSource::Local).await
+                {
+                    tracing::error!("Failed to persist startup config: {}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub async fn update_config(&self, sb_config: model::ServiceConfig) -> Result<(), Error> {
+        self.apply_config(sb_config, ConfigSource::Pushed).await
+    }
+
+    async fn apply_config(
+        &self,
+        sb_config: model::ServiceConfig,
+        source: ConfigSource,
+    ) -> Result<(), Error> {
         let original_config_version = self.current_config.read().await.digest_sha256_base64();
         let new_config_version = sb_config.digest_sha256_base64();
         let new_state = KernelState::new(KernelStateKind::Updating {
@@ -231,11 +490,22 @@ impl KernelContext {
             new_config_version: new_config_version.clone(),
         });
         self.set_state(new_state);
+        let persisted_config = self
+            .kernel_config
+            .persist
+            .is_some()
+            .then(|| sb_config.clone());
         self.load_config(sb_config).await?;
         let running_state = KernelState::new(KernelStateKind::Running {
             config_version: new_config_version,
         });
         self.set_state(running_state);
+        if let Some(config) = persisted_config {
+            // the config is live already, failing to save it only costs the offline restart
+            if let Err(e) = self.save_config(&config, source).await {
+                tracing::error!("Failed to persist applied config: {}", e);
+            }
+        }
         Ok(())
     }
 
@@ -350,6 +620,14 @@ impl KernelContext {
         pending_lock.take();
         Ok(())
     }
+    /// Ask the kernel owner to run [`KernelContext::shutdown`].
+    pub fn request_shutdown(&self) {
+        self.shutdown_signal.cancel();
+    }
+    /// Resolves once [`KernelContext::request_shutdown`] has been called.
+    pub async fn shutdown_requested(&self) {
+        self.shutdown_signal.cancelled().await
+    }
     pub async fn shutdown(&self) {
         self.set_state(KernelState::new(KernelStateKind::ShuttingDown));
 
@@ -360,9 +638,139 @@ impl KernelContext {
         // shutdown controller listener
         tracing::info!("Shutting down controller listener...");
         self.shutdown_controller_listener().await;
+        self.shutdown_reverse_connection().await;
+        self.shutdown_admin_listener().await;
         // shutdown controller
         // tracing::info!("Shutting down controller...");
         // self.shutdown_controller().await;
         tracing::info!("Kernel shutdown complete.");
     }
 }
+
+/// Whether a listener can be bound to `bind`, port 0 would pick a random port.
+fn is_bindable(bind: SocketAddr) -> bool {
+    let broadcast = match bind.ip() {
+        IpAddr::V4(ip) => ip.is_broadcast(),
+        IpAddr::V6(_) => false,
+    };
+    bind.port() != 0 && !bind.ip().is_multicast() && !broadcast
+}
+
+/// Whether two listeners take the same address. A wildcard address takes its port on every
+/// address of its family, and `[::]` on the ipv4 ones as well on dual-stack hosts.
+fn listeners_overlap(a: SocketAddr, b: SocketAddr) -> bool {
+    let covers = |wildcard: SocketAddr, other: SocketAddr| match wildcard.ip() {
+        IpAddr::V4(ip) => ip.is_unspecified() && other.is_ipv4(),
+        IpAddr::V6(ip) => ip.is_unspecified(),
+    };
+    a.port() == b.port() && (a.ip() == b.ip() || covers(a, b) || covers(b, a))
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use switchboard_model::{Listener, TcpServiceConfig, tcp_route::TcpRoute};
+
+    use super::*;
+
+    fn route(bind: SocketAddr, service: &str, tls: Option<&str>) -> (SocketAddr, TcpRoute) {
+        (
+            bind,
+            TcpRoute {
+                bind,
+                service: service.to_string(),
+                tls: tls.map(str::to_string),
+            },
+        )
+    }
+
+    fn paths(issues: &[ValidationIssue]) -> Vec<&str> {
+        let mut paths = issues
+            .iter()
+            .map(|issue| issue.path.as_str())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn test_validate_config() {
+        let context = KernelContext::new(KernelConfig::default());
+        assert_eq!(
+            context
+                .validate_config(&model::ServiceConfig::default())
+                .await,
+            ValidationReport::default()
+        );
+
+        // a wildcard listener takes its port on every address, and port 0 isn't a fixed port
+        let listener = |bind: &str| {
+            let bind = bind.parse::<SocketAddr>().unwrap();
+            (
+                bind,
+                Listener {
+                    bind,
+                    description: None,
+                    limits: Default::default(),
+                },
+            )
+        };
+        let local = SocketAddr::from(([127, 0, 0, 1], 8080));
+        let unbound = SocketAddr::from(([127, 0, 0, 1], 1));
+        let config = model::ServiceConfig {
+            tcp_services: [(
+                "web".to_string(),
+                TcpServiceConfig {
+                    provider: "no-such-provider".to_string(),
+                    name: "web".to_string(),
+                    config: None,
+                    description: None,
+                },
+            )]
+            .into(),
+            tcp_listeners: [
+                listener("0.0.0.0:8080"),
+                listener("127.0.0.1:8080"),
+                listener("127.0.0.1:0"),
+                listener("127.0.0.1:8081"),
+            ]
+            .into(),
+            tcp_routes: [
+                route(local, "missing", Some("missing")),
+                route(unbound, "web", None),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let report = context.validate_config(&config).await;
+        assert!(!report.is_valid());
+        assert_eq!(
+            paths(&report.errors),
+            [
+                "/tcp_listeners/127.0.0.1:0",
+                "/tcp_listeners/127.0.0.1:8080",
+                "/tcp_routes/127.0.0.1:8080/service",
+                "/tcp_routes/127.0.0.1:8080/tls",
+                "/tcp_services/web/provider",
+            ]
+        );
+        assert_eq!(paths(&report.warnings), ["/tcp_routes/127.0.0.1:1"]);
+        // validating doesn't bind or apply anything
+        assert_eq!(
+            *context.current_config.read().await,
+            model::ServiceConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_listeners_overlap() {
+        let overlap = |a: &str, b: &str| listeners_overlap(a.parse().unwrap(), b.parse().unwrap());
+        assert!(overlap("0.0.0.0:80", "10.0.0.1:80"));
+        assert!(overlap("[::]:80", "10.0.0.1:80"));
+        assert!(overlap("[::1]:80", "[::]:80"));
+        assert!(!overlap("0.0.0.0:80", "[::1]:80"));
+        assert!(!overlap("0.0.0.0:80", "0.0.0.0:81"));
+        assert!(!overlap("10.0.0.1:80", "10.0.0.2:80"));
+    }
+}
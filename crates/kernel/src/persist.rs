@@ -0,0 +1,480 @@
+//! Last-known-good configs on disk, so a restarted kernel keeps serving without its controller.
+//!
+//! Every applied config is written to its own file in the state directory, named by an
+//! increasing sequence number. Only the newest `retain` files are kept, older ones can be
+//! restored for a local rollback.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use switchboard_model::{ServiceConfig, chrono};
+
+const DEFAULT_RETAIN: usize = 5;
+const SAVED_CONFIG_EXTENSION: &str = "bin";
+
+/// # Example
+/// ```toml
+/// [persist]
+/// dir = "/var/lib/switchboard/kernel"
+/// retain = 5
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct PersistConfig {
+    pub dir: PathBuf,
+    /// How many configs to keep, the current one included.
+    #[serde(default = "default_retain")]
+    pub retain: usize,
+}
+
+fn default_retain() -> usize {
+    DEFAULT_RETAIN
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PersistError {
+    #[error("Persist io error on {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to encode saved config: {0}")]
+    Encode(#[from] bincode::error::EncodeError),
+    #[error("Failed to decode saved config {path:?}: {source}")]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: bincode::error::DecodeError,
+    },
+}
+
+/// Where an applied config came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub enum ConfigSource {
+    /// The kernel's own `config` file.
+    Local,
+    /// Pushed by a controller or through the admin api.
+    Pushed,
+}
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct SavedConfig {
+    pub version: String,
+    #[bincode(with_serde)]
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+    pub source: ConfigSource,
+    pub config: ServiceConfig,
+}
+
+/// A config the kernel can start with.
+#[derive(Debug, Clone)]
+pub enum StartupConfig {
+    Local(ServiceConfig),
+    Saved(SavedConfig),
+}
+
+impl StartupConfig {
+    fn version(&self) -> String {
+        match self {
+            StartupConfig::Local(config) => config.digest_sha256_base64(),
+            StartupConfig::Saved(saved) => saved.version.clone(),
+        }
+    }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> PersistError {
+    let path = path.to_path_buf();
+    move |source| PersistError::Io { path, source }
+}
+
+impl PersistConfig {
+    /// Saved config files, oldest first.
+    async fn files(&self) -> Result<Vec<(u64, PathBuf)>, PersistError> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error(&self.dir)(e)),
+        };
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(io_error(&self.dir))? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SAVED_CONFIG_EXTENSION) {
+                continue;
+            }
+            if let Some(seq) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            {
+                files.push((seq, path));
+            }
+        }
+        files.sort_by_key(|(seq, _)| *seq);
+        Ok(files)
+    }
+
+    async fn read(path: &Path) -> Result<SavedConfig, PersistError> {
+        let bytes = tokio::fs::read(path).await.map_err(io_error(path))?;
+        let (saved, _) =
+            bincode::decode_from_slice(&bytes, bincode::config::standard()).map_err(|source| {
+                PersistError::Decode {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            })?;
+        Ok(saved)
+    }
+
+    /// Save `config` as the newest one and drop the ones beyond `retain`.
+    ///
+    /// Nothing is written when the newest saved config already has the same version and source.
+    pub async fn save(
+        &self,
+        config: &ServiceConfig,
+        source: ConfigSource,
+    ) -> Result<SavedConfig, PersistError> {
+        let version = config.digest_sha256_base64();
+        let files = self.files().await?;
+        if let Some((_, path)) = files.last()
+            && let Ok(newest) = Self::read(path).await
+            && newest.version == version
+            && newest.source == source
+        {
+            return Ok(newest);
+        }
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(io_error(&self.dir))?;
+        let saved = SavedConfig {
+            version,
+            saved_at: chrono::Utc::now(),
+            source,
+            config: config.clone(),
+        };
+        let bytes = bincode::encode_to_vec(&saved, bincode::config::standard())?;
+        let seq = files.last().map_or(0, |(seq, _)| seq + 1);
+        let path = self.dir.join(format!("{seq:020}.{SAVED_CONFIG_EXTENSION}"));
+        // a crash while writing must not leave a truncated newest config behind
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, &bytes)
+            .await
+            .map_err(io_error(&tmp_path))?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(io_error(&path))?;
+        let retain = self.retain.max(1);
+        let stale = (files.len() + 1).saturating_sub(retain);
+        for (_, path) in files.iter().take(stale) {
+            if let Err(e) = tokio::fs::remove_file(path).await {
+                tracing::warn!(?path, "Failed to remove stale saved config: {}", e);
+            }
+        }
+        Ok(saved)
+    }
+
+    /// Every readable saved config, newest first. Unreadable files are skipped.
+    pub async fn list(&self) -> Result<Vec<SavedConfig>, PersistError> {
+        let mut saved = Vec::new();
+        for (_, path) in self.files().await?.iter().rev() {
+            match Self::read(path).await {
+                Ok(config) => saved.push(config),
+                Err(e) => tracing::warn!("Skipping saved config: {}", e),
+            }
+        }
+        Ok(saved)
+    }
+
+    /// The newest readable saved config.
+    pub async fn latest(&self) -> Result<Option<SavedConfig>, PersistError> {
+        for (_, path) in self.files().await?.iter().rev() {
+            match Self::read(path).await {
+                Ok(config) => return Ok(Some(config)),
+                Err(e) => tracing::warn!("Skipping saved config: {}", e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl crate::KernelContext {
+    fn persist_config(&self) -> Result<&PersistConfig, crate::Error> {
+        self.kernel_config
+            .persist
+            .as_ref()
+            .ok_or(crate::Error::PersistNotConfigured)
+    }
+
+    /// The configs to start with, in the order they are tried until one loads.
+    ///
+    /// The newest saved config goes first when it was pushed, or saved after the local config
+    /// file last changed. Otherwise the local file goes first, and the saved configs follow it
+    /// newest first. A local file which can't be read is skipped when there are saved configs.
+    pub async fn startup_configs(&self) -> Result<Vec<StartupConfig>, crate::Error> {
+        let saved = match &self.kernel_config.persist {
+            Some(persist) => persist.list().await?,
+            None => Vec::new(),
+        };
+        let local = match self.fetch_config_locally().await {
+            Ok(local) => local,
+            Err(e) if !saved.is_empty() => {
+                tracing::error!("Failed to load local service config: {}", e);
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        let local_modified = self.local_config_modified().await;
+        let saved_first = saved.first().is_some_and(|newest| {
+            local.is_none()
+                || newest.source == ConfigSource::Pushed
+                || local_modified.is_some_and(|modified| newest.saved_at > modified)
+        });
+        let mut saved = saved.into_iter();
+        let mut configs = Vec::new();
+        if saved_first {
+            configs.extend(saved.next().map(StartupConfig::Saved));
+        }
+        configs.extend(local.map(StartupConfig::Local));
+        configs.extend(saved.map(StartupConfig::Saved));
+        let mut versions = HashSet::new();
+        configs.retain(|config| versions.insert(config.version()));
+        Ok(configs)
+    }
+
+    async fn local_config_modified(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let path = self.kernel_config.config.as_ref()?.as_link()?;
+        let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+        Some(modified.into())
+    }
+
+    pub(crate) async fn save_config(
+        &self,
+        config: &ServiceConfig,
+        source: ConfigSource,
+    ) -> Result<(), crate::Error> {
+        let saved = self.persist_config()?.save(config, source).await?;
+        tracing::debug!(version = %saved.version, "Persisted applied service config");
+        Ok(())
+    }
+
+    /// Saved configs, newest first.
+    pub async fn list_saved_configs(&self) -> Result<Vec<SavedConfig>, crate::Error> {
+        Ok(self.persist_config()?.list().await?)
+    }
+
+    /// Apply a saved config again, by default the newest one older than the running config.
+    ///
+    /// # Errors
+    /// Returns an error when persistence is not configured, no saved config matches, or
+    /// applying it fails.
+    pub async fn restore_saved_config(
+        &self,
+        version: Option<&str>,
+    ) -> Result<String, crate::Error> {
+        let saved = self.list_saved_configs().await?;
+        let saved = match version {
+            Some(version) => saved
+                .into_iter()
+                .find(|saved| saved.version == version)
+                .ok_or_else(|| crate::Error::SavedConfigNotFound(version.to_string()))?,
+            None => {
+                let current = self.current_config.read().await.digest_sha256_base64();
+                // a restored config is saved again as the newest, so step back from its oldest
+                // copy to keep walking back on repeated rollbacks
+                let position = saved.iter().rposition(|saved| saved.version == current);
+                saved
+                    .into_iter()
+                    .skip(position.map_or(0, |position| position + 1))
+                    .find(|saved| saved.version != current)
+                    .ok_or(crate::Error::NoPreviousSavedConfig)?
+            }
+        };
+        tracing::info!(version = %saved.version, saved_at = %saved.saved_at, "Restoring saved service config");
+        self.update_config(saved.config).await?;
+        Ok(saved.version)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use switchboard_model::{
+        TcpServiceConfig, switchboard_serde_value::value, tcp_route::TcpRoute,
+    };
+
+    use super::*;
+
+    fn persist_config(name: &str, retain: usize) -> PersistConfig {
+        let dir = std::env::temp_dir().join(format!(
+            "switchboard-kernel-persist-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        PersistConfig { dir, retain }
+    }
+
+    fn config(port: u16) -> ServiceConfig {
+        let bind = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+        ServiceConfig {
+            tcp_services: [(
+                "web".to_string(),
+                TcpServiceConfig {
+                    provider: "http".to_string(),
+                    name: "web".to_string(),
+                    config: Some(value!({
+                        "flow": { "entrypoint": { "node": "router" }, "nodes": { "router": [1, 2.5, true, "next"] } },
+                    })),
+                    description: Some("web service".to_string()),
+                },
+            )]
+            .into(),
+            tcp_routes: [(
+                bind,
+                TcpRoute {
+                    bind,
+                    service: "web".to_string(),
+                    tls: None,
+                },
+            )]
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    fn versions(saved: &[SavedConfig]) -> Vec<&str> {
+        saved.iter().map(|saved| saved.version.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let persist = persist_config("round-trip", 5);
+        assert!(persist.latest().await.unwrap().is_none());
+        assert!(persist.list().await.unwrap().is_empty());
+
+        let config = config(8080);
+        let saved = persist.save(&config, ConfigSource::Pushed).await.unwrap();
+        assert_eq!(saved.version, config.digest_sha256_base64());
+        let latest = persist.latest().await.unwrap().unwrap();
+        assert_eq!(latest.config, config);
+        assert_eq!(latest.version, saved.version);
+        assert_eq!(latest.saved_at, saved.saved_at);
+
+        // saving the newest config again is a no-op
+        persist.save(&config, ConfigSource::Pushed).await.unwrap();
+        assert_eq!(persist.list().await.unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(&persist.dir);
+    }
+
+    #[tokio::test]
+    async fn test_retain() {
+        let persist = persist_config("retain", 3);
+        let configs = (0..5).map(|i| config(8080 + i)).collect::<Vec<_>>();
+        for config in &configs {
+            persist.save(config, ConfigSource::Pushed).await.unwrap();
+        }
+        let saved = persist.list().await.unwrap();
+        let expected = configs
+            .iter()
+            .rev()
+            .take(3)
+            .map(|config| config.digest_sha256_base64())
+            .collect::<Vec<_>>();
+        assert_eq!(versions(&saved), expected);
+        assert_eq!(persist.files().await.unwrap().len(), 3);
+
+        // an older config saved again becomes the newest
+        persist
+            .save(&configs[2], ConfigSource::Pushed)
+            .await
+            .unwrap();
+        let saved = persist.list().await.unwrap();
+        assert_eq!(saved.len(), 3);
+        assert_eq!(saved[0].config, configs[2]);
+        let _ = std::fs::remove_dir_all(&persist.dir);
+    }
+
+    #[tokio::test]
+    async fn test_skip_unreadable() {
+        let persist = persist_config("unreadable", 5);
+        let first = config(8080);
+        persist.save(&first, ConfigSource::Pushed).await.unwrap();
+        // a corrupted newest file and unrelated files are skipped
+        std::fs::write(persist.dir.join(format!("{:020}.bin", 100)), b"garbage").unwrap();
+        std::fs::write(persist.dir.join("00000000000000000200.tmp"), b"partial").unwrap();
+        std::fs::write(persist.dir.join("notes.bin"), b"not a config").unwrap();
+        let latest = persist.latest().await.unwrap().unwrap();
+        assert_eq!(latest.config, first);
+        assert_eq!(persist.list().await.unwrap().len(), 1);
+
+        // the next save still goes after the corrupted one
+        let newer = config(9090);
+        persist.save(&newer, ConfigSource::Pushed).await.unwrap();
+        assert_eq!(persist.latest().await.unwrap().unwrap().config, newer);
+        let _ = std::fs::remove_dir_all(&persist.dir);
+    }
+
+    #[tokio::test]
+    async fn test_startup_configs() {
+        let persist = persist_config("startup", 5);
+        std::fs::create_dir_all(&persist.dir).unwrap();
+        let local_path = persist.dir.join("config.toml");
+        let write_local = |content: &str| {
+            std::fs::write(&local_path, content).unwrap();
+            // the saves of this test happen within the mtime granularity of some filesystems
+            let modified = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+            std::fs::File::options()
+                .write(true)
+                .open(&local_path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let service = |name: &str| {
+            format!("[[tcp_services]]\nname = \"{name}\"\nprovider = \"http\"\nbinds = []\n")
+        };
+        let context = crate::KernelContext::new(crate::config::KernelConfig {
+            config: Some(switchboard_link_or_value::LinkOrValue::Link(
+                local_path.clone(),
+            )),
+            persist: Some(persist.clone()),
+            ..Default::default()
+        });
+        let order = async || {
+            context
+                .startup_configs()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|config| {
+                    let (kind, config) = match config {
+                        StartupConfig::Local(config) => ("local", config),
+                        StartupConfig::Saved(saved) => ("saved", saved.config),
+                    };
+                    let name = config.tcp_services.into_keys().next().unwrap_or_default();
+                    format!("{kind} {name}")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        write_local(&service("first"));
+        assert_eq!(order().await, ["local first"]);
+        // a saved copy of the local file is the same config, tried once
+        let local = context.fetch_config_locally().await.unwrap().unwrap();
+        persist.save(&local, ConfigSource::Local).await.unwrap();
+        assert_eq!(order().await, ["local first"]);
+        // the local file changed after it was saved, so it wins over the saved copy
+        write_local(&service("edited"));
+        assert_eq!(order().await, ["local edited", "saved first"]);
+        // a pushed config wins over the local file, whatever their times
+        persist
+            .save(&config(8080), ConfigSource::Pushed)
+            .await
+            .unwrap();
+        assert_eq!(order().await, ["saved web", "local edited", "saved first"]);
+        // a broken local file is skipped when there is a saved config to start with
+        write_local("[[tcp_services]");
+        assert_eq!(order().await, ["saved web", "saved first"]);
+        let _ = std::fs::remove_dir_all(&persist.dir);
+    }
+}
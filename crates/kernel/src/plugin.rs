@@ -0,0 +1,167 @@
+//! Plugins pushed by the controller at runtime.
+//!
+//! The kernel only stores the artifacts, loading them is up to the [`PluginHost`] registered by
+//! the binary, which knows the service providers.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use futures::future::BoxFuture;
+use sha2::Digest;
+use switchboard_model::kernel::{ClassList, PluginInfo, PluginKind, PluginList};
+
+use crate::KernelContext;
+
+#[cfg(target_os = "linux")]
+const NATIVE_EXT: &str = "so";
+#[cfg(target_os = "macos")]
+const NATIVE_EXT: &str = "dylib";
+#[cfg(target_os = "windows")]
+const NATIVE_EXT: &str = "dll";
+const WASM_EXT: &str = "wasm";
+
+pub type PluginHostError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A checked artifact written into the plugin dir.
+#[derive(Debug, Clone)]
+pub struct PluginArtifact {
+    pub name: String,
+    pub kind: PluginKind,
+    pub path: PathBuf,
+    /// Hex sha256 of the file.
+    pub sha256: String,
+}
+
+/// Loads plugin artifacts into a service provider.
+pub trait PluginHost: Send + Sync + 'static {
+    /// Load `artifact`, replacing the plugin of the same name.
+    fn load(&self, artifact: PluginArtifact) -> BoxFuture<'_, Result<PluginInfo, PluginHostError>>;
+    fn list(&self) -> BoxFuture<'_, PluginList>;
+    /// The classes of the current registry generation, plugin classes included.
+    fn classes(&self) -> BoxFuture<'_, ClassList>;
+}
+
+pub(crate) type SharedPluginHost = Arc<dyn PluginHost>;
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    sha2::Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn is_valid_plugin_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Write through a temporary file, so a library mapped by the kernel is never overwritten.
+async fn write_artifact(path: &Path, artifact: &[u8]) -> std::io::Result<()> {
+    let temp_path = path.with_extension("part");
+    tokio::fs::write(&temp_path, artifact).await?;
+    tokio::fs::rename(&temp_path, path).await
+}
+
+impl KernelContext {
+    /// Set the host loading pushed plugins, pushes are refused until one is set.
+    pub async fn set_plugin_host<H: PluginHost>(&self, host: H) {
+        *self.plugin_host.write().await = Some(Arc::new(host));
+    }
+
+    /// Check a pushed artifact against its sha256, write it into the plugin dir and load it.
+    /// Pushing a loaded plugin again with the same checksum does nothing.
+    ///
+    /// # Errors
+    /// Returns an error when the name or checksum is invalid, no plugin dir or host is set up,
+    /// or the artifact can't be written or loaded.
+    pub async fn push_plugin(
+        &self,
+        name: String,
+        kind: PluginKind,
+        artifact: &[u8],
+        sha256: &str,
+    ) -> Result<PluginInfo, crate::Error> {
+        if !is_valid_plugin_name(&name) {
+            return Err(crate::Error::InvalidPluginName(name));
+        }
+        let actual = sha256_hex(artifact);
+        if !actual.eq_ignore_ascii_case(sha256) {
+            return Err(crate::Error::PluginChecksumMismatch {
+                expected: sha256.to_string(),
+                actual,
+            });
+        }
+        let plugin_dir = self
+            .kernel_config
+            .provider
+            .http
+            .plugin_dir
+            .as_ref()
+            .ok_or(crate::Error::PluginDirNotConfigured)?;
+        let host = self
+            .plugin_host
+            .read()
+            .await
+            .clone()
+            .ok_or(crate::Error::PluginHostNotSet)?;
+        if let Some(loaded) = host
+            .list()
+            .await
+            .plugins
+            .into_iter()
+            .find(|plugin| plugin.name == name && plugin.sha256.as_deref() == Some(&actual))
+        {
+            tracing::info!(plugin = %name, "plugin already loaded, skip push");
+            return Ok(loaded);
+        }
+        let ext = match kind {
+            PluginKind::Native => NATIVE_EXT,
+            PluginKind::Wasm => WASM_EXT,
+        };
+        // a new file per version, the loader may still hold the previous one
+        let path = plugin_dir.join(format!("{name}-{}.{ext}", &actual[..16]));
+        tokio::fs::create_dir_all(plugin_dir)
+            .await
+            .map_err(crate::Error::WritePlugin)?;
+        write_artifact(&path, artifact)
+            .await
+            .map_err(crate::Error::WritePlugin)?;
+        let artifact = PluginArtifact {
+            name,
+            kind,
+            path,
+            sha256: actual,
+        };
+        tracing::info!(plugin = %artifact.name, path = ?artifact.path, "loading pushed plugin");
+        match host.load(artifact.clone()).await {
+            Ok(info) => Ok(info),
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&artifact.path).await;
+                Err(crate::Error::LoadPlugin(e))
+            }
+        }
+    }
+
+    /// The plugins loaded by the plugin host, empty without one.
+    pub async fn list_plugins(&self) -> PluginList {
+        let host = self.plugin_host.read().await.clone();
+        match host {
+            Some(host) => host.list().await,
+            None => PluginList::default(),
+        }
+    }
+
+    /// The classes known to the plugin host, empty without one.
+    pub async fn list_classes(&self) -> ClassList {
+        let host = self.plugin_host.read().await.clone();
+        match host {
+            Some(host) => host.classes().await,
+            None => ClassList::default(),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::{net::SocketAddr, sync::Arc};
 use switchboard_model::TcpServiceConfig;
 use switchboard_service::{
-    TcpServiceProvider,
+    TcpServiceProvider, ValidationReport,
     registry::{ServiceProviderRegistry, ServiceProviderRegistryError},
     tcp::SharedTcpService,
 };
@@ -55,6 +55,18 @@ impl Registry {
             .await?;
         Ok(service)
     }
+    pub async fn validate_tcp_service(
+        &self,
+        config: &TcpServiceConfig,
+    ) -> Result<ValidationReport, RegistryError> {
+        let report = self
+            .registry
+            .read()
+            .await
+            .validate_tcp(&config.provider, config.config.clone())
+            .await?;
+        Ok(report)
+    }
     // pub async fn load_prelude(&self) {
     //     crate::register_prelude(&mut *self.registry.write().await);
     // }
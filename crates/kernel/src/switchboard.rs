@@ -1,4 +1,6 @@
 use std::sync::Arc;
 pub type ResourceKey = Arc<str>;
 
+pub mod connection;
+pub mod limit;
 pub mod tcp;
@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use switchboard_model::chrono::{DateTime, Utc};
+use switchboard_service::tcp::TcpConnectionStats;
+use tokio_util::sync::CancellationToken;
+
+use crate::switchboard::ResourceKey;
+
+pub type ConnectionId = u64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub id: ConnectionId,
+    pub bind: SocketAddr,
+    pub peer: SocketAddr,
+    pub service: String,
+    pub tls: bool,
+    /// Server name from the TLS ClientHello, once the service read it.
+    pub sni: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+
+struct ConnectionEntry {
+    bind: SocketAddr,
+    peer: SocketAddr,
+    service: ResourceKey,
+    tls: bool,
+    started_at: DateTime<Utc>,
+    stats: Arc<TcpConnectionStats>,
+    ct: CancellationToken,
+}
+
+impl ConnectionEntry {
+    fn info(&self, id: ConnectionId) -> ConnectionInfo {
+        ConnectionInfo {
+            id,
+            bind: self.bind,
+            peer: self.peer,
+            service: self.service.to_string(),
+            tls: self.tls,
+            sni: self.stats.sni().map(str::to_owned),
+            started_at: self.started_at,
+            bytes_received: self.stats.bytes_received(),
+            bytes_sent: self.stats.bytes_sent(),
+        }
+    }
+}
+
+/// A peer to match connections from, either a whole ip or a single `ip:port`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerFilter {
+    Ip(IpAddr),
+    Addr(SocketAddr),
+}
+
+impl PeerFilter {
+    pub fn matches(&self, peer: &SocketAddr) -> bool {
+        match self {
+            PeerFilter::Ip(ip) => peer.ip() == *ip,
+            PeerFilter::Addr(addr) => peer == addr,
+        }
+    }
+}
+
+impl std::str::FromStr for PeerFilter {
+    type Err = std::net::AddrParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<SocketAddr>() {
+            Ok(addr) => Ok(PeerFilter::Addr(addr)),
+            Err(_) => s.parse::<IpAddr>().map(PeerFilter::Ip),
+        }
+    }
+}
+
+impl std::fmt::Display for PeerFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeerFilter::Ip(ip) => write!(f, "{ip}"),
+            PeerFilter::Addr(addr) => write!(f, "{addr}"),
+        }
+    }
+}
+
+impl Serialize for PeerFilter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PeerFilter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Picks connections, every set field must match. The empty filter matches everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionFilter {
+    pub service: Option<String>,
+    pub bind: Option<SocketAddr>,
+    pub peer: Option<PeerFilter>,
+    pub sni: Option<String>,
+}
+
+impl ConnectionFilter {
+    pub fn is_empty(&self) -> bool {
+        self.service.is_none() && self.bind.is_none() && self.peer.is_none() && self.sni.is_none()
+    }
+    fn matches(&self, entry: &ConnectionEntry) -> bool {
+        self.service
+            .as_deref()
+            .is_none_or(|service| service == &*entry.service)
+            && self.bind.is_none_or(|bind| bind == entry.bind)
+            && self.peer.is_none_or(|peer| peer.matches(&entry.peer))
+            && self
+                .sni
+                .as_deref()
+                .is_none_or(|sni| entry.stats.sni() == Some(sni))
+    }
+}
+
+/// Connections served by the switchboard, shared by every clone.
+#[derive(Clone, Default)]
+pub struct ConnectionTable {
+    next_id: Arc<AtomicU64>,
+    entries: Arc<Mutex<HashMap<ConnectionId, ConnectionEntry>>>,
+}
+
+/// Removes its connection from the table when dropped, i.e. when the serve task ends.
+pub(crate) struct ConnectionGuard {
+    table: ConnectionTable,
+    id: ConnectionId,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut entries) = self.table.entries.lock() {
+            entries.remove(&self.id);
+        }
+    }
+}
+
+pub(crate) struct TrackedConnection {
+    pub bind: SocketAddr,
+    pub peer: SocketAddr,
+    pub service: ResourceKey,
+    pub tls: bool,
+    pub stats: Arc<TcpConnectionStats>,
+}
+
+impl ConnectionTable {
+    /// Record a connection, returning its guard and the token cancelling it.
+    pub(crate) fn track(
+        &self,
+        connection: TrackedConnection,
+    ) -> (ConnectionGuard, CancellationToken) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let ct = CancellationToken::new();
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(
+                id,
+                ConnectionEntry {
+                    bind: connection.bind,
+                    peer: connection.peer,
+                    service: connection.service,
+                    tls: connection.tls,
+                    started_at: Utc::now(),
+                    stats: connection.stats,
+                    ct: ct.clone(),
+                },
+            );
+        }
+        let guard = ConnectionGuard {
+            table: self.clone(),
+            id,
+        };
+        (guard, ct)
+    }
+
+    pub fn list(&self, filter: &ConnectionFilter) -> Vec<ConnectionInfo> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let mut connections = entries
+            .iter()
+            .filter(|(_, entry)| filter.matches(entry))
+            .map(|(id, entry)| entry.info(*id))
+            .collect::<Vec<_>>();
+        connections.sort_by_key(|connection| connection.id);
+        connections
+    }
+
+    pub fn count_by_bind(&self) -> HashMap<SocketAddr, usize> {
+        let mut counts = HashMap::new();
+        if let Ok(entries) = self.entries.lock() {
+            for entry in entries.values() {
+                *counts.entry(entry.bind).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    /// Cancel every connection matching `filter`, returning how many were closed.
+    pub fn close(&self, filter: &ConnectionFilter) -> usize {
+        let Ok(entries) = self.entries.lock() else {
+            return 0;
+        };
+        let mut closed = 0;
+        for entry in entries.values() {
+            if filter.matches(entry) && !entry.ct.is_cancelled() {
+                entry.ct.cancel();
+                closed += 1;
+            }
+        }
+        closed
+    }
+
+    /// Cancel the connection `id`, returning whether it was open.
+    pub fn close_by_id(&self, id: ConnectionId) -> bool {
+        let Ok(entries) = self.entries.lock() else {
+            return false;
+        };
+        match entries.get(&id) {
+            Some(entry) if !entry.ct.is_cancelled() => {
+                entry.ct.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl crate::KernelContext {
+    pub async fn list_connections(
+        &self,
+        filter: &ConnectionFilter,
+    ) -> Result<Vec<ConnectionInfo>, crate::Error> {
+        let switchboard = self.tcp_switchboard.read().await;
+        Ok(switchboard.handle()?.connections.list(filter))
+    }
+
+    /// Close the connections matching `filter`, returning how many were closed.
+    ///
+    /// # Errors
+    /// Returns an error for the empty filter, use [`ConnectionTable::close`] to close everything.
+    pub async fn close_connections(
+        &self,
+        filter: &ConnectionFilter,
+    ) -> Result<usize, crate::Error> {
+        if filter.is_empty() {
+            return Err(crate::Error::EmptyConnectionFilter);
+        }
+        let switchboard = self.tcp_switchboard.read().await;
+        let closed = switchboard.handle()?.connections.close(filter);
+        tracing::info!(?filter, closed, "Closed connections on request");
+        Ok(closed)
+    }
+
+    pub async fn close_connection(&self, id: ConnectionId) -> Result<bool, crate::Error> {
+        let switchboard = self.tcp_switchboard.read().await;
+        let closed = switchboard.handle()?.connections.close_by_id(id);
+        tracing::info!(id, closed, "Closed connection on request");
+        Ok(closed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use switchboard_service::tcp::CountedStream;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    fn track(
+        table: &ConnectionTable,
+        bind: &str,
+        peer: &str,
+        service: &str,
+        sni: Option<&str>,
+    ) -> (ConnectionGuard, CancellationToken, Arc<TcpConnectionStats>) {
+        let stats = Arc::new(TcpConnectionStats::default());
+        if let Some(sni) = sni {
+            stats.set_sni(sni);
+        }
+        let (guard, ct) = table.track(TrackedConnection {
+            bind: addr(bind),
+            peer: addr(peer),
+            service: service.into(),
+            tls: sni.is_some(),
+            stats: stats.clone(),
+        });
+        (guard, ct, stats)
+    }
+
+    fn ids(connections: &[ConnectionInfo]) -> Vec<ConnectionId> {
+        connections.iter().map(|connection| connection.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_connection_table() {
+        let table = ConnectionTable::default();
+        let (web, web_ct, web_stats) = track(
+            &table,
+            "0.0.0.0:443",
+            "192.0.2.1:50000",
+            "web",
+            Some("example.com"),
+        );
+        let (_api, api_ct, _) = track(&table, "0.0.0.0:443", "192.0.2.1:50001", "api", None);
+        let (_other, other_ct, _) = track(&table, "0.0.0.0:80", "192.0.2.2:50000", "web", None);
+
+        // byte counts are read live from the connection's stats
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = CountedStream::new(server, web_stats);
+        client.write_all(b"hello").await.unwrap();
+        let mut buf = [0; 5];
+        server.read_exact(&mut buf).await.unwrap();
+        server.write_all(b"hi").await.unwrap();
+
+        let all = table.list(&ConnectionFilter::default());
+        assert_eq!(ids(&all), [0, 1, 2]);
+        assert_eq!(all[0].service, "web");
+        assert_eq!(all[0].sni.as_deref(), Some("example.com"));
+        assert!(all[0].tls && !all[1].tls);
+        assert_eq!((all[0].bytes_received, all[0].bytes_sent), (5, 2));
+
+        let filter = |json| serde_json::from_value::<ConnectionFilter>(json).unwrap();
+        let list = |json| ids(&table.list(&filter(json)));
+        assert_eq!(list(serde_json::json!({ "service": "web" })), [0, 2]);
+        assert_eq!(list(serde_json::json!({ "bind": "0.0.0.0:443" })), [0, 1]);
+        assert_eq!(list(serde_json::json!({ "peer": "192.0.2.1" })), [0, 1]);
+        assert_eq!(list(serde_json::json!({ "peer": "192.0.2.1:50001" })), [1]);
+        assert_eq!(list(serde_json::json!({ "sni": "example.com" })), [0]);
+        assert!(list(serde_json::json!({ "service": "web", "bind": "0.0.0.0:8080" })).is_empty());
+        assert_eq!(
+            table.count_by_bind(),
+            HashMap::from([(addr("0.0.0.0:443"), 2), (addr("0.0.0.0:80"), 1)])
+        );
+
+        // closing cancels the serve task, which then drops the guard
+        assert_eq!(
+            table.close(&filter(serde_json::json!({ "peer": "192.0.2.1" }))),
+            2
+        );
+        assert!(web_ct.is_cancelled() && api_ct.is_cancelled() && !other_ct.is_cancelled());
+        // already closing connections are not counted again
+        assert_eq!(
+            table.close(&filter(serde_json::json!({ "bind": "0.0.0.0:443" }))),
+            0
+        );
+        assert!(table.close_by_id(2));
+        assert!(other_ct.is_cancelled());
+        assert!(!table.close_by_id(2));
+        assert!(!table.close_by_id(42));
+
+        drop(web);
+        assert_eq!(ids(&table.list(&ConnectionFilter::default())), [1, 2]);
+    }
+
+    #[test]
+    fn test_peer_filter() {
+        let ip: PeerFilter = "192.0.2.1".parse().unwrap();
+        let socket: PeerFilter = "[2001:db8::1]:443".parse().unwrap();
+        assert_eq!(ip, PeerFilter::Ip(IpAddr::from([192, 0, 2, 1])));
+        assert_eq!(socket, PeerFilter::Addr(addr("[2001:db8::1]:443")));
+        assert!("example.com".parse::<PeerFilter>().is_err());
+        assert!(ip.matches(&addr("192.0.2.1:1")));
+        assert!(!ip.matches(&addr("192.0.2.2:1")));
+        assert!(socket.matches(&addr("[2001:db8::1]:443")));
+        assert!(!socket.matches(&addr("[2001:db8::1]:444")));
+        assert_eq!(socket.to_string(), "[2001:db8::1]:443");
+        assert_eq!(serde_json::to_value(ip).unwrap(), "192.0.2.1");
+        assert!(ConnectionFilter::default().is_empty());
+        assert!(
+            !ConnectionFilter {
+                peer: Some(ip),
+                ..Default::default()
+            }
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,398 @@
+//! Per-listener connection limits.
+//!
+//! The concurrent connection caps and the accept rate are checked by the listener task right
+//! after accepting, before the connection reaches the switchboard. The timeouts are raced against
+//! the service while it serves the connection.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use switchboard_model::{AcceptRate, ListenerLimits};
+use switchboard_service::tcp::TcpConnectionStats;
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+pub enum LimitReached {
+    #[error("listener reached max_connections ({0})")]
+    MaxConnections(u32),
+    #[error("source ip reached max_connections_per_ip ({0})")]
+    MaxConnectionsPerIp(u32),
+    #[error("accept rate exceeded")]
+    AcceptRate,
+    #[error("handshake not completed within the handshake timeout")]
+    HandshakeTimeout,
+    #[error("nothing sent or received within the idle timeout")]
+    IdleTimeout,
+}
+
+/// How many times each limit of a listener was reached.
+#[derive(Debug, Default)]
+pub struct LimitCounters {
+    max_connections: AtomicU64,
+    max_connections_per_ip: AtomicU64,
+    accept_rate: AtomicU64,
+    handshake_timeout: AtomicU64,
+    idle_timeout: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LimitCounts {
+    pub max_connections: u64,
+    pub max_connections_per_ip: u64,
+    pub accept_rate: u64,
+    pub handshake_timeout: u64,
+    pub idle_timeout: u64,
+}
+
+impl LimitCounters {
+    pub(crate) fn record(&self, bind: SocketAddr, peer: SocketAddr, reached: LimitReached) {
+        let counter = match reached {
+            LimitReached::MaxConnections(_) => &self.max_connections,
+            LimitReached::MaxConnectionsPerIp(_) => &self.max_connections_per_ip,
+            LimitReached::AcceptRate => &self.accept_rate,
+            LimitReached::HandshakeTimeout => &self.handshake_timeout,
+            LimitReached::IdleTimeout => &self.idle_timeout,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(%bind, %peer, "Closing connection: {}", reached);
+    }
+    pub fn counts(&self) -> LimitCounts {
+        LimitCounts {
+            max_connections: self.max_connections.load(Ordering::Relaxed),
+            max_connections_per_ip: self.max_connections_per_ip.load(Ordering::Relaxed),
+            accept_rate: self.accept_rate.load(Ordering::Relaxed),
+            handshake_timeout: self.handshake_timeout.load(Ordering::Relaxed),
+            idle_timeout: self.idle_timeout.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct TokenBucket {
+    rate: AcceptRate,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: AcceptRate, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate.burst() as f64,
+            refilled_at: now,
+        }
+    }
+    fn try_take(&mut self, now: Instant) -> bool {
+        let refill =
+            now.duration_since(self.refilled_at).as_secs_f64() * self.rate.per_second as f64;
+        self.tokens = (self.tokens + refill).min(self.rate.burst() as f64);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Default)]
+struct AdmittedCounts {
+    total: u32,
+    per_ip: HashMap<IpAddr, u32>,
+}
+
+/// Admits the connections of one listener, owned by its listener task.
+pub(crate) struct ListenerLimiter {
+    limits: tokio::sync::watch::Receiver<ListenerLimits>,
+    bucket: Option<TokenBucket>,
+    admitted: Arc<Mutex<AdmittedCounts>>,
+    pub(crate) counters: Arc<LimitCounters>,
+}
+
+/// A connection counted against its listener limits until dropped.
+pub(crate) struct Admission {
+    admitted: Arc<Mutex<AdmittedCounts>>,
+    ip: IpAddr,
+    handshake_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    pub(crate) counters: Arc<LimitCounters>,
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        if let Ok(mut admitted) = self.admitted.lock() {
+            admitted.total = admitted.total.saturating_sub(1);
+            if let Some(count) = admitted.per_ip.get_mut(&self.ip) {
+                *count -= 1;
+                if *count == 0 {
+                    admitted.per_ip.remove(&self.ip);
+                }
+            }
+        }
+    }
+}
+
+impl ListenerLimiter {
+    pub(crate) fn new(limits: tokio::sync::watch::Receiver<ListenerLimits>) -> Self {
+        Self {
+            limits,
+            bucket: None,
+            admitted: Default::default(),
+            counters: Default::default(),
+        }
+    }
+
+    pub(crate) fn admit(&mut self, peer: IpAddr) -> Result<Admission, LimitReached> {
+        let limits = *self.limits.borrow();
+        let now = Instant::now();
+        match limits.accept_rate {
+            Some(rate) => {
+                let bucket = self
+                    .bucket
+                    .get_or_insert_with(|| TokenBucket::new(rate, now));
+                // the limits were updated by a new config
+                if bucket.rate != rate {
+                    *bucket = TokenBucket::new(rate, now);
+                }
+                if !bucket.try_take(now) {
+                    return Err(LimitReached::AcceptRate);
+                }
+            }
+            None => self.bucket = None,
+        }
+        let mut admitted = self
+            .admitted
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(max) = limits.max_connections
+            && admitted.total >= max
+        {
+            return Err(LimitReached::MaxConnections(max));
+        }
+        let from_peer = admitted.per_ip.get(&peer).copied().unwrap_or_default();
+        if let Some(max) = limits.max_connections_per_ip
+            && from_peer >= max
+        {
+            return Err(LimitReached::MaxConnectionsPerIp(max));
+        }
+        admitted.total += 1;
+        admitted.per_ip.insert(peer, from_peer + 1);
+        Ok(Admission {
+            admitted: self.admitted.clone(),
+            ip: peer,
+            handshake_timeout: limits.handshake_timeout_secs.map(Duration::from_secs),
+            idle_timeout: limits.idle_timeout_secs.map(Duration::from_secs),
+            counters: self.counters.clone(),
+        })
+    }
+}
+
+impl Admission {
+    /// Resolves once the connection reached one of its timeouts, never if it has none.
+    pub(crate) async fn timed_out(&self, stats: &TcpConnectionStats) -> LimitReached {
+        if let Some(timeout) = self.handshake_timeout {
+            tokio::time::sleep(timeout).await;
+            if !stats.handshake_done() {
+                return LimitReached::HandshakeTimeout;
+            }
+        }
+        let Some(idle_timeout) = self.idle_timeout else {
+            return std::future::pending().await;
+        };
+        let transferred = || stats.bytes_received() + stats.bytes_sent();
+        let mut last_transferred = transferred();
+        let mut last_active = tokio::time::Instant::now();
+        loop {
+            // poll the counters instead of timing every read and write
+            tokio::time::sleep((idle_timeout / 4).max(Duration::from_millis(100))).await;
+            let now_transferred = transferred();
+            if now_transferred != last_transferred {
+                last_transferred = now_transferred;
+                last_active = tokio::time::Instant::now();
+            } else if last_active.elapsed() >= idle_timeout {
+                return LimitReached::IdleTimeout;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use switchboard_service::tcp::CountedStream;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    fn limiter(
+        limits: ListenerLimits,
+    ) -> (tokio::sync::watch::Sender<ListenerLimits>, ListenerLimiter) {
+        let (sender, receiver) = tokio::sync::watch::channel(limits);
+        (sender, ListenerLimiter::new(receiver))
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 0, 2, last])
+    }
+
+    #[test]
+    fn test_max_connections_per_ip() {
+        let (limits, mut limiter) = limiter(ListenerLimits {
+            max_connections_per_ip: Some(2),
+            ..Default::default()
+        });
+        let first = limiter.admit(ip(1)).unwrap();
+        let _second = limiter.admit(ip(1)).unwrap();
+        assert!(matches!(
+            limiter.admit(ip(1)),
+            Err(LimitReached::MaxConnectionsPerIp(2))
+        ));
+        // other sources have their own count
+        let _other = limiter.admit(ip(2)).unwrap();
+        // a closed connection frees its slot
+        drop(first);
+        let _third = limiter.admit(ip(1)).unwrap();
+        assert!(limiter.admit(ip(1)).is_err());
+
+        // the total cap applies across sources
+        limits.send_replace(ListenerLimits {
+            max_connections: Some(3),
+            ..Default::default()
+        });
+        assert!(matches!(
+            limiter.admit(ip(3)),
+            Err(LimitReached::MaxConnections(3))
+        ));
+        limits.send_replace(ListenerLimits::default());
+        let _unlimited = (0..10)
+            .map(|_| limiter.admit(ip(1)).unwrap())
+            .collect::<Vec<_>>();
+    }
+
+    #[test]
+    fn test_token_bucket_refill() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut bucket = TokenBucket::new(
+            AcceptRate {
+                per_second: 10,
+                burst: Some(3),
+            },
+            start,
+        );
+        // starts full
+        assert!((0..3).all(|_| bucket.try_take(start)));
+        assert!(!bucket.try_take(start));
+        // one token every 100ms
+        assert!(!bucket.try_take(at(50)));
+        assert!(bucket.try_take(at(100)));
+        assert!(!bucket.try_take(at(100)));
+        assert!(bucket.try_take(at(250)));
+        assert!(!bucket.try_take(at(250)));
+        // refills up to the burst, not beyond
+        assert_eq!((0..10).filter(|_| bucket.try_take(at(60_000))).count(), 3);
+    }
+
+    #[test]
+    fn test_accept_rate() {
+        let (limits, mut limiter) = limiter(ListenerLimits {
+            accept_rate: Some(AcceptRate {
+                per_second: 1,
+                burst: Some(2),
+            }),
+            ..Default::default()
+        });
+        let _admitted = (0..2)
+            .map(|_| limiter.admit(ip(1)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            limiter.admit(ip(2)),
+            Err(LimitReached::AcceptRate)
+        ));
+        // a new rate starts with a full bucket
+        limits.send_replace(ListenerLimits {
+            accept_rate: Some(AcceptRate {
+                per_second: 1,
+                burst: Some(3),
+            }),
+            ..Default::default()
+        });
+        assert!(limiter.admit(ip(2)).is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_handshake_timeout() {
+        let (_limits, mut limiter) = limiter(ListenerLimits {
+            handshake_timeout_secs: Some(10),
+            ..Default::default()
+        });
+        let admission = limiter.admit(ip(1)).unwrap();
+        let stats = Arc::new(TcpConnectionStats::default());
+        let start = tokio::time::Instant::now();
+        assert!(matches!(
+            admission.timed_out(&stats).await,
+            LimitReached::HandshakeTimeout
+        ));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+
+        // sending part of a request doesn't count as a handshake
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = CountedStream::new(server, stats.clone());
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let mut buf = [0; 64];
+        let _ = server.read(&mut buf).await.unwrap();
+        assert!(matches!(
+            admission.timed_out(&stats).await,
+            LimitReached::HandshakeTimeout
+        ));
+
+        // a connection which finished its handshake is left alone without an idle timeout
+        stats.set_handshake_done();
+        assert!(
+            tokio::time::timeout(Duration::from_secs(3600), admission.timed_out(&stats))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_timeout() {
+        let (_limits, mut limiter) = limiter(ListenerLimits {
+            handshake_timeout_secs: Some(10),
+            idle_timeout_secs: Some(60),
+            ..Default::default()
+        });
+        let admission = limiter.admit(ip(1)).unwrap();
+        let stats = Arc::new(TcpConnectionStats::default());
+        stats.set_handshake_done();
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = CountedStream::new(server, stats.clone());
+        let start = tokio::time::Instant::now();
+        let traffic = async {
+            // keeps the connection active for 100s, then goes quiet
+            for _ in 0..10 {
+                client.write_all(b"ping").await.unwrap();
+                let mut buf = [0; 4];
+                server.read_exact(&mut buf).await.unwrap();
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            std::future::pending::<()>().await
+        };
+        let reached = tokio::select! {
+            reached = admission.timed_out(&stats) => reached,
+            _ = traffic => unreachable!(),
+        };
+        assert!(matches!(reached, LimitReached::IdleTimeout));
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_secs(150) && elapsed <= Duration::from_secs(180),
+            "{elapsed:?}"
+        );
+    }
+}
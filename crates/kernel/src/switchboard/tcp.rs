@@ -1,6 +1,9 @@
 use crate::switchboard::ResourceKey;
+use crate::switchboard::connection::{ConnectionTable, TrackedConnection};
+use crate::switchboard::limit::{Admission, LimitCounters, ListenerLimiter};
 use std::net::SocketAddr;
 use std::{collections::HashMap, sync::Arc};
+use switchboard_model::ListenerLimits;
 use switchboard_service::tcp::{SharedTcpService, TcpAccepted, TcpListener};
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
@@ -15,6 +18,7 @@ pub(crate) enum TcpSwitchboardEvent {
     NewAccepted {
         from_bind: SocketAddr,
         tcp_accepted: TcpAccepted,
+        admission: Admission,
     },
     UpdateRouter(Arc<TcpSwitchboardRouter>),
     Halt,
@@ -59,6 +63,7 @@ pub struct TcpSwitchboardContext {
     pub(crate) router: Arc<TcpSwitchboardRouter>,
     pub(crate) task_set: tokio::task::JoinSet<tokio::io::Result<()>>,
     pub(crate) local_event_buffer: Vec<TcpSwitchboardEvent>,
+    pub(crate) connections: ConnectionTable,
 }
 
 #[derive(Clone)]
@@ -78,11 +83,14 @@ impl TcpSwitchboardRouter {
             routes: HashMap::new(),
         }
     }
-    fn get_service(&self, bind: &SocketAddr) -> Option<(SharedTcpService, Option<Tls>)> {
+    fn get_service(
+        &self,
+        bind: &SocketAddr,
+    ) -> Option<(ResourceKey, SharedTcpService, Option<Tls>)> {
         let route = self.routes.get(bind)?;
         let service = self.tcp_services.get(&route.service)?;
         let tls = route.tls.as_ref().and_then(|k| self.tlss.get(k));
-        Some((service.clone(), tls.cloned()))
+        Some((route.service.clone(), service.clone(), tls.cloned()))
     }
 }
 
@@ -91,6 +99,7 @@ pub struct TcpSwitchboardHandle {
     current_router: RwLock<Arc<TcpSwitchboardRouter>>,
     task_handle: tokio::task::JoinHandle<TcpSwitchboardContext>,
     pub(crate) tcp_listeners: HashMap<SocketAddr, TcpListenerTask>,
+    pub(crate) connections: ConnectionTable,
 }
 #[derive(Debug, thiserror::Error)]
 pub enum TcpSwitchboardError {
@@ -101,11 +110,21 @@ impl TcpSwitchboardHandle {
     pub async fn create_listener_task(
         &mut self,
         listener: TcpListener,
+        limits: ListenerLimits,
     ) -> Result<(), crate::Error> {
-        let listener_task = TcpListenerTask::spawn(listener, self.event_sender.clone());
+        let listener_task = TcpListenerTask::spawn(listener, limits, self.event_sender.clone());
         self.tcp_listeners.insert(listener_task.bind, listener_task);
         Ok(())
     }
+    pub fn update_listener_limits(&self, bind: &SocketAddr, limits: ListenerLimits) {
+        if let Some(listener_task) = self.tcp_listeners.get(bind) {
+            listener_task.limits.send_if_modified(|current| {
+                let modified = *current != limits;
+                *current = limits;
+                modified
+            });
+        }
+    }
     pub async fn remove_listener_task(&mut self, bind: &SocketAddr) {
         if let Some(listener_task) = self.tcp_listeners.remove(bind) {
             listener_task.cancel().await;
@@ -199,14 +218,19 @@ impl TcpSwitchboardContext {
             task_set: tokio::task::JoinSet::new(),
             router: TcpSwitchboardRouter::new().into(),
             local_event_buffer: Vec::with_capacity(LOCAL_EVENT_BUFFER_BATCH_SIZE),
+            connections: ConnectionTable::default(),
         }
     }
-    fn get_service(&self, bind: &SocketAddr) -> Option<(SharedTcpService, Option<Tls>)> {
+    fn get_service(
+        &self,
+        bind: &SocketAddr,
+    ) -> Option<(ResourceKey, SharedTcpService, Option<Tls>)> {
         self.router.get_service(bind)
     }
     pub fn spawn(self) -> TcpSwitchboardHandle {
         let event_sender = self.event_sender.clone();
         let current_router = RwLock::new(self.router.clone());
+        let connections = self.connections.clone();
         let span = tracing::warn_span!("tcp-switchboard-event-loop");
 
         let handle = tokio::spawn(self.run_event_loop().instrument(span));
@@ -215,6 +239,7 @@ impl TcpSwitchboardContext {
             task_handle: handle,
             current_router,
             tcp_listeners: HashMap::new(),
+            connections,
         }
     }
     async fn run_event_loop(mut self) -> Self {
@@ -230,8 +255,10 @@ impl TcpSwitchboardContext {
                         TcpSwitchboardEvent::NewAccepted {
                             from_bind,
                             mut tcp_accepted,
+                            admission,
                         } => {
-                            let Some((service, tls)) = self.get_service(&from_bind) else {
+                            let Some((service_name, service, tls)) = self.get_service(&from_bind)
+                            else {
                                 self.task_set.spawn(tcp_accepted.close_directly());
                                 continue;
                             };
@@ -239,7 +266,33 @@ impl TcpSwitchboardContext {
                                 tcp_accepted.replace_tls(tls);
                             };
                             let peer = tcp_accepted.context.peer_addr;
-                            let id = self.task_set.spawn(service.serve(tcp_accepted)).id();
+                            // not a child of the listener token, removing a listener keeps its connections
+                            let (guard, ct) = self.connections.track(TrackedConnection {
+                                bind: from_bind,
+                                peer,
+                                service: service_name,
+                                tls: tcp_accepted.context.tls_acceptor.is_some(),
+                                stats: tcp_accepted.context.stats.clone(),
+                            });
+                            let stats = tcp_accepted.context.stats.clone();
+                            let serve = service.serve(tcp_accepted);
+                            let id = self
+                                .task_set
+                                .spawn(async move {
+                                    let _guard = guard;
+                                    tokio::select! {
+                                        result = serve => result,
+                                        _ = ct.cancelled() => {
+                                            tracing::debug!(%peer, "connection closed by request");
+                                            Ok(())
+                                        }
+                                        reached = admission.timed_out(&stats) => {
+                                            admission.counters.record(from_bind, peer, reached);
+                                            Ok(())
+                                        }
+                                    }
+                                })
+                                .id();
                             tracing::debug!(name: "serve", bind= %from_bind, task_id = %id, peer = %peer);
                         }
                         TcpSwitchboardEvent::UpdateRouter(router) => {
@@ -289,6 +342,8 @@ pub struct TcpListenerTask {
     pub bind: SocketAddr,
     pub task_handle: tokio::task::JoinHandle<TcpListenerServiceQuitReason>,
     pub ct: CancellationToken,
+    pub(crate) limits: tokio::sync::watch::Sender<ListenerLimits>,
+    pub(crate) limit_counters: Arc<LimitCounters>,
 }
 #[derive(Debug)]
 pub enum TcpListenerServiceQuitReason {
@@ -303,7 +358,11 @@ impl TcpListenerTask {
             .await
             .expect("TcpListenerService task shouldn't panic by design")
     }
-    pub(crate) fn spawn(tcp_listener: TcpListener, event_sender: EventSender) -> Self {
+    pub(crate) fn spawn(
+        tcp_listener: TcpListener,
+        limits: ListenerLimits,
+        event_sender: EventSender,
+    ) -> Self {
         let bind = tcp_listener.bind;
         let span = tracing::warn_span!(
             parent: None,
@@ -312,6 +371,9 @@ impl TcpListenerTask {
         );
         let ct = CancellationToken::new();
         let handle_ct = ct.clone();
+        let (limits, limits_receiver) = tokio::sync::watch::channel(limits);
+        let mut limiter = ListenerLimiter::new(limits_receiver);
+        let limit_counters = limiter.counters.clone();
         let listener_task = async move {
             let quit_reason = loop {
                 let accepted = tokio::select! {
@@ -330,10 +392,20 @@ impl TcpListenerTask {
                     }
                 };
                 tracing::debug!(name:"tcp-accept", bind = %bind, peer = %accepted.context.peer_addr, "Accepted new TCP connection");
+                let peer = accepted.context.peer_addr;
+                let admission = match limiter.admit(peer.ip()) {
+                    Ok(admission) => admission,
+                    Err(reached) => {
+                        // dropping the stream closes it
+                        limiter.counters.record(bind, peer, reached);
+                        continue;
+                    }
+                };
                 if event_sender
                     .send(TcpSwitchboardEvent::NewAccepted {
                         from_bind: bind,
                         tcp_accepted: accepted,
+                        admission,
                     })
                     .await
                     .is_err()
@@ -350,6 +422,8 @@ impl TcpListenerTask {
             bind,
             task_handle,
             ct: handle_ct,
+            limits,
+            limit_counters,
         }
     }
 }
@@ -17,6 +17,8 @@ pub enum TlsBuildError {
     RustlsError(#[from] rustls::Error),
     #[error("No default crypto provider")]
     NoDefaultCryptoProvider,
+    #[error("Client certificate verifier error: {0}")]
+    ClientVerifierError(#[from] rustls::server::VerifierBuilderError),
 }
 fn ensure_crypto_provider_installed() {
     static INSTALL: Once = Once::new();
@@ -25,11 +27,24 @@ fn ensure_crypto_provider_installed() {
     });
 }
 pub fn build_tls_config(tls_config: Tls) -> Result<Arc<rustls::ServerConfig>, TlsBuildError> {
+    build_tls_config_with_client_auth(tls_config, None)
+}
+
+/// Build a server config which requires a client certificate signed by `client_roots`, when set.
+pub fn build_tls_config_with_client_auth(
+    tls_config: Tls,
+    client_roots: Option<rustls::RootCertStore>,
+) -> Result<Arc<rustls::ServerConfig>, TlsBuildError> {
     ensure_crypto_provider_installed();
     let resolver = build_resolver(tls_config.resolver)?;
-    let mut config = rustls::ServerConfig::builder()
-        .with_no_client_auth()
-        .with_cert_resolver(resolver);
+    let builder = rustls::ServerConfig::builder();
+    let builder = match client_roots {
+        Some(roots) => builder.with_client_cert_verifier(
+            rustls::server::WebPkiClientVerifier::builder(Arc::new(roots)).build()?,
+        ),
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_cert_resolver(resolver);
     let tls_config_option = tls_config.options.unwrap_or_default();
     config.alpn_protocols = tls_config_option
         .alpn_protocols
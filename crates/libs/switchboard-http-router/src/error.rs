@@ -20,4 +20,8 @@ pub enum BuildError {
     InvalidHeaderValue(#[from] http::header::InvalidHeaderValue),
     #[error("invalid http method: {0}")]
     InvalidHttpMethod(#[from] http::method::InvalidMethod),
+    #[error("invalid client ip or cidr: {0}")]
+    InvalidClientIp(String),
+    #[error("nested negation in rule: !{0}")]
+    NestedNot(String),
 }
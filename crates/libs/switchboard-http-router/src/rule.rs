@@ -1,6 +1,6 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
 
-use http::{HeaderName, HeaderValue, request::Parts};
+use http::{HeaderName, HeaderValue, header::COOKIE, request::Parts};
 
 use crate::utils::{
     query_kv::QueryKvIter,
@@ -67,6 +67,17 @@ impl<T> RuleBucketMatched<T> {
     }
 }
 
+/// Facts about the connection of a request that rules can match on, put into the request
+/// extensions by the server. Client ip, SNI and ALPN conditions never match without it.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionMeta {
+    pub client_ip: Option<IpAddr>,
+    /// Server name the client sent in its TLS hello.
+    pub sni: Option<Arc<str>>,
+    /// Protocol negotiated through TLS ALPN, e.g. `h2`.
+    pub alpn: Option<Arc<str>>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RuleMatch {
     pub method: Option<http::Method>,
@@ -74,17 +85,39 @@ pub struct RuleMatch {
     pub headers: Vec<HeaderMatch>,
     // max: 256
     pub queries: Vec<QueryMatch>,
+    // max: 256
+    pub cookies: Vec<CookieMatch>,
+    // max: 256
+    pub client_ips: Vec<ClientIpMatch>,
+    pub sni: Option<RegexOrExact<Arc<str>>>,
+    pub alpn: Option<RegexOrExact<Arc<str>>>,
+    /// Conditions the request must not match, max: 256
+    pub not: Vec<RuleCondition>,
 }
 
 impl RuleMatch {
     pub fn is_fallback_rule(&self) -> bool {
-        self.method.is_none() && self.headers.is_empty() && self.queries.is_empty()
+        self.method.is_none()
+            && self.headers.is_empty()
+            && self.queries.is_empty()
+            && self.cookies.is_empty()
+            && self.client_ips.is_empty()
+            && self.sni.is_none()
+            && self.alpn.is_none()
+            && self.not.is_empty()
     }
-    pub fn priority(&self) -> u32 {
-        let method_priority: u8 = if self.method.is_some() { 1 } else { 0 };
-        let header_priority: u8 = self.headers.len().min(255) as u8;
-        let query_priority: u8 = self.queries.len().min(255) as u8;
-        ((method_priority as u32) << 16) | ((header_priority as u32) << 8) | (query_priority as u32)
+    /// Rules are ordered by this, then by their order in the config.
+    pub fn priority(&self) -> u64 {
+        rule_priority(RulePriorityCounts {
+            sni: self.sni.is_some(),
+            alpn: self.alpn.is_some(),
+            method: self.method.is_some(),
+            client_ips: self.client_ips.len(),
+            headers: self.headers.len(),
+            cookies: self.cookies.len(),
+            queries: self.queries.len(),
+            not: self.not.len(),
+        })
     }
     pub fn fallback_rule() -> Self {
         Self::default()
@@ -93,6 +126,7 @@ impl RuleMatch {
         let mut method_matched = false;
         let mut header_matches = Vec::with_capacity(self.headers.len());
         let mut query_matches = Vec::with_capacity(self.queries.len());
+        let mut cookie_matches = Vec::with_capacity(self.cookies.len());
         // check method
         if let Some(method) = &self.method {
             if parts.method != *method {
@@ -101,17 +135,43 @@ impl RuleMatch {
                 method_matched = true;
             }
         }
+        // check connection
+        let connection = parts.extensions.get::<ConnectionMeta>();
+        if let Some(sni) = &self.sni {
+            sni.match_str_value(connection?.sni.as_deref()?)?;
+        }
+        if let Some(alpn) = &self.alpn {
+            alpn.match_str_value(connection?.alpn.as_deref()?)?;
+        }
+        if !self.client_ips.is_empty() {
+            let client_ip = connection?.client_ip?;
+            if !self.client_ips.iter().all(|ips| ips.contains(client_ip)) {
+                return None;
+            }
+        }
         // check headers
         for header_match in &self.headers {
             let matched = header_match.match_headers(parts)?;
             header_matches.push(matched);
         }
+        // check cookies
+        for cookie_match in &self.cookies {
+            let matched = cookie_match.match_cookies(parts)?;
+            cookie_matches.push(matched);
+        }
+        // check negated conditions, an unknown connection fact fails them
+        for condition in &self.not {
+            if condition.is_match(parts, connection)? {
+                return None;
+            }
+        }
         // if queries is empty, return early
         if self.queries.is_empty() {
             return Some(RuleMatched {
                 method_matched,
                 header_matches: header_matches.into(),
                 query_matches: query_matches.into(),
+                cookie_matches: cookie_matches.into(),
             });
         }
         // otherwise, we should expect uri has a query
@@ -125,6 +185,71 @@ impl RuleMatch {
             method_matched,
             header_matches: header_matches.into(),
             query_matches: query_matches.into(),
+            cookie_matches: cookie_matches.into(),
+        })
+    }
+}
+
+/// How many conditions of each kind a rule has.
+pub(crate) struct RulePriorityCounts {
+    pub sni: bool,
+    pub alpn: bool,
+    pub method: bool,
+    pub client_ips: usize,
+    pub headers: usize,
+    pub cookies: usize,
+    pub queries: usize,
+    pub not: usize,
+}
+
+/// One byte per kind of condition, connection level conditions weigh the most.
+pub(crate) fn rule_priority(counts: RulePriorityCounts) -> u64 {
+    let count = |n: usize| n.min(255) as u64;
+    ((counts.sni as u64) << 56)
+        | ((counts.alpn as u64) << 48)
+        | ((counts.method as u64) << 40)
+        | (count(counts.client_ips) << 32)
+        | (count(counts.headers) << 24)
+        | (count(counts.cookies) << 16)
+        | (count(counts.queries) << 8)
+        | count(counts.not)
+}
+
+/// A single condition of a rule, used for negated conditions.
+#[derive(Debug, Clone)]
+pub enum RuleCondition {
+    Method(http::Method),
+    Header(HeaderMatch),
+    Query(QueryMatch),
+    Cookie(CookieMatch),
+    ClientIp(ClientIpMatch),
+    Sni(RegexOrExact<Arc<str>>),
+    Alpn(RegexOrExact<Arc<str>>),
+}
+
+impl RuleCondition {
+    /// `None` when the condition is about the connection and the request carries no
+    /// [`ConnectionMeta`].
+    pub fn is_match(&self, parts: &Parts, connection: Option<&ConnectionMeta>) -> Option<bool> {
+        Some(match self {
+            RuleCondition::Method(method) => parts.method == *method,
+            RuleCondition::Header(header_match) => header_match.match_headers(parts).is_some(),
+            RuleCondition::Query(query_match) => parts.uri.query().is_some_and(|query| {
+                let queries = QueryKvIter::new(query).collect::<BTreeMap<&str, Option<&str>>>();
+                query_match.match_query(&queries).is_some()
+            }),
+            RuleCondition::Cookie(cookie_match) => cookie_match.match_cookies(parts).is_some(),
+            RuleCondition::ClientIp(ips) => {
+                connection?.client_ip.is_some_and(|ip| ips.contains(ip))
+            }
+            RuleCondition::Sni(sni) => connection?
+                .sni
+                .as_deref()
+                .is_some_and(|value| sni.match_str_value(value).is_some()),
+            RuleCondition::Alpn(alpn) => connection?
+                .alpn
+                .as_deref()
+                .is_some_and(|value| alpn.match_str_value(value).is_some()),
         })
     }
 }
@@ -134,6 +259,7 @@ pub struct RuleMatched {
     pub method_matched: bool,
     pub header_matches: Arc<[BytesRegexOrExactMatched<HeaderValue>]>,
     pub query_matches: Arc<[RegexOrExactMatched<Arc<str>>]>,
+    pub cookie_matches: Arc<[RegexOrExactMatched<Arc<str>>]>,
 }
 
 #[derive(Debug, Clone)]
@@ -224,3 +350,51 @@ impl QueryMatch {
         self.query_value.match_str_value(value)
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct CookieMatch {
+    pub cookie_name: Arc<str>,
+    pub cookie_value: RegexOrExact<Arc<str>>,
+}
+
+impl CookieMatch {
+    /// this will return matched when first cookie of the name is matched
+    pub fn match_cookies(&self, parts: &Parts) -> Option<RegexOrExactMatched<Arc<str>>> {
+        request_cookies(parts)
+            .filter(|(name, _)| *name == self.cookie_name.as_ref())
+            .find_map(|(_, value)| self.cookie_value.match_str_value(value))
+    }
+}
+
+/// Name and value pairs of all `Cookie` headers, in order.
+pub fn request_cookies(parts: &Parts) -> impl Iterator<Item = (&str, &str)> {
+    parts
+        .headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .map(|(name, value)| {
+            // values may be quoted, see rfc 6265
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            (name, value)
+        })
+}
+
+/// Matches when the client ip is in any of the networks.
+#[derive(Debug, Clone)]
+pub struct ClientIpMatch {
+    pub networks: Vec<ipnet::IpNet>,
+}
+
+impl ClientIpMatch {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // clients of dual stack listeners show up as ipv4-mapped ipv6 addresses
+        let ip = ip.to_canonical();
+        self.networks.iter().any(|network| network.contains(&ip))
+    }
+}
@@ -4,10 +4,17 @@ use std::{collections::BTreeMap, sync::Arc};
 
 use crate::{hostname::HostnameTree, path::PathTree};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
-
+#[derive(
+    Debug,
+    Clone,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    schemars::JsonSchema,
+)]
 pub struct RouterSerde<T> {
-    #[serde(default = "BTreeMap::new")]
+    #[serde(default)]
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     pub hostname: BTreeMap<String, path::PathTreeSerdeMapStyle<T>>,
 }
@@ -19,7 +19,15 @@ pub struct PathTreeSerde<T> {
     pub fallback: Option<T>,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+#[derive(
+    Debug,
+    Clone,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    schemars::JsonSchema,
+)]
 #[serde(transparent)]
 pub struct PathTreeSerdeMapStyle<T> {
     /// 1. match routes: "/get/users/*"
@@ -1,9 +1,10 @@
-use std::{convert::Infallible, str::FromStr, sync::Arc};
+use std::{convert::Infallible, net::IpAddr, str::FromStr, sync::Arc};
 
 use http::HeaderValue;
 
 use crate::rule::{
-    BytesRegexOrExact, HeaderMatch, QueryMatch, RegexOrExact, RuleBucket, RuleMatch,
+    BytesRegexOrExact, ClientIpMatch, CookieMatch, HeaderMatch, QueryMatch, RegexOrExact,
+    RuleBucket, RuleCondition, RuleMatch, RulePriorityCounts, rule_priority,
 };
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
@@ -48,7 +49,15 @@ impl<T> RuleBucketSerde<T> {
     }
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode, serde::Deserialize, serde::Serialize)]
+#[derive(
+    Debug,
+    Clone,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+)]
 #[serde(untagged)]
 /// T should be something string like, otherwise serde would cause error
 pub enum RuleBucketSimplifiedSerde<T> {
@@ -94,11 +103,7 @@ where
             RuleBucketSimplifiedSerde::Rules { rules, target } => {
                 let mut bucket = RuleBucketSerde::new(target);
                 for expr in rules {
-                    let mut rule_match_serde = RuleMatchSerde {
-                        method: None,
-                        headers: Vec::new(),
-                        queries: Vec::new(),
-                    };
+                    let mut rule_match_serde = RuleMatchSerde::default();
                     for e in expr.exprs {
                         match e {
                             RuleMatchExpr::Method(m) => {
@@ -110,6 +115,21 @@ where
                             RuleMatchExpr::Query(q) => {
                                 rule_match_serde.queries.push(q);
                             }
+                            RuleMatchExpr::Cookie(c) => {
+                                rule_match_serde.cookies.push(c);
+                            }
+                            RuleMatchExpr::ClientIp(ips) => {
+                                rule_match_serde.client_ips.push(ips);
+                            }
+                            RuleMatchExpr::Sni(sni) => {
+                                rule_match_serde.sni = Some(sni);
+                            }
+                            RuleMatchExpr::Alpn(alpn) => {
+                                rule_match_serde.alpn = Some(alpn);
+                            }
+                            RuleMatchExpr::Not(e) => {
+                                rule_match_serde.not.push(*e);
+                            }
                         }
                     }
                     bucket.rules.push(rule_match_serde);
@@ -141,6 +161,21 @@ where
             for q in &rule_match_serde.queries {
                 exprs.push(RuleMatchExpr::Query(q.clone()));
             }
+            for c in &rule_match_serde.cookies {
+                exprs.push(RuleMatchExpr::Cookie(c.clone()));
+            }
+            for ips in &rule_match_serde.client_ips {
+                exprs.push(RuleMatchExpr::ClientIp(ips.clone()));
+            }
+            if let Some(sni) = &rule_match_serde.sni {
+                exprs.push(RuleMatchExpr::Sni(sni.clone()));
+            }
+            if let Some(alpn) = &rule_match_serde.alpn {
+                exprs.push(RuleMatchExpr::Alpn(alpn.clone()));
+            }
+            for e in &rule_match_serde.not {
+                exprs.push(RuleMatchExpr::Not(Box::new(e.clone())));
+            }
             rules.push(RuleMatchExprGroup { exprs });
         }
         let target = bucket.target;
@@ -148,7 +183,15 @@ where
     }
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Serialize,
+    serde::Deserialize,
+    schemars::JsonSchema,
+)]
 #[serde(transparent)]
 pub struct RuleMatchExprGroup {
     pub exprs: Vec<RuleMatchExpr>,
@@ -172,6 +215,12 @@ pub enum RuleMatchExpr {
     Method(String),
     Header(HeaderMatchSerde),
     Query(QueryMatchSerde),
+    Cookie(CookieMatchSerde),
+    ClientIp(ClientIpMatchSerde),
+    Sni(RegexOrExactSerde),
+    Alpn(RegexOrExactSerde),
+    /// `!<expr>`, matches when the expression doesn't.
+    Not(Box<RuleMatchExpr>),
 }
 
 impl<'de> serde::Deserialize<'de> for RuleMatchExpr {
@@ -192,6 +241,21 @@ impl serde::Serialize for RuleMatchExpr {
     }
 }
 
+impl schemars::JsonSchema for RuleMatchExpr {
+    fn inline_schema() -> bool {
+        true
+    }
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "RuleMatchExpr".into()
+    }
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "description": "`method=<method>`, `header.<name>=<value>`, `query.<name>=<value>`, `cookie.<name>=<value>`, `client_ip=<ip or cidr>[|<ip or cidr>...]`, `sni=<value>` or `alpn=<value>`, prefixed with `!` to negate. Values prefixed with `re:` are regexes",
+        })
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum RuleMatchExprParseError {
     #[error("expected dot `.` in rule match expression")]
@@ -202,6 +266,8 @@ pub enum RuleMatchExprParseError {
     ExpectEqualSign,
     #[error("invalid exact or regex in rule match expression")]
     InvalidExactOrRegex(#[from] Infallible),
+    #[error("nested negation in rule match expression: {0}")]
+    NestedNot(String),
 }
 
 impl std::fmt::Display for RuleMatchExpr {
@@ -210,12 +276,24 @@ impl std::fmt::Display for RuleMatchExpr {
             RuleMatchExpr::Method(m) => write!(f, "method={}", m),
             RuleMatchExpr::Header(h) => write!(f, "header.{}={}", h.header_name, h.header_value),
             RuleMatchExpr::Query(q) => write!(f, "query.{}={}", q.query_name, q.query_value),
+            RuleMatchExpr::Cookie(c) => write!(f, "cookie.{}={}", c.cookie_name, c.cookie_value),
+            RuleMatchExpr::ClientIp(ips) => write!(f, "client_ip={}", ips),
+            RuleMatchExpr::Sni(sni) => write!(f, "sni={}", sni),
+            RuleMatchExpr::Alpn(alpn) => write!(f, "alpn={}", alpn),
+            RuleMatchExpr::Not(e) => write!(f, "!{}", e),
         }
     }
 }
 impl FromStr for RuleMatchExpr {
     type Err = RuleMatchExprParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(negated) = s.trim_start().strip_prefix('!') {
+            let expr = RuleMatchExpr::from_str(negated)?;
+            if let RuleMatchExpr::Not(_) = expr {
+                return Err(RuleMatchExprParseError::NestedNot(s.to_string()));
+            }
+            return Ok(RuleMatchExpr::Not(Box::new(expr)));
+        }
         let Some((field, cond)) = s.split_once('=') else {
             return Err(RuleMatchExprParseError::ExpectEqualSign);
         };
@@ -223,6 +301,12 @@ impl FromStr for RuleMatchExpr {
         let cond = cond.trim();
         if field.eq_ignore_ascii_case("method") {
             Ok(RuleMatchExpr::Method(cond.to_string()))
+        } else if field.eq_ignore_ascii_case("client_ip") {
+            Ok(RuleMatchExpr::ClientIp(ClientIpMatchSerde::from_str(cond)?))
+        } else if field.eq_ignore_ascii_case("sni") {
+            Ok(RuleMatchExpr::Sni(RegexOrExactSerde::from_str(cond)?))
+        } else if field.eq_ignore_ascii_case("alpn") {
+            Ok(RuleMatchExpr::Alpn(RegexOrExactSerde::from_str(cond)?))
         } else if let Some((kind, key)) = field.split_once('.') {
             let kind = kind.trim().to_lowercase();
             match kind.as_str() {
@@ -240,6 +324,13 @@ impl FromStr for RuleMatchExpr {
                     };
                     Ok(RuleMatchExpr::Query(query_match))
                 }
+                "cookie" => {
+                    let cookie_match = CookieMatchSerde {
+                        cookie_name: key.trim().to_string(),
+                        cookie_value: RegexOrExactSerde::from_str(cond)?,
+                    };
+                    Ok(RuleMatchExpr::Cookie(cookie_match))
+                }
                 _ => Err(RuleMatchExprParseError::InvalidRuleKind(s.to_string())),
             }
         } else {
@@ -261,6 +352,19 @@ pub struct RuleMatchSerde {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     // max: 256
     pub queries: Vec<QueryMatchSerde>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    // max: 256
+    pub cookies: Vec<CookieMatchSerde>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    // max: 256
+    pub client_ips: Vec<ClientIpMatchSerde>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<RegexOrExactSerde>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<RegexOrExactSerde>,
+    /// Conditions the request must not match, max: 256
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub not: Vec<RuleMatchExpr>,
 }
 
 impl TryInto<RuleMatch> for RuleMatchSerde {
@@ -279,20 +383,65 @@ impl TryInto<RuleMatch> for RuleMatchSerde {
         for q in self.queries {
             queries.push(q.try_into()?);
         }
+        let mut cookies = Vec::with_capacity(self.cookies.len());
+        for c in self.cookies {
+            cookies.push(c.try_into()?);
+        }
+        let mut client_ips = Vec::with_capacity(self.client_ips.len());
+        for ips in self.client_ips {
+            client_ips.push(ips.try_into()?);
+        }
+        let sni: Option<RegexOrExact<Arc<str>>> = self.sni.map(TryInto::try_into).transpose()?;
+        let alpn: Option<RegexOrExact<Arc<str>>> = self.alpn.map(TryInto::try_into).transpose()?;
+        let mut not = Vec::with_capacity(self.not.len());
+        for e in self.not {
+            not.push(e.try_into()?);
+        }
         Ok(RuleMatch {
             method,
             headers,
             queries,
+            cookies,
+            client_ips,
+            sni,
+            alpn,
+            not,
+        })
+    }
+}
+
+impl TryInto<RuleCondition> for RuleMatchExpr {
+    type Error = crate::error::BuildError;
+
+    fn try_into(self) -> Result<RuleCondition, Self::Error> {
+        Ok(match self {
+            RuleMatchExpr::Method(m) => RuleCondition::Method(m.parse()?),
+            RuleMatchExpr::Header(h) => RuleCondition::Header(h.try_into()?),
+            RuleMatchExpr::Query(q) => RuleCondition::Query(q.try_into()?),
+            RuleMatchExpr::Cookie(c) => RuleCondition::Cookie(c.try_into()?),
+            RuleMatchExpr::ClientIp(ips) => RuleCondition::ClientIp(ips.try_into()?),
+            RuleMatchExpr::Sni(sni) => RuleCondition::Sni(sni.try_into()?),
+            RuleMatchExpr::Alpn(alpn) => RuleCondition::Alpn(alpn.try_into()?),
+            RuleMatchExpr::Not(e) => {
+                return Err(crate::error::BuildError::NestedNot(e.to_string()));
+            }
         })
     }
 }
 
 impl RuleMatchSerde {
-    pub fn priority(&self) -> u32 {
-        let method_priority: u8 = if self.method.is_some() { 1 } else { 0 };
-        let header_priority: u8 = self.headers.len().min(255) as u8;
-        let query_priority: u8 = self.queries.len().min(255) as u8;
-        ((method_priority as u32) << 16) | ((header_priority as u32) << 8) | (query_priority as u32)
+    /// Same as [`RuleMatch::priority`].
+    pub fn priority(&self) -> u64 {
+        rule_priority(RulePriorityCounts {
+            sni: self.sni.is_some(),
+            alpn: self.alpn.is_some(),
+            method: self.method.is_some(),
+            client_ips: self.client_ips.len(),
+            headers: self.headers.len(),
+            cookies: self.cookies.len(),
+            queries: self.queries.len(),
+            not: self.not.len(),
+        })
     }
     pub fn fallback_rule() -> Self {
         Self::default()
@@ -392,3 +541,71 @@ impl TryInto<QueryMatch> for QueryMatchSerde {
         })
     }
 }
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+
+pub struct CookieMatchSerde {
+    pub cookie_name: String,
+    pub cookie_value: RegexOrExactSerde,
+}
+
+impl TryInto<CookieMatch> for CookieMatchSerde {
+    type Error = crate::error::BuildError;
+
+    fn try_into(self) -> Result<CookieMatch, Self::Error> {
+        Ok(CookieMatch {
+            cookie_name: Arc::from(self.cookie_name.as_str()),
+            cookie_value: self.cookie_value.try_into()?,
+        })
+    }
+}
+
+/// Ips or cidrs, e.g. `10.0.0.0/8|192.168.1.7`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+#[serde(transparent)]
+pub struct ClientIpMatchSerde {
+    pub networks: Vec<String>,
+}
+
+impl FromStr for ClientIpMatchSerde {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(ClientIpMatchSerde {
+            networks: s
+                .split('|')
+                .map(str::trim)
+                .filter(|network| !network.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+}
+
+impl std::fmt::Display for ClientIpMatchSerde {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.networks.join("|"))
+    }
+}
+
+impl TryInto<ClientIpMatch> for ClientIpMatchSerde {
+    type Error = crate::error::BuildError;
+
+    fn try_into(self) -> Result<ClientIpMatch, Self::Error> {
+        if self.networks.is_empty() {
+            return Err(crate::error::BuildError::InvalidClientIp(String::new()));
+        }
+        let networks = self
+            .networks
+            .into_iter()
+            .map(|network| {
+                network
+                    .parse::<ipnet::IpNet>()
+                    .or_else(|_| network.parse::<IpAddr>().map(ipnet::IpNet::from))
+                    .map(|network| network.trunc())
+                    .map_err(|_| crate::error::BuildError::InvalidClientIp(network))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ClientIpMatch { networks })
+    }
+}
@@ -2,13 +2,14 @@
 ///
 /// # Example
 /// ```rust
-/// # use spacegate_kernel::utils::QueryKvIter;
+/// # use switchboard_http_router::utils::query_kv::QueryKvIter;
 /// # fn main() {
-/// let query = "a=1&b=2&c";
+/// let query = "a=1&b=2&c&d=4";
 /// let mut iter = QueryKvIter::new(query);
 /// assert_eq!(iter.next(), Some(("a", Some("1"))));
 /// assert_eq!(iter.next(), Some(("b", Some("2"))));
 /// assert_eq!(iter.next(), Some(("c", None)));
+/// assert_eq!(iter.next(), Some(("d", Some("4"))));
 /// # }
 /// ```
 #[derive(Debug)]
@@ -29,19 +30,45 @@ impl<'a> Iterator for QueryKvIter<'a> {
         if self.inner.is_empty() {
             return None;
         }
-        match self.inner.split_once('&') {
-            Some((k, v)) => {
-                self.inner = v;
-                match k.split_once('=') {
-                    Some((k, v)) => Some((k, Some(v))),
-                    None => Some((k, None)),
-                }
-            }
-            None => {
-                let k = self.inner;
-                self.inner = "";
-                Some((k, None))
+        let pair = match self.inner.split_once('&') {
+            Some((pair, rest)) => {
+                self.inner = rest;
+                pair
             }
+            None => std::mem::take(&mut self.inner),
+        };
+        match pair.split_once('=') {
+            Some((k, v)) => Some((k, Some(v))),
+            None => Some((pair, None)),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_query_kv_iter() {
+        let pairs = QueryKvIter::new("a=1&b&c=&d=4").collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            [
+                ("a", Some("1")),
+                ("b", None),
+                ("c", Some("")),
+                ("d", Some("4")),
+            ]
+        );
+        // the trailing pair keeps its value
+        assert_eq!(
+            QueryKvIter::new("page=2").collect::<Vec<_>>(),
+            [("page", Some("2"))]
+        );
+        assert_eq!(
+            QueryKvIter::new("flag").collect::<Vec<_>>(),
+            [("flag", None)]
+        );
+        assert_eq!(QueryKvIter::new("").next(), None);
+    }
+}
@@ -4,7 +4,11 @@ use http::{Request, header::HOST};
 use switchboard_http_router::{
     Router,
     path::{PathTree, PathTreeMatched},
-    rule::{BytesRegexOrExact, HeaderMatch, QueryMatch, RegexOrExact, RuleBucket, RuleMatch},
+    rule::{
+        BytesRegexOrExact, ConnectionMeta, HeaderMatch, QueryMatch, RegexOrExact, RuleBucket,
+        RuleMatch,
+    },
+    serde::rule::{RuleBucketSimplifiedSerde, RuleMatchExpr},
 };
 
 fn build_parts(
@@ -104,6 +108,7 @@ fn rule_bucket_matches_method_header_and_query() {
             query_name: Arc::from("a"),
             query_value: RegexOrExact::Exact(Arc::from("111")),
         }],
+        ..Default::default()
     };
     bucket.add_rule(rule);
     tree.add_matchit_route("/foo", bucket).unwrap();
@@ -168,3 +173,149 @@ fn host_not_found_error() {
         _ => panic!("unexpected error kind"),
     }
 }
+
+/// A bucket with one rule per group of expressions.
+fn bucket_of(groups: &[&[&str]]) -> RuleBucket<&'static str> {
+    let rules = serde_json::json!({ "rules": groups, "target": "T" });
+    let bucket: RuleBucketSimplifiedSerde<String> =
+        serde_json::from_value(rules).expect("valid rules");
+    let bucket: RuleBucket<String> = bucket.try_into().expect("rules should build");
+    RuleBucket {
+        rules: bucket.rules,
+        target: "T",
+    }
+}
+
+fn with_connection(mut parts: http::request::Parts, meta: ConnectionMeta) -> http::request::Parts {
+    parts.extensions.insert(meta);
+    parts
+}
+
+#[test]
+fn rule_matches_cookie() {
+    let bucket = bucket_of(&[&["cookie.group=re:^beta-"]]);
+    let parts = build_parts(
+        "example.com",
+        "/",
+        http::Method::GET,
+        &[("cookie", "session=abc; group=beta-7")],
+        None,
+    );
+    let matched = bucket.match_request_part(&parts).expect("should match");
+    let rule_matched = matched.matched.expect("rule should be reported");
+    assert_eq!(rule_matched.cookie_matches.len(), 1);
+
+    let parts = build_parts(
+        "example.com",
+        "/",
+        http::Method::GET,
+        &[("cookie", "group=stable")],
+        None,
+    );
+    assert!(bucket.match_request_part(&parts).is_none());
+}
+
+#[test]
+fn rule_matches_client_ip_and_negation() {
+    let bucket = bucket_of(&[&["!client_ip=10.1.0.0/16|192.168.0.1"]]);
+    let office = ConnectionMeta {
+        client_ip: Some("10.1.2.3".parse().unwrap()),
+        ..Default::default()
+    };
+    let parts = build_parts("example.com", "/", http::Method::GET, &[], None);
+    assert!(
+        bucket
+            .match_request_part(&with_connection(parts, office))
+            .is_none()
+    );
+
+    // ipv4-mapped addresses of dual stack listeners are the same client
+    let mapped = ConnectionMeta {
+        client_ip: Some("::ffff:192.168.0.1".parse().unwrap()),
+        ..Default::default()
+    };
+    let parts = build_parts("example.com", "/", http::Method::GET, &[], None);
+    assert!(
+        bucket
+            .match_request_part(&with_connection(parts, mapped))
+            .is_none()
+    );
+
+    let outside = ConnectionMeta {
+        client_ip: Some("203.0.113.9".parse().unwrap()),
+        ..Default::default()
+    };
+    let parts = build_parts("example.com", "/", http::Method::GET, &[], None);
+    assert!(
+        bucket
+            .match_request_part(&with_connection(parts, outside))
+            .is_some()
+    );
+
+    // without connection facts a negated client ip can't be proven, so it fails closed
+    let parts = build_parts("example.com", "/", http::Method::GET, &[], None);
+    assert!(bucket.match_request_part(&parts).is_none());
+}
+
+#[test]
+fn rule_matches_sni_and_alpn() {
+    let bucket = bucket_of(&[&["sni=re:\\.example\\.com$", "alpn=h2"]]);
+    let meta = ConnectionMeta {
+        sni: Some(Arc::from("api.example.com")),
+        alpn: Some(Arc::from("h2")),
+        ..Default::default()
+    };
+    let parts = build_parts("api.example.com", "/", http::Method::GET, &[], None);
+    assert!(
+        bucket
+            .match_request_part(&with_connection(parts, meta))
+            .is_some()
+    );
+
+    let meta = ConnectionMeta {
+        sni: Some(Arc::from("api.example.com")),
+        alpn: Some(Arc::from("http/1.1")),
+        ..Default::default()
+    };
+    let parts = build_parts("api.example.com", "/", http::Method::GET, &[], None);
+    assert!(
+        bucket
+            .match_request_part(&with_connection(parts, meta))
+            .is_none()
+    );
+}
+
+#[test]
+fn rule_match_expr_round_trips() {
+    for expr in [
+        "cookie.session=re:^beta",
+        "client_ip=10.0.0.0/8|::1",
+        "sni=example.com",
+        "alpn=h2",
+        "!header.x-internal=1",
+    ] {
+        let parsed: RuleMatchExpr = expr.parse().expect("valid expression");
+        assert_eq!(parsed.to_string(), expr);
+    }
+    assert!("!!method=GET".parse::<RuleMatchExpr>().is_err());
+}
+
+#[test]
+fn rule_priority_is_deterministic() {
+    let bucket = bucket_of(&[
+        &["header.x-a=1"],
+        &["method=GET"],
+        &["sni=example.com"],
+        &["query.q=1"],
+    ]);
+    let priorities = bucket
+        .rules
+        .iter()
+        .map(RuleMatch::priority)
+        .collect::<Vec<_>>();
+    let mut sorted = priorities.clone();
+    sorted.sort();
+    assert_eq!(priorities, sorted);
+    assert!(bucket.rules[3].sni.is_some());
+    assert!(bucket.rules[2].method.is_some());
+}
@@ -0,0 +1,261 @@
+//! A JWKS kept in memory and reloaded when a token names a `kid` it doesn't know.
+
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty, Limited};
+use jsonwebtoken::jwk::JwkSet;
+use tokio::sync::{Mutex, RwLock};
+
+/// An unknown `kid` triggers a reload, at most this often. A failed load is retried at the
+/// same pace.
+pub const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+pub const MAX_JWKS_BYTES: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JwksSource {
+    File(PathBuf),
+    Url(String),
+}
+
+impl std::fmt::Display for JwksSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JwksSource::File(path) => write!(f, "{}", path.display()),
+            JwksSource::Url(url) => f.write_str(url),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwksError {
+    #[error("Failed to read JWKS file {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to fetch JWKS from {url}: {source}")]
+    Fetch {
+        url: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Invalid JWKS from {source_name}: {error}")]
+    Invalid {
+        source_name: String,
+        #[source]
+        error: serde_json::Error,
+    },
+    #[error("JWKS from {source_name} failed to load recently: {message}")]
+    RecentlyFailed {
+        source_name: String,
+        message: String,
+    },
+}
+
+#[derive(Default)]
+struct CacheState {
+    set: Option<Arc<JwkSet>>,
+    /// The last load, whether it succeeded or not.
+    attempted_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+pub struct JwksCache {
+    source: JwksSource,
+    min_refresh_interval: Duration,
+    state: RwLock<CacheState>,
+    /// Held while loading, so concurrent callers wait for one load instead of starting their own.
+    loading: Mutex<()>,
+}
+
+impl JwksCache {
+    pub fn new(source: JwksSource) -> Self {
+        Self {
+            source,
+            min_refresh_interval: MIN_REFRESH_INTERVAL,
+            state: RwLock::new(CacheState::default()),
+            loading: Mutex::new(()),
+        }
+    }
+
+    pub fn source(&self) -> &JwksSource {
+        &self.source
+    }
+
+    /// The cached JWKS, reloaded when it doesn't contain `kid` and wasn't loaded recently.
+    ///
+    /// A failed reload keeps the cached set, the error is only returned when there is none.
+    pub async fn get(&self, kid: Option<&str>) -> Result<Arc<JwkSet>, JwksError> {
+        if let Some(cached) = self.cached(&*self.state.read().await, kid) {
+            return cached;
+        }
+        let _loading = self.loading.lock().await;
+        // another caller may have loaded while this one waited
+        if let Some(cached) = self.cached(&*self.state.read().await, kid) {
+            return cached;
+        }
+        let loaded = self.load().await;
+        let mut state = self.state.write().await;
+        state.attempted_at = Some(Instant::now());
+        match loaded {
+            Ok(set) => {
+                let set = Arc::new(set);
+                state.set = Some(set.clone());
+                state.last_error = None;
+                Ok(set)
+            }
+            Err(e) => {
+                state.last_error = Some(e.to_string());
+                match &state.set {
+                    Some(set) => {
+                        tracing::warn!(source = %self.source, error = %e, "failed to reload jwks, keeping the cached one");
+                        Ok(set.clone())
+                    }
+                    None => Err(e),
+                }
+            }
+        }
+    }
+
+    /// The answer without loading, `None` when a load is due.
+    fn cached(
+        &self,
+        state: &CacheState,
+        kid: Option<&str>,
+    ) -> Option<Result<Arc<JwkSet>, JwksError>> {
+        let recent = state
+            .attempted_at
+            .is_some_and(|at| at.elapsed() < self.min_refresh_interval);
+        match &state.set {
+            Some(set) => {
+                let has_key = kid.is_none_or(|kid| set.find(kid).is_some());
+                (has_key || recent).then(|| Ok(set.clone()))
+            }
+            None => recent.then(|| {
+                Err(JwksError::RecentlyFailed {
+                    source_name: self.source.to_string(),
+                    message: state.last_error.clone().unwrap_or_default(),
+                })
+            }),
+        }
+    }
+
+    async fn load(&self) -> Result<JwkSet, JwksError> {
+        let bytes = match &self.source {
+            JwksSource::File(path) => {
+                tokio::fs::read(path)
+                    .await
+                    .map(Bytes::from)
+                    .map_err(|source| JwksError::Read {
+                        path: path.clone(),
+                        source,
+                    })?
+            }
+            JwksSource::Url(url) => tokio::time::timeout(FETCH_TIMEOUT, fetch(url))
+                .await
+                .unwrap_or_else(|_| Err("timed out".into()))
+                .map_err(|source| JwksError::Fetch {
+                    url: url.clone(),
+                    source,
+                })?,
+        };
+        serde_json::from_slice(&bytes).map_err(|error| JwksError::Invalid {
+            source_name: self.source.to_string(),
+            error,
+        })
+    }
+}
+
+async fn fetch(url: &str) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_provider_and_native_roots(rustls::crypto::aws_lc_rs::default_provider())?
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+        .build::<_, Empty<Bytes>>(connector);
+    let response = client.get(url.parse()?).await?;
+    if !response.status().is_success() {
+        return Err(format!("status {}", response.status()).into());
+    }
+    Ok(Limited::new(response.into_body(), MAX_JWKS_BYTES)
+        .collect()
+        .await?
+        .to_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn jwks(kid: &str) -> String {
+        serde_json::json!({
+            "keys": [{
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "kid": kid,
+                "x": "w7aRtBvUNzMzwebyMnoGsRLAjcSzNcZ3GSLKyi_1I7o",
+            }],
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_reload_on_unknown_kid() {
+        let path =
+            std::env::temp_dir().join(format!("switchboard-jwks-{}.json", std::process::id()));
+        tokio::fs::write(&path, jwks("a")).await.unwrap();
+        let mut cache = JwksCache::new(JwksSource::File(path.clone()));
+        assert!(cache.get(Some("a")).await.unwrap().find("a").is_some());
+
+        // rotated keys are only picked up once the last load is old enough
+        tokio::fs::write(&path, jwks("b")).await.unwrap();
+        assert!(cache.get(Some("b")).await.unwrap().find("b").is_none());
+        cache.min_refresh_interval = Duration::ZERO;
+        assert!(cache.get(Some("b")).await.unwrap().find("b").is_some());
+
+        // a failed reload keeps serving the cached keys
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(cache.get(Some("c")).await.unwrap().find("b").is_some());
+        let mut empty = JwksCache::new(JwksSource::File(path.clone()));
+        assert!(matches!(empty.get(None).await, Err(JwksError::Read { .. })));
+
+        // a failed load isn't retried by every caller
+        tokio::fs::write(&path, jwks("a")).await.unwrap();
+        assert!(matches!(
+            empty.get(None).await,
+            Err(JwksError::RecentlyFailed { .. })
+        ));
+        empty.min_refresh_interval = Duration::ZERO;
+        assert!(empty.get(Some("a")).await.unwrap().find("a").is_some());
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_body_limit() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/jwks.json", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 1024]).await.unwrap();
+            let body = vec![b' '; MAX_JWKS_BYTES + 1];
+            let head = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", body.len());
+            stream.write_all(head.as_bytes()).await.unwrap();
+            let _ = stream.write_all(&body).await;
+        });
+        let cache = JwksCache::new(JwksSource::Url(url));
+        assert!(matches!(
+            cache.get(None).await,
+            Err(JwksError::Fetch { .. })
+        ));
+    }
+}
@@ -0,0 +1,355 @@
+//! Pre-shared key bearer tokens for the controller to kernel gRPC channel.
+//!
+//! A token is `psk1.<key id>.<unix seconds>.<nonce>.<base64url hmac-sha256(key, "<key id>.<unix
+//! seconds>.<nonce>")>`, signed per request with a random nonce. The kernel accepts any of its
+//! configured keys, so a key can be rotated by adding the new key to the kernels, switching the
+//! controller to it, then removing the old one. Each token is accepted once, a captured token
+//! can't be replayed while its timestamp is still within the allowed clock skew.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const AUTHORIZATION_HEADER: &str = "authorization";
+const BEARER_PREFIX: &str = "Bearer ";
+const TOKEN_SCHEME: &str = "psk1";
+pub const DEFAULT_MAX_CLOCK_SKEW_SECS: u64 = 300;
+
+#[derive(Clone)]
+pub struct PskKey {
+    pub id: String,
+    pub key: Vec<u8>,
+}
+
+impl std::fmt::Debug for PskKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PskKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PskTokenError {
+    #[error("missing psk bearer token")]
+    Missing,
+    #[error("malformed psk token")]
+    Malformed,
+    #[error("unknown psk key id {0}")]
+    UnknownKey(String),
+    #[error("psk token timestamp is outside the allowed clock skew")]
+    Expired,
+    #[error("psk token signature mismatch")]
+    BadSignature,
+    #[error("psk token was already used")]
+    Replayed,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn mac(key: &[u8], message: &str) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256>>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac
+}
+
+fn nonce() -> String {
+    URL_SAFE_NO_PAD.encode(rand::random::<[u8; 16]>())
+}
+
+impl PskKey {
+    pub fn sign(&self, timestamp: u64) -> String {
+        self.sign_with_nonce(timestamp, &nonce())
+    }
+    fn sign_with_nonce(&self, timestamp: u64, nonce: &str) -> String {
+        let message = format!("{}.{}.{}", self.id, timestamp, nonce);
+        let signature = URL_SAFE_NO_PAD.encode(mac(&self.key, &message).finalize().into_bytes());
+        format!("{TOKEN_SCHEME}.{message}.{signature}")
+    }
+}
+
+/// Check a token against every accepted key, returning the key it was signed with.
+///
+/// # Errors
+/// Returns an error when the token is malformed, signed by an unknown key, signed with a wrong
+/// key, or its timestamp is more than `max_skew_secs` away from `now`.
+pub fn verify_psk_token<'a>(
+    token: &str,
+    keys: &'a [PskKey],
+    now: u64,
+    max_skew_secs: u64,
+) -> Result<&'a PskKey, PskTokenError> {
+    let mut parts = token.splitn(5, '.');
+    let (Some(TOKEN_SCHEME), Some(id), Some(timestamp), Some(nonce), Some(signature)) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return Err(PskTokenError::Malformed);
+    };
+    let timestamp = timestamp
+        .parse::<u64>()
+        .map_err(|_| PskTokenError::Malformed)?;
+    if nonce.is_empty() {
+        return Err(PskTokenError::Malformed);
+    }
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| PskTokenError::Malformed)?;
+    let key = keys
+        .iter()
+        .find(|key| key.id == id)
+        .ok_or_else(|| PskTokenError::UnknownKey(id.to_string()))?;
+    if now.abs_diff(timestamp) > max_skew_secs {
+        return Err(PskTokenError::Expired);
+    }
+    mac(&key.key, &format!("{id}.{timestamp}.{nonce}"))
+        .verify_slice(&signature)
+        .map_err(|_| PskTokenError::BadSignature)?;
+    Ok(key)
+}
+
+/// Adds a freshly signed psk token to every request, does nothing without a key.
+#[derive(Debug, Clone, Default)]
+pub struct PskClientInterceptor {
+    pub key: Option<PskKey>,
+}
+
+impl tonic::service::Interceptor for PskClientInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        if let Some(key) = &self.key {
+            let value = format!("{BEARER_PREFIX}{}", key.sign(unix_now()))
+                .parse()
+                .map_err(|_| tonic::Status::internal("invalid psk token header"))?;
+            request.metadata_mut().insert(AUTHORIZATION_HEADER, value);
+        }
+        Ok(request)
+    }
+}
+
+/// Tokens accepted within the last clock skew window.
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    /// Token to the unix second after which it can't pass the timestamp check anymore.
+    seen: Mutex<HashMap<String, u64>>,
+}
+
+impl ReplayGuard {
+    /// Accept a verified token once.
+    ///
+    /// # Errors
+    /// Returns [`PskTokenError::Replayed`] when the token was accepted before.
+    pub fn check(&self, token: &str, now: u64, max_skew_secs: u64) -> Result<(), PskTokenError> {
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        seen.retain(|_, expires| *expires >= now);
+        if seen.contains_key(token) {
+            return Err(PskTokenError::Replayed);
+        }
+        // the token's timestamp is at most `max_skew_secs` ahead of now
+        seen.insert(
+            token.to_string(),
+            now.saturating_add(max_skew_secs.saturating_mul(2)),
+        );
+        Ok(())
+    }
+}
+
+/// Id of the key a request was signed with, added to the request extensions by
+/// [`PskServerInterceptor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PskKeyId(pub String);
+
+/// Rejects requests without a valid psk token, accepts everything when no key is configured.
+#[derive(Debug, Clone)]
+pub struct PskServerInterceptor {
+    pub keys: Arc<[PskKey]>,
+    pub max_skew_secs: u64,
+    replay: Arc<ReplayGuard>,
+}
+
+impl PskServerInterceptor {
+    pub fn new(keys: Arc<[PskKey]>, max_skew_secs: u64) -> Self {
+        Self {
+            keys,
+            max_skew_secs,
+            replay: Arc::default(),
+        }
+    }
+}
+
+impl tonic::service::Interceptor for PskServerInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        if self.keys.is_empty() {
+            return Ok(request);
+        }
+        let token = request
+            .metadata()
+            .get(AUTHORIZATION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(BEARER_PREFIX))
+            .ok_or(PskTokenError::Missing);
+        let now = unix_now();
+        let key = token
+            .and_then(|token| {
+                let key = verify_psk_token(token, &self.keys, now, self.max_skew_secs)?;
+                self.replay.check(token, now, self.max_skew_secs)?;
+                Ok(key)
+            })
+            .map_err(|e| tonic::Status::unauthenticated(e.to_string()))?;
+        let key_id = PskKeyId(key.id.clone());
+        request.extensions_mut().insert(key_id);
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn keys() -> Vec<PskKey> {
+        vec![
+            PskKey {
+                id: "old".to_string(),
+                key: b"old secret".to_vec(),
+            },
+            PskKey {
+                id: "new".to_string(),
+                key: b"new secret".to_vec(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_verify_psk_token() {
+        let keys = keys();
+        for key in &keys {
+            let signed_with = verify_psk_token(&key.sign(NOW), &keys, NOW, 300).unwrap();
+            assert_eq!(signed_with.id, key.id);
+        }
+        let token = keys[1].sign_with_nonce(NOW, "nonce");
+        assert!(verify_psk_token(&token, &keys, NOW + 300, 300).is_ok());
+        assert!(verify_psk_token(&token, &keys, NOW - 300, 300).is_ok());
+        assert_ne!(keys[1].sign(NOW), keys[1].sign(NOW));
+    }
+
+    #[test]
+    fn test_verify_psk_token_bad_signature() {
+        let keys = keys();
+        let token = keys[0].sign_with_nonce(NOW, "nonce");
+        // signed with another key under the same id
+        let forged = PskKey {
+            id: "old".to_string(),
+            key: b"guess".to_vec(),
+        }
+        .sign_with_nonce(NOW, "nonce");
+        // the signature doesn't cover another nonce
+        let swapped = token.replace(".nonce.", ".other.");
+        for token in [forged, swapped] {
+            assert!(matches!(
+                verify_psk_token(&token, &keys, NOW, 300),
+                Err(PskTokenError::BadSignature)
+            ));
+        }
+        assert!(matches!(
+            verify_psk_token(&token, &keys[1..], NOW, 300),
+            Err(PskTokenError::UnknownKey(id)) if id == "old"
+        ));
+    }
+
+    #[test]
+    fn test_verify_psk_token_clock_skew() {
+        let keys = keys();
+        let token = keys[0].sign(NOW);
+        for now in [NOW + 301, NOW - 301, 0, u64::MAX] {
+            assert!(matches!(
+                verify_psk_token(&token, &keys, now, 300),
+                Err(PskTokenError::Expired)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_verify_psk_token_malformed() {
+        let keys = keys();
+        let signature = keys[0].sign(NOW).rsplit_once('.').unwrap().1.to_string();
+        for token in [
+            String::new(),
+            "psk1".to_string(),
+            format!("psk1.old.{NOW}.{signature}"),
+            format!("psk1.old.{NOW}..{signature}"),
+            format!("psk2.old.{NOW}.nonce.{signature}"),
+            format!("psk1.old.yesterday.nonce.{signature}"),
+            format!("psk1.old.{NOW}.nonce.not+base64"),
+        ] {
+            assert!(
+                matches!(
+                    verify_psk_token(&token, &keys, NOW, 300),
+                    Err(PskTokenError::Malformed)
+                ),
+                "{token}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_replay_guard() {
+        let guard = ReplayGuard::default();
+        assert!(guard.check("a", NOW, 300).is_ok());
+        assert!(guard.check("b", NOW, 300).is_ok());
+        assert!(matches!(
+            guard.check("a", NOW + 600, 300),
+            Err(PskTokenError::Replayed)
+        ));
+        // forgotten once the token can't pass the timestamp check anymore
+        assert!(guard.check("c", NOW + 601, 300).is_ok());
+        assert!(guard.check("a", NOW + 601, 300).is_ok());
+    }
+
+    #[test]
+    fn test_server_interceptor_rejects_replay() {
+        use tonic::service::Interceptor;
+
+        let keys: Arc<[PskKey]> = keys().into();
+        let mut interceptor = PskServerInterceptor::new(keys.clone(), 300);
+        let mut client = PskClientInterceptor {
+            key: keys.first().cloned(),
+        };
+        let request = client.call(tonic::Request::new(())).unwrap();
+        let metadata = request.metadata().clone();
+        let accepted = interceptor.call(request).unwrap();
+        assert_eq!(
+            accepted.extensions().get::<PskKeyId>(),
+            Some(&PskKeyId("old".to_string()))
+        );
+        let replayed = tonic::Request::from_parts(metadata, Default::default(), ());
+        let status = interceptor.call(replayed).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert!(interceptor.call(tonic::Request::new(())).is_err());
+        assert!(
+            PskServerInterceptor::new(Arc::new([]), 300)
+                .call(tonic::Request::new(()))
+                .is_ok()
+        );
+    }
+}
@@ -4,3 +4,10 @@ pub mod kernel {
 pub use tonic_health;
 mod type_convert;
 pub use type_convert::TryFromProtoKernelStateError;
+pub mod auth;
+#[cfg(feature = "client")]
+pub mod tls;
+#[cfg(feature = "tunnel")]
+pub mod tunnel;
+#[cfg(all(feature = "client", unix))]
+pub mod uds;
@@ -0,0 +1,166 @@
+//! Connect to a kernel gRPC service over TLS, optionally presenting a client certificate.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use hyper_util::rt::TokioIo;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject};
+use tonic::transport::{Channel, Endpoint, Uri};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientConfigError {
+    #[error("Failed to read {path:?}: {source}")]
+    ReadPem {
+        path: PathBuf,
+        #[source]
+        source: rustls::pki_types::pem::Error,
+    },
+    #[error("Tls client cert and key must be configured together")]
+    IncompleteClientCert,
+    #[error("Tls config error: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// Build a client config trusting certificates signed by `ca`, presenting the `cert` chain and
+/// `key` when both are set.
+///
+/// Files are read on every call, so rotated certificates are picked up on reconnect.
+pub fn build_client_config(
+    ca: &Path,
+    cert: Option<&Path>,
+    key: Option<&Path>,
+) -> Result<Arc<rustls::ClientConfig>, ClientConfigError> {
+    let read_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| ClientConfigError::ReadPem { path, source }
+    };
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca).map_err(read_error(ca))? {
+        roots.add(cert.map_err(read_error(ca))?)?;
+    }
+    // both rustls providers are compiled in through dependencies, so pick one explicitly
+    let builder = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_root_certificates(roots);
+    let mut config = match (cert, key) {
+        (Some(cert_path), Some(key_path)) => {
+            let certs = CertificateDer::pem_file_iter(cert_path)
+                .map_err(read_error(cert_path))?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(read_error(cert_path))?;
+            let key = PrivateKeyDer::from_pem_file(key_path).map_err(read_error(key_path))?;
+            builder.with_client_auth_cert(certs, key)?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err(ClientConfigError::IncompleteClientCert),
+    };
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(Arc::new(config))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectTlsError {
+    #[error("invalid kernel uri: {0}")]
+    InvalidUri(#[from] tonic::codegen::http::uri::InvalidUri),
+    #[error("kernel uri has no host: {0}")]
+    MissingHost(String),
+    #[error("invalid tls server name: {0}")]
+    InvalidServerName(#[from] rustls::pki_types::InvalidDnsNameError),
+    #[error("gRPC connection error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+}
+
+/// Whether a peer's end entity certificate is issued for `name`, a dns name or ip address.
+///
+/// Only the subject alternative names are checked, the chain is verified by the tls handshake.
+pub fn cert_is_issued_for(cert: &CertificateDer<'_>, name: &str) -> bool {
+    let (Ok(cert), Ok(name)) = (
+        rustls::server::ParsedCertificate::try_from(cert),
+        ServerName::try_from(name),
+    ) else {
+        return false;
+    };
+    rustls::client::verify_server_name(&cert, &name).is_ok()
+}
+
+/// Connect to `uri` (any scheme, e.g. `grpc://kernel:9000`) and speak gRPC over TLS.
+///
+/// The certificate is checked against `server_name`, or the uri host when it is `None`.
+pub async fn connect_tls(
+    uri: &str,
+    config: Arc<rustls::ClientConfig>,
+    server_name: Option<&str>,
+) -> Result<Channel, ConnectTlsError> {
+    let parsed: Uri = uri.parse()?;
+    let authority = parsed
+        .authority()
+        .ok_or_else(|| ConnectTlsError::MissingHost(uri.to_string()))?
+        .clone();
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = authority.port_u16().unwrap_or(443);
+    let server_name = rustls::pki_types::ServerName::try_from(
+        server_name
+            .map(str::to_string)
+            .unwrap_or_else(|| host.clone()),
+    )?;
+    let connector = tokio_rustls::TlsConnector::from(config);
+    // tls is done by the connector, tonic itself sees plain http/2
+    let channel = Endpoint::from_shared(format!("http://{authority}"))?
+        .connect_with_connector(tower::service_fn(move |_| {
+            let connector = connector.clone();
+            let server_name = server_name.clone();
+            let host = host.clone();
+            async move {
+                let stream = tokio::net::TcpStream::connect((host.as_str(), port)).await?;
+                let stream = connector.connect(server_name, stream).await?;
+                Ok::<_, std::io::Error>(TokioIo::new(stream))
+            }
+        }))
+        .await?;
+    Ok(channel)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A self signed certificate with the common name `kernel` for the subject alternative
+    /// names `sans`.
+    fn self_signed(sans: &[&str]) -> CertificateDer<'static> {
+        let mut params = rcgen::CertificateParams::new(
+            sans.iter().map(|san| san.to_string()).collect::<Vec<_>>(),
+        )
+        .unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "kernel");
+        let key = rcgen::KeyPair::generate().unwrap();
+        params.self_signed(&key).unwrap().der().clone()
+    }
+
+    #[test]
+    fn test_cert_is_issued_for() {
+        let cert = self_signed(&["edge-1", "*.kernels.test"]);
+        assert!(cert_is_issued_for(&cert, "edge-1"));
+        assert!(cert_is_issued_for(&cert, "eu.kernels.test"));
+        assert!(!cert_is_issued_for(&cert, "edge-2"));
+        // the common name is not a subject alternative name
+        assert!(!cert_is_issued_for(&cert, "kernel"));
+        assert!(!cert_is_issued_for(&cert, "not a name"));
+        let cert = self_signed(&["10.0.0.1"]);
+        assert!(cert_is_issued_for(&cert, "10.0.0.1"));
+        assert!(!cert_is_issued_for(&cert, "10.0.0.2"));
+        assert!(!cert_is_issued_for(
+            &CertificateDer::from(vec![0; 8]),
+            "edge-1"
+        ));
+    }
+}
@@ -0,0 +1,67 @@
+//! Byte stream over a `ControllerService.Tunnel` stream, for reverse connected kernels.
+//!
+//! Both ends see a plain [`DuplexStream`]: the kernel serves `KernelService` on it and the
+//! controller dials it like any other kernel connection.
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream, WriteHalf},
+    task::JoinHandle,
+};
+use tokio_stream::{Stream, StreamExt, wrappers::ReceiverStream};
+
+use crate::kernel::TunnelFrame;
+
+/// Metadata carrying the id of the kernel opening a tunnel.
+pub const KERNEL_ID_METADATA: &str = "x-switchboard-kernel-id";
+const TUNNEL_BUFFER_SIZE: usize = 64 * 1024;
+const TUNNEL_FRAME_CHANNEL_SIZE: usize = 32;
+
+/// Writes the frames received from the peer into the local end of the tunnel.
+pub struct TunnelInbound {
+    writer: WriteHalf<DuplexStream>,
+}
+
+impl TunnelInbound {
+    /// Pump `frames` into the tunnel, the returned task finishes when the peer closes its side
+    /// or the stream fails.
+    pub fn forward<S>(mut self, mut frames: S) -> JoinHandle<()>
+    where
+        S: Stream<Item = Result<TunnelFrame, tonic::Status>> + Send + Unpin + 'static,
+    {
+        tokio::spawn(async move {
+            // a failed stream closes the tunnel like a finished one, both ends see eof
+            while let Some(Ok(frame)) = frames.next().await {
+                if self.writer.write_all(&frame.data).await.is_err() {
+                    break;
+                }
+            }
+            let _ = self.writer.shutdown().await;
+        })
+    }
+}
+
+/// Create a tunnel: the local io, the frames to send to the peer, and the sink for frames the
+/// peer sends.
+///
+/// Dropping the io closes the outbound frame stream.
+pub fn tunnel() -> (DuplexStream, ReceiverStream<TunnelFrame>, TunnelInbound) {
+    let (io, bridged) = tokio::io::duplex(TUNNEL_BUFFER_SIZE);
+    let (mut reader, writer) = tokio::io::split(bridged);
+    let (sender, receiver) = tokio::sync::mpsc::channel(TUNNEL_FRAME_CHANNEL_SIZE);
+    tokio::spawn(async move {
+        let mut buffer = vec![0; TUNNEL_BUFFER_SIZE];
+        loop {
+            let size = match reader.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(size) => size,
+            };
+            let frame = TunnelFrame {
+                data: buffer[..size].to_vec(),
+            };
+            if sender.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+    (io, ReceiverStream::new(receiver), TunnelInbound { writer })
+}
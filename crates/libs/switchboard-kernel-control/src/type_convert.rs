@@ -14,6 +14,7 @@ impl From<super::kernel::KernelInfo> for model::kernel::KernelInfo {
                 .meta
                 .map(model::kernel::KernelMeta::from)
                 .unwrap_or_default(),
+            labels: info.labels.into_iter().collect(),
         }
     }
 }
@@ -25,6 +26,7 @@ impl From<model::kernel::KernelInfo> for super::kernel::KernelInfo {
             id: val.id,
             description: val.description,
             meta: Some(val.meta.into()),
+            labels: val.labels.into_iter().collect(),
         }
     }
 }
@@ -85,6 +87,42 @@ impl From<model::error::ErrorStackFrame> for super::kernel::ErrorFrame {
     }
 }
 
+impl From<super::kernel::ValidationIssue> for model::validation::ValidationIssue {
+    fn from(value: super::kernel::ValidationIssue) -> Self {
+        model::validation::ValidationIssue {
+            path: value.path,
+            error: value.error.map(Into::into).unwrap_or_default(),
+        }
+    }
+}
+
+impl From<model::validation::ValidationIssue> for super::kernel::ValidationIssue {
+    fn from(val: model::validation::ValidationIssue) -> Self {
+        super::kernel::ValidationIssue {
+            path: val.path,
+            error: Some(val.error.into()),
+        }
+    }
+}
+
+impl From<super::kernel::ValidationReport> for model::validation::ValidationReport {
+    fn from(value: super::kernel::ValidationReport) -> Self {
+        model::validation::ValidationReport {
+            errors: value.errors.into_iter().map(Into::into).collect(),
+            warnings: value.warnings.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<model::validation::ValidationReport> for super::kernel::ValidationReport {
+    fn from(val: model::validation::ValidationReport) -> Self {
+        super::kernel::ValidationReport {
+            errors: val.errors.into_iter().map(Into::into).collect(),
+            warnings: val.warnings.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum TryFromProtoKernelStateError {
     #[error("Failed to parse datetime: {0}")]
@@ -196,3 +234,125 @@ impl From<model::kernel::KernelState> for super::kernel::KernelState {
         }
     }
 }
+
+impl From<super::kernel::PluginKind> for model::kernel::PluginKind {
+    fn from(value: super::kernel::PluginKind) -> Self {
+        match value {
+            super::kernel::PluginKind::Native => model::kernel::PluginKind::Native,
+            super::kernel::PluginKind::Wasm => model::kernel::PluginKind::Wasm,
+        }
+    }
+}
+
+impl From<model::kernel::PluginKind> for super::kernel::PluginKind {
+    fn from(value: model::kernel::PluginKind) -> Self {
+        match value {
+            model::kernel::PluginKind::Native => super::kernel::PluginKind::Native,
+            model::kernel::PluginKind::Wasm => super::kernel::PluginKind::Wasm,
+        }
+    }
+}
+
+impl From<super::kernel::Plugin> for model::kernel::PluginInfo {
+    fn from(value: super::kernel::Plugin) -> Self {
+        model::kernel::PluginInfo {
+            name: value.name,
+            path: value.path,
+            sha256: value.sha256,
+            generation: value.generation,
+            classes: value.classes,
+        }
+    }
+}
+
+impl From<model::kernel::PluginInfo> for super::kernel::Plugin {
+    fn from(value: model::kernel::PluginInfo) -> Self {
+        super::kernel::Plugin {
+            name: value.name,
+            path: value.path,
+            sha256: value.sha256,
+            generation: value.generation,
+            classes: value.classes,
+        }
+    }
+}
+
+impl From<super::kernel::PluginList> for model::kernel::PluginList {
+    fn from(value: super::kernel::PluginList) -> Self {
+        model::kernel::PluginList {
+            generation: value.generation,
+            plugins: value.plugins.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<model::kernel::PluginList> for super::kernel::PluginList {
+    fn from(value: model::kernel::PluginList) -> Self {
+        super::kernel::PluginList {
+            generation: value.generation,
+            plugins: value.plugins.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<super::kernel::InstanceType> for model::services::http::InstanceType {
+    fn from(value: super::kernel::InstanceType) -> Self {
+        match value {
+            super::kernel::InstanceType::Node => model::services::http::InstanceType::Node,
+            super::kernel::InstanceType::Filter => model::services::http::InstanceType::Filter,
+        }
+    }
+}
+
+impl From<model::services::http::InstanceType> for super::kernel::InstanceType {
+    fn from(value: model::services::http::InstanceType) -> Self {
+        match value {
+            model::services::http::InstanceType::Node => super::kernel::InstanceType::Node,
+            model::services::http::InstanceType::Filter => super::kernel::InstanceType::Filter,
+        }
+    }
+}
+
+impl From<super::kernel::Class> for model::kernel::ClassInfo {
+    fn from(value: super::kernel::Class) -> Self {
+        model::kernel::ClassInfo {
+            instance_type: value.instance_type().into(),
+            id: value.id,
+            version: value.version,
+            description: value.description,
+            // a schema that doesn't parse can't reject anything
+            config_schema: serde_json::from_str(&value.config_schema)
+                .unwrap_or(serde_json::Value::Bool(true)),
+        }
+    }
+}
+
+impl From<model::kernel::ClassInfo> for super::kernel::Class {
+    fn from(value: model::kernel::ClassInfo) -> Self {
+        super::kernel::Class {
+            id: value.id,
+            instance_type: super::kernel::InstanceType::from(value.instance_type).into(),
+            version: value.version,
+            description: value.description,
+            config_schema: value.config_schema.to_string(),
+        }
+    }
+}
+
+impl From<super::kernel::ClassList> for model::kernel::ClassList {
+    fn from(value: super::kernel::ClassList) -> Self {
+        model::kernel::ClassList {
+            generation: value.generation,
+            classes: value.classes.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<model::kernel::ClassList> for super::kernel::ClassList {
+    fn from(value: model::kernel::ClassList) -> Self {
+        super::kernel::ClassList {
+            generation: value.generation,
+            classes: value.classes.into_iter().map(Into::into).collect(),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! Connect to a kernel gRPC service listening on a unix domain socket.
+
+use std::path::Path;
+
+use hyper_util::rt::TokioIo;
+use tonic::transport::{Channel, Endpoint};
+
+/// The uri is required by tonic but ignored by the connector, every request goes to the socket.
+const UDS_PLACEHOLDER_URI: &str = "http://[::]:0";
+
+pub async fn connect_uds(path: impl AsRef<Path>) -> Result<Channel, tonic::transport::Error> {
+    let path = path.as_ref().to_owned();
+    Endpoint::from_static(UDS_PLACEHOLDER_URI)
+        .connect_with_connector(tower::service_fn(move |_| {
+            let path = path.clone();
+            async move {
+                let stream = tokio::net::UnixStream::connect(path).await?;
+                Ok::<_, std::io::Error>(TokioIo::new(stream))
+            }
+        }))
+        .await
+}
@@ -0,0 +1,664 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Digest;
+
+use crate::{
+    ServiceConfig, TcpServiceConfig,
+    tls::{PemsFile, Tls, TlsCertParams, TlsResolver},
+    validation::json_pointer,
+};
+
+const HTTP_PROVIDER: &str = "http";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A single value change, `None` on one side means the value is absent there.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ValueChange {
+    /// JSON pointer relative to the changed item.
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ItemChange {
+    pub key: String,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<ValueChange>,
+}
+
+/// A certificate chain change, identified by the SHA-256 fingerprint of its leaf certificate.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CertChange {
+    /// The SNI domain, `None` for a single certificate resolver.
+    pub sni: Option<String>,
+    pub kind: ChangeKind,
+    pub old_fingerprint: Option<String>,
+    pub new_fingerprint: Option<String>,
+    pub key_changed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TlsChange {
+    pub name: String,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub certs: Vec<CertChange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<ValueChange>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FlowDiff {
+    pub nodes: Vec<ItemChange>,
+    pub filters: Vec<ItemChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceChange {
+    pub name: String,
+    pub kind: ChangeKind,
+    /// Changes outside of the http flow nodes and filters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<ValueChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow: Option<FlowDiff>,
+}
+
+/// Structured difference between two [`ServiceConfig`]s.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ConfigDiff {
+    pub tcp_listeners: Vec<ItemChange>,
+    pub tcp_routes: Vec<ItemChange>,
+    pub tls: Vec<TlsChange>,
+    pub tcp_services: Vec<ServiceChange>,
+}
+
+impl ConfigDiff {
+    pub fn between(old: &ServiceConfig, new: &ServiceConfig) -> Self {
+        ConfigDiff {
+            tcp_listeners: diff_items(&old.tcp_listeners, &new.tcp_listeners),
+            tcp_routes: diff_items(&old.tcp_routes, &new.tcp_routes),
+            tls: diff_keyed(&old.tls, &new.tls)
+                .filter_map(|(name, old, new)| diff_tls(name, old, new))
+                .collect(),
+            tcp_services: diff_keyed(&old.tcp_services, &new.tcp_services)
+                .filter_map(|(name, old, new)| diff_service(name, old, new))
+                .collect(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.tcp_listeners.is_empty()
+            && self.tcp_routes.is_empty()
+            && self.tls.is_empty()
+            && self.tcp_services.is_empty()
+    }
+}
+
+impl std::fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn mark(kind: ChangeKind) -> char {
+            match kind {
+                ChangeKind::Added => '+',
+                ChangeKind::Removed => '-',
+                ChangeKind::Modified => '~',
+            }
+        }
+        fn write_changes(
+            f: &mut std::fmt::Formatter<'_>,
+            indent: &str,
+            changes: &[ValueChange],
+        ) -> std::fmt::Result {
+            for change in changes {
+                let show = |value: &Option<Value>| {
+                    value
+                        .as_ref()
+                        .map(Value::to_string)
+                        .unwrap_or_else(|| "<none>".to_string())
+                };
+                writeln!(
+                    f,
+                    "{indent}{}: {} -> {}",
+                    change.path,
+                    show(&change.old),
+                    show(&change.new)
+                )?;
+            }
+            Ok(())
+        }
+        for (section, items) in [
+            ("tcp_listeners", &self.tcp_listeners),
+            ("tcp_routes", &self.tcp_routes),
+        ] {
+            for item in items {
+                writeln!(f, "{} {section}/{}", mark(item.kind), item.key)?;
+                write_changes(f, "    ", &item.changes)?;
+            }
+        }
+        for tls in &self.tls {
+            writeln!(f, "{} tls/{}", mark(tls.kind), tls.name)?;
+            for cert in &tls.certs {
+                let show = |fingerprint: &Option<String>| {
+                    fingerprint.clone().unwrap_or_else(|| "<none>".to_string())
+                };
+                writeln!(
+                    f,
+                    "    {} cert {}: {} -> {}{}",
+                    mark(cert.kind),
+                    cert.sni.as_deref().unwrap_or("<default>"),
+                    show(&cert.old_fingerprint),
+                    show(&cert.new_fingerprint),
+                    if cert.key_changed {
+                        " (key changed)"
+                    } else {
+                        ""
+                    }
+                )?;
+            }
+            write_changes(f, "    options", &tls.options)?;
+        }
+        for service in &self.tcp_services {
+            writeln!(f, "{} tcp_services/{}", mark(service.kind), service.name)?;
+            write_changes(f, "    ", &service.changes)?;
+            if let Some(flow) = &service.flow {
+                for (section, items) in [("nodes", &flow.nodes), ("filters", &flow.filters)] {
+                    for item in items {
+                        writeln!(f, "    {} flow/{section}/{}", mark(item.kind), item.key)?;
+                        write_changes(f, "        ", &item.changes)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// SHA-256 fingerprint of a DER encoded certificate, as colon separated upper case hex.
+pub fn cert_fingerprint(der: &[u8]) -> String {
+    sha2::Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Iterate the union of keys of two maps with the value on each side.
+fn diff_keyed<'a, K: Ord + ToString, V>(
+    old: &'a BTreeMap<K, V>,
+    new: &'a BTreeMap<K, V>,
+) -> impl Iterator<Item = (String, Option<&'a V>, Option<&'a V>)> {
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|key| (key.to_string(), old.get(key), new.get(key)))
+}
+
+fn diff_items<K: Ord + ToString, V: Serialize>(
+    old: &BTreeMap<K, V>,
+    new: &BTreeMap<K, V>,
+) -> Vec<ItemChange> {
+    diff_keyed(old, new)
+        .filter_map(|(key, old, new)| diff_item(key, old.map(to_value), new.map(to_value)))
+        .collect()
+}
+
+fn diff_item(key: String, old: Option<Value>, new: Option<Value>) -> Option<ItemChange> {
+    let kind = match (&old, &new) {
+        (None, None) => return None,
+        (None, Some(_)) => ChangeKind::Added,
+        (Some(_), None) => ChangeKind::Removed,
+        (Some(old), Some(new)) if old == new => return None,
+        (Some(_), Some(_)) => ChangeKind::Modified,
+    };
+    let mut changes = Vec::new();
+    if let (Some(old), Some(new)) = (&old, &new) {
+        diff_values(&mut Vec::new(), old, new, &mut changes);
+    }
+    Some(ItemChange { key, kind, changes })
+}
+
+fn to_value<V: Serialize>(value: &V) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Recursively collect leaf changes between two json values.
+pub fn diff_values(path: &mut Vec<String>, old: &Value, new: &Value, out: &mut Vec<ValueChange>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            let keys = old_map
+                .keys()
+                .chain(new_map.keys())
+                .collect::<BTreeSet<_>>();
+            for key in keys {
+                path.push(key.clone());
+                match (old_map.get(key), new_map.get(key)) {
+                    (Some(old), Some(new)) => diff_values(path, old, new, out),
+                    (old, new) => out.push(ValueChange {
+                        path: json_pointer(path.iter()),
+                        old: old.cloned(),
+                        new: new.cloned(),
+                    }),
+                }
+                path.pop();
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for index in 0..old_items.len().max(new_items.len()) {
+                path.push(index.to_string());
+                match (old_items.get(index), new_items.get(index)) {
+                    (Some(old), Some(new)) => diff_values(path, old, new, out),
+                    (old, new) => out.push(ValueChange {
+                        path: json_pointer(path.iter()),
+                        old: old.cloned(),
+                        new: new.cloned(),
+                    }),
+                }
+                path.pop();
+            }
+        }
+        (old, new) if old != new => out.push(ValueChange {
+            path: json_pointer(path.iter()),
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+fn leaf_fingerprint(certs: &PemsFile) -> Option<String> {
+    certs.0.first().map(|pem| cert_fingerprint(pem.contents()))
+}
+
+fn diff_cert(
+    sni: Option<String>,
+    old: Option<&TlsCertParams>,
+    new: Option<&TlsCertParams>,
+) -> Option<CertChange> {
+    let old_fingerprint = old.and_then(|params| leaf_fingerprint(&params.certs));
+    let new_fingerprint = new.and_then(|params| leaf_fingerprint(&params.certs));
+    let key_changed = match (old, new) {
+        (Some(old), Some(new)) => old.key != new.key,
+        _ => false,
+    };
+    let kind = match (old, new) {
+        (None, None) => return None,
+        (None, Some(_)) => ChangeKind::Added,
+        (Some(_), None) => ChangeKind::Removed,
+        (Some(old), Some(new)) if old == new => return None,
+        (Some(_), Some(_)) => ChangeKind::Modified,
+    };
+    Some(CertChange {
+        sni,
+        kind,
+        old_fingerprint,
+        new_fingerprint,
+        key_changed,
+    })
+}
+
+fn cert_params(tls: &Tls) -> BTreeMap<Option<String>, &TlsCertParams> {
+    match &tls.resolver {
+        TlsResolver::Single(params) => BTreeMap::from([(None, params)]),
+        TlsResolver::Sni(snis) => snis
+            .iter()
+            .map(|(domain, params)| (Some(domain.clone()), params))
+            .collect(),
+    }
+}
+
+// key material never leaves this function, only fingerprints and a changed flag
+fn diff_tls(name: String, old: Option<&Tls>, new: Option<&Tls>) -> Option<TlsChange> {
+    let kind = match (old, new) {
+        (None, None) => return None,
+        (None, Some(_)) => ChangeKind::Added,
+        (Some(_), None) => ChangeKind::Removed,
+        (Some(old), Some(new)) if old == new => return None,
+        (Some(_), Some(_)) => ChangeKind::Modified,
+    };
+    let old_certs = old.map(cert_params).unwrap_or_default();
+    let new_certs = new.map(cert_params).unwrap_or_default();
+    let certs = old_certs
+        .keys()
+        .chain(new_certs.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|sni| {
+            diff_cert(
+                sni.clone(),
+                old_certs.get(sni).copied(),
+                new_certs.get(sni).copied(),
+            )
+        })
+        .collect();
+    let mut options = Vec::new();
+    if let (Some(old), Some(new)) = (old, new) {
+        diff_values(
+            &mut Vec::new(),
+            &to_value(&old.options.clone().unwrap_or_default()),
+            &to_value(&new.options.clone().unwrap_or_default()),
+            &mut options,
+        );
+    }
+    Some(TlsChange {
+        name,
+        kind,
+        certs,
+        options,
+    })
+}
+
+/// Take `flow.<section>` out of an http service config value.
+fn take_flow_section(config: &mut Value, section: &str) -> BTreeMap<String, Value> {
+    config
+        .get_mut("flow")
+        .and_then(Value::as_object_mut)
+        .and_then(|flow| flow.remove(section))
+        .and_then(|value| match value {
+            Value::Object(map) => Some(map.into_iter().collect()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+fn diff_service(
+    name: String,
+    old: Option<&TcpServiceConfig>,
+    new: Option<&TcpServiceConfig>,
+) -> Option<ServiceChange> {
+    let (old, new) = match (old, new) {
+        (None, None) => return None,
+        (None, Some(_)) | (Some(_), None) => {
+            return Some(ServiceChange {
+                name,
+                kind: if old.is_none() {
+                    ChangeKind::Added
+                } else {
+                    ChangeKind::Removed
+                },
+                changes: Vec::new(),
+                flow: None,
+            });
+        }
+        (Some(old), Some(new)) if old == new => return None,
+        (Some(old), Some(new)) => (old, new),
+    };
+    let mut old_value = to_value(old);
+    let mut new_value = to_value(new);
+    let mut flow = None;
+    // http flows are diffed by node and filter id instead of by raw json
+    if old.provider == HTTP_PROVIDER && new.provider == HTTP_PROVIDER {
+        let mut flow_diff = FlowDiff::default();
+        for (section, items) in [
+            ("nodes", &mut flow_diff.nodes),
+            ("filters", &mut flow_diff.filters),
+        ] {
+            let old_items = old_value
+                .get_mut("config")
+                .map(|config| take_flow_section(config, section))
+                .unwrap_or_default();
+            let new_items = new_value
+                .get_mut("config")
+                .map(|config| take_flow_section(config, section))
+                .unwrap_or_default();
+            *items = diff_keyed(&old_items, &new_items)
+                .filter_map(|(key, old, new)| diff_item(key, old.cloned(), new.cloned()))
+                .collect();
+        }
+        flow = Some(flow_diff);
+    }
+    let mut changes = Vec::new();
+    diff_values(&mut Vec::new(), &old_value, &new_value, &mut changes);
+    Some(ServiceChange {
+        name,
+        kind: ChangeKind::Modified,
+        changes,
+        flow,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Listener, TcpRoute, switchboard_serde_value::value};
+
+    fn route(bind: &str, service: &str) -> TcpRoute {
+        TcpRoute {
+            bind: bind.parse().unwrap(),
+            service: service.to_string(),
+            tls: None,
+        }
+    }
+
+    fn config(routes: &[TcpRoute]) -> ServiceConfig {
+        ServiceConfig {
+            tcp_routes: routes
+                .iter()
+                .map(|route| (route.bind, route.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn http_service(nodes: crate::SerdeValue, filters: crate::SerdeValue) -> TcpServiceConfig {
+        TcpServiceConfig {
+            provider: HTTP_PROVIDER.to_string(),
+            name: "web".to_string(),
+            config: Some(value!({
+                "flow": {
+                    "entrypoint": { "node": "router" },
+                    "nodes": nodes,
+                    "filters": filters,
+                },
+            })),
+            description: None,
+        }
+    }
+
+    fn values(old: Value, new: Value) -> Vec<ValueChange> {
+        let mut changes = Vec::new();
+        diff_values(&mut Vec::new(), &old, &new, &mut changes);
+        changes
+    }
+
+    fn change(path: &str, old: Option<Value>, new: Option<Value>) -> ValueChange {
+        ValueChange {
+            path: path.to_string(),
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn test_empty_diff() {
+        let config = ServiceConfig {
+            tcp_services: BTreeMap::from([(
+                "web".to_string(),
+                http_service(value!({ "router": {} }), value!({})),
+            )]),
+            tcp_listeners: BTreeMap::from([(
+                "0.0.0.0:80".parse().unwrap(),
+                Listener {
+                    bind: "0.0.0.0:80".parse().unwrap(),
+                    description: None,
+                    limits: Default::default(),
+                },
+            )]),
+            ..config(&[route("0.0.0.0:80", "web")])
+        };
+        let diff = ConfigDiff::between(&config, &config.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff, ConfigDiff::default());
+        assert_eq!(diff.to_string(), "");
+        assert!(
+            ConfigDiff::between(&ServiceConfig::default(), &ServiceConfig::default()).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_added_removed_modified_items() {
+        let old = config(&[route("0.0.0.0:80", "web"), route("0.0.0.0:81", "api")]);
+        let new = config(&[route("0.0.0.0:80", "web-v2"), route("0.0.0.0:82", "admin")]);
+        let diff = ConfigDiff::between(&old, &new);
+        assert_eq!(
+            diff.tcp_routes,
+            vec![
+                ItemChange {
+                    key: "0.0.0.0:80".to_string(),
+                    kind: ChangeKind::Modified,
+                    changes: vec![change(
+                        "/service",
+                        Some(json!("web")),
+                        Some(json!("web-v2"))
+                    )],
+                },
+                ItemChange {
+                    key: "0.0.0.0:81".to_string(),
+                    kind: ChangeKind::Removed,
+                    changes: vec![],
+                },
+                ItemChange {
+                    key: "0.0.0.0:82".to_string(),
+                    kind: ChangeKind::Added,
+                    changes: vec![],
+                },
+            ]
+        );
+        assert!(diff.tcp_listeners.is_empty() && diff.tls.is_empty());
+        assert_eq!(
+            diff.to_string(),
+            "~ tcp_routes/0.0.0.0:80\n    /service: \"web\" -> \"web-v2\"\n\
+             - tcp_routes/0.0.0.0:81\n\
+             + tcp_routes/0.0.0.0:82\n"
+        );
+        // the reverse diff swaps additions and removals
+        let reverse = ConfigDiff::between(&new, &old);
+        assert_eq!(reverse.tcp_routes[1].kind, ChangeKind::Added);
+        assert_eq!(reverse.tcp_routes[2].kind, ChangeKind::Removed);
+    }
+
+    #[test]
+    fn test_nested_maps() {
+        let old = json!({
+            "timeouts": { "read": "10s", "write": "10s" },
+            "headers": { "x-old": "1", "a/b": { "c~d": 1 } },
+            "same": { "deep": { "value": true } },
+        });
+        let new = json!({
+            "timeouts": { "read": "30s", "write": "10s" },
+            "headers": { "x-new": "1", "a/b": { "c~d": 2 } },
+            "same": { "deep": { "value": true } },
+        });
+        assert_eq!(
+            values(old, new),
+            vec![
+                change("/headers/a~1b/c~0d", Some(json!(1)), Some(json!(2))),
+                change("/headers/x-new", None, Some(json!("1"))),
+                change("/headers/x-old", Some(json!("1")), None),
+                change("/timeouts/read", Some(json!("10s")), Some(json!("30s"))),
+            ]
+        );
+        // a value replaced by a map of another shape is one change
+        assert_eq!(
+            values(json!({ "a": 1 }), json!({ "a": { "b": 1 } })),
+            vec![change("/a", Some(json!(1)), Some(json!({ "b": 1 })))]
+        );
+    }
+
+    #[test]
+    fn test_arrays_by_position() {
+        // reordering shows up as a change at every moved position
+        assert_eq!(
+            values(json!(["a", "b", "c"]), json!(["c", "b", "a"])),
+            vec![
+                change("/0", Some(json!("a")), Some(json!("c"))),
+                change("/2", Some(json!("c")), Some(json!("a"))),
+            ]
+        );
+        assert_eq!(
+            values(json!({ "list": [1] }), json!({ "list": [1, 2] })),
+            vec![change("/list/1", None, Some(json!(2)))]
+        );
+        assert_eq!(
+            values(json!([{ "id": 1 }, { "id": 2 }]), json!([{ "id": 1 }])),
+            vec![change("/1", Some(json!({ "id": 2 })), None)]
+        );
+        assert!(values(json!([1, [2, 3]]), json!([1, [2, 3]])).is_empty());
+    }
+
+    #[test]
+    fn test_http_flow_by_id() {
+        let old = ServiceConfig {
+            tcp_services: BTreeMap::from([(
+                "web".to_string(),
+                http_service(
+                    value!({
+                        "router": { "class": "router", "config": { "rules": ["/"] } },
+                        "old-backend": { "class": "reverse-proxy" },
+                    }),
+                    value!({ "cors": { "class": "cors" } }),
+                ),
+            )]),
+            ..Default::default()
+        };
+        let mut new_service = http_service(
+            value!({
+                "router": { "class": "router", "config": { "rules": ["/", "/api"] } },
+                "new-backend": { "class": "reverse-proxy" },
+            }),
+            value!({ "cors": { "class": "cors" } }),
+        );
+        new_service.description = Some("public site".to_string());
+        let new = ServiceConfig {
+            tcp_services: BTreeMap::from([("web".to_string(), new_service)]),
+            ..Default::default()
+        };
+        let diff = ConfigDiff::between(&old, &new);
+        let [service] = diff.tcp_services.as_slice() else {
+            panic!("one changed service expected, got {:?}", diff.tcp_services);
+        };
+        assert_eq!(service.kind, ChangeKind::Modified);
+        // flow items are not repeated in the service changes
+        assert_eq!(
+            service.changes,
+            vec![change(
+                "/description",
+                Some(Value::Null),
+                Some(json!("public site"))
+            )]
+        );
+        let flow = service.flow.as_ref().unwrap();
+        assert!(flow.filters.is_empty());
+        assert_eq!(
+            flow.nodes
+                .iter()
+                .map(|item| (item.key.as_str(), item.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                ("new-backend", ChangeKind::Added),
+                ("old-backend", ChangeKind::Removed),
+                ("router", ChangeKind::Modified),
+            ]
+        );
+        assert_eq!(
+            flow.nodes[2].changes,
+            vec![change("/config/rules/1", None, Some(json!("/api")))]
+        );
+
+        let removed = ConfigDiff::between(&old, &ServiceConfig::default());
+        assert_eq!(removed.tcp_services[0].kind, ChangeKind::Removed);
+        assert!(removed.tcp_services[0].flow.is_none());
+    }
+}
@@ -1,7 +1,16 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(
-    Debug, Clone, Hash, Serialize, Deserialize, bincode::Encode, bincode::Decode, PartialEq, Eq,
+    Debug,
+    Clone,
+    Default,
+    Hash,
+    Serialize,
+    Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    PartialEq,
+    Eq,
 )]
 
 pub struct ErrorStack {
@@ -31,8 +40,11 @@ pub struct ErrorStackFrame {
 
 impl ErrorStack {
     pub fn from_std<E: std::error::Error + 'static>(e: E) -> Self {
+        Self::from_dyn(&e)
+    }
+    pub fn from_dyn(e: &(dyn std::error::Error + 'static)) -> Self {
         let mut frames = Vec::new();
-        let mut current: Option<&(dyn std::error::Error + 'static)> = Some(&e);
+        let mut current: Option<&(dyn std::error::Error + 'static)> = Some(e);
         while let Some(err) = current {
             frames.push(ErrorStackFrame {
                 error: err.to_string(),
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
@@ -5,6 +7,7 @@ use crate::error::ErrorStack;
 
 pub const RUN_FILE_DEFAULT_PATH: &str = "/var/run/switchboard/kernel/default.run";
 pub const RUN_FILE_DEFAULT_DIR: &str = "/var/run/switchboard/kernel/";
+pub const UDS_DEFAULT_PATH: &str = "/var/run/switchboard/kernel/default.sock";
 pub const HTTP_DEFAULT_PORT: u16 = 8055;
 
 #[derive(
@@ -17,6 +20,14 @@ pub struct KernelInfo {
     pub description: Option<String>,
     #[serde(default)]
     pub meta: KernelMeta,
+    /// Labels like `region`, `role` or `tier`, used by controllers to select kernels.
+    pub labels: BTreeMap<String, String>,
+}
+
+impl KernelInfo {
+    pub fn matches(&self, selector: &crate::label::LabelSelector) -> bool {
+        selector.matches(&self.labels)
+    }
 }
 
 impl Default for KernelInfo {
@@ -26,6 +37,7 @@ impl Default for KernelInfo {
             id: "default".to_string(),
             description: None,
             meta: KernelMeta::default(),
+            labels: BTreeMap::new(),
         }
     }
 }
@@ -121,3 +133,60 @@ pub enum KernelConnectionAndState {
     FetchError(ErrorStack),
     Disconnected,
 }
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginKind {
+    /// A shared library, C ABI or Rust ABI.
+    Native,
+    /// A wasm component.
+    Wasm,
+}
+
+/// A plugin loaded by a kernel, with the classes it contributes.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PluginInfo {
+    pub name: String,
+    pub path: String,
+    /// Hex sha256 of the artifact, for plugins pushed by the controller.
+    pub sha256: Option<String>,
+    /// The class registry generation the plugin was loaded in.
+    pub generation: u64,
+    pub classes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PluginList {
+    /// The current class registry generation.
+    pub generation: u64,
+    pub plugins: Vec<PluginInfo>,
+}
+
+/// A class of the http provider of a kernel, with the JSON Schema of its config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassInfo {
+    pub id: String,
+    pub instance_type: crate::services::http::InstanceType,
+    pub version: String,
+    pub description: Option<String>,
+    pub config_schema: serde_json::Value,
+}
+
+impl From<crate::services::http::ClassData> for ClassInfo {
+    fn from(value: crate::services::http::ClassData) -> Self {
+        ClassInfo {
+            id: value.id.to_string(),
+            instance_type: value.instance_type,
+            version: value.meta.version,
+            description: value.meta.description,
+            config_schema: value.config_schema.to_value(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassList {
+    /// The current class registry generation.
+    pub generation: u64,
+    pub classes: Vec<ClassInfo>,
+}
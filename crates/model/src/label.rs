@@ -0,0 +1,326 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Selects kernels by their labels, written like a kubernetes label selector.
+///
+/// Requirements are separated by commas and must all match:
+/// `key=value`, `key!=value`, `key in (a,b)`, `key notin (a,b)`, `key` (exists) and `!key`.
+/// An empty selector matches everything.
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct LabelSelector {
+    pub requirements: Vec<LabelRequirement>,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum LabelRequirement {
+    Equals { key: String, value: String },
+    NotEquals { key: String, value: String },
+    In { key: String, values: Vec<String> },
+    NotIn { key: String, values: Vec<String> },
+    Exists { key: String },
+    DoesNotExist { key: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LabelSelectorParseError {
+    #[error("empty label key in requirement {0:?}")]
+    EmptyKey(String),
+    #[error("unclosed value set in requirement {0:?}")]
+    UnclosedSet(String),
+    #[error("invalid label requirement {0:?}")]
+    InvalidRequirement(String),
+}
+
+impl LabelRequirement {
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        match self {
+            LabelRequirement::Equals { key, value } => labels.get(key) == Some(value),
+            LabelRequirement::NotEquals { key, value } => labels.get(key) != Some(value),
+            LabelRequirement::In { key, values } => {
+                labels.get(key).is_some_and(|value| values.contains(value))
+            }
+            LabelRequirement::NotIn { key, values } => {
+                labels.get(key).is_none_or(|value| !values.contains(value))
+            }
+            LabelRequirement::Exists { key } => labels.contains_key(key),
+            LabelRequirement::DoesNotExist { key } => !labels.contains_key(key),
+        }
+    }
+
+    fn parse(requirement: &str) -> Result<Self, LabelSelectorParseError> {
+        let key = |key: &str| {
+            let key = key.trim();
+            if key.is_empty() {
+                Err(LabelSelectorParseError::EmptyKey(requirement.to_string()))
+            } else {
+                Ok(key.to_string())
+            }
+        };
+        let set = |values: &str| -> Result<Vec<String>, LabelSelectorParseError> {
+            let values = values
+                .trim()
+                .strip_prefix('(')
+                .and_then(|values| values.strip_suffix(')'))
+                .ok_or_else(|| LabelSelectorParseError::UnclosedSet(requirement.to_string()))?;
+            Ok(values
+                .split(',')
+                .map(|value| value.trim().to_string())
+                .collect())
+        };
+        if let Some((k, v)) = requirement.split_once(" notin ") {
+            return Ok(LabelRequirement::NotIn {
+                key: key(k)?,
+                values: set(v)?,
+            });
+        }
+        if let Some((k, v)) = requirement.split_once(" in ") {
+            return Ok(LabelRequirement::In {
+                key: key(k)?,
+                values: set(v)?,
+            });
+        }
+        if let Some((k, v)) = requirement.split_once("!=") {
+            return Ok(LabelRequirement::NotEquals {
+                key: key(k)?,
+                value: v.trim().to_string(),
+            });
+        }
+        if let Some((k, v)) = requirement
+            .split_once("==")
+            .or_else(|| requirement.split_once('='))
+        {
+            return Ok(LabelRequirement::Equals {
+                key: key(k)?,
+                value: v.trim().to_string(),
+            });
+        }
+        let requirement = requirement.trim();
+        if requirement.contains(char::is_whitespace) {
+            return Err(LabelSelectorParseError::InvalidRequirement(
+                requirement.to_string(),
+            ));
+        }
+        match requirement.strip_prefix('!') {
+            Some(k) => Ok(LabelRequirement::DoesNotExist { key: key(k)? }),
+            None => Ok(LabelRequirement::Exists {
+                key: key(requirement)?,
+            }),
+        }
+    }
+}
+
+impl Display for LabelRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LabelRequirement::Equals { key, value } => write!(f, "{key}={value}"),
+            LabelRequirement::NotEquals { key, value } => write!(f, "{key}!={value}"),
+            LabelRequirement::In { key, values } => write!(f, "{key} in ({})", values.join(",")),
+            LabelRequirement::NotIn { key, values } => {
+                write!(f, "{key} notin ({})", values.join(","))
+            }
+            LabelRequirement::Exists { key } => write!(f, "{key}"),
+            LabelRequirement::DoesNotExist { key } => write!(f, "!{key}"),
+        }
+    }
+}
+
+impl LabelSelector {
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.requirements
+            .iter()
+            .all(|requirement| requirement.matches(labels))
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = LabelSelectorParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut requirements = Vec::new();
+        let mut start = 0;
+        let mut depth = 0usize;
+        // commas inside a `(a,b)` value set don't end the requirement
+        for (index, char) in s.char_indices() {
+            match char {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    requirements.push(&s[start..index]);
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        requirements.push(&s[start..]);
+        let requirements = requirements
+            .into_iter()
+            .filter(|requirement| !requirement.trim().is_empty())
+            .map(LabelRequirement::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(LabelSelector { requirements })
+    }
+}
+
+impl Display for LabelSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, requirement) in self.requirements.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{requirement}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for LabelSelector {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LabelSelector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn matches(selector: &str, pairs: &[(&str, &str)]) -> bool {
+        selector
+            .parse::<LabelSelector>()
+            .unwrap()
+            .matches(&labels(pairs))
+    }
+
+    #[test]
+    fn test_parse() {
+        let selector: LabelSelector =
+            "region = eu, tier!=canary,zone in (a, b),env notin (dev),gpu,!legacy"
+                .parse()
+                .unwrap();
+        let owned = |values: &[&str]| values.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            selector.requirements,
+            [
+                LabelRequirement::Equals {
+                    key: "region".to_string(),
+                    value: "eu".to_string()
+                },
+                LabelRequirement::NotEquals {
+                    key: "tier".to_string(),
+                    value: "canary".to_string()
+                },
+                LabelRequirement::In {
+                    key: "zone".to_string(),
+                    values: owned(&["a", "b"])
+                },
+                LabelRequirement::NotIn {
+                    key: "env".to_string(),
+                    values: owned(&["dev"])
+                },
+                LabelRequirement::Exists {
+                    key: "gpu".to_string()
+                },
+                LabelRequirement::DoesNotExist {
+                    key: "legacy".to_string()
+                },
+            ]
+        );
+        // the canonical form parses back to the same selector
+        let canonical = selector.to_string();
+        assert_eq!(
+            canonical,
+            "region=eu,tier!=canary,zone in (a,b),env notin (dev),gpu,!legacy"
+        );
+        assert_eq!(canonical.parse::<LabelSelector>().unwrap(), selector);
+        assert_eq!(
+            "region==eu".parse::<LabelSelector>().unwrap().to_string(),
+            "region=eu"
+        );
+        assert!("".parse::<LabelSelector>().unwrap().requirements.is_empty());
+        assert!(
+            " , "
+                .parse::<LabelSelector>()
+                .unwrap()
+                .requirements
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            "=eu".parse::<LabelSelector>(),
+            Err(LabelSelectorParseError::EmptyKey(_))
+        ));
+        assert!(matches!(
+            "!".parse::<LabelSelector>(),
+            Err(LabelSelectorParseError::EmptyKey(_))
+        ));
+        assert!(matches!(
+            "zone in (a,b".parse::<LabelSelector>(),
+            Err(LabelSelectorParseError::UnclosedSet(_))
+        ));
+        assert!(matches!(
+            "zone in a".parse::<LabelSelector>(),
+            Err(LabelSelectorParseError::UnclosedSet(_))
+        ));
+        assert!(matches!(
+            "region eu".parse::<LabelSelector>(),
+            Err(LabelSelectorParseError::InvalidRequirement(_))
+        ));
+    }
+
+    #[test]
+    fn test_matches() {
+        let eu = [("region", "eu"), ("zone", "a")];
+        assert!(matches("", &[]));
+        assert!(matches("region=eu", &eu));
+        assert!(!matches("region=us", &eu));
+        assert!(!matches("region=eu", &[]));
+        // a missing label is not equal to anything
+        assert!(matches("tier!=canary", &eu));
+        assert!(!matches("region!=eu", &eu));
+        assert!(matches("zone in (a,b)", &eu));
+        assert!(!matches("zone in (b,c)", &eu));
+        assert!(!matches("tier in (a)", &eu));
+        assert!(matches("tier notin (canary)", &eu));
+        assert!(!matches("zone notin (a)", &eu));
+        assert!(matches("zone", &eu));
+        assert!(!matches("tier", &eu));
+        assert!(matches("!tier", &eu));
+        assert!(!matches("!zone", &eu));
+        // every requirement must match
+        assert!(matches("region=eu,zone in (a)", &eu));
+        assert!(!matches("region=eu,zone=b", &eu));
+    }
+
+    #[test]
+    fn test_serde() {
+        let selector: LabelSelector = serde_json::from_str(r#""region=eu,zone in (a,b)""#).unwrap();
+        assert_eq!(selector.requirements.len(), 2);
+        assert_eq!(
+            serde_json::to_string(&selector).unwrap(),
+            r#""region=eu,zone in (a,b)""#
+        );
+        assert!(serde_json::from_str::<LabelSelector>(r#""zone in (a""#).is_err());
+    }
+}
@@ -24,14 +24,17 @@ use crate::tcp_route::TcpRoute;
 pub mod bytes;
 pub mod control;
 pub mod controller;
+pub mod diff;
 pub mod discovery;
 pub mod error;
 pub mod http;
 pub mod kernel;
+pub mod label;
 pub mod protocol;
 pub mod regex;
 pub mod services;
 pub mod tcp_route;
+pub mod validation;
 
 pub type HumanReadableServiceConfig<L> = FileStyleConfig<L>;
 
@@ -21,6 +21,77 @@ use serde::{Deserialize, Serialize};
 pub struct Listener {
     pub bind: SocketAddr,
     pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "ListenerLimits::is_unlimited")]
+    #[builder(default)]
+    pub limits: ListenerLimits,
+}
+
+/// Protection of a listener against clients opening too many connections.
+///
+/// # Example
+/// ```toml
+/// max_connections = 10000
+/// max_connections_per_ip = 100
+/// accept_rate = { per_second = 500, burst = 1000 }
+/// handshake_timeout_secs = 10
+/// idle_timeout_secs = 300
+/// ```
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Serialize,
+    Deserialize,
+    Hash,
+    bincode::Encode,
+    bincode::Decode,
+    PartialEq,
+    Eq,
+)]
+#[serde(default)]
+pub struct ListenerLimits {
+    /// Concurrent connections on this listener.
+    pub max_connections: Option<u32>,
+    /// Concurrent connections on this listener from a single source ip.
+    pub max_connections_per_ip: Option<u32>,
+    pub accept_rate: Option<AcceptRate>,
+    /// Close connections which send nothing, e.g. no TLS ClientHello or HTTP request line,
+    /// within this time.
+    pub handshake_timeout_secs: Option<u64>,
+    /// Close connections which neither send nor receive anything within this time.
+    pub idle_timeout_secs: Option<u64>,
+}
+
+impl ListenerLimits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A token bucket refilled with `per_second` tokens a second, holding up to `burst` tokens.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    Hash,
+    bincode::Encode,
+    bincode::Decode,
+    PartialEq,
+    Eq,
+)]
+pub struct AcceptRate {
+    pub per_second: u32,
+    /// Defaults to `per_second`.
+    pub burst: Option<u32>,
+}
+
+impl AcceptRate {
+    pub fn burst(&self) -> u32 {
+        self.burst.unwrap_or(self.per_second)
+    }
 }
 
 impl std::fmt::Display for Listener {
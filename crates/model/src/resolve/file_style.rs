@@ -42,6 +42,8 @@ pub struct FileBind {
     pub bind: SocketAddr,
     pub tls: Option<String>,
     pub description: Option<String>,
+    #[serde(skip_serializing_if = "ListenerLimits::is_unlimited")]
+    pub limits: ListenerLimits,
 }
 
 impl<'de> Deserialize<'de> for FileBind {
@@ -53,6 +55,8 @@ impl<'de> Deserialize<'de> for FileBind {
         pub struct FileBindStruct {
             pub bind: SocketAddr,
             pub tls: Option<String>,
+            #[serde(default)]
+            pub limits: ListenerLimits,
         }
         pub struct FileBindVisitor;
         impl<'de> serde::de::Visitor<'de> for FileBindVisitor {
@@ -79,6 +83,7 @@ impl<'de> Deserialize<'de> for FileBind {
                     bind: fb_struct.bind,
                     tls: fb_struct.tls,
                     description: None,
+                    limits: fb_struct.limits,
                 })
             }
         }
@@ -97,6 +102,7 @@ impl FileBind {
                 bind: addr,
                 tls,
                 description: None,
+                limits: ListenerLimits::default(),
             })
         } else {
             let addr: SocketAddr = expr.trim().parse()?;
@@ -104,6 +110,7 @@ impl FileBind {
                 bind: addr,
                 tls: None,
                 description: None,
+                limits: ListenerLimits::default(),
             })
         }
     }
@@ -204,17 +211,15 @@ impl<L> FileStyleConfig<L> {
 
         let mut service_binds: BTreeMap<String, Vec<FileBind>> = BTreeMap::new();
         for (addr, route) in &config.tcp_routes {
-            let description = config
-                .tcp_listeners
-                .get(addr)
-                .and_then(|l| l.description.clone());
+            let listener = config.tcp_listeners.get(addr);
             service_binds
                 .entry(route.service.clone())
                 .or_default()
                 .push(FileBind {
                     bind: *addr,
                     tls: route.tls.clone(),
-                    description,
+                    description: listener.and_then(|l| l.description.clone()),
+                    limits: listener.map(|l| l.limits).unwrap_or_default(),
                 });
         }
 
@@ -293,6 +298,7 @@ impl<L> FileStyleConfig<L> {
                     crate::Listener {
                         bind: bind.bind,
                         description: bind.description.clone(),
+                        limits: bind.limits,
                     },
                 );
                 tcp_routes.insert(
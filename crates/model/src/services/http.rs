@@ -1,4 +1,5 @@
 use crate::SerdeValue;
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
 use serde::{Deserialize, Serialize};
 use switchboard_link_or_value::{LinkOrValue, Resolvable, Resolver};
 pub mod consts;
@@ -192,6 +193,7 @@ pub enum InstanceType {
     Ord,
     bincode::Encode,
     bincode::Decode,
+    JsonSchema,
 )]
 #[serde(transparent)]
 /// instance id can only contain alphanumeric characters, hyphens, dots, and underscores
@@ -331,6 +333,21 @@ impl<'de> Deserialize<'de> for NodePort {
     }
 }
 
+impl JsonSchema for NodePort {
+    fn inline_schema() -> bool {
+        true
+    }
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "NodePort".into()
+    }
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "A port name, `$default` for the default port",
+        })
+    }
+}
+
 impl std::fmt::Display for NodePort {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -354,7 +371,7 @@ pub struct NodeInput {
     pub filters: Vec<FilterReference>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode, JsonSchema)]
 pub struct NodeOutput {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub filters: Vec<FilterReference>,
@@ -434,6 +451,21 @@ impl<'de> Deserialize<'de> for NodeTarget {
     }
 }
 
+impl JsonSchema for NodeTarget {
+    fn inline_schema() -> bool {
+        true
+    }
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "NodeTarget".into()
+    }
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "A node id, optionally followed by `:<port>`",
+        })
+    }
+}
+
 impl FromStr for NodeTarget {
     type Err = Infallible;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -459,7 +491,7 @@ impl Display for NodeTarget {
 
 pub type FilterId = InstanceId;
 
-#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode, JsonSchema)]
 #[serde(transparent)]
 pub struct FilterReference {
     pub id: FilterId,
@@ -573,10 +605,11 @@ pub struct ClassData {
     pub id: ClassId,
     pub meta: ClassMeta,
     pub instance_type: InstanceType,
-    // pub config_schema: Schema,
+    /// JSON Schema of the instance config, `true` when the class doesn't describe it.
+    pub config_schema: Schema,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode, JsonSchema)]
 
 pub struct WithOutputs<C> {
     #[serde(flatten)]
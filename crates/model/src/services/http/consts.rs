@@ -7,6 +7,7 @@ pub const FILTER_URL_REWRITE_CLASS_ID: &str = "url-rewrite";
 pub const ROUTER_CLASS_ID: &str = "router";
 
 pub const BALANCER_CLASS_ID: &str = "balancer";
+pub const TRAFFIC_SPLIT_CLASS_ID: &str = "traffic-split";
 
 pub const REVERSE_PROXY_CLASS_ID: &str = "reverse-proxy";
 pub const HTTP_CLIENT_CLASS_ID: &str = "http-client";
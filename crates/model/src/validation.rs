@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::ErrorStack;
+
+/// One problem found while validating a [`ServiceConfig`](crate::ServiceConfig).
+#[derive(
+    Debug, Clone, Hash, Serialize, Deserialize, bincode::Encode, bincode::Decode, PartialEq, Eq,
+)]
+pub struct ValidationIssue {
+    /// JSON pointer to the offending item, e.g. `/tcp_services/web/config/flow/nodes/router`.
+    pub path: String,
+    pub error: ErrorStack,
+}
+
+impl ValidationIssue {
+    pub fn new<S: AsRef<str>>(segments: impl IntoIterator<Item = S>, error: ErrorStack) -> Self {
+        Self {
+            path: json_pointer(segments),
+            error,
+        }
+    }
+}
+
+/// The result of a dry-run validation, every error and warning at once.
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    Hash,
+    Serialize,
+    Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    PartialEq,
+    Eq,
+)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+    pub fn merge(&mut self, other: ValidationReport) {
+        self.errors.extend(other.errors);
+        self.warnings.extend(other.warnings);
+    }
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (level, issues) in [("error", &self.errors), ("warning", &self.warnings)] {
+            for issue in issues {
+                let message = issue
+                    .error
+                    .frames
+                    .first()
+                    .map(|frame| frame.error.as_str())
+                    .unwrap_or_default();
+                writeln!(f, "{level} at {}: {message}", issue.path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Build a JSON pointer (RFC 6901) from path segments.
+pub fn json_pointer<S: AsRef<str>>(segments: impl IntoIterator<Item = S>) -> String {
+    let mut pointer = String::new();
+    for segment in segments {
+        pointer.push('/');
+        pointer.push_str(&segment.as_ref().replace('~', "~0").replace('/', "~1"));
+    }
+    pointer
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("{0}")]
+    struct TestError(&'static str);
+
+    fn issue(segments: &[&str], message: &'static str) -> ValidationIssue {
+        ValidationIssue::new(segments, ErrorStack::from_std(TestError(message)))
+    }
+
+    #[test]
+    fn test_json_pointer() {
+        assert_eq!(json_pointer::<&str>([]), "");
+        assert_eq!(
+            json_pointer(["tcp_routes", "127.0.0.1:80", "service"]),
+            "/tcp_routes/127.0.0.1:80/service"
+        );
+        // `~` is escaped before `/`, so an escaped `/` isn't escaped twice
+        assert_eq!(json_pointer(["a/b", "c~d", "~/"]), "/a~1b/c~0d/~0~1");
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = ValidationReport::default();
+        assert!(report.is_valid());
+        report.merge(ValidationReport {
+            errors: Vec::new(),
+            warnings: vec![issue(&["nodes", "unused"], "unreachable")],
+        });
+        assert!(report.is_valid());
+        report.merge(ValidationReport {
+            errors: vec![issue(&["tls", "web"], "bad certificate")],
+            warnings: Vec::new(),
+        });
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.to_string(),
+            "error at /tls/web: bad certificate\nwarning at /nodes/unused: unreachable\n"
+        );
+    }
+}
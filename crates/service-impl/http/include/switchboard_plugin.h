@@ -0,0 +1,140 @@
+/*
+ * Stable C ABI of switchboard native http plugins, see
+ * crates/service-impl/http/src/instance/class/cabi.rs for the host side.
+ *
+ * A plugin is a shared library exporting `switchboard_plugin_v1`. The kernel calls it once with
+ * the host api and refuses the plugin if its major version differs, its minor version is newer
+ * than the kernel's, or it requires capabilities the kernel lacks.
+ *
+ * Instances may be called from several threads at once and must not block.
+ */
+#ifndef SWITCHBOARD_PLUGIN_H
+#define SWITCHBOARD_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SB_ABI_VERSION_MAJOR 1
+#define SB_ABI_VERSION_MINOR 1
+
+/* host capabilities */
+#define SB_CAP_NODE        (1ull << 0)
+#define SB_CAP_FILTER      (1ull << 1)
+#define SB_CAP_ON_RESPONSE (1ull << 2)
+#define SB_CAP_BODY        (1ull << 3)
+#define SB_CAP_CAPTURES    (1ull << 4)
+
+/* class kinds */
+#define SB_CLASS_NODE   0u
+#define SB_CLASS_FILTER 1u
+
+/* class flags */
+#define SB_CLASS_ON_RESPONSE   (1u << 0) /* call on_response with the response */
+#define SB_CLASS_REQUEST_BODY  (1u << 1) /* buffer the request body for the plugin */
+#define SB_CLASS_RESPONSE_BODY (1u << 2) /* buffer the response body for on_response */
+
+/* results */
+#define SB_OK      0
+#define SB_ERROR   (-1)
+#define SB_NEXT    0 /* on_request: pass the request on */
+#define SB_RESPOND 1 /* on_request: answer with the filled response */
+
+/* log levels */
+#define SB_LOG_TRACE 0u
+#define SB_LOG_DEBUG 1u
+#define SB_LOG_INFO  2u
+#define SB_LOG_WARN  3u
+#define SB_LOG_ERROR 4u
+
+/*
+ * A borrowed string, not nul terminated. Strings returned by the host are valid until the handle
+ * they came from is modified or the call returns. A missing value has a null `ptr`.
+ */
+typedef struct sb_str {
+    const uint8_t *ptr;
+    size_t len;
+} sb_str;
+
+typedef struct sb_request sb_request;
+typedef struct sb_response sb_response;
+typedef struct sb_headers sb_headers;
+
+typedef struct sb_host_api {
+    uint32_t abi_major;
+    uint32_t abi_minor;
+    uint64_t capabilities;
+    void (*log)(uint32_t level, sb_str message);
+    /* the error reported for a failed construct or a call returning SB_ERROR */
+    void (*set_error)(sb_str message);
+
+    sb_str (*request_method)(const sb_request *req);
+    int32_t (*request_set_method)(sb_request *req, sb_str method);
+    sb_str (*request_uri)(const sb_request *req);
+    int32_t (*request_set_uri)(sb_request *req, sb_str uri);
+    sb_headers *(*request_headers)(sb_request *req);
+    sb_str (*request_body)(const sb_request *req);
+    void (*request_set_body)(sb_request *req, sb_str body);
+    sb_str (*request_peer_addr)(const sb_request *req);
+    sb_str (*request_capture)(const sb_request *req, sb_str name);
+    int32_t (*request_set_capture)(sb_request *req, sb_str name, sb_str value);
+
+    uint16_t (*response_status)(const sb_response *res);
+    int32_t (*response_set_status)(sb_response *res, uint16_t status);
+    sb_headers *(*response_headers)(sb_response *res);
+    sb_str (*response_body)(const sb_response *res);
+    void (*response_set_body)(sb_response *res, sb_str body);
+
+    size_t (*headers_len)(const sb_headers *headers);
+    int32_t (*headers_at)(const sb_headers *headers, size_t index, sb_str *name, sb_str *value);
+    sb_str (*headers_get)(const sb_headers *headers, sb_str name);
+    int32_t (*headers_set)(sb_headers *headers, sb_str name, sb_str value);
+    int32_t (*headers_append)(sb_headers *headers, sb_str name, sb_str value);
+    void (*headers_remove)(sb_headers *headers, sb_str name);
+} sb_host_api;
+
+typedef int32_t (*sb_call_fn)(void *instance, sb_request *req, sb_response *res);
+typedef int32_t (*sb_on_response_fn)(void *instance, const sb_request *req, sb_response *res);
+
+typedef struct sb_class {
+    sb_str namespace_;
+    sb_str name;
+    sb_str version;
+    sb_str description;
+    uint32_t kind;
+    uint32_t flags;
+    /* build an instance from the json config */
+    int32_t (*construct)(sb_str config, void **instance);
+    void (*destroy)(void *instance);
+    /* nodes: fill the response */
+    sb_call_fn call_node;
+    /* filters: return SB_NEXT, SB_RESPOND or SB_ERROR */
+    sb_call_fn on_request;
+    /* filters with SB_CLASS_ON_RESPONSE */
+    sb_on_response_fn on_response;
+} sb_class;
+
+typedef struct sb_plugin {
+    sb_str name;
+    uint32_t abi_major;
+    uint32_t abi_minor;
+    uint64_t required_capabilities;
+    size_t class_count;
+    const sb_class *classes;
+    /* since 1.1: json schemas of the class configs, parallel to classes, may be NULL */
+    const sb_str *config_schemas;
+} sb_plugin;
+
+#define SB_STR(literal) ((sb_str){(const uint8_t *)(literal), sizeof(literal) - 1})
+
+/* the entry every plugin exports */
+const sb_plugin *switchboard_plugin_v1(const sb_host_api *host);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -5,6 +5,8 @@ pub const ERR_HTTP_CLIENT: &str = "service.http-client";
 pub const ERR_REVERSE_PROXY: &str = "service.reverse-proxy";
 pub const ERR_STATIC_FILE: &str = "service.static-file";
 pub const ERR_FLOW: &str = "flow";
+pub const ERR_WASM_PLUGIN: &str = "plugin.wasm";
+pub const ERR_NATIVE_PLUGIN: &str = "plugin.native";
 
 pub const ERROR_BALANCER: &str = "balancer";
 // FILTER ERRORS
@@ -13,10 +15,15 @@ pub const ERR_FILTER_REQUEST_MIRROR: &str = "filter.request-mirror";
 pub const ERR_FILTER_REQUEST_RATE_LIMIT: &str = "filter.request-rate-limit";
 pub const ERR_FILTER_REQUEST_HEADER_MODIFY: &str = "filter.request-header-modify";
 pub const ERR_FILTER_RESPONSE_HEADER_MODIFY: &str = "filter.response-header-modify";
+pub const ERR_FILTER_BASIC_AUTH: &str = "filter.basic-auth";
+pub const ERR_FILTER_API_KEY_AUTH: &str = "filter.api-key-auth";
+pub const ERR_FILTER_JWT_AUTH: &str = "filter.jwt-auth";
 
 // headers
 pub const X_FORWARDED_FOR: &str = "x-forwarded-for";
 pub const X_FORWARDED_HEADERS: &str = "x-forwarded-headers";
 pub const X_FORWARDED_HOST: &str = "x-forwarded-host";
+pub const X_FORWARDED_METHOD: &str = "x-forwarded-method";
 pub const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+pub const X_FORWARDED_URI: &str = "x-forwarded-uri";
 pub const X_REAL_IP: &str = "x-real-ip";
@@ -1,2 +1,4 @@
+pub mod auth;
 pub mod captures;
 pub mod marker;
+pub mod traffic_split;
@@ -0,0 +1,48 @@
+use std::{borrow::Cow, sync::Arc};
+
+/// Who an auth filter let through, in the request extensions.
+#[derive(Debug, Clone)]
+pub struct Authenticated {
+    pub method: AuthMethod,
+    /// the basic auth user, the api key name or the `sub` claim
+    pub principal: Arc<str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    Basic,
+    ApiKey,
+    Jwt,
+}
+
+/// The verified claims of a JWT, in the request extensions.
+#[derive(Debug, Clone)]
+pub struct JwtClaims(pub Arc<serde_json::Value>);
+
+impl JwtClaims {
+    /// The claim at a dot separated path, like `org.id`.
+    pub fn get(&self, path: &str) -> Option<&serde_json::Value> {
+        path.split('.')
+            .try_fold(self.0.as_ref(), |value, segment| value.get(segment))
+    }
+
+    /// The claim as text: strings as they are, arrays joined with `,`, other values as JSON.
+    pub fn get_text(&self, path: &str) -> Option<Cow<'_, str>> {
+        let text = match self.get(path)? {
+            serde_json::Value::Null => return None,
+            serde_json::Value::String(value) => Cow::Borrowed(value.as_str()),
+            serde_json::Value::Array(values) => Cow::Owned(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        serde_json::Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            value => Cow::Owned(value.to_string()),
+        };
+        Some(text)
+    }
+}
@@ -0,0 +1,19 @@
+use switchboard_model::services::http::{NodeId, NodePort};
+
+/// The variant a traffic split node sent the request to, in both request and response extensions.
+#[derive(Debug, Clone)]
+pub struct TrafficSplitVariant {
+    pub node: NodeId,
+    pub variant: NodePort,
+    pub assigned_by: VariantAssignment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantAssignment {
+    /// forced by the override header or cookie
+    Override,
+    /// kept from a signed sticky pin
+    Sticky,
+    /// picked at random by weight
+    Weighted,
+}
@@ -15,6 +15,7 @@ use http_body::Body;
 
 use serde::{Deserialize, Serialize};
 use switchboard_model::services::http::{FilterId, NodeId, NodePort, NodeTarget};
+use switchboard_service::tcp::TcpConnectionStats;
 
 use crate::{
     BoxedError, DynBody, DynRequest, DynResponse, IntoDynResponse, box_error, clone_body,
@@ -267,11 +268,15 @@ pub struct ConnectionInfo {
     pub peer_addr: SocketAddr,
     pub http_version: http::Version,
     pub is_tls: bool,
+    pub sni: Option<Arc<str>>,
+    pub alpn: Option<Arc<str>>,
 }
 
 pub struct FlowWithConnectionInfo {
     pub flow: Flow,
     pub connection_info: ConnectionInfo,
+    /// Marked handshake done once the first request was parsed.
+    pub stats: Arc<TcpConnectionStats>,
 }
 
 #[cfg(feature = "service-impl")]
@@ -288,7 +293,9 @@ where
         let FlowWithConnectionInfo {
             flow,
             connection_info,
+            stats,
         } = self;
+        stats.set_handshake_done();
         let req = req.map(|body| {
             use http_body_util::BodyExt;
             body.map_err(box_error).boxed_unsync()
@@ -15,8 +15,9 @@ use crate::{
 mod ip_hash;
 mod random;
 mod round_robin;
+pub mod traffic_split;
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 #[serde(untagged)]
 pub enum WeightedPortsConfig {
     List(Vec<NodePort>),
@@ -37,7 +38,7 @@ pub struct Balancer {
     pub outputs: BTreeMap<NodePort, NodeOutput>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 #[serde(tag = "type", content = "config")]
 pub enum BalancerConfig {
     RoundRobin(WeightedPortsConfig),
@@ -0,0 +1,363 @@
+//! Weighted traffic split, for canaries.
+//!
+//! A variant is taken from the override header or cookie, then from a valid sticky pin, then
+//! picked at random by weight. A pin is
+//! `<variant>.<base64url hmac-sha256(secret, "<name>=<variant>")>`, so clients can't pin
+//! themselves to a variant of their choosing. Variants weighted `0` get no new traffic and lose
+//! their pins, but can still be forced by the override.
+use std::collections::BTreeMap;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use http::{HeaderName, HeaderValue, header::SET_COOKIE, request::Parts};
+use rand::{
+    SeedableRng,
+    distr::{Distribution, weighted::WeightedIndex},
+};
+use sha2::Sha256;
+use switchboard_http_router::rule::request_cookies;
+use switchboard_model::services::http::{
+    ClassId, NodeInterface, NodeOutput, NodePort, WithOutputs, consts::TRAFFIC_SPLIT_CLASS_ID,
+};
+
+use crate::{
+    DynRequest, DynResponse,
+    extension::traffic_split::{TrafficSplitVariant, VariantAssignment},
+    flow::{
+        FlowContext,
+        balancer::WeightedPortsConfig,
+        node::{NodeClass, NodeLike},
+    },
+};
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct TrafficSplitConfig {
+    pub variants: WeightedPortsConfig,
+    #[serde(default)]
+    pub sticky: Option<StickyConfig>,
+    /// Forces the named variant, for testers
+    #[serde(default, rename = "override")]
+    pub override_by: Option<VariantSource>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantSource {
+    Cookie(String),
+    Header(String),
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct StickyConfig {
+    /// Where the pin is kept, a header pin is returned in the same response header for the
+    /// client to send back
+    #[serde(flatten)]
+    pub source: VariantSource,
+    /// HMAC-SHA256 key signing the pins
+    pub secret: String,
+    /// Max-Age of the pin cookie in seconds, a session cookie when unset
+    #[serde(default)]
+    pub max_age: Option<u64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TrafficSplitBuildError {
+    #[error("invalid variant weights: {0}")]
+    Weights(#[from] rand::distr::weighted::Error),
+    #[error("invalid header name: {0}")]
+    HeaderName(#[from] http::header::InvalidHeaderName),
+    #[error("empty cookie name")]
+    EmptyCookieName,
+    #[error("empty sticky secret")]
+    EmptySecret,
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Cookie(String),
+    Header(HeaderName),
+}
+
+impl Source {
+    fn new(config: VariantSource) -> Result<Self, TrafficSplitBuildError> {
+        match config {
+            VariantSource::Cookie(name) if name.is_empty() => {
+                Err(TrafficSplitBuildError::EmptyCookieName)
+            }
+            VariantSource::Cookie(name) => Ok(Source::Cookie(name)),
+            VariantSource::Header(name) => Ok(Source::Header(HeaderName::try_from(name)?)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Source::Cookie(name) => name,
+            Source::Header(name) => name.as_str(),
+        }
+    }
+
+    fn read<'p>(&self, parts: &'p Parts) -> Option<&'p str> {
+        match self {
+            Source::Cookie(name) => request_cookies(parts)
+                .find(|(key, _)| *key == name.as_str())
+                .map(|(_, value)| value),
+            Source::Header(name) => parts.headers.get(name)?.to_str().ok(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Sticky {
+    source: Source,
+    secret: Vec<u8>,
+    max_age: Option<u64>,
+}
+
+impl Sticky {
+    fn mac(&self, variant: &str) -> Hmac<Sha256> {
+        let mut mac =
+            <Hmac<Sha256>>::new_from_slice(&self.secret).expect("hmac accepts keys of any length");
+        mac.update(self.source.name().as_bytes());
+        mac.update(b"=");
+        mac.update(variant.as_bytes());
+        mac
+    }
+
+    fn sign(&self, variant: &str) -> String {
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(variant).finalize().into_bytes());
+        format!("{variant}.{signature}")
+    }
+
+    /// The variant of a pin with a valid signature.
+    fn verify<'v>(&self, pin: &'v str) -> Option<&'v str> {
+        let (variant, signature) = pin.rsplit_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(variant).verify_slice(&signature).ok()?;
+        Some(variant)
+    }
+
+    fn pin_header(&self, variant: &NodePort, secure: bool) -> Option<(HeaderName, HeaderValue)> {
+        let pin = self.sign(variant.as_str());
+        match &self.source {
+            Source::Header(name) => Some((name.clone(), HeaderValue::from_str(&pin).ok()?)),
+            Source::Cookie(name) => {
+                let mut cookie = format!("{name}={pin}; Path=/; HttpOnly; SameSite=Lax");
+                if let Some(max_age) = self.max_age {
+                    cookie.push_str(&format!("; Max-Age={max_age}"));
+                }
+                if secure {
+                    cookie.push_str("; Secure");
+                }
+                Some((SET_COOKIE, HeaderValue::from_str(&cookie).ok()?))
+            }
+        }
+    }
+}
+
+pub struct TrafficSplit {
+    variants: Vec<(NodePort, u32)>,
+    weights: WeightedIndex<u32>,
+    sticky: Option<Sticky>,
+    override_by: Option<Source>,
+    outputs: BTreeMap<NodePort, NodeOutput>,
+}
+
+impl TrafficSplit {
+    /// A configured variant, which must have a positive weight unless `any_weight`.
+    fn variant(&self, name: &str, any_weight: bool) -> Option<NodePort> {
+        self.variants
+            .iter()
+            .find(|(port, weight)| port.as_str() == name && (any_weight || *weight > 0))
+            .map(|(port, _)| port.clone())
+    }
+
+    fn assign(&self, parts: &Parts) -> (NodePort, VariantAssignment) {
+        let forced = self
+            .override_by
+            .as_ref()
+            .and_then(|source| source.read(parts))
+            .and_then(|name| self.variant(name, true));
+        if let Some(variant) = forced {
+            return (variant, VariantAssignment::Override);
+        }
+        let pinned = self
+            .sticky
+            .as_ref()
+            .and_then(|sticky| sticky.verify(sticky.source.read(parts)?))
+            .and_then(|name| self.variant(name, false));
+        if let Some(variant) = pinned {
+            return (variant, VariantAssignment::Sticky);
+        }
+        thread_local! {
+            static RNG: std::cell::RefCell<rand::prelude::SmallRng> = std::cell::RefCell::new(rand::prelude::SmallRng::from_os_rng());
+        };
+        let index = RNG.with_borrow_mut(|rng| self.weights.sample(rng));
+        let (variant, _) = self
+            .variants
+            .get(index)
+            .expect("weighted index samples one of the variants");
+        (variant.clone(), VariantAssignment::Weighted)
+    }
+}
+
+impl NodeLike for TrafficSplit {
+    fn call<'c>(
+        &self,
+        req: DynRequest,
+        context: &'c mut FlowContext,
+    ) -> impl Future<Output = DynResponse> + 'c + Send {
+        let (mut parts, body) = req.into_parts();
+        let (variant, assigned_by) = self.assign(&parts);
+        let split = TrafficSplitVariant {
+            node: context.current_state.node.clone(),
+            variant: variant.clone(),
+            assigned_by,
+        };
+        parts.extensions.insert(split.clone());
+        // only new weighted picks are pinned, a forced variant must not stick
+        let secure = context
+            .connection_info
+            .as_ref()
+            .is_some_and(|info| info.is_tls);
+        let pin = match (&self.sticky, assigned_by) {
+            (Some(sticky), VariantAssignment::Weighted) => sticky.pin_header(&variant, secure),
+            _ => None,
+        };
+        let req = DynRequest::from_parts(parts, body);
+        async move {
+            let mut response = context.call(req, variant).await;
+            if let Some((name, value)) = pin {
+                response.headers_mut().append(name, value);
+            }
+            response.extensions_mut().insert(split);
+            response
+        }
+    }
+
+    fn interface(&self) -> NodeInterface {
+        NodeInterface::with_default_input(self.outputs.clone())
+    }
+}
+
+pub struct TrafficSplitClass;
+
+impl NodeClass for TrafficSplitClass {
+    type Config = WithOutputs<TrafficSplitConfig>;
+    type Error = TrafficSplitBuildError;
+    type Node = TrafficSplit;
+    fn construct(&self, config: Self::Config) -> Result<Self::Node, Self::Error> {
+        let TrafficSplitConfig {
+            variants,
+            sticky,
+            override_by,
+        } = config.config;
+        let variants = variants.to_map().into_iter().collect::<Vec<_>>();
+        let weights = WeightedIndex::new(variants.iter().map(|(_, weight)| *weight))?;
+        let sticky = sticky
+            .map(|sticky| {
+                if sticky.secret.is_empty() {
+                    return Err(TrafficSplitBuildError::EmptySecret);
+                }
+                Ok(Sticky {
+                    source: Source::new(sticky.source)?,
+                    secret: sticky.secret.into_bytes(),
+                    max_age: sticky.max_age,
+                })
+            })
+            .transpose()?;
+        let override_by = override_by.map(Source::new).transpose()?;
+        Ok(TrafficSplit {
+            variants,
+            weights,
+            sticky,
+            override_by,
+            outputs: config.output,
+        })
+    }
+
+    fn id(&self) -> ClassId {
+        ClassId::std(TRAFFIC_SPLIT_CLASS_ID)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn split(sticky: Option<Sticky>) -> TrafficSplit {
+        let variants = vec![(NodePort::from("stable"), 1), (NodePort::from("canary"), 0)];
+        TrafficSplit {
+            weights: WeightedIndex::new(variants.iter().map(|(_, weight)| *weight)).unwrap(),
+            variants,
+            sticky,
+            override_by: Some(Source::Header(HeaderName::from_static("x-variant"))),
+            outputs: BTreeMap::new(),
+        }
+    }
+
+    fn sticky(secret: &str) -> Sticky {
+        Sticky {
+            source: Source::Cookie("split".to_string()),
+            secret: secret.as_bytes().to_vec(),
+            max_age: Some(60),
+        }
+    }
+
+    fn parts(headers: &[(&str, &str)]) -> Parts {
+        let mut request = http::Request::builder();
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_pin_signature() {
+        let sticky = sticky("secret");
+        let pin = sticky.sign("canary");
+        assert_eq!(sticky.verify(&pin), Some("canary"));
+        assert_eq!(sticky.verify(&pin.replace("canary", "stable")), None);
+        assert_eq!(self::sticky("other").verify(&pin), None);
+        assert_eq!(sticky.verify("canary"), None);
+        let (_, cookie) = sticky.pin_header(&NodePort::from("canary"), true).unwrap();
+        assert_eq!(
+            cookie.to_str().unwrap(),
+            format!("split={pin}; Path=/; HttpOnly; SameSite=Lax; Max-Age=60; Secure")
+        );
+    }
+
+    #[test]
+    fn test_assign_variant() {
+        let node = split(Some(sticky("secret")));
+        let weighted = node.assign(&parts(&[]));
+        assert_eq!(
+            weighted,
+            (NodePort::from("stable"), VariantAssignment::Weighted)
+        );
+
+        // a zero weight variant can only be forced
+        let forced = node.assign(&parts(&[("x-variant", "canary")]));
+        assert_eq!(
+            forced,
+            (NodePort::from("canary"), VariantAssignment::Override)
+        );
+        let unknown = node.assign(&parts(&[("x-variant", "nope")]));
+        assert_eq!(unknown.1, VariantAssignment::Weighted);
+        let cookie = format!("split={}", sticky("secret").sign("canary"));
+        let drained = node.assign(&parts(&[("cookie", cookie.as_str())]));
+        assert_eq!(
+            drained,
+            (NodePort::from("stable"), VariantAssignment::Weighted)
+        );
+
+        let cookie = format!("a=1; split={}", sticky("secret").sign("stable"));
+        let pinned = node.assign(&parts(&[("cookie", cookie.as_str())]));
+        assert_eq!(
+            pinned,
+            (NodePort::from("stable"), VariantAssignment::Sticky)
+        );
+        let forged = format!("split={}", sticky("guess").sign("stable"));
+        let forged = node.assign(&parts(&[("cookie", forged.as_str())]));
+        assert_eq!(forged.1, VariantAssignment::Weighted);
+    }
+}
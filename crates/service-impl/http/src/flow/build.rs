@@ -1,8 +1,12 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
 
 use switchboard_model::services::http::{
-    FlowConfig, InstanceId, InstanceType, NodePort, NodeTarget,
+    FilterId, FlowConfig, InstanceId, InstanceType, NodeId, NodePort, NodeTarget,
 };
+use switchboard_service::ValidationReport;
 
 use crate::flow::Flow;
 use crate::instance::{self, class::registry::ClassRegistryError};
@@ -52,6 +56,52 @@ pub enum FlowCheckError {
     },
 }
 
+impl std::error::Error for FlowCheckError {}
+
+impl FlowCheckError {
+    /// A dangling node target only fails the requests routed to it, the flow still works.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, FlowCheckError::NodeTargetNotFound { .. })
+    }
+}
+
+/// The section of a flow config an instance is declared in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlowSection {
+    Nodes,
+    Filters,
+}
+
+impl FlowSection {
+    fn path(self, id: impl ToString) -> Vec<String> {
+        let section = match self {
+            FlowSection::Nodes => "nodes",
+            FlowSection::Filters => "filters",
+        };
+        vec![section.to_string(), id.to_string()]
+    }
+    fn instance_type(self) -> InstanceType {
+        match self {
+            FlowSection::Nodes => InstanceType::Node,
+            FlowSection::Filters => InstanceType::Filter,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FlowValidateError {
+    #[error("Class `{class}` constructs a {actual:?}, but is declared as a {expected:?}")]
+    InstanceTypeMismatch {
+        class: switchboard_model::services::http::ClassId,
+        expected: InstanceType,
+        actual: InstanceType,
+    },
+    #[error("Node `{0}` is unreachable from the entrypoint")]
+    UnreachableNode(NodeId),
+    #[error("Filter `{0}` is never referenced")]
+    UnusedFilter(FilterId),
+}
+
 impl std::fmt::Display for FlowCheckError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -143,6 +193,158 @@ impl Flow {
         check_errors
     }
 
+    /// Construct every node and filter separately and check the resulting graph,
+    /// reporting all problems instead of stopping at the first one.
+    ///
+    /// Issue paths are relative to the flow config, e.g. `["nodes", "<id>"]`.
+    pub fn validate(config: FlowConfig, class_registry: &ClassRegistry) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let mut failed = HashSet::new();
+        let mut filters = HashMap::new();
+        let mut nodes = HashMap::new();
+        for (section, id, instance) in config
+            .nodes
+            .into_iter()
+            .map(|(id, instance)| (FlowSection::Nodes, id, instance))
+            .chain(
+                config
+                    .filters
+                    .into_iter()
+                    .map(|(id, instance)| (FlowSection::Filters, id, instance)),
+            )
+        {
+            let path = section.path(&id);
+            let class = instance.class.clone();
+            // point at the offending fields rather than at a serde error for the whole config
+            #[cfg(feature = "service-impl")]
+            {
+                let schema_errors = class_registry.check_config(&class, &instance.config);
+                if !schema_errors.is_empty() {
+                    for error in schema_errors {
+                        let field_path = path
+                            .iter()
+                            .cloned()
+                            .chain(["config".to_string()])
+                            .chain(error.path.iter().cloned())
+                            .collect::<Vec<_>>();
+                        report.error(field_path, error);
+                    }
+                    failed.insert(id);
+                    continue;
+                }
+            }
+            match (
+                section,
+                class_registry.construct(instance.class, instance.config),
+            ) {
+                (FlowSection::Nodes, Ok(instance::InstanceValue::Node(node))) => {
+                    nodes.insert(id, node);
+                }
+                (FlowSection::Filters, Ok(instance::InstanceValue::Filter(filter))) => {
+                    filters.insert(id, filter);
+                }
+                (_, Ok(instance)) => {
+                    let actual = match instance {
+                        instance::InstanceValue::Node(_) => InstanceType::Node,
+                        instance::InstanceValue::Filter(_) => InstanceType::Filter,
+                    };
+                    report.error(
+                        path,
+                        FlowValidateError::InstanceTypeMismatch {
+                            class,
+                            expected: section.instance_type(),
+                            actual,
+                        },
+                    );
+                    failed.insert(id);
+                }
+                (_, Err(e)) => {
+                    report.error(path, FlowBuildError::from(e));
+                    failed.insert(id);
+                }
+            }
+        }
+        let flow = Flow {
+            nodes: Arc::new(nodes),
+            filters: Arc::new(filters),
+            entrypoint: config.entrypoint,
+        };
+        // references to instances that failed to construct are already reported
+        for check_error in flow.check() {
+            let path = match &check_error {
+                FlowCheckError::EntrypointNotFound { entrypoint } => {
+                    if failed.contains(&entrypoint.id) {
+                        continue;
+                    }
+                    vec!["entrypoint".to_string()]
+                }
+                FlowCheckError::FilterNotFound { filter, location } => {
+                    if failed.contains(filter) {
+                        continue;
+                    }
+                    FlowSection::Nodes.path(&location.node_id)
+                }
+                FlowCheckError::NodeTargetNotFound { target, location } => {
+                    if failed.contains(&target.id) {
+                        continue;
+                    }
+                    FlowSection::Nodes.path(&location.node_id)
+                }
+            };
+            if check_error.is_fatal() {
+                report.error(path, check_error);
+            } else {
+                report.warning(path, check_error);
+            }
+        }
+        // reachability is meaningless when part of the graph could not be built
+        if failed.is_empty() {
+            let mut reachable = HashSet::new();
+            let mut used_filters = HashSet::new();
+            let mut queue = VecDeque::from([flow.entrypoint.id.clone()]);
+            while let Some(node_id) = queue.pop_front() {
+                let Some(node) = flow.nodes.get(&node_id) else {
+                    continue;
+                };
+                if !reachable.insert(node_id) {
+                    continue;
+                }
+                for input in node.interface.inputs.values() {
+                    used_filters.extend(input.filters.iter().map(|f| f.id.clone()));
+                }
+                for output in node.interface.outputs.values() {
+                    used_filters.extend(output.filters.iter().map(|f| f.id.clone()));
+                    queue.push_back(output.target.id.clone());
+                }
+            }
+            let mut unreachable = flow
+                .nodes
+                .keys()
+                .filter(|id| !reachable.contains(*id))
+                .collect::<Vec<_>>();
+            unreachable.sort();
+            for node_id in unreachable {
+                report.warning(
+                    FlowSection::Nodes.path(node_id),
+                    FlowValidateError::UnreachableNode(node_id.clone()),
+                );
+            }
+            let mut unused = flow
+                .filters
+                .keys()
+                .filter(|id| !used_filters.contains(*id))
+                .collect::<Vec<_>>();
+            unused.sort();
+            for filter_id in unused {
+                report.warning(
+                    FlowSection::Filters.path(filter_id),
+                    FlowValidateError::UnusedFilter(filter_id.clone()),
+                );
+            }
+        }
+        report
+    }
+
     pub fn build(
         config: FlowConfig,
         class_registry: &ClassRegistry,
@@ -176,7 +378,11 @@ impl Flow {
             filters: Arc::new(filters),
             entrypoint: config.entrypoint,
         };
-        let errors = flow.check();
+        let (errors, dangling): (Vec<_>, Vec<_>) =
+            flow.check().into_iter().partition(FlowCheckError::is_fatal);
+        for check_error in dangling {
+            tracing::warn!("{check_error}, requests routed to it fail");
+        }
         if errors.is_empty() {
             Ok(flow)
         } else {
@@ -184,3 +390,106 @@ impl Flow {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn validate(flow: serde_json::Value) -> ValidationReport {
+        let mut registry = ClassRegistry::default();
+        registry.register_prelude();
+        Flow::validate(serde_json::from_value(flow).unwrap(), &registry)
+    }
+
+    fn paths(issues: &[switchboard_service::ValidationIssue]) -> Vec<String> {
+        let mut paths = issues
+            .iter()
+            .map(|issue| issue.path.join("/"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_validate_graph() {
+        let report = validate(serde_json::json!({
+            "entrypoint": "ok",
+            "nodes": {
+                "ok": { "class": "static-response", "config": { "status_code": 200 } },
+                "orphan": { "class": "static-response", "config": {} },
+            },
+            "filters": {
+                "headers": { "class": "request-header-modify", "config": {} },
+            },
+        }));
+        assert!(report.errors.is_empty(), "{:?}", paths(&report.errors));
+        assert_eq!(paths(&report.warnings), ["filters/headers", "nodes/orphan"]);
+
+        let report = validate(serde_json::json!({
+            "entrypoint": "missing",
+            "nodes": {
+                "ok": { "class": "static-response", "config": {} },
+            },
+        }));
+        assert_eq!(paths(&report.errors), ["entrypoint"]);
+
+        // a dangling target only fails the requests routed to it
+        let report = validate(serde_json::json!({
+            "entrypoint": "split",
+            "nodes": {
+                "split": { "class": "balancer", "config": {
+                    "type": "RoundRobin",
+                    "config": ["a", "b"],
+                    "output": { "a": { "target": "ok" }, "b": { "target": "missing" } },
+                } },
+                "ok": { "class": "static-response", "config": {} },
+            },
+        }));
+        assert!(report.errors.is_empty(), "{:?}", paths(&report.errors));
+        assert_eq!(paths(&report.warnings), ["nodes/split"]);
+    }
+
+    #[test]
+    fn test_validate_instances() {
+        // every broken instance is reported, not only the first one
+        let report = validate(serde_json::json!({
+            "entrypoint": "unknown",
+            "nodes": {
+                "unknown": { "class": "no-such-class", "config": {} },
+                "invalid": { "class": "static-response", "config": { "status_code": 1000 } },
+                "misplaced": { "class": "request-header-modify", "config": {} },
+            },
+        }));
+        assert_eq!(
+            paths(&report.errors),
+            ["nodes/invalid", "nodes/misplaced", "nodes/unknown"]
+        );
+        // the entrypoint failed to construct and the graph is incomplete, so no follow-up issues
+        assert!(report.warnings.is_empty());
+        let misplaced = report
+            .errors
+            .iter()
+            .find(|issue| issue.path == ["nodes", "misplaced"])
+            .unwrap();
+        assert!(matches!(
+            misplaced.error.downcast_ref::<FlowValidateError>(),
+            Some(FlowValidateError::InstanceTypeMismatch {
+                expected: InstanceType::Node,
+                actual: InstanceType::Filter,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validate_config_schema() {
+        let report = validate(serde_json::json!({
+            "entrypoint": "ok",
+            "nodes": {
+                "ok": { "class": "static-response", "config": { "status_code": "ok" } },
+            },
+        }));
+        // the schema error points at the field, and the node isn't reported again as missing
+        assert_eq!(paths(&report.errors), ["nodes/ok/config/status_code"]);
+    }
+}
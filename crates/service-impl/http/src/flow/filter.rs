@@ -1,4 +1,14 @@
 #[cfg(feature = "service-impl")]
+pub mod api_key_auth;
+#[cfg(feature = "service-impl")]
+pub mod basic_auth;
+#[cfg(feature = "service-impl")]
+pub mod cors;
+#[cfg(feature = "service-impl")]
+pub mod forward_auth;
+#[cfg(feature = "service-impl")]
+pub mod jwt_auth;
+#[cfg(feature = "service-impl")]
 pub mod request_header_modify;
 #[cfg(feature = "service-impl")]
 pub mod request_mirror;
@@ -16,6 +26,7 @@ pub mod url_rewrite;
 use std::sync::Arc;
 
 use futures::future::BoxFuture;
+use schemars::{JsonSchema, Schema, schema_for};
 use serde::de::DeserializeOwned;
 use switchboard_model::services::http::*;
 
@@ -108,11 +119,14 @@ impl Filter {
 pub trait FilterClass: Send + Sync + 'static {
     type Filter: FilterLike;
     type Error: std::error::Error + Send + Sync + 'static;
-    type Config: DeserializeOwned;
+    type Config: DeserializeOwned + JsonSchema;
     fn id(&self) -> ClassId;
     fn meta(&self) -> ClassMeta {
         ClassMeta::from_env()
     }
+    fn schema(&self) -> Schema {
+        schema_for!(Self::Config)
+    }
     fn construct(&self, config: Self::Config) -> Result<Self::Filter, Self::Error>;
 }
 
@@ -131,6 +145,9 @@ where
     fn meta(&self) -> ClassMeta {
         ClassMeta::default()
     }
+    fn schema(&self) -> Schema {
+        self.0.schema()
+    }
     fn instance_type(&self) -> InstanceType {
         InstanceType::Filter
     }
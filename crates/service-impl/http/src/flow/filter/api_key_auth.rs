@@ -0,0 +1,238 @@
+use std::{collections::HashMap, sync::Arc};
+
+use http::{HeaderName, StatusCode, request::Parts};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use switchboard_http_router::utils::query_kv::QueryKvIter;
+use switchboard_model::services::http::ClassId;
+
+use crate::{
+    DynRequest, DynResponse,
+    consts::ERR_FILTER_API_KEY_AUTH,
+    extension::auth::{AuthMethod, Authenticated},
+    flow::filter::{FilterClass, FilterLike},
+    utils::error_response,
+};
+
+/// A list kept in the controller storage can be linked as the whole config, like
+/// `config = "storage://api-keys#3"`.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ApiKeyAuthFilterConfig {
+    /// the header carrying the key, `x-api-key` when neither `header` nor `query` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// the query parameter carrying the key, compared as sent without percent-decoding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ApiKeyConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// the hex encoded SHA-256 of the key, keeps the key itself out of the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiKeyAuthFilterConfigError {
+    #[error("Invalid header name: {0}")]
+    InvalidHeaderName(#[from] http::header::InvalidHeaderName),
+    #[error("Key `{0}` needs exactly one of `key` or a hex `sha256`")]
+    InvalidKey(String),
+    #[error("No keys configured")]
+    NoKeys,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiKeyAuthError {
+    #[error("Missing api key")]
+    MissingKey,
+    #[error("Invalid api key")]
+    InvalidKey,
+}
+
+pub struct ApiKeyAuthFilter {
+    pub header: Option<HeaderName>,
+    pub query: Option<String>,
+    /// key names by the SHA-256 of the key, so lookups don't time the key itself
+    pub keys: HashMap<[u8; 32], Arc<str>>,
+}
+
+impl ApiKeyAuthFilter {
+    fn presented_key<'p>(&self, parts: &'p Parts) -> Option<&'p str> {
+        let from_header = self.header.as_ref().and_then(|header| {
+            parts
+                .headers
+                .get(header)
+                .and_then(|value| value.to_str().ok())
+        });
+        let from_query = || {
+            let query = self.query.as_deref()?;
+            QueryKvIter::new(parts.uri.query()?)
+                .find(|(key, _)| *key == query)
+                .and_then(|(_, value)| value)
+        };
+        from_header
+            .or_else(from_query)
+            .filter(|key| !key.is_empty())
+    }
+
+    /// The name of the presented key.
+    pub fn authenticate(&self, parts: &Parts) -> Result<Arc<str>, ApiKeyAuthError> {
+        let key = self
+            .presented_key(parts)
+            .ok_or(ApiKeyAuthError::MissingKey)?;
+        let digest: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        self.keys
+            .get(&digest)
+            .cloned()
+            .ok_or(ApiKeyAuthError::InvalidKey)
+    }
+}
+
+impl FilterLike for ApiKeyAuthFilter {
+    async fn call(
+        self: Arc<Self>,
+        req: DynRequest,
+        ctx: &mut crate::flow::FlowContext,
+        next: super::Next,
+    ) -> DynResponse {
+        let (mut parts, body) = req.into_parts();
+        match self.authenticate(&parts) {
+            Ok(name) => {
+                parts.extensions.insert(Authenticated {
+                    method: AuthMethod::ApiKey,
+                    principal: name,
+                });
+                next.call(DynRequest::from_parts(parts, body), ctx).await
+            }
+            Err(e) => {
+                tracing::debug!(error = %e, "api key rejected");
+                error_response(StatusCode::UNAUTHORIZED, e, ERR_FILTER_API_KEY_AUTH)
+            }
+        }
+    }
+}
+
+fn parse_sha256(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim().as_bytes();
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut digest = [0u8; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.chunks_exact(2)) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(digest)
+}
+
+pub struct ApiKeyAuthFilterClass;
+
+impl FilterClass for ApiKeyAuthFilterClass {
+    type Filter = ApiKeyAuthFilter;
+    type Error = ApiKeyAuthFilterConfigError;
+    type Config = ApiKeyAuthFilterConfig;
+
+    fn id(&self) -> ClassId {
+        ClassId::std("api-key-auth")
+    }
+
+    fn construct(&self, config: Self::Config) -> Result<Self::Filter, Self::Error> {
+        let header = match (&config.header, &config.query) {
+            (Some(header), _) => Some(HeaderName::from_bytes(header.as_bytes())?),
+            (None, None) => Some(HeaderName::from_static("x-api-key")),
+            (None, Some(_)) => None,
+        };
+        if config.keys.is_empty() {
+            return Err(ApiKeyAuthFilterConfigError::NoKeys);
+        }
+        let keys = config
+            .keys
+            .into_iter()
+            .map(|key| {
+                let digest = match (&key.key, &key.sha256) {
+                    (Some(plain), None) if !plain.is_empty() => {
+                        Some(Sha256::digest(plain.as_bytes()).into())
+                    }
+                    (None, Some(hex)) => parse_sha256(hex),
+                    _ => None,
+                };
+                digest
+                    .map(|digest| (digest, Arc::from(key.name.as_str())))
+                    .ok_or(ApiKeyAuthFilterConfigError::InvalidKey(key.name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ApiKeyAuthFilter {
+            header,
+            query: config.query,
+            keys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parts(request: http::request::Builder) -> Parts {
+        request.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let filter = ApiKeyAuthFilterClass
+            .construct(
+                serde_json::from_value(serde_json::json!({
+                    "header": "x-api-key",
+                    "query": "api_key",
+                    "keys": [
+                        { "name": "ci", "key": "ci-key" },
+                        {
+                            "name": "partner",
+                            // sha256 of "partner-key"
+                            "sha256": "346E50AF211B5135824BB2BB58FE0F9E6DF228ADCF10C58A37FBC46B57BAEE74",
+                        },
+                    ],
+                }))
+                .unwrap(),
+            )
+            .unwrap();
+        let by_header = parts(http::Request::builder().header("x-api-key", "ci-key"));
+        assert_eq!(filter.authenticate(&by_header).unwrap().as_ref(), "ci");
+        let partner = parts(http::Request::builder().header("x-api-key", "partner-key"));
+        assert_eq!(filter.authenticate(&partner).unwrap().as_ref(), "partner");
+        let by_query = parts(http::Request::builder().uri("/?page=2&api_key=ci-key"));
+        assert_eq!(filter.authenticate(&by_query).unwrap().as_ref(), "ci");
+        let wrong = parts(http::Request::builder().header("x-api-key", "ci-key2"));
+        assert!(matches!(
+            filter.authenticate(&wrong),
+            Err(ApiKeyAuthError::InvalidKey)
+        ));
+        let missing = parts(http::Request::builder().uri("/?api_key="));
+        assert!(matches!(
+            filter.authenticate(&missing),
+            Err(ApiKeyAuthError::MissingKey)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let cases = [
+            serde_json::json!({ "keys": [] }),
+            serde_json::json!({ "keys": [{ "name": "none" }] }),
+            serde_json::json!({ "keys": [{ "name": "both", "key": "a", "sha256": "00" }] }),
+            serde_json::json!({ "keys": [{ "name": "short", "sha256": "abcd" }] }),
+            serde_json::json!({ "header": "bad header", "keys": [{ "name": "a", "key": "a" }] }),
+        ];
+        for config in cases {
+            let config = serde_json::from_value(config).unwrap();
+            assert!(ApiKeyAuthFilterClass.construct(config).is_err());
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use http::{
+    HeaderMap, HeaderValue, StatusCode,
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use switchboard_model::services::http::ClassId;
+
+use crate::{
+    DynRequest, DynResponse,
+    consts::ERR_FILTER_BASIC_AUTH,
+    extension::auth::{AuthMethod, Authenticated},
+    flow::filter::{FilterClass, FilterLike},
+    utils::{constant_time::constant_time_eq, error_response},
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct BasicAuthFilterConfig {
+    /// htpasswd lines like `alice:$2y$10$...`, bcrypt (`htpasswd -B`) and `{SHA}` hashes are
+    /// supported
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<String>,
+    /// an htpasswd file read when the filter is built, `users` win over its lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub htpasswd_file: Option<PathBuf>,
+    #[serde(default = "default_realm")]
+    pub realm: String,
+}
+
+fn default_realm() -> String {
+    "switchboard".to_string()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BasicAuthFilterConfigError {
+    #[error("Failed to read htpasswd file {}: {source}", path.display())]
+    ReadHtpasswd {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid htpasswd line {0}, expected `user:hash`")]
+    InvalidLine(usize),
+    #[error("Unsupported password hash of user `{0}`, use bcrypt (`htpasswd -B`) or `{{SHA}}`")]
+    UnsupportedHash(String),
+    #[error("Realm can't contain quotes, backslashes or control characters")]
+    InvalidRealm,
+    #[error("No users configured")]
+    NoUsers,
+}
+
+#[derive(Debug, Clone)]
+pub enum PasswordHash {
+    Bcrypt(String),
+    /// the `{SHA}` scheme, a base64 encoded SHA-1 digest
+    Sha1(Vec<u8>),
+}
+
+impl PasswordHash {
+    fn parse(user: &str, hash: &str) -> Result<Self, BasicAuthFilterConfigError> {
+        if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$") {
+            return Ok(PasswordHash::Bcrypt(hash.to_string()));
+        }
+        hash.strip_prefix("{SHA}")
+            .and_then(|digest| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(digest)
+                    .ok()
+            })
+            .filter(|digest| digest.len() == 20)
+            .map(PasswordHash::Sha1)
+            .ok_or_else(|| BasicAuthFilterConfigError::UnsupportedHash(user.to_string()))
+    }
+}
+
+/// Add the users of htpasswd formatted `source`, skipping blank and `#` lines.
+pub fn parse_htpasswd(
+    source: &str,
+    users: &mut HashMap<String, PasswordHash>,
+) -> Result<(), BasicAuthFilterConfigError> {
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (user, hash) = line
+            .split_once(':')
+            .filter(|(user, hash)| !user.is_empty() && !hash.is_empty())
+            .ok_or(BasicAuthFilterConfigError::InvalidLine(index + 1))?;
+        users.insert(user.to_string(), PasswordHash::parse(user, hash)?);
+    }
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BasicAuthError {
+    #[error("Missing basic auth credentials")]
+    MissingCredentials,
+    #[error("Invalid basic auth credentials")]
+    InvalidCredentials,
+}
+
+pub struct BasicAuthFilter {
+    pub users: HashMap<String, PasswordHash>,
+    pub challenge: HeaderValue,
+    /// bcrypt is slow on purpose, a password that passed once is remembered by its keyed digest
+    verified: Mutex<HashMap<String, Vec<u8>>>,
+    verified_key: [u8; 32],
+}
+
+impl BasicAuthFilter {
+    fn verified_digest(&self, password: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.verified_key)
+            .expect("hmac accepts keys of any length");
+        mac.update(password.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// The user name of valid `Authorization: Basic` credentials.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<String, BasicAuthError> {
+        let credentials = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("basic"))
+            .map(|(_, credentials)| credentials.trim())
+            .ok_or(BasicAuthError::MissingCredentials)?;
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(credentials)
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .ok_or(BasicAuthError::InvalidCredentials)?;
+        let (user, password) = decoded
+            .split_once(':')
+            .ok_or(BasicAuthError::InvalidCredentials)?;
+        let hash = self
+            .users
+            .get(user)
+            .ok_or(BasicAuthError::InvalidCredentials)?;
+        let verified = match hash {
+            PasswordHash::Sha1(digest) => {
+                constant_time_eq(Sha1::digest(password.as_bytes()).as_slice(), digest)
+            }
+            PasswordHash::Bcrypt(hash) => {
+                let digest = self.verified_digest(password);
+                let remembered = self
+                    .verified
+                    .lock()
+                    .expect("verified passwords lock poisoned")
+                    .get(user)
+                    .is_some_and(|remembered| constant_time_eq(remembered, &digest));
+                if remembered {
+                    true
+                } else {
+                    let hash = hash.clone();
+                    let password = password.to_string();
+                    // keep the hashing off the async workers
+                    let verified =
+                        tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash))
+                            .await
+                            .is_ok_and(|verified| verified.unwrap_or(false));
+                    if verified {
+                        self.verified
+                            .lock()
+                            .expect("verified passwords lock poisoned")
+                            .insert(user.to_string(), digest);
+                    }
+                    verified
+                }
+            }
+        };
+        if !verified {
+            return Err(BasicAuthError::InvalidCredentials);
+        }
+        Ok(user.to_string())
+    }
+}
+
+impl FilterLike for BasicAuthFilter {
+    async fn call(
+        self: Arc<Self>,
+        mut req: DynRequest,
+        ctx: &mut crate::flow::FlowContext,
+        next: super::Next,
+    ) -> DynResponse {
+        let authenticated = self.authenticate(req.headers()).await;
+        match authenticated {
+            Ok(user) => {
+                req.extensions_mut().insert(Authenticated {
+                    method: AuthMethod::Basic,
+                    principal: Arc::from(user),
+                });
+                next.call(req, ctx).await
+            }
+            Err(e) => {
+                tracing::debug!(error = %e, "basic auth rejected");
+                let mut response =
+                    error_response(StatusCode::UNAUTHORIZED, e, ERR_FILTER_BASIC_AUTH);
+                response
+                    .headers_mut()
+                    .insert(WWW_AUTHENTICATE, self.challenge.clone());
+                response
+            }
+        }
+    }
+}
+
+pub struct BasicAuthFilterClass;
+
+impl FilterClass for BasicAuthFilterClass {
+    type Filter = BasicAuthFilter;
+    type Error = BasicAuthFilterConfigError;
+    type Config = BasicAuthFilterConfig;
+
+    fn id(&self) -> ClassId {
+        ClassId::std("basic-auth")
+    }
+
+    fn construct(&self, config: Self::Config) -> Result<Self::Filter, Self::Error> {
+        let mut users = HashMap::new();
+        if let Some(path) = config.htpasswd_file {
+            let source = std::fs::read_to_string(&path)
+                .map_err(|source| BasicAuthFilterConfigError::ReadHtpasswd { path, source })?;
+            parse_htpasswd(&source, &mut users)?;
+        }
+        parse_htpasswd(&config.users.join("\n"), &mut users)?;
+        if users.is_empty() {
+            return Err(BasicAuthFilterConfigError::NoUsers);
+        }
+        if config
+            .realm
+            .chars()
+            .any(|c| c == '"' || c == '\\' || c.is_control())
+        {
+            return Err(BasicAuthFilterConfigError::InvalidRealm);
+        }
+        let challenge = HeaderValue::from_str(&format!(
+            "Basic realm=\"{}\", charset=\"UTF-8\"",
+            config.realm
+        ))
+        .map_err(|_| BasicAuthFilterConfigError::InvalidRealm)?;
+        Ok(BasicAuthFilter {
+            users,
+            challenge,
+            verified: Mutex::new(HashMap::new()),
+            verified_key: rand::random(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn headers(user: &str, password: &str) -> HeaderMap {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{user}:{password}"));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Basic {credentials}")).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_parse_htpasswd() {
+        let mut users = HashMap::new();
+        parse_htpasswd(
+            "# admins\nalice:$2y$05$abcdefghijklmnopqrstuu\n\nbob:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=\n",
+            &mut users,
+        )
+        .unwrap();
+        assert!(matches!(users["alice"], PasswordHash::Bcrypt(_)));
+        assert!(matches!(users["bob"], PasswordHash::Sha1(_)));
+        assert!(matches!(
+            parse_htpasswd("carol:$apr1$salt$hash", &mut users),
+            Err(BasicAuthFilterConfigError::UnsupportedHash(user)) if user == "carol"
+        ));
+        assert!(matches!(
+            parse_htpasswd("ok:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=\nbroken", &mut users),
+            Err(BasicAuthFilterConfigError::InvalidLine(2))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let bcrypt_hash = bcrypt::hash("s3cret", 4).unwrap();
+        let filter = BasicAuthFilterClass
+            .construct(BasicAuthFilterConfig {
+                users: vec![
+                    format!("alice:{bcrypt_hash}"),
+                    "bob:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=".to_string(),
+                ],
+                htpasswd_file: None,
+                realm: default_realm(),
+            })
+            .unwrap();
+        for _ in 0..2 {
+            let user = filter.authenticate(&headers("alice", "s3cret")).await;
+            assert_eq!(user.unwrap(), "alice");
+        }
+        assert_eq!(
+            filter
+                .authenticate(&headers("bob", "password"))
+                .await
+                .unwrap(),
+            "bob"
+        );
+        assert!(matches!(
+            filter.authenticate(&headers("alice", "wrong")).await,
+            Err(BasicAuthError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            filter.authenticate(&headers("mallory", "s3cret")).await,
+            Err(BasicAuthError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            filter.authenticate(&HeaderMap::new()).await,
+            Err(BasicAuthError::MissingCredentials)
+        ));
+    }
+}
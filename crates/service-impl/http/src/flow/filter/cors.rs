@@ -0,0 +1,444 @@
+use http::{
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+    header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+        ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD,
+        ORIGIN, VARY,
+    },
+};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use switchboard_model::services::http::ClassId;
+
+use crate::{
+    DynRequest, DynResponse, empty_body,
+    flow::filter::{FilterClass, FilterLike},
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct CorsFilterConfig {
+    /// `*`, an exact origin like `https://example.com`, a subdomain wildcard like
+    /// `https://*.example.com`, or `re:<regex>` matched against the whole origin
+    pub allow_origins: Vec<String>,
+    /// `*` allows any method
+    pub allow_methods: Vec<String>,
+    /// `*` allows any header
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allow_headers: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expose_headers: Vec<String>,
+    /// wildcards are answered with the requested origin, method and headers instead, since
+    /// browsers refuse `*` for credentialed requests
+    pub allow_credentials: bool,
+    /// how long a preflight may be cached, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u64>,
+}
+
+impl Default for CorsFilterConfig {
+    fn default() -> Self {
+        Self {
+            allow_origins: Vec::new(),
+            allow_methods: vec!["GET".to_string(), "HEAD".to_string(), "POST".to_string()],
+            allow_headers: Vec::new(),
+            expose_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CorsFilterConfigError {
+    #[error("Invalid origin regex: {0}")]
+    InvalidOriginRegex(#[from] regex::Error),
+    #[error("Invalid origin: {0}")]
+    InvalidOrigin(String),
+    #[error("Invalid method: {0}")]
+    InvalidMethod(#[from] http::method::InvalidMethod),
+    #[error("Invalid header name: {0}")]
+    InvalidHeaderName(#[from] http::header::InvalidHeaderName),
+    #[error("Invalid header value: {0}")]
+    InvalidHeaderValue(#[from] http::header::InvalidHeaderValue),
+    #[error("`*` origin can't be used with allow_credentials")]
+    CredentialsWithAnyOrigin,
+    #[error("`*` expose_headers can't be used with allow_credentials")]
+    CredentialsWithAnyExposeHeader,
+}
+
+#[derive(Debug, Clone)]
+pub enum OriginMatcher {
+    Any,
+    /// lowercased
+    Exact(String),
+    /// `https://*.example.com` is `https://` and `.example.com`
+    Subdomain {
+        scheme: String,
+        suffix: String,
+    },
+    Regex(Regex),
+}
+
+impl OriginMatcher {
+    fn parse(origin: &str) -> Result<Self, CorsFilterConfigError> {
+        let origin = origin.trim();
+        if origin == "*" {
+            return Ok(OriginMatcher::Any);
+        }
+        if let Some(regex) = origin.strip_prefix("re:") {
+            return Ok(OriginMatcher::Regex(Regex::new(&format!("^(?:{regex})$"))?));
+        }
+        let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+        let wildcard = origin
+            .split_once("://")
+            .and_then(|(scheme, host)| Some((scheme, host.strip_prefix('*')?)));
+        if let Some((scheme, suffix)) = wildcard {
+            if !suffix.starts_with('.') || suffix.contains('*') {
+                return Err(CorsFilterConfigError::InvalidOrigin(origin.clone()));
+            }
+            return Ok(OriginMatcher::Subdomain {
+                scheme: format!("{scheme}://"),
+                suffix: suffix.to_string(),
+            });
+        }
+        if origin.contains('*') {
+            return Err(CorsFilterConfigError::InvalidOrigin(origin));
+        }
+        Ok(OriginMatcher::Exact(origin))
+    }
+
+    fn is_match(&self, origin: &str) -> bool {
+        match self {
+            OriginMatcher::Any => true,
+            OriginMatcher::Exact(exact) => origin.eq_ignore_ascii_case(exact),
+            OriginMatcher::Subdomain { scheme, suffix } => {
+                let origin = origin.to_ascii_lowercase();
+                origin
+                    .strip_prefix(scheme.as_str())
+                    .and_then(|host| host.strip_suffix(suffix.as_str()))
+                    .is_some_and(|subdomain| {
+                        !subdomain.is_empty()
+                            && subdomain
+                                .bytes()
+                                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.')
+                    })
+            }
+            OriginMatcher::Regex(regex) => regex.is_match(origin),
+        }
+    }
+}
+
+/// A configured list, or `*`.
+#[derive(Debug, Clone)]
+pub enum AllowList {
+    Any,
+    List(HeaderValue),
+}
+
+impl AllowList {
+    fn parse(values: &[String]) -> Result<Option<Self>, CorsFilterConfigError> {
+        if values.iter().any(|value| value.trim() == "*") {
+            return Ok(Some(AllowList::Any));
+        }
+        if values.is_empty() {
+            return Ok(None);
+        }
+        let list = values
+            .iter()
+            .map(|value| value.trim())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(Some(AllowList::List(HeaderValue::from_str(&list)?)))
+    }
+
+    /// With credentials `*` is taken literally by browsers, so the requested values are echoed.
+    fn header_value(
+        &self,
+        credentials: bool,
+        requested: Option<&HeaderValue>,
+    ) -> Option<HeaderValue> {
+        match self {
+            AllowList::Any if credentials => requested.cloned(),
+            AllowList::Any => Some(HeaderValue::from_static("*")),
+            AllowList::List(list) => Some(list.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsFilter {
+    pub allow_origins: Vec<OriginMatcher>,
+    pub allow_methods: Option<AllowList>,
+    pub allow_headers: Option<AllowList>,
+    pub expose_headers: Option<AllowList>,
+    pub allow_credentials: bool,
+    pub max_age: Option<HeaderValue>,
+}
+
+impl CorsFilter {
+    fn allows_any_origin(&self) -> bool {
+        self.allow_origins
+            .iter()
+            .any(|matcher| matches!(matcher, OriginMatcher::Any))
+    }
+
+    /// The `Access-Control-Allow-Origin` value for an allowed origin.
+    fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        if self.allows_any_origin() {
+            return Some(HeaderValue::from_static("*"));
+        }
+        let origin_str = origin.to_str().ok()?;
+        self.allow_origins
+            .iter()
+            .any(|matcher| matcher.is_match(origin_str))
+            .then(|| origin.clone())
+    }
+
+    fn set_vary(&self, headers: &mut HeaderMap, preflight: bool) {
+        if !self.allows_any_origin() {
+            append_vary(headers, ORIGIN);
+        }
+        if preflight {
+            append_vary(headers, ACCESS_CONTROL_REQUEST_METHOD);
+            append_vary(headers, ACCESS_CONTROL_REQUEST_HEADERS);
+        }
+    }
+
+    fn set_allow_origin(&self, headers: &mut HeaderMap, allow_origin: HeaderValue) {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if self.allow_credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    fn preflight(&self, request_headers: &HeaderMap, origin: &HeaderValue) -> DynResponse {
+        let mut response = DynResponse::new(empty_body());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let headers = response.headers_mut();
+        self.set_vary(headers, true);
+        let Some(allow_origin) = self.allow_origin(origin) else {
+            tracing::debug!(?origin, "cors preflight from a disallowed origin");
+            return response;
+        };
+        self.set_allow_origin(headers, allow_origin);
+        let requested_method = request_headers.get(ACCESS_CONTROL_REQUEST_METHOD);
+        if let Some(value) = self
+            .allow_methods
+            .as_ref()
+            .and_then(|methods| methods.header_value(self.allow_credentials, requested_method))
+        {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, value);
+        }
+        let requested_headers = request_headers.get(ACCESS_CONTROL_REQUEST_HEADERS);
+        if let Some(value) = self
+            .allow_headers
+            .as_ref()
+            .and_then(|allowed| allowed.header_value(self.allow_credentials, requested_headers))
+        {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, value);
+        }
+        if let Some(max_age) = &self.max_age {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, max_age.clone());
+        }
+        response
+    }
+}
+
+/// Add a field to `Vary` unless it's already there or `Vary` is `*`.
+fn append_vary(headers: &mut HeaderMap, field: HeaderName) {
+    let present = headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|value| value == "*" || value.eq_ignore_ascii_case(field.as_str()));
+    if !present {
+        headers.append(VARY, HeaderValue::from(field));
+    }
+}
+
+impl FilterLike for CorsFilter {
+    async fn call(
+        self: std::sync::Arc<Self>,
+        req: DynRequest,
+        ctx: &mut crate::flow::FlowContext,
+        next: super::Next,
+    ) -> DynResponse {
+        let origin = req.headers().get(ORIGIN).cloned();
+        if let Some(origin) = &origin
+            && req.method() == Method::OPTIONS
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            return self.preflight(req.headers(), origin);
+        }
+        let mut response = next.call(req, ctx).await;
+        let headers = response.headers_mut();
+        // responses without an origin vary too, so caches don't serve them to cors requests
+        self.set_vary(headers, false);
+        let allow_origin = origin.and_then(|origin| self.allow_origin(&origin));
+        if let Some(allow_origin) = allow_origin {
+            self.set_allow_origin(headers, allow_origin);
+            if let Some(value) = self
+                .expose_headers
+                .as_ref()
+                .and_then(|exposed| exposed.header_value(self.allow_credentials, None))
+            {
+                headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, value);
+            }
+        }
+        response
+    }
+}
+
+pub struct CorsFilterClass;
+
+impl FilterClass for CorsFilterClass {
+    type Filter = CorsFilter;
+    type Error = CorsFilterConfigError;
+    type Config = CorsFilterConfig;
+
+    fn id(&self) -> ClassId {
+        ClassId::std("cors")
+    }
+
+    fn construct(&self, config: Self::Config) -> Result<Self::Filter, Self::Error> {
+        let allow_origins = config
+            .allow_origins
+            .iter()
+            .map(|origin| OriginMatcher::parse(origin))
+            .collect::<Result<Vec<_>, _>>()?;
+        let allow_methods = AllowList::parse(&config.allow_methods)?;
+        if let Some(AllowList::List(_)) = &allow_methods {
+            for method in &config.allow_methods {
+                Method::from_bytes(method.trim().as_bytes())?;
+            }
+        }
+        let allow_headers = AllowList::parse(&config.allow_headers)?;
+        let expose_headers = AllowList::parse(&config.expose_headers)?;
+        for header in config.allow_headers.iter().chain(&config.expose_headers) {
+            if header.trim() != "*" {
+                HeaderName::from_bytes(header.trim().as_bytes())?;
+            }
+        }
+        let filter = CorsFilter {
+            allow_origins,
+            allow_methods,
+            allow_headers,
+            expose_headers,
+            allow_credentials: config.allow_credentials,
+            max_age: config.max_age.map(HeaderValue::from),
+        };
+        if filter.allow_credentials {
+            if filter.allows_any_origin() {
+                return Err(CorsFilterConfigError::CredentialsWithAnyOrigin);
+            }
+            if let Some(AllowList::Any) = filter.expose_headers {
+                return Err(CorsFilterConfigError::CredentialsWithAnyExposeHeader);
+            }
+        }
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn filter(config: serde_json::Value) -> Result<CorsFilter, CorsFilterConfigError> {
+        CorsFilterClass.construct(serde_json::from_value(config).unwrap())
+    }
+
+    #[test]
+    fn test_allow_origin() {
+        let filter = filter(serde_json::json!({
+            "allow_origins": ["https://Example.com/", "https://*.example.org", "re:http://localhost:\\d+"],
+            "allow_credentials": true,
+        }))
+        .unwrap();
+        let cases = [
+            ("https://example.com", true),
+            ("https://EXAMPLE.com", true),
+            ("http://example.com", false),
+            ("https://a.b.example.org", true),
+            ("https://example.org", false),
+            ("https://evil-example.org", false),
+            ("https://a.example.org.evil.com", false),
+            ("http://localhost:8080", true),
+            ("http://localhost:8080.evil.com", false),
+            ("null", false),
+        ];
+        for (origin, allowed) in cases {
+            let origin = HeaderValue::from_static(origin);
+            let expected = allowed.then(|| origin.clone());
+            assert_eq!(filter.allow_origin(&origin), expected, "{origin:?}");
+        }
+    }
+
+    #[test]
+    fn test_preflight() {
+        let filter = filter(serde_json::json!({
+            "allow_origins": ["https://example.com"],
+            "allow_methods": ["*"],
+            "allow_headers": ["*"],
+            "allow_credentials": true,
+            "max_age": 600,
+        }))
+        .unwrap();
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(
+            ACCESS_CONTROL_REQUEST_METHOD,
+            HeaderValue::from_static("PUT"),
+        );
+        request_headers.insert(
+            ACCESS_CONTROL_REQUEST_HEADERS,
+            HeaderValue::from_static("x-token"),
+        );
+        let response = filter.preflight(
+            &request_headers,
+            &HeaderValue::from_static("https://example.com"),
+        );
+        let headers = response.headers();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://example.com");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "PUT");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "x-token");
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+        assert_eq!(headers.get_all(VARY).iter().count(), 3);
+
+        let response = filter.preflight(
+            &request_headers,
+            &HeaderValue::from_static("https://evil.com"),
+        );
+        assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[test]
+    fn test_credentials_refuse_wildcards() {
+        let any_origin = filter(serde_json::json!({
+            "allow_origins": ["*"],
+            "allow_credentials": true,
+        }));
+        assert!(matches!(
+            any_origin,
+            Err(CorsFilterConfigError::CredentialsWithAnyOrigin)
+        ));
+        let any_expose = filter(serde_json::json!({
+            "allow_origins": ["https://example.com"],
+            "expose_headers": ["*"],
+            "allow_credentials": true,
+        }));
+        assert!(matches!(
+            any_expose,
+            Err(CorsFilterConfigError::CredentialsWithAnyExposeHeader)
+        ));
+        assert!(filter(serde_json::json!({ "allow_origins": ["https://*example.com"] })).is_err());
+    }
+}
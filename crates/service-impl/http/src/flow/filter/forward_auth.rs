@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use http::{
+    HeaderName, HeaderValue, Method,
+    header::{CONTENT_LENGTH, TRANSFER_ENCODING},
+    request::Parts,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use switchboard_model::services::http::{ClassId, NodeTarget};
+
+use crate::{
+    DynRequest, DynResponse, IntoDynResponse,
+    consts::{X_FORWARDED_METHOD, X_FORWARDED_URI},
+    empty_body,
+    flow::{
+        FlowContext, FlowError,
+        filter::{FilterClass, FilterLike, Next, NextLocation},
+    },
+};
+
+/// Asks another node of the flow first, the request goes on when it answers 2xx and gets the
+/// auth node's response otherwise.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ForwardAuthFilterConfig {
+    /// gets a `GET` with the request headers, `x-forwarded-method` and `x-forwarded-uri`
+    pub target: NodeTarget,
+    /// auth response headers copied into the request, replacing any the client sent
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub copy_headers: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ForwardAuthFilterConfigError {
+    #[error("Invalid header name: {0}")]
+    InvalidHeaderName(#[from] http::header::InvalidHeaderName),
+}
+
+pub struct ForwardAuthFilter {
+    pub target: NodeTarget,
+    pub copy_headers: Vec<HeaderName>,
+}
+
+impl ForwardAuthFilter {
+    /// A call into the auth node through the filters of its input port.
+    fn auth_next(&self, ctx: &FlowContext) -> Result<Next, FlowError> {
+        if let Some(max_loop) = ctx.config.max_loop
+            && ctx.trace.loop_count_at(&self.target.id) > max_loop as usize
+        {
+            return Err(FlowError::LoopDetected {
+                node: self.target.id.clone(),
+                limit: max_loop,
+                trace: ctx.trace.clone(),
+            });
+        }
+        let node = ctx
+            .flow
+            .nodes
+            .get(&self.target.id)
+            .ok_or_else(|| FlowError::NodeNotFound(self.target.id.clone()))?;
+        let input_filters = node
+            .interface
+            .inputs
+            .get(&self.target.port)
+            .ok_or_else(|| FlowError::InvalidPort(self.target.port.clone()))?
+            .filters
+            .clone();
+        Ok(Next {
+            target: self.target.clone(),
+            output_filters: Vec::new(),
+            input_filters,
+            call: node.call.clone(),
+            location: NextLocation::Source,
+        })
+    }
+}
+
+/// A bodiless `GET` carrying the headers of the request and where it was going.
+fn auth_request(parts: &Parts) -> DynRequest {
+    let mut auth_parts = parts.clone();
+    auth_parts.method = Method::GET;
+    auth_parts.headers.remove(CONTENT_LENGTH);
+    auth_parts.headers.remove(TRANSFER_ENCODING);
+    auth_parts.headers.insert(
+        X_FORWARDED_METHOD,
+        HeaderValue::from_str(parts.method.as_str()).expect("methods are valid header values"),
+    );
+    if let Some(uri) = parts
+        .uri
+        .path_and_query()
+        .and_then(|uri| HeaderValue::from_str(uri.as_str()).ok())
+    {
+        auth_parts.headers.insert(X_FORWARDED_URI, uri);
+    }
+    DynRequest::from_parts(auth_parts, empty_body())
+}
+
+impl FilterLike for ForwardAuthFilter {
+    async fn call(
+        self: Arc<Self>,
+        req: DynRequest,
+        ctx: &mut FlowContext,
+        next: Next,
+    ) -> DynResponse {
+        let auth_next = match self.auth_next(ctx) {
+            Ok(auth_next) => auth_next,
+            Err(e) => {
+                tracing::error!("Forward auth error: {}", e);
+                return e.into_dyn_response();
+            }
+        };
+        let (mut parts, body) = req.into_parts();
+        let auth_response = auth_next.call(auth_request(&parts), ctx).await;
+        if !auth_response.status().is_success() {
+            tracing::debug!(status = %auth_response.status(), node = %self.target, "forward auth rejected");
+            return auth_response;
+        }
+        for header in &self.copy_headers {
+            parts.headers.remove(header);
+            for value in auth_response.headers().get_all(header) {
+                parts.headers.append(header.clone(), value.clone());
+            }
+        }
+        next.call(DynRequest::from_parts(parts, body), ctx).await
+    }
+}
+
+pub struct ForwardAuthFilterClass;
+
+impl FilterClass for ForwardAuthFilterClass {
+    type Filter = ForwardAuthFilter;
+    type Error = ForwardAuthFilterConfigError;
+    type Config = ForwardAuthFilterConfig;
+
+    fn id(&self) -> ClassId {
+        ClassId::std("forward-auth")
+    }
+
+    fn construct(&self, config: Self::Config) -> Result<Self::Filter, Self::Error> {
+        let copy_headers = config
+            .copy_headers
+            .iter()
+            .map(|header| HeaderName::from_bytes(header.as_bytes()))
+            .collect::<Result<_, _>>()?;
+        Ok(ForwardAuthFilter {
+            target: config.target,
+            copy_headers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_auth_request() {
+        let (parts, _) = http::Request::post("https://example.com/orders?page=2")
+            .header("authorization", "Bearer token")
+            .header(CONTENT_LENGTH, "42")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let request = auth_request(&parts);
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.uri(), "https://example.com/orders?page=2");
+        assert_eq!(request.headers()["authorization"], "Bearer token");
+        assert_eq!(request.headers()[X_FORWARDED_METHOD], "POST");
+        assert_eq!(request.headers()[X_FORWARDED_URI], "/orders?page=2");
+        assert!(!request.headers().contains_key(CONTENT_LENGTH));
+    }
+}
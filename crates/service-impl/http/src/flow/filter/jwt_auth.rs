@@ -0,0 +1,526 @@
+use std::{collections::BTreeMap, path::PathBuf, str::FromStr, sync::Arc};
+
+use http::{
+    HeaderMap, HeaderName, HeaderValue, StatusCode, Uri,
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, jwk::JwkSet};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use switchboard_jwks::{JwksCache, JwksError, JwksSource};
+use switchboard_model::services::http::ClassId;
+
+use crate::{
+    DynRequest, DynResponse,
+    consts::ERR_FILTER_JWT_AUTH,
+    extension::auth::{AuthMethod, Authenticated, JwtClaims},
+    flow::filter::{FilterClass, FilterLike},
+    utils::error_response,
+};
+
+/// Exactly one of `secret`, `public_key`, `jwks_file` or `jwks_url` verifies the tokens.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct JwtAuthFilterConfig {
+    /// shared secret of HS256, HS384 and HS512 tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// PEM encoded RSA, EC or Ed25519 public key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// a JWKS read when the filter is built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks_file: Option<PathBuf>,
+    /// a JWKS fetched on the first request, and again when a token names an unknown `kid`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks_url: Option<String>,
+    /// accepted `alg` values, every algorithm of the key type by default, HS* never with a JWKS
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub algorithms: Vec<String>,
+    /// required `iss`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// `aud` must contain one of these when set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audience: Vec<String>,
+    /// clock skew allowed for `exp` and `nbf`, in seconds
+    #[serde(default = "default_leeway")]
+    pub leeway: u64,
+    /// dot separated claim paths copied into request headers, like `{ "org.id" = "x-org-id" }`,
+    /// headers of the same name sent by the client are removed
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub claims_to_headers: BTreeMap<String, String>,
+}
+
+fn default_leeway() -> u64 {
+    60
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwtAuthFilterConfigError {
+    #[error("Exactly one of secret, public_key, jwks_file or jwks_url is needed")]
+    KeySource,
+    #[error("Invalid public key, expected a PEM encoded RSA, EC or Ed25519 key")]
+    InvalidPublicKey,
+    #[error("Invalid algorithm `{0}`")]
+    InvalidAlgorithm(String),
+    #[error("Algorithm {0:?} doesn't fit the configured key")]
+    AlgorithmMismatch(Algorithm),
+    #[error("Failed to read JWKS file {}: {source}", path.display())]
+    ReadJwks {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid JWKS: {0}")]
+    InvalidJwks(#[from] serde_json::Error),
+    #[error("Invalid JWKS url: {0}")]
+    InvalidJwksUrl(#[from] http::uri::InvalidUri),
+    #[error("Invalid header name: {0}")]
+    InvalidHeaderName(#[from] http::header::InvalidHeaderName),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwtAuthError {
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("Algorithm {0:?} is not allowed")]
+    DisallowedAlgorithm(Algorithm),
+    #[error("No key {0:?} in JWKS")]
+    UnknownKey(Option<String>),
+    #[error(transparent)]
+    Jwks(#[from] JwksError),
+}
+
+impl JwtAuthError {
+    fn status(&self) -> StatusCode {
+        match self {
+            JwtAuthError::Jwks(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn challenge(&self) -> HeaderValue {
+        match self {
+            JwtAuthError::MissingToken => HeaderValue::from_static("Bearer"),
+            _ => HeaderValue::from_static("Bearer error=\"invalid_token\""),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyFamily {
+    Hmac,
+    Rsa,
+    Ec,
+    Ed,
+}
+
+impl KeyFamily {
+    fn of(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => KeyFamily::Hmac,
+            Algorithm::ES256 | Algorithm::ES384 => KeyFamily::Ec,
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => KeyFamily::Rsa,
+            Algorithm::EdDSA => KeyFamily::Ed,
+        }
+    }
+
+    fn algorithms(self) -> Vec<Algorithm> {
+        match self {
+            KeyFamily::Hmac => vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512],
+            KeyFamily::Rsa => vec![
+                Algorithm::RS256,
+                Algorithm::RS384,
+                Algorithm::RS512,
+                Algorithm::PS256,
+                Algorithm::PS384,
+                Algorithm::PS512,
+            ],
+            KeyFamily::Ec => vec![Algorithm::ES256, Algorithm::ES384],
+            KeyFamily::Ed => vec![Algorithm::EdDSA],
+        }
+    }
+}
+
+pub enum JwtKeys {
+    Static {
+        key: DecodingKey,
+        algorithms: Vec<Algorithm>,
+    },
+    JwksFile {
+        set: Arc<JwkSet>,
+        algorithms: Vec<Algorithm>,
+    },
+    JwksUrl {
+        cache: JwksCache,
+        algorithms: Vec<Algorithm>,
+    },
+}
+
+impl JwtKeys {
+    /// The key and the accepted algorithms for a token with this header.
+    async fn resolve(
+        &self,
+        header: &jsonwebtoken::Header,
+    ) -> Result<(DecodingKey, Vec<Algorithm>), JwtAuthError> {
+        let (set, algorithms) = match self {
+            JwtKeys::Static { key, algorithms } => return Ok((key.clone(), algorithms.clone())),
+            JwtKeys::JwksFile { set, algorithms } => (set.clone(), algorithms),
+            JwtKeys::JwksUrl { cache, algorithms } => {
+                (cache.get(header.kid.as_deref()).await?, algorithms)
+            }
+        };
+        // keys come from the JWKS, never accept a shared secret chosen by the token
+        if !algorithms.contains(&header.alg) {
+            return Err(JwtAuthError::DisallowedAlgorithm(header.alg));
+        }
+        let jwk = match &header.kid {
+            Some(kid) => set.find(kid),
+            None => set.keys.first(),
+        }
+        .ok_or_else(|| JwtAuthError::UnknownKey(header.kid.clone()))?;
+        if let Some(key_algorithm) = jwk.common.key_algorithm
+            && key_algorithm.to_string() != format!("{:?}", header.alg)
+        {
+            return Err(JwtAuthError::DisallowedAlgorithm(header.alg));
+        }
+        Ok((DecodingKey::from_jwk(jwk)?, vec![header.alg]))
+    }
+}
+
+pub struct JwtAuthFilter {
+    pub keys: JwtKeys,
+    /// issuer, audience and leeway, the algorithms are set per token
+    pub validation: Validation,
+    pub claims_to_headers: Vec<(String, HeaderName)>,
+}
+
+impl JwtAuthFilter {
+    /// The verified claims of the bearer token.
+    pub async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<serde_json::Value, JwtAuthError> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .ok_or(JwtAuthError::MissingToken)?;
+        let header = jsonwebtoken::decode_header(token)?;
+        let (key, algorithms) = self.keys.resolve(&header).await?;
+        let mut validation = self.validation.clone();
+        validation.algorithms = algorithms;
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &key, &validation)?.claims;
+        Ok(claims)
+    }
+
+    fn apply_claims(&self, headers: &mut HeaderMap, claims: &JwtClaims) {
+        for (path, header) in &self.claims_to_headers {
+            headers.remove(header);
+            let value = claims
+                .get_text(path)
+                .and_then(|value| HeaderValue::from_str(&value).ok());
+            if let Some(value) = value {
+                headers.insert(header.clone(), value);
+            }
+        }
+    }
+}
+
+impl FilterLike for JwtAuthFilter {
+    async fn call(
+        self: Arc<Self>,
+        req: DynRequest,
+        ctx: &mut crate::flow::FlowContext,
+        next: super::Next,
+    ) -> DynResponse {
+        let (mut parts, body) = req.into_parts();
+        let authenticated = self.authenticate(&parts.headers).await;
+        match authenticated {
+            Ok(claims) => {
+                let claims = JwtClaims(Arc::new(claims));
+                self.apply_claims(&mut parts.headers, &claims);
+                let subject = claims.get_text("sub").unwrap_or_default();
+                parts.extensions.insert(Authenticated {
+                    method: AuthMethod::Jwt,
+                    principal: Arc::from(subject.as_ref()),
+                });
+                parts.extensions.insert(claims);
+                next.call(DynRequest::from_parts(parts, body), ctx).await
+            }
+            Err(e) => {
+                tracing::debug!(error = %e, "jwt rejected");
+                let challenge = e.challenge();
+                let mut response = error_response(e.status(), e, ERR_FILTER_JWT_AUTH);
+                response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+                response
+            }
+        }
+    }
+}
+
+fn parse_algorithms(
+    algorithms: &[String],
+    family: Option<KeyFamily>,
+) -> Result<Vec<Algorithm>, JwtAuthFilterConfigError> {
+    let algorithms = algorithms
+        .iter()
+        .map(|name| {
+            Algorithm::from_str(name.trim())
+                .map_err(|_| JwtAuthFilterConfigError::InvalidAlgorithm(name.clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for algorithm in &algorithms {
+        let fits = match family {
+            Some(family) => KeyFamily::of(*algorithm) == family,
+            None => KeyFamily::of(*algorithm) != KeyFamily::Hmac,
+        };
+        if !fits {
+            return Err(JwtAuthFilterConfigError::AlgorithmMismatch(*algorithm));
+        }
+    }
+    if !algorithms.is_empty() {
+        return Ok(algorithms);
+    }
+    Ok(match family {
+        Some(family) => family.algorithms(),
+        None => [KeyFamily::Rsa, KeyFamily::Ec, KeyFamily::Ed]
+            .into_iter()
+            .flat_map(KeyFamily::algorithms)
+            .collect(),
+    })
+}
+
+fn parse_public_key(pem: &str) -> Result<(DecodingKey, KeyFamily), JwtAuthFilterConfigError> {
+    let pem = pem.as_bytes();
+    if let Ok(key) = DecodingKey::from_rsa_pem(pem) {
+        return Ok((key, KeyFamily::Rsa));
+    }
+    if let Ok(key) = DecodingKey::from_ec_pem(pem) {
+        return Ok((key, KeyFamily::Ec));
+    }
+    if let Ok(key) = DecodingKey::from_ed_pem(pem) {
+        return Ok((key, KeyFamily::Ed));
+    }
+    Err(JwtAuthFilterConfigError::InvalidPublicKey)
+}
+
+pub struct JwtAuthFilterClass;
+
+impl FilterClass for JwtAuthFilterClass {
+    type Filter = JwtAuthFilter;
+    type Error = JwtAuthFilterConfigError;
+    type Config = JwtAuthFilterConfig;
+
+    fn id(&self) -> ClassId {
+        ClassId::std("jwt-auth")
+    }
+
+    fn construct(&self, config: Self::Config) -> Result<Self::Filter, Self::Error> {
+        let keys = match (
+            config.secret,
+            config.public_key,
+            config.jwks_file,
+            config.jwks_url,
+        ) {
+            (Some(secret), None, None, None) if !secret.is_empty() => JwtKeys::Static {
+                key: DecodingKey::from_secret(secret.as_bytes()),
+                algorithms: parse_algorithms(&config.algorithms, Some(KeyFamily::Hmac))?,
+            },
+            (None, Some(pem), None, None) => {
+                let (key, family) = parse_public_key(&pem)?;
+                JwtKeys::Static {
+                    key,
+                    algorithms: parse_algorithms(&config.algorithms, Some(family))?,
+                }
+            }
+            (None, None, Some(path), None) => {
+                let source = std::fs::read(&path)
+                    .map_err(|source| JwtAuthFilterConfigError::ReadJwks { path, source })?;
+                JwtKeys::JwksFile {
+                    set: Arc::new(serde_json::from_slice(&source)?),
+                    algorithms: parse_algorithms(&config.algorithms, None)?,
+                }
+            }
+            (None, None, None, Some(url)) => {
+                let algorithms = parse_algorithms(&config.algorithms, None)?;
+                url.parse::<Uri>()?;
+                JwtKeys::JwksUrl {
+                    cache: JwksCache::new(JwksSource::Url(url)),
+                    algorithms,
+                }
+            }
+            _ => return Err(JwtAuthFilterConfigError::KeySource),
+        };
+        let mut validation = Validation::default();
+        validation.leeway = config.leeway;
+        validation.validate_nbf = true;
+        // jsonwebtoken only checks `iss` and `aud` when the token carries them
+        let mut required_claims = vec!["exp"];
+        if let Some(issuer) = &config.issuer {
+            validation.set_issuer(&[issuer]);
+            required_claims.push("iss");
+        }
+        if config.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(config.audience.as_slice());
+            required_claims.push("aud");
+        }
+        validation.set_required_spec_claims(&required_claims);
+        let claims_to_headers = config
+            .claims_to_headers
+            .into_iter()
+            .map(|(path, header)| {
+                HeaderName::from_bytes(header.as_bytes()).map(|header| (path, header))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(JwtAuthFilter {
+            keys,
+            validation,
+            claims_to_headers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use jsonwebtoken::{EncodingKey, Header};
+
+    use super::*;
+
+    const SECRET: &str = "a-shared-secret-of-reasonable-length";
+
+    fn filter(config: serde_json::Value) -> JwtAuthFilter {
+        JwtAuthFilterClass
+            .construct(serde_json::from_value(config).unwrap())
+            .unwrap()
+    }
+
+    fn bearer(claims: serde_json::Value, algorithm: Algorithm) -> HeaderMap {
+        let token = jsonwebtoken::encode(
+            &Header::new(algorithm),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        );
+        headers
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[tokio::test]
+    async fn test_validate_claims() {
+        let filter = filter(serde_json::json!({
+            "secret": SECRET,
+            "algorithms": ["HS256"],
+            "issuer": "https://idp.example.com",
+            "audience": ["api"],
+            "leeway": 0,
+        }));
+        let valid = serde_json::json!({
+            "sub": "alice",
+            "iss": "https://idp.example.com",
+            "aud": ["web", "api"],
+            "exp": now() + 300,
+            "nbf": now() - 10,
+        });
+        let claims = filter
+            .authenticate(&bearer(valid.clone(), Algorithm::HS256))
+            .await
+            .unwrap();
+        assert_eq!(claims["sub"], "alice");
+
+        let with = |key: &str, value: serde_json::Value| {
+            let mut claims = valid.clone();
+            claims[key] = value;
+            bearer(claims, Algorithm::HS256)
+        };
+        let without = |key: &str| {
+            let mut claims = valid.clone();
+            claims.as_object_mut().unwrap().remove(key);
+            bearer(claims, Algorithm::HS256)
+        };
+        let rejected = [
+            without("exp"),
+            without("iss"),
+            without("aud"),
+            with("exp", (now() - 10).into()),
+            with("nbf", (now() + 300).into()),
+            with("aud", "web".into()),
+            with("iss", "https://evil.example.com".into()),
+            bearer(valid.clone(), Algorithm::HS512),
+            HeaderMap::new(),
+        ];
+        for headers in rejected {
+            assert!(filter.authenticate(&headers).await.is_err(), "{headers:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_claims_to_headers() {
+        let filter = filter(serde_json::json!({
+            "secret": SECRET,
+            "claims_to_headers": {
+                "sub": "x-user",
+                "org.id": "x-org",
+                "roles": "x-roles",
+                "missing": "x-missing",
+            },
+        }));
+        let mut headers = bearer(
+            serde_json::json!({
+                "sub": "alice",
+                "org": { "id": 42 },
+                "roles": ["admin", "dev"],
+                "exp": now() + 300,
+            }),
+            Algorithm::HS384,
+        );
+        let claims = JwtClaims(Arc::new(filter.authenticate(&headers).await.unwrap()));
+        headers.insert("x-missing", HeaderValue::from_static("spoofed"));
+        filter.apply_claims(&mut headers, &claims);
+        assert_eq!(headers["x-user"], "alice");
+        assert_eq!(headers["x-org"], "42");
+        assert_eq!(headers["x-roles"], "admin,dev");
+        assert!(!headers.contains_key("x-missing"));
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        let cases = [
+            serde_json::json!({}),
+            serde_json::json!({ "secret": "" }),
+            serde_json::json!({ "secret": SECRET, "jwks_url": "https://idp.example.com/jwks" }),
+            serde_json::json!({ "secret": SECRET, "algorithms": ["RS256"] }),
+            serde_json::json!({ "secret": SECRET, "algorithms": ["none"] }),
+            serde_json::json!({ "public_key": "not a pem" }),
+            serde_json::json!({ "jwks_url": "https://idp.example.com/jwks", "algorithms": ["HS256"] }),
+        ];
+        for config in cases {
+            let config = serde_json::from_value(config).unwrap();
+            assert!(JwtAuthFilterClass.construct(config).is_err());
+        }
+    }
+}
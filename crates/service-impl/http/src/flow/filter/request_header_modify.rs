@@ -1,5 +1,6 @@
 use http::{HeaderName, HeaderValue};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use switchboard_model::services::http::ClassId;
 
@@ -8,7 +9,7 @@ use crate::{
     flow::filter::{FilterClass, FilterLike},
 };
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct RequestHeaderModifyFilterConfig {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub set: Vec<(String, String)>,
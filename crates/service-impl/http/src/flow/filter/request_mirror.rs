@@ -2,6 +2,7 @@ use std::str::FromStr;
 
 use http::StatusCode;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use switchboard_model::services::http::{ClassId, NodeTarget};
 
@@ -12,7 +13,7 @@ use crate::{
     utils::error_response,
 };
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct RequestMirrorFilterConfig {
     pub target: NodeTarget,
     #[serde(default)]
@@ -64,6 +65,22 @@ impl<'de> serde::Deserialize<'de> for FractionOrPercentage {
     }
 }
 
+impl JsonSchema for FractionOrPercentage {
+    fn inline_schema() -> bool {
+        true
+    }
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "FractionOrPercentage".into()
+    }
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "pattern": "^\\s*\\d+\\s*(/\\s*\\d+\\s*|%)$",
+            "examples": ["1/10", "25%"],
+        })
+    }
+}
+
 impl FromStr for FractionOrPercentage {
     type Err = FractionOrPercentageParseError;
 
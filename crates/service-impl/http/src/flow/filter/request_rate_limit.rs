@@ -5,6 +5,7 @@ use std::{
 };
 
 use http::StatusCode;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use switchboard_model::services::http::ClassId;
 use tokio::sync::RwLock;
@@ -23,15 +24,18 @@ const DEFAULT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
 const DEFAULT_STATUS_CODE: u16 = 429;
 const UNKNOWN_KEY: &str = "unknown";
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(default)]
 pub struct RequestRateLimitFilterConfig {
     pub capacity: usize,
     #[serde(with = "crate::utils::duration_expr")]
+    #[schemars(with = "crate::utils::duration_expr::DurationExpr")]
     pub rate: Duration,
     #[serde(with = "crate::utils::duration_expr")]
+    #[schemars(with = "crate::utils::duration_expr::DurationExpr")]
     pub idle_ttl: Duration,
     #[serde(with = "crate::utils::duration_expr")]
+    #[schemars(with = "crate::utils::duration_expr::DurationExpr")]
     pub cleanup_interval: Duration,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key_template: Option<String>,
@@ -3,6 +3,7 @@ use std::{convert::Infallible, time::Duration};
 use hyper::rt::Timer;
 use hyper_util::rt::TokioTimer;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use switchboard_model::services::http::ClassId;
 
@@ -40,9 +41,10 @@ impl FilterLike for TimeoutFilter {
         }
     }
 }
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct TimeoutConfig {
     #[serde(with = "crate::utils::duration_expr")]
+    #[schemars(with = "crate::utils::duration_expr::DurationExpr")]
     pub timeout: Duration,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeout_message: Option<String>,
@@ -2,6 +2,7 @@ use std::{collections::HashMap, convert::Infallible, str::FromStr};
 
 use http::{HeaderValue, StatusCode};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use switchboard_http_router::utils::str_template::StrTemplate;
 use switchboard_model::services::http::ClassId;
@@ -13,7 +14,7 @@ use crate::{
     utils::error_response,
 };
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct UrlRewriteFilterConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use futures::future::BoxFuture;
+use schemars::{JsonSchema, Schema, schema_for};
 use serde::de::DeserializeOwned;
 
 use crate::{DynRequest, DynResponse, flow::FlowContext, instance::class::Class};
@@ -56,15 +57,15 @@ pub trait IntoNode {
 }
 
 pub trait NodeType {
-    type Config: DeserializeOwned;
+    type Config: DeserializeOwned + JsonSchema;
     type Error: std::error::Error + Send + Sync + 'static;
     fn id(&self) -> ClassId;
     fn meta(&self) -> ClassMeta {
         ClassMeta::default()
     }
-    // fn schema(&self) -> Schema {
-    //     schema_for!(Self::Config)
-    // }
+    fn schema(&self) -> Schema {
+        schema_for!(Self::Config)
+    }
     fn construct(&self, config: Self::Config) -> Result<Node, Self::Error>;
 }
 
@@ -72,14 +73,14 @@ pub struct AsNodeClass<N>(pub N);
 pub trait NodeClass: Send + Sync + 'static {
     type Node: NodeLike;
     type Error: std::error::Error + Send + Sync + 'static;
-    type Config: DeserializeOwned;
+    type Config: DeserializeOwned + JsonSchema;
     fn id(&self) -> ClassId;
     fn meta(&self) -> ClassMeta {
         ClassMeta::from_env()
     }
-    // fn schema(&self) -> Schema {
-    //     schema_for!(Self::Config)
-    // }
+    fn schema(&self) -> Schema {
+        schema_for!(Self::Config)
+    }
     fn construct(&self, config: Self::Config) -> Result<Self::Node, Self::Error>;
 }
 
@@ -98,6 +99,10 @@ where
         self.0.meta()
     }
 
+    fn schema(&self) -> Schema {
+        self.0.schema()
+    }
+
     fn construct(
         &self,
         config: Self::Config,
@@ -2,6 +2,8 @@ use std::collections::BTreeMap;
 // pub mod host_match;
 // pub mod path_match;
 #[cfg(feature = "service-impl")]
+pub mod matcher;
+#[cfg(feature = "service-impl")]
 #[allow(clippy::module_inception)]
 pub mod router;
 #[cfg(feature = "service-impl")]
@@ -15,7 +17,7 @@ use crate::{
 use switchboard_model::services::http::{NodeInterface, NodeOutput, NodePort};
 
 pub trait Router: Send + Sync + 'static {
-    fn route(&self, req: &mut http::request::Parts) -> NodePort;
+    fn route(&self, req: &mut http::request::Parts, context: &FlowContext) -> NodePort;
 }
 
 pub struct RouterNode<R: Router> {
@@ -37,7 +39,7 @@ impl<R: Router> NodeLike for RouterNode<R> {
     ) -> impl Future<Output = DynResponse> + 'c + Send {
         let req = req;
         let (mut parts, body) = req.into_parts();
-        let port = self.router.route(&mut parts);
+        let port = self.router.route(&mut parts, context);
         let req = DynRequest::from_parts(parts, body);
         context.call(req, port)
     }
@@ -0,0 +1,89 @@
+pub mod expr;
+
+use std::str::FromStr;
+
+use http::request::Parts;
+use switchboard_model::services::http::{ClassId, WithOutputs};
+
+use crate::flow::{FlowContext, node::NodeClass, router::RouterNode};
+
+use super::{NodePort, Router};
+use expr::{EvalContext, Expr, ExprParseError};
+
+/// Picks the output of the first rule whose expression is true, or the default output.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct MatchRouterConfig {
+    #[serde(default)]
+    pub rules: Vec<MatchRuleConfig>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct MatchRuleConfig {
+    /// e.g. `method == "GET" && header.x-tenant in ["acme", "globex"]`
+    pub when: String,
+    pub target: NodePort,
+}
+
+pub struct MatchRule {
+    pub expr: Expr,
+    pub target: NodePort,
+}
+
+pub struct MatchRouter {
+    pub rules: Vec<MatchRule>,
+}
+
+impl Router for MatchRouter {
+    fn route(&self, req: &mut Parts, context: &FlowContext) -> NodePort {
+        let eval_context = EvalContext {
+            parts: req,
+            connection: context.connection_info.as_ref(),
+        };
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.expr.eval(&eval_context) {
+                tracing::trace!(index, target = %rule.target, "match rule hit");
+                return rule.target.clone();
+            }
+        }
+        NodePort::Default
+    }
+}
+
+pub struct MatchRouterClass;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MatchRouterConstructError {
+    #[error("invalid expression of rule {index}: {source}")]
+    InvalidExpr {
+        index: usize,
+        #[source]
+        source: ExprParseError,
+    },
+}
+
+impl NodeClass for MatchRouterClass {
+    type Config = WithOutputs<MatchRouterConfig>;
+    type Error = MatchRouterConstructError;
+    type Node = RouterNode<MatchRouter>;
+    fn construct(&self, config: Self::Config) -> Result<Self::Node, Self::Error> {
+        let rules = config
+            .config
+            .rules
+            .into_iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                let expr = Expr::from_str(&rule.when)
+                    .map_err(|source| MatchRouterConstructError::InvalidExpr { index, source })?;
+                Ok(MatchRule {
+                    expr,
+                    target: rule.target,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(RouterNode::new(config.output, MatchRouter { rules }))
+    }
+
+    fn id(&self) -> ClassId {
+        ClassId::std("match")
+    }
+}
@@ -0,0 +1,656 @@
+//! A small boolean expression language over a request.
+//!
+//! ```text
+//! expr    := and ("||" and)*
+//! and     := unary ("&&" unary)*
+//! unary   := "!" unary | primary
+//! primary := "(" expr ")" | "true" | "false" | "tls" | field [op value]
+//! op      := "==" | "!=" | "~=" | "^=" | "$=" | "in"
+//! value   := string | "[" string ("," string)* "]"
+//! ```
+//!
+//! A field alone tests that it has a value. Comparisons are false when the field has no value,
+//! except `!=` which is the negation of `==`.
+use std::{borrow::Cow, net::IpAddr, str::FromStr};
+
+use http::{HeaderName, request::Parts};
+use regex::Regex;
+use switchboard_http_router::{
+    error::BuildError,
+    rule::{ClientIpMatch, request_cookies},
+    serde::rule::ClientIpMatchSerde,
+    utils::{hostname::try_extract_hostname, query_kv::QueryKvIter},
+};
+
+use crate::{
+    extension::{auth::JwtClaims, captures::Captures},
+    flow::{ConnectionInfo, router::router::TreeRouterMatched},
+};
+
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExprParseError {
+    #[error("unexpected character {1:?} at {0}")]
+    UnexpectedChar(usize, char),
+    #[error("unterminated string at {0}")]
+    UnterminatedString(usize),
+    #[error("expected {expected} at {position}, found {found}")]
+    Unexpected {
+        position: usize,
+        expected: &'static str,
+        found: String,
+    },
+    #[error("unknown field `{0}`")]
+    UnknownField(String),
+    #[error("invalid header name `{0}`")]
+    InvalidHeaderName(String),
+    #[error("invalid regex: {0}")]
+    InvalidRegex(#[from] regex::Error),
+    #[error(transparent)]
+    InvalidNetwork(#[from] BuildError),
+    #[error("expression nested deeper than 64 levels")]
+    TooDeep,
+}
+
+#[derive(Debug, Clone)]
+pub enum Field {
+    Method,
+    /// lowercased, without port
+    Host,
+    Path,
+    Header(HeaderName),
+    Query(String),
+    Cookie(String),
+    PeerIp,
+    TlsSni,
+    TlsAlpn,
+    /// captures of the path tree router and of plugins
+    Capture(String),
+    /// dot separated path into the claims verified by a `jwt-auth` filter
+    Claim(String),
+}
+
+impl Field {
+    fn parse(ident: &str) -> Result<Self, ExprParseError> {
+        let field = match ident.split_once('.') {
+            None => match ident {
+                "method" => Field::Method,
+                "host" => Field::Host,
+                "path" => Field::Path,
+                "peer_ip" => Field::PeerIp,
+                _ => return Err(ExprParseError::UnknownField(ident.to_string())),
+            },
+            Some((_, "")) => return Err(ExprParseError::UnknownField(ident.to_string())),
+            Some(("header", name)) => Field::Header(
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| ExprParseError::InvalidHeaderName(name.to_string()))?,
+            ),
+            Some(("query", name)) => Field::Query(name.to_string()),
+            Some(("cookie", name)) => Field::Cookie(name.to_string()),
+            Some(("capture", name)) => Field::Capture(name.to_string()),
+            Some(("claim", path)) => Field::Claim(path.to_string()),
+            Some(("tls", "sni")) => Field::TlsSni,
+            Some(("tls", "alpn")) => Field::TlsAlpn,
+            Some(_) => return Err(ExprParseError::UnknownField(ident.to_string())),
+        };
+        Ok(field)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CompareOp {
+    Eq(String),
+    Regex(Regex),
+    StartsWith(String),
+    EndsWith(String),
+    In(Vec<String>),
+}
+
+impl CompareOp {
+    fn is_match(&self, value: &str) -> bool {
+        match self {
+            CompareOp::Eq(expected) => value == expected,
+            CompareOp::Regex(regex) => regex.is_match(value),
+            CompareOp::StartsWith(prefix) => value.starts_with(prefix.as_str()),
+            CompareOp::EndsWith(suffix) => value.ends_with(suffix.as_str()),
+            CompareOp::In(values) => values.iter().any(|expected| value == expected),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Bool(bool),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    /// the connection is tls terminated by this listener
+    Tls,
+    Exists(Field),
+    Compare {
+        field: Field,
+        op: CompareOp,
+    },
+    /// `peer_ip ==` and `peer_ip in` compare addresses, not strings
+    PeerIpIn(ClientIpMatch),
+}
+
+/// What an expression is evaluated against.
+pub struct EvalContext<'a> {
+    pub parts: &'a Parts,
+    pub connection: Option<&'a ConnectionInfo>,
+}
+
+impl Expr {
+    pub fn eval(&self, context: &EvalContext<'_>) -> bool {
+        match self {
+            Expr::Bool(value) => *value,
+            Expr::Not(expr) => !expr.eval(context),
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.eval(context)),
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.eval(context)),
+            Expr::Tls => context.connection.is_some_and(|info| info.is_tls),
+            Expr::Exists(field) => context.value(field).is_some(),
+            Expr::Compare { field, op } => context
+                .value(field)
+                .is_some_and(|value| op.is_match(&value)),
+            Expr::PeerIpIn(networks) => context.peer_ip().is_some_and(|ip| networks.contains(ip)),
+        }
+    }
+}
+
+impl<'a> EvalContext<'a> {
+    fn peer_ip(&self) -> Option<IpAddr> {
+        self.connection
+            .map(|info| info.peer_addr.ip().to_canonical())
+    }
+
+    fn value(&self, field: &Field) -> Option<Cow<'a, str>> {
+        let parts = self.parts;
+        match field {
+            Field::Method => Some(Cow::Borrowed(parts.method.as_str())),
+            Field::Host => try_extract_hostname(parts)
+                .ok()
+                .map(|host| Cow::Owned(strip_port(host).to_ascii_lowercase())),
+            Field::Path => Some(Cow::Borrowed(parts.uri.path())),
+            Field::Header(name) => parts.headers.get(name)?.to_str().ok().map(Cow::Borrowed),
+            Field::Query(name) => QueryKvIter::new(parts.uri.query()?)
+                .find(|(key, _)| *key == name.as_str())
+                .map(|(_, value)| Cow::Borrowed(value.unwrap_or_default())),
+            Field::Cookie(name) => request_cookies(parts)
+                .find(|(key, _)| *key == name.as_str())
+                .map(|(_, value)| Cow::Borrowed(value)),
+            Field::PeerIp => self.peer_ip().map(|ip| Cow::Owned(ip.to_string())),
+            Field::TlsSni => self.connection?.sni.as_deref().map(Cow::Borrowed),
+            Field::TlsAlpn => self.connection?.alpn.as_deref().map(Cow::Borrowed),
+            Field::Capture(name) => {
+                if let Some(value) = parts
+                    .extensions
+                    .get::<Captures>()
+                    .and_then(|captures| captures.captures.get(name.as_str()))
+                {
+                    return Some(Cow::Borrowed(value.as_ref()));
+                }
+                parts
+                    .extensions
+                    .get::<TreeRouterMatched>()?
+                    .path_tree_matched
+                    .captures_iter()
+                    .find(|(key, _)| *key == name.as_str())
+                    .map(|(_, value)| Cow::Borrowed(value))
+            }
+            Field::Claim(path) => parts.extensions.get::<JwtClaims>()?.get_text(path),
+        }
+    }
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.ends_with(':') && port.bytes().all(|b| b.is_ascii_digit()) => {
+            name
+        }
+        _ => host,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareKind {
+    Eq,
+    Ne,
+    Regex,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Not,
+    And,
+    Or,
+    Compare(CompareKind),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(ident) => format!("`{ident}`"),
+            Token::Str(value) => format!("{value:?}"),
+            Token::LParen => "`(`".to_string(),
+            Token::RParen => "`)`".to_string(),
+            Token::LBracket => "`[`".to_string(),
+            Token::RBracket => "`]`".to_string(),
+            Token::Comma => "`,`".to_string(),
+            Token::Not => "`!`".to_string(),
+            Token::And => "`&&`".to_string(),
+            Token::Or => "`||`".to_string(),
+            Token::Compare(kind) => match kind {
+                CompareKind::Eq => "`==`",
+                CompareKind::Ne => "`!=`",
+                CompareKind::Regex => "`~=`",
+                CompareKind::StartsWith => "`^=`",
+                CompareKind::EndsWith => "`$=`",
+            }
+            .to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExprParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ',' => Token::Comma,
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, quote)) if quote == c => break,
+                        // only quotes and backslashes are escaped, so regexes can be written as is
+                        Some((_, '\\')) => {
+                            match chars.next_if(|(_, next)| *next == c || *next == '\\') {
+                                Some((_, escaped)) => value.push(escaped),
+                                None => value.push('\\'),
+                            }
+                        }
+                        Some((_, ch)) => value.push(ch),
+                        None => return Err(ExprParseError::UnterminatedString(position)),
+                    }
+                }
+                Token::Str(value)
+            }
+            '!' | '=' | '~' | '^' | '$' | '&' | '|' => {
+                let next = chars.peek().map(|(_, next)| *next);
+                let (token, paired) = match (c, next) {
+                    ('!', Some('=')) => (Token::Compare(CompareKind::Ne), true),
+                    ('!', _) => (Token::Not, false),
+                    ('=', Some('=')) => (Token::Compare(CompareKind::Eq), true),
+                    ('~', Some('=')) => (Token::Compare(CompareKind::Regex), true),
+                    ('^', Some('=')) => (Token::Compare(CompareKind::StartsWith), true),
+                    ('$', Some('=')) => (Token::Compare(CompareKind::EndsWith), true),
+                    ('&', Some('&')) => (Token::And, true),
+                    ('|', Some('|')) => (Token::Or, true),
+                    _ => return Err(ExprParseError::UnexpectedChar(position, c)),
+                };
+                if paired {
+                    chars.next();
+                }
+                token
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = String::from(c);
+                while let Some((_, next)) = chars.next_if(|(_, next)| {
+                    next.is_ascii_alphanumeric() || matches!(*next, '_' | '-' | '.')
+                }) {
+                    ident.push(next);
+                }
+                Token::Ident(ident)
+            }
+            _ => return Err(ExprParseError::UnexpectedChar(position, c)),
+        };
+        tokens.push((position, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ExprParseError {
+        let (position, found) = match self.tokens.get(self.index) {
+            Some((position, token)) => (*position, token.describe()),
+            None => (self.end, "end of expression".to_string()),
+        };
+        ExprParseError::Unexpected {
+            position,
+            expected,
+            found,
+        }
+    }
+
+    fn expect(&mut self, token: Token, expected: &'static str) -> Result<(), ExprParseError> {
+        if self.peek() == Some(&token) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Expr, ExprParseError>,
+    ) -> Result<Expr, ExprParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ExprParseError::TooDeep);
+        }
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ExprParseError> {
+        let mut exprs = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.index += 1;
+            exprs.push(self.parse_and()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.remove(0),
+            _ => Expr::Or(exprs),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ExprParseError> {
+        let mut exprs = vec![self.parse_unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.index += 1;
+            exprs.push(self.parse_unary()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.remove(0),
+            _ => Expr::And(exprs),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ExprParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.index += 1;
+            let expr = self.nested(Self::parse_unary)?;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ExprParseError> {
+        match self.peek().cloned() {
+            Some(Token::LParen) => {
+                self.index += 1;
+                let expr = self.nested(Self::parse_or)?;
+                self.expect(Token::RParen, "`)`")?;
+                Ok(expr)
+            }
+            Some(Token::Ident(ident)) => {
+                self.index += 1;
+                match ident.as_str() {
+                    "true" => Ok(Expr::Bool(true)),
+                    "false" => Ok(Expr::Bool(false)),
+                    "tls" => Ok(Expr::Tls),
+                    _ => {
+                        let field = Field::parse(&ident)?;
+                        self.parse_comparison(field)
+                    }
+                }
+            }
+            _ => Err(self.unexpected("an expression")),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: Field) -> Result<Expr, ExprParseError> {
+        let kind = match self.peek() {
+            Some(Token::Compare(kind)) => Some(*kind),
+            Some(Token::Ident(ident)) if ident == "in" => None,
+            _ => return Ok(Expr::Exists(field)),
+        };
+        self.index += 1;
+        let Some(kind) = kind else {
+            let values = self.parse_list()?;
+            return Ok(match field {
+                Field::PeerIp => Expr::PeerIpIn(parse_networks(values)?),
+                field => Expr::Compare {
+                    field,
+                    op: CompareOp::In(values),
+                },
+            });
+        };
+        let value = self.parse_string()?;
+        let expr = match (kind, field) {
+            (CompareKind::Eq | CompareKind::Ne, Field::PeerIp) => {
+                Expr::PeerIpIn(parse_networks(vec![value])?)
+            }
+            (CompareKind::Eq | CompareKind::Ne, field) => Expr::Compare {
+                field,
+                op: CompareOp::Eq(value),
+            },
+            (CompareKind::Regex, field) => Expr::Compare {
+                field,
+                op: CompareOp::Regex(Regex::new(&value)?),
+            },
+            (CompareKind::StartsWith, field) => Expr::Compare {
+                field,
+                op: CompareOp::StartsWith(value),
+            },
+            (CompareKind::EndsWith, field) => Expr::Compare {
+                field,
+                op: CompareOp::EndsWith(value),
+            },
+        };
+        Ok(match kind {
+            CompareKind::Ne => Expr::Not(Box::new(expr)),
+            _ => expr,
+        })
+    }
+
+    fn parse_string(&mut self) -> Result<String, ExprParseError> {
+        match self.peek().cloned() {
+            Some(Token::Str(value)) => {
+                self.index += 1;
+                Ok(value)
+            }
+            _ => Err(self.unexpected("a string")),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Vec<String>, ExprParseError> {
+        self.expect(Token::LBracket, "`[`")?;
+        let mut values = Vec::new();
+        while self.peek() != Some(&Token::RBracket) {
+            values.push(self.parse_string()?);
+            if self.peek() == Some(&Token::Comma) {
+                self.index += 1;
+            } else {
+                break;
+            }
+        }
+        self.expect(Token::RBracket, "`,` or `]`")?;
+        Ok(values)
+    }
+}
+
+fn parse_networks(networks: Vec<String>) -> Result<ClientIpMatch, ExprParseError> {
+    Ok(ClientIpMatchSerde { networks }.try_into()?)
+}
+
+impl FromStr for Expr {
+    type Err = ExprParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            index: 0,
+            end: source.len(),
+            depth: 0,
+        };
+        let expr = parser.parse_or()?;
+        if parser.index < parser.tokens.len() {
+            return Err(parser.unexpected("`&&`, `||` or end of expression"));
+        }
+        Ok(expr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::SocketAddr, sync::Arc};
+
+    use super::*;
+
+    fn eval(source: &str, request: http::Request<()>, connection: Option<&ConnectionInfo>) -> bool {
+        let expr = Expr::from_str(source).unwrap();
+        let (parts, _) = request.into_parts();
+        expr.eval(&EvalContext {
+            parts: &parts,
+            connection,
+        })
+    }
+
+    fn connection(peer: &str, tls: bool) -> ConnectionInfo {
+        ConnectionInfo {
+            peer_addr: peer.parse::<SocketAddr>().unwrap(),
+            http_version: http::Version::HTTP_11,
+            is_tls: tls,
+            sni: tls.then(|| Arc::from("example.com")),
+            alpn: tls.then(|| Arc::from("h2")),
+        }
+    }
+
+    fn request() -> http::Request<()> {
+        http::Request::builder()
+            .method("POST")
+            .uri("/api/v1/users?tenant=acme&debug")
+            .header("host", "Example.COM:8080")
+            .header("x-group", "beta")
+            .header("cookie", "session=1; variant=\"b\"")
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_eval_request_fields() {
+        let cases = [
+            (r#"method == "POST""#, true),
+            (r#"method in ["GET", "HEAD"]"#, false),
+            (r#"host == "example.com""#, true),
+            (r#"path ^= "/api/" && path $= "/users""#, true),
+            (r#"path ~= '^/api/v\d+/'"#, true),
+            (r#"header.X-Group == "beta""#, true),
+            (r#"header.x-missing != "beta""#, true),
+            (r#"header.x-missing ^= """#, false),
+            ("query.debug && !query.trace", true),
+            (r#"query.tenant in ["acme", "globex"]"#, true),
+            (r#"cookie.variant == "b""#, true),
+            (r#"capture.id == "1""#, false),
+            (
+                r#"(method == "GET" || header.x-group == "beta") && true"#,
+                true,
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval(source, request(), None), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_eval_claims() {
+        let with_claims = || {
+            let mut request = request();
+            request
+                .extensions_mut()
+                .insert(JwtClaims(Arc::new(serde_json::json!({
+                    "sub": "alice",
+                    "org": { "id": 42, "plan": "pro" },
+                    "roles": ["admin", "dev"],
+                }))));
+            request
+        };
+        let cases = [
+            (r#"claim.sub == "alice""#, true),
+            (r#"claim.org.plan in ["pro", "team"]"#, true),
+            (r#"claim.org.id == "42""#, true),
+            (r#"claim.roles ~= "(^|,)admin(,|$)""#, true),
+            ("claim.org.missing", false),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval(source, with_claims(), None), expected, "{source}");
+        }
+        assert!(!eval("claim.sub", request(), None));
+    }
+
+    #[test]
+    fn test_eval_connection_fields() {
+        let tls = connection("[::ffff:10.1.2.3]:443", true);
+        let plain = connection("192.168.0.1:80", false);
+        let cases = [
+            (r#"peer_ip in ["10.0.0.0/8", "::1"]"#, true, false),
+            (r#"peer_ip == "192.168.0.1""#, false, true),
+            (r#"peer_ip != "192.168.0.1""#, true, false),
+            (
+                r#"tls && tls.sni $= ".com" && tls.alpn == "h2""#,
+                true,
+                false,
+            ),
+            ("!tls.alpn", false, true),
+        ];
+        for (source, on_tls, on_plain) in cases {
+            assert_eq!(eval(source, request(), Some(&tls)), on_tls, "{source}");
+            assert_eq!(eval(source, request(), Some(&plain)), on_plain, "{source}");
+        }
+        assert!(!eval(r#"peer_ip in ["0.0.0.0/0"]"#, request(), None));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            "",
+            "method ==",
+            r#"method = "GET""#,
+            r#"method == "GET"#,
+            r#"header. == "a""#,
+            r#"body == "a""#,
+            r#"path ~= "(""#,
+            r#"peer_ip in ["not-an-ip"]"#,
+            r#"(method == "GET""#,
+            r#"method == "GET" path == "/""#,
+        ];
+        for source in cases {
+            assert!(Expr::from_str(source).is_err(), "{source}");
+        }
+        let deep = format!("{}true{}", "(".repeat(100), ")".repeat(100));
+        assert!(matches!(
+            Expr::from_str(&deep),
+            Err(ExprParseError::TooDeep)
+        ));
+    }
+}
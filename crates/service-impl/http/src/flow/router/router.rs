@@ -1,9 +1,11 @@
 use http::request::Parts;
 
-use switchboard_http_router::{Router as TreeRouterInner, serde::RouterSerde};
+use switchboard_http_router::{
+    Router as TreeRouterInner, rule::ConnectionMeta, serde::RouterSerde,
+};
 use switchboard_model::services::http::{ClassId, WithOutputs};
 
-use crate::flow::{node::NodeClass, router::RouterNode};
+use crate::flow::{FlowContext, node::NodeClass, router::RouterNode};
 pub type TreeRouterMatched = switchboard_http_router::RouterMatched<NodePort>;
 
 use super::{NodePort, Router};
@@ -13,7 +15,7 @@ pub struct RouterRouter {
     pub options: TreeRouterOptions,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct RouterRouterConfig {
     #[serde(flatten)]
     pub router: RouterSerde<NodePort>,
@@ -21,7 +23,7 @@ pub struct RouterRouterConfig {
     pub options: TreeRouterOptions,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, schemars::JsonSchema)]
 #[serde(default)]
 pub struct TreeRouterOptions {
     // #[serde(default)]
@@ -41,8 +43,16 @@ pub struct PathRouterEndpoint {
 }
 
 impl Router for RouterRouter {
-    fn route(&self, req: &mut Parts) -> NodePort {
+    fn route(&self, req: &mut Parts, context: &FlowContext) -> NodePort {
         use switchboard_http_router::error::Error as RouterError;
+        // connection facts for client ip, sni and alpn rules
+        if let Some(info) = &context.connection_info {
+            req.extensions.insert(ConnectionMeta {
+                client_ip: Some(info.peer_addr.ip()),
+                sni: info.sni.clone(),
+                alpn: info.alpn.clone(),
+            });
+        }
         let match_result = self.router.match_request_parts(req);
         match match_result {
             Ok(matched) => {
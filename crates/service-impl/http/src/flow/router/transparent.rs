@@ -1,9 +1,9 @@
-use super::{NodePort, Router};
+use super::{FlowContext, NodePort, Router};
 
 pub struct Transparent;
 
 impl Router for Transparent {
-    fn route(&self, _req: &mut http::request::Parts) -> NodePort {
+    fn route(&self, _req: &mut http::request::Parts, _context: &FlowContext) -> NodePort {
         NodePort::Default
     }
 }
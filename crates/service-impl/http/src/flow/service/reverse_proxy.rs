@@ -27,7 +27,7 @@ use tracing::{Instrument, info_span};
 use crate::{DynRequest, DynResponse, box_error};
 use http::header::{HOST, VIA};
 const DEFAULT_UPGRADE_BUFFER_SIZE: usize = 1 << 13; // 8kb
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[serde(default)]
 pub struct ReverseProxyServiceConfig {
     /// Backend authority (host:port) for proxying requests, default is empty
@@ -32,7 +32,7 @@ use self::index::GeneratedIndex;
 use self::path::{ensure_within_root, resolve_relative_path_from_uri_path};
 use self::response::{empty_response, forbidden, not_found, not_modified_from};
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default, schemars::JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum DirWithoutIndexPolicy {
     #[default]
@@ -49,7 +49,7 @@ impl DirWithoutIndexPolicy {
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default, schemars::JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum FileResponseMode {
     #[default]
@@ -58,7 +58,7 @@ pub enum FileResponseMode {
     Deny,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[serde(default)]
 pub struct ResponseConfig {
     pub index: OneOrMany<PathBuf>,
@@ -86,7 +86,7 @@ impl Default for ResponseConfig {
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[serde(default)]
 pub struct CacheConfig {
     pub enabled: bool,
@@ -113,7 +113,7 @@ impl Default for CacheConfig {
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[serde(default)]
 pub struct EtagConfig {
     pub enabled: bool,
@@ -129,7 +129,7 @@ impl Default for EtagConfig {
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default, schemars::JsonSchema)]
 #[serde(default)]
 pub struct CachePolicy {
     pub mode: CacheMode,
@@ -141,7 +141,7 @@ pub struct CachePolicy {
     pub proxy_revalidate: bool,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default, schemars::JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum CacheMode {
     #[default]
@@ -152,7 +152,7 @@ pub enum CacheMode {
     Immutable,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default, schemars::JsonSchema)]
 #[serde(default)]
 pub struct CacheRule {
     pub ext: Option<Vec<String>>,
@@ -161,7 +161,7 @@ pub struct CacheRule {
     pub policy: CachePolicy,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[serde(default)]
 pub struct MimeSniffConfig {
     pub default_type: String,
@@ -177,7 +177,7 @@ impl Default for MimeSniffConfig {
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default, schemars::JsonSchema)]
 #[serde(default)]
 pub struct StaticFileServiceConfig {
     pub root: PathBuf,
@@ -5,8 +5,8 @@ use rustls::ServerConfig;
 use std::{ops::Deref, sync::Arc};
 use switchboard_model::services::http::HttpVersion;
 use switchboard_service::{
-    SerdeValue, SerdeValueError, TcpServiceProvider,
-    tcp::{TcpAccepted, TcpConnectionContext},
+    SerdeValue, SerdeValueError, TcpServiceProvider, ValidationReport,
+    tcp::{TcpAccepted, TcpConnectionContext, TcpConnectionStats},
 };
 use tokio_util::sync::CancellationToken;
 
@@ -32,6 +32,7 @@ impl Http {
         self,
         stream: impl switchboard_service::tcp::AsyncStream,
         mut connection_info: ConnectionInfo,
+        stats: Arc<TcpConnectionStats>,
         ct: CancellationToken,
     ) -> std::io::Result<()> {
         connection_info.http_version = http::Version::HTTP_11;
@@ -43,6 +44,7 @@ impl Http {
                 FlowWithConnectionInfo {
                     flow: self.service,
                     connection_info,
+                    stats,
                 },
             )
             .with_upgrades();
@@ -65,6 +67,7 @@ impl Http {
         self,
         stream: impl switchboard_service::tcp::AsyncStream,
         mut connection_info: ConnectionInfo,
+        stats: Arc<TcpConnectionStats>,
         ct: CancellationToken,
     ) -> std::io::Result<()> {
         connection_info.http_version = http::Version::HTTP_2;
@@ -75,6 +78,7 @@ impl Http {
             FlowWithConnectionInfo {
                 flow: self.service,
                 connection_info,
+                stats,
             },
         );
         tokio::select! {
@@ -96,23 +100,34 @@ impl Http {
         let accepted = accepted.maybe_tls_terminate().await?;
         let stream = accepted.stream;
         let is_tls = stream.is_tls();
-        let TcpConnectionContext { peer_addr, ct, .. } = accepted.context;
+        let sni = stream.server_name().map(Arc::from);
+        let alpn = stream
+            .alpn_protocol()
+            .map(|protocol| Arc::from(String::from_utf8_lossy(protocol).as_ref()));
+        let TcpConnectionContext {
+            peer_addr,
+            ct,
+            stats,
+            ..
+        } = accepted.context;
         let connection_info = ConnectionInfo {
             peer_addr,
             http_version: http::Version::HTTP_11,
             is_tls,
+            sni,
+            alpn,
         };
         match self.version {
             HttpVersion::Http1 => {
                 self.as_ref()
                     .clone()
-                    .serve_http1(stream, connection_info, ct)
+                    .serve_http1(stream, connection_info, stats, ct)
                     .await
             }
             HttpVersion::Http2 => {
                 self.as_ref()
                     .clone()
-                    .serve_http2(stream, connection_info, ct)
+                    .serve_http2(stream, connection_info, stats, ct)
                     .await
             }
             HttpVersion::Auto => {
@@ -131,13 +146,13 @@ impl Http {
                     HttpVersion::Http1 => {
                         self.as_ref()
                             .clone()
-                            .serve_http1(rewind, connection_info, ct)
+                            .serve_http1(rewind, connection_info, stats, ct)
                             .await
                     }
                     HttpVersion::Http2 => {
                         self.as_ref()
                             .clone()
-                            .serve_http2(rewind, connection_info, ct)
+                            .serve_http2(rewind, connection_info, stats, ct)
                             .await
                     }
                     HttpVersion::Auto => {
@@ -171,7 +186,9 @@ pub enum HttpBuildError {
 }
 
 pub struct HttpProvider {
-    pub rust_dyn_libs: Vec<libloading::Library>,
+    pub rust_dyn_libs: Vec<Arc<crate::instance::class::plugin::PluginLibrary>>,
+    #[cfg(feature = "wasm")]
+    pub wasm_plugins: Vec<Arc<crate::instance::class::wasm::WasmPlugin>>,
 }
 
 impl TcpServiceProvider for HttpProvider {
@@ -188,4 +205,20 @@ impl TcpServiceProvider for HttpProvider {
         };
         Ok(service)
     }
+    async fn validate(&self, config: Option<SerdeValue>) -> ValidationReport {
+        let config: crate::config::Config = match config.unwrap_or_default().deserialize_into() {
+            Ok(config) => config,
+            Err(e) => {
+                let mut report = ValidationReport::default();
+                report.error(Vec::<String>::new(), HttpBuildError::from(e));
+                return report;
+            }
+        };
+        let class_registry = ClassRegistry::global(self);
+        let mut report = Flow::validate(config.flow, class_registry.read_owned().await.deref());
+        for issue in report.errors.iter_mut().chain(report.warnings.iter_mut()) {
+            issue.path.insert(0, "flow".to_string());
+        }
+        report
+    }
 }
@@ -1,21 +1,30 @@
+#[cfg(feature = "service-impl")]
+pub mod cabi;
 pub mod plugin;
 pub mod registry;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 use std::sync::Arc;
 
+use schemars::{JsonSchema, Schema, schema_for};
 use serde::de::DeserializeOwned;
 use switchboard_model::services::http::*;
 use switchboard_service::SerdeValue;
 
-use crate::instance::InstanceValue;
+use crate::{DynBody, DynResponse, instance::InstanceValue};
 
 pub trait Class: Send + Sync + 'static {
-    type Config: DeserializeOwned;
+    type Config: DeserializeOwned + JsonSchema;
     type Error: std::error::Error + Send + Sync + 'static;
     fn id(&self) -> ClassId;
     fn meta(&self) -> ClassMeta {
         ClassMeta::default()
     }
+    /// JSON Schema of [`Class::Config`], served to config editors and checked by dry-runs.
+    fn schema(&self) -> Schema {
+        schema_for!(Self::Config)
+    }
     fn instance_type(&self) -> InstanceType;
     fn construct(&self, config: Self::Config) -> Result<InstanceValue, Self::Error>;
 }
@@ -59,3 +68,42 @@ impl Constructor {
         (self.constructor)(config)
     }
 }
+
+/// The body a plugin replaced, without the `Content-Length` of the old one in `headers`.
+pub fn replace_plugin_body(
+    headers: &mut http::HeaderMap,
+    body: impl Into<bytes::Bytes>,
+) -> DynBody {
+    headers.remove(http::header::CONTENT_LENGTH);
+    crate::bytes_body(body)
+}
+
+/// Buffer a body for a plugin, refusing one larger than `limit` with `413`.
+#[cfg(feature = "service-impl")]
+pub(crate) async fn read_plugin_body(
+    mut body: DynBody,
+    limit: usize,
+    kind: &'static str,
+) -> Result<Vec<u8>, DynResponse> {
+    use http::StatusCode;
+    use http_body_util::BodyExt;
+
+    use crate::utils::error_response;
+
+    let mut bytes = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|e| error_response(StatusCode::BAD_REQUEST, e, kind))?;
+        let Ok(data) = frame.into_data() else {
+            continue;
+        };
+        if bytes.len() + data.len() > limit {
+            return Err(error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("body is larger than {limit} bytes"),
+                kind,
+            ));
+        }
+        bytes.extend_from_slice(&data);
+    }
+    Ok(bytes)
+}
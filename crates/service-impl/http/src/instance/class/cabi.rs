@@ -0,0 +1,1064 @@
+//! Stable C ABI for native plugins, declared for plugin authors in
+//! `include/switchboard_plugin.h`.
+//!
+//! A plugin exports `switchboard_plugin_v1`, which gets the host api and returns a descriptor
+//! with the abi version it was built against, the host capabilities it requires and a vtable per
+//! class. Requests and responses are opaque handles only touched through the host api, so a
+//! plugin doesn't depend on the compiler or crate versions the kernel was built with.
+//!
+//! Instances may be called from several threads at once, and calls are made on the runtime
+//! threads, so they must not block.
+
+use std::{cell::RefCell, collections::HashMap, ffi::c_void, sync::Arc};
+
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use schemars::Schema;
+use switchboard_model::services::http::{
+    ClassData, ClassId, ClassMeta, InstanceType, NodeInterface,
+};
+use switchboard_service::SerdeValue;
+
+use crate::{
+    DynBody, DynRequest, DynResponse, bytes_body,
+    consts::ERR_NATIVE_PLUGIN,
+    extension::captures::Captures,
+    flow::{
+        FlowContext,
+        filter::{Filter, Next},
+        node::Node,
+    },
+    instance::{
+        InstanceValue,
+        class::{
+            ConstructError, Constructor, read_plugin_body,
+            registry::{ClassDataWithConstructor, ClassRegistry},
+            replace_plugin_body,
+        },
+    },
+    utils::error_response,
+};
+
+pub const SB_ABI_VERSION_MAJOR: u32 = 1;
+pub const SB_ABI_VERSION_MINOR: u32 = 1;
+
+/// Name of the entry a plugin exports, a [`SbPluginEntryFn`].
+pub const SB_PLUGIN_ENTRY_SYMBOL: &[u8] = b"switchboard_plugin_v1\0";
+
+pub const SB_CAP_NODE: u64 = 1 << 0;
+pub const SB_CAP_FILTER: u64 = 1 << 1;
+pub const SB_CAP_ON_RESPONSE: u64 = 1 << 2;
+pub const SB_CAP_BODY: u64 = 1 << 3;
+pub const SB_CAP_CAPTURES: u64 = 1 << 4;
+/// Everything this host supports.
+pub const SB_HOST_CAPABILITIES: u64 =
+    SB_CAP_NODE | SB_CAP_FILTER | SB_CAP_ON_RESPONSE | SB_CAP_BODY | SB_CAP_CAPTURES;
+
+pub const SB_CLASS_NODE: u32 = 0;
+pub const SB_CLASS_FILTER: u32 = 1;
+
+/// The filter is called with the response as well.
+pub const SB_CLASS_ON_RESPONSE: u32 = 1 << 0;
+/// The request body is buffered and readable by the plugin.
+pub const SB_CLASS_REQUEST_BODY: u32 = 1 << 1;
+/// The response body is buffered and readable by `on_response`.
+pub const SB_CLASS_RESPONSE_BODY: u32 = 1 << 2;
+
+pub const SB_OK: i32 = 0;
+pub const SB_ERROR: i32 = -1;
+/// Returned by `on_request` to pass the request on.
+pub const SB_NEXT: i32 = 0;
+/// Returned by `on_request` to answer with the response it filled.
+pub const SB_RESPOND: i32 = 1;
+
+/// Bodies are buffered for the plugin, larger ones are refused with `413`.
+const MAX_BODY_BYTES: usize = 4 << 20;
+
+/// A borrowed utf-8 string or byte string. Strings returned by the host are valid until the
+/// handle they came from is modified or the call returns.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SbStr {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+impl SbStr {
+    pub const NULL: Self = Self {
+        ptr: std::ptr::null(),
+        len: 0,
+    };
+    fn new(bytes: &[u8]) -> Self {
+        Self {
+            ptr: bytes.as_ptr(),
+            len: bytes.len(),
+        }
+    }
+    /// # Safety
+    /// `ptr` must be null or point to `len` readable bytes living for `'a`.
+    unsafe fn as_bytes<'a>(self) -> &'a [u8] {
+        if self.ptr.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+    /// # Safety
+    /// See [`SbStr::as_bytes`].
+    unsafe fn as_str<'a>(self) -> Option<&'a str> {
+        std::str::from_utf8(unsafe { self.as_bytes() }).ok()
+    }
+}
+
+/// Opaque to plugins.
+pub struct SbRequest {
+    parts: http::request::Parts,
+    uri: String,
+    /// Buffered or replaced by the plugin.
+    body: Option<Vec<u8>>,
+    captures: HashMap<Arc<str>, Arc<str>>,
+    peer_addr: String,
+}
+
+/// Opaque to plugins.
+pub struct SbResponse {
+    parts: http::response::Parts,
+    /// Buffered or replaced by the plugin.
+    body: Option<Vec<u8>>,
+}
+
+/// Opaque to plugins.
+pub type SbHeaders = HeaderMap;
+
+#[repr(C)]
+pub struct SbHostApi {
+    pub abi_major: u32,
+    pub abi_minor: u32,
+    pub capabilities: u64,
+    pub log: unsafe extern "C" fn(level: u32, message: SbStr),
+    /// Set the error reported for a failed `construct` or a call returning `SB_ERROR`.
+    pub set_error: unsafe extern "C" fn(message: SbStr),
+
+    pub request_method: unsafe extern "C" fn(req: *const SbRequest) -> SbStr,
+    pub request_set_method: unsafe extern "C" fn(req: *mut SbRequest, method: SbStr) -> i32,
+    pub request_uri: unsafe extern "C" fn(req: *const SbRequest) -> SbStr,
+    pub request_set_uri: unsafe extern "C" fn(req: *mut SbRequest, uri: SbStr) -> i32,
+    pub request_headers: unsafe extern "C" fn(req: *mut SbRequest) -> *mut SbHeaders,
+    pub request_body: unsafe extern "C" fn(req: *const SbRequest) -> SbStr,
+    pub request_set_body: unsafe extern "C" fn(req: *mut SbRequest, body: SbStr),
+    pub request_peer_addr: unsafe extern "C" fn(req: *const SbRequest) -> SbStr,
+    pub request_capture: unsafe extern "C" fn(req: *const SbRequest, name: SbStr) -> SbStr,
+    pub request_set_capture:
+        unsafe extern "C" fn(req: *mut SbRequest, name: SbStr, value: SbStr) -> i32,
+
+    pub response_status: unsafe extern "C" fn(res: *const SbResponse) -> u16,
+    pub response_set_status: unsafe extern "C" fn(res: *mut SbResponse, status: u16) -> i32,
+    pub response_headers: unsafe extern "C" fn(res: *mut SbResponse) -> *mut SbHeaders,
+    pub response_body: unsafe extern "C" fn(res: *const SbResponse) -> SbStr,
+    pub response_set_body: unsafe extern "C" fn(res: *mut SbResponse, body: SbStr),
+
+    pub headers_len: unsafe extern "C" fn(headers: *const SbHeaders) -> usize,
+    pub headers_at: unsafe extern "C" fn(
+        headers: *const SbHeaders,
+        index: usize,
+        name: *mut SbStr,
+        value: *mut SbStr,
+    ) -> i32,
+    pub headers_get: unsafe extern "C" fn(headers: *const SbHeaders, name: SbStr) -> SbStr,
+    pub headers_set:
+        unsafe extern "C" fn(headers: *mut SbHeaders, name: SbStr, value: SbStr) -> i32,
+    pub headers_append:
+        unsafe extern "C" fn(headers: *mut SbHeaders, name: SbStr, value: SbStr) -> i32,
+    pub headers_remove: unsafe extern "C" fn(headers: *mut SbHeaders, name: SbStr),
+}
+
+/// `call_node` and `on_request` of a class.
+pub type SbCallFn =
+    unsafe extern "C" fn(instance: *mut c_void, req: *mut SbRequest, res: *mut SbResponse) -> i32;
+pub type SbOnResponseFn =
+    unsafe extern "C" fn(instance: *mut c_void, req: *const SbRequest, res: *mut SbResponse) -> i32;
+pub type SbConstructFn = unsafe extern "C" fn(config: SbStr, instance: *mut *mut c_void) -> i32;
+
+#[repr(C)]
+pub struct SbClass {
+    pub namespace: SbStr,
+    pub name: SbStr,
+    pub version: SbStr,
+    pub description: SbStr,
+    /// `SB_CLASS_NODE` or `SB_CLASS_FILTER`.
+    pub kind: u32,
+    /// `SB_CLASS_*` flags.
+    pub flags: u32,
+    /// Build an instance from the json config, returning `SB_OK` or `SB_ERROR`.
+    pub construct: SbConstructFn,
+    pub destroy: Option<unsafe extern "C" fn(instance: *mut c_void)>,
+    /// Nodes: fill the response, returning `SB_OK` or `SB_ERROR`.
+    pub call_node: Option<SbCallFn>,
+    /// Filters: return `SB_NEXT`, `SB_RESPOND` or `SB_ERROR`.
+    pub on_request: Option<SbCallFn>,
+    /// Filters with `SB_CLASS_ON_RESPONSE`: modify the response, returning `SB_OK` or `SB_ERROR`.
+    pub on_response: Option<SbOnResponseFn>,
+}
+
+#[repr(C)]
+pub struct SbPlugin {
+    pub name: SbStr,
+    pub abi_major: u32,
+    pub abi_minor: u32,
+    pub required_capabilities: u64,
+    pub class_count: usize,
+    pub classes: *const SbClass,
+    /// Since abi 1.1: JSON Schemas of the class configs, parallel to `classes`. The array or an
+    /// entry may be empty when a class doesn't describe its config.
+    pub config_schemas: *const SbStr,
+}
+
+pub type SbPluginEntryFn = unsafe extern "C" fn(host: *const SbHostApi) -> *const SbPlugin;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CPluginError {
+    #[error("Plugin returned no descriptor")]
+    NullDescriptor,
+    #[error(
+        "Plugin `{plugin}` is built for abi {plugin_major}.{plugin_minor}, this kernel supports {}.{}",
+        SB_ABI_VERSION_MAJOR,
+        SB_ABI_VERSION_MINOR
+    )]
+    IncompatibleAbi {
+        plugin: String,
+        plugin_major: u32,
+        plugin_minor: u32,
+    },
+    #[error("Plugin `{plugin}` requires capabilities {missing:#x} this kernel doesn't support")]
+    MissingCapabilities { plugin: String, missing: u64 },
+    #[error("Plugin `{plugin}` has an invalid class #{index}: {reason}")]
+    InvalidClass {
+        plugin: String,
+        index: usize,
+        reason: &'static str,
+    },
+    #[error("Native plugin failed to construct `{class}`: {message}")]
+    Construct { class: ClassId, message: String },
+    #[error("Native plugin `{class}` failed: {message}")]
+    Call { class: ClassId, message: String },
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Forget the error of an earlier call, so it isn't blamed on the next failing one.
+fn clear_last_error() {
+    LAST_ERROR.with_borrow_mut(|error| *error = None);
+}
+
+fn take_last_error() -> String {
+    LAST_ERROR
+        .with_borrow_mut(Option::take)
+        .unwrap_or_else(|| "no error message".to_string())
+}
+
+unsafe extern "C" fn host_log(level: u32, message: SbStr) {
+    let message = String::from_utf8_lossy(unsafe { message.as_bytes() });
+    match level {
+        0 => tracing::trace!("{message}"),
+        1 => tracing::debug!("{message}"),
+        2 => tracing::info!("{message}"),
+        3 => tracing::warn!("{message}"),
+        _ => tracing::error!("{message}"),
+    }
+}
+
+unsafe extern "C" fn host_set_error(message: SbStr) {
+    let message = String::from_utf8_lossy(unsafe { message.as_bytes() }).into_owned();
+    LAST_ERROR.with_borrow_mut(|error| *error = Some(message));
+}
+
+unsafe extern "C" fn request_method(req: *const SbRequest) -> SbStr {
+    SbStr::new(unsafe { &*req }.parts.method.as_str().as_bytes())
+}
+
+unsafe extern "C" fn request_set_method(req: *mut SbRequest, method: SbStr) -> i32 {
+    let req = unsafe { &mut *req };
+    match http::Method::from_bytes(unsafe { method.as_bytes() }) {
+        Ok(method) => {
+            req.parts.method = method;
+            SB_OK
+        }
+        Err(_) => SB_ERROR,
+    }
+}
+
+unsafe extern "C" fn request_uri(req: *const SbRequest) -> SbStr {
+    SbStr::new(unsafe { &*req }.uri.as_bytes())
+}
+
+unsafe extern "C" fn request_set_uri(req: *mut SbRequest, uri: SbStr) -> i32 {
+    let req = unsafe { &mut *req };
+    let Some(uri) = (unsafe { uri.as_str() }) else {
+        return SB_ERROR;
+    };
+    match uri.parse() {
+        Ok(parsed) => {
+            req.parts.uri = parsed;
+            req.uri = uri.to_string();
+            SB_OK
+        }
+        Err(_) => SB_ERROR,
+    }
+}
+
+unsafe extern "C" fn request_headers(req: *mut SbRequest) -> *mut SbHeaders {
+    &mut unsafe { &mut *req }.parts.headers
+}
+
+unsafe extern "C" fn request_body(req: *const SbRequest) -> SbStr {
+    unsafe { &*req }
+        .body
+        .as_deref()
+        .map(SbStr::new)
+        .unwrap_or(SbStr::NULL)
+}
+
+unsafe extern "C" fn request_set_body(req: *mut SbRequest, body: SbStr) {
+    unsafe { &mut *req }.body = Some(unsafe { body.as_bytes() }.to_vec());
+}
+
+unsafe extern "C" fn request_peer_addr(req: *const SbRequest) -> SbStr {
+    SbStr::new(unsafe { &*req }.peer_addr.as_bytes())
+}
+
+unsafe extern "C" fn request_capture(req: *const SbRequest, name: SbStr) -> SbStr {
+    let req = unsafe { &*req };
+    unsafe { name.as_str() }
+        .and_then(|name| req.captures.get(name))
+        .map(|value| SbStr::new(value.as_bytes()))
+        .unwrap_or(SbStr::NULL)
+}
+
+unsafe extern "C" fn request_set_capture(req: *mut SbRequest, name: SbStr, value: SbStr) -> i32 {
+    let req = unsafe { &mut *req };
+    let (Some(name), Some(value)) = (unsafe { name.as_str() }, unsafe { value.as_str() }) else {
+        return SB_ERROR;
+    };
+    req.captures.insert(name.into(), value.into());
+    SB_OK
+}
+
+unsafe extern "C" fn response_status(res: *const SbResponse) -> u16 {
+    unsafe { &*res }.parts.status.as_u16()
+}
+
+unsafe extern "C" fn response_set_status(res: *mut SbResponse, status: u16) -> i32 {
+    match StatusCode::from_u16(status) {
+        Ok(status) => {
+            unsafe { &mut *res }.parts.status = status;
+            SB_OK
+        }
+        Err(_) => SB_ERROR,
+    }
+}
+
+unsafe extern "C" fn response_headers(res: *mut SbResponse) -> *mut SbHeaders {
+    &mut unsafe { &mut *res }.parts.headers
+}
+
+unsafe extern "C" fn response_body(res: *const SbResponse) -> SbStr {
+    unsafe { &*res }
+        .body
+        .as_deref()
+        .map(SbStr::new)
+        .unwrap_or(SbStr::NULL)
+}
+
+unsafe extern "C" fn response_set_body(res: *mut SbResponse, body: SbStr) {
+    unsafe { &mut *res }.body = Some(unsafe { body.as_bytes() }.to_vec());
+}
+
+unsafe extern "C" fn headers_len(headers: *const SbHeaders) -> usize {
+    unsafe { &*headers }.len()
+}
+
+unsafe extern "C" fn headers_at(
+    headers: *const SbHeaders,
+    index: usize,
+    name: *mut SbStr,
+    value: *mut SbStr,
+) -> i32 {
+    match unsafe { &*headers }.iter().nth(index) {
+        Some((header_name, header_value)) => {
+            unsafe {
+                *name = SbStr::new(header_name.as_str().as_bytes());
+                *value = SbStr::new(header_value.as_bytes());
+            }
+            SB_OK
+        }
+        None => SB_ERROR,
+    }
+}
+
+unsafe extern "C" fn headers_get(headers: *const SbHeaders, name: SbStr) -> SbStr {
+    let Ok(name) = HeaderName::from_bytes(unsafe { name.as_bytes() }) else {
+        return SbStr::NULL;
+    };
+    unsafe { &*headers }
+        .get(name)
+        .map(|value| SbStr::new(value.as_bytes()))
+        .unwrap_or(SbStr::NULL)
+}
+
+unsafe fn header_pair(name: SbStr, value: SbStr) -> Option<(HeaderName, HeaderValue)> {
+    let name = HeaderName::from_bytes(unsafe { name.as_bytes() }).ok()?;
+    let value = HeaderValue::from_bytes(unsafe { value.as_bytes() }).ok()?;
+    Some((name, value))
+}
+
+unsafe extern "C" fn headers_set(headers: *mut SbHeaders, name: SbStr, value: SbStr) -> i32 {
+    match unsafe { header_pair(name, value) } {
+        Some((name, value)) => {
+            unsafe { &mut *headers }.insert(name, value);
+            SB_OK
+        }
+        None => SB_ERROR,
+    }
+}
+
+unsafe extern "C" fn headers_append(headers: *mut SbHeaders, name: SbStr, value: SbStr) -> i32 {
+    match unsafe { header_pair(name, value) } {
+        Some((name, value)) => {
+            unsafe { &mut *headers }.append(name, value);
+            SB_OK
+        }
+        None => SB_ERROR,
+    }
+}
+
+unsafe extern "C" fn headers_remove(headers: *mut SbHeaders, name: SbStr) {
+    if let Ok(name) = HeaderName::from_bytes(unsafe { name.as_bytes() }) {
+        unsafe { &mut *headers }.remove(name);
+    }
+}
+
+static HOST_API: SbHostApi = SbHostApi {
+    abi_major: SB_ABI_VERSION_MAJOR,
+    abi_minor: SB_ABI_VERSION_MINOR,
+    capabilities: SB_HOST_CAPABILITIES,
+    log: host_log,
+    set_error: host_set_error,
+    request_method,
+    request_set_method,
+    request_uri,
+    request_set_uri,
+    request_headers,
+    request_body,
+    request_set_body,
+    request_peer_addr,
+    request_capture,
+    request_set_capture,
+    response_status,
+    response_set_status,
+    response_headers,
+    response_body,
+    response_set_body,
+    headers_len,
+    headers_at,
+    headers_get,
+    headers_set,
+    headers_append,
+    headers_remove,
+};
+
+/// A class of a native plugin, with its strings copied out of the plugin.
+#[derive(Clone)]
+struct CClass {
+    id: ClassId,
+    meta: ClassMeta,
+    instance_type: InstanceType,
+    config_schema: Schema,
+    flags: u32,
+    construct: SbConstructFn,
+    destroy: Option<unsafe extern "C" fn(instance: *mut c_void)>,
+    call_node: Option<SbCallFn>,
+    on_request: Option<SbCallFn>,
+    on_response: Option<SbOnResponseFn>,
+}
+
+/// A native plugin whose descriptor passed the version and capability checks.
+pub struct CPlugin {
+    name: String,
+    classes: Vec<CClass>,
+}
+
+impl CPlugin {
+    /// Call the plugin entry and check what it returned against this host.
+    ///
+    /// # Safety
+    /// `entry` must be a `switchboard_plugin_v1` of a library that stays loaded for as long as
+    /// the plugin and the instances built from it live.
+    pub unsafe fn from_entry(entry: SbPluginEntryFn) -> Result<Self, CPluginError> {
+        clear_last_error();
+        let descriptor = unsafe { entry(&HOST_API) };
+        let Some(descriptor) = (unsafe { descriptor.as_ref() }) else {
+            return Err(CPluginError::NullDescriptor);
+        };
+        let name = String::from_utf8_lossy(unsafe { descriptor.name.as_bytes() }).into_owned();
+        // minor versions only add to the host api, so older plugins keep working
+        if descriptor.abi_major != SB_ABI_VERSION_MAJOR
+            || descriptor.abi_minor > SB_ABI_VERSION_MINOR
+        {
+            return Err(CPluginError::IncompatibleAbi {
+                plugin: name,
+                plugin_major: descriptor.abi_major,
+                plugin_minor: descriptor.abi_minor,
+            });
+        }
+        let missing = descriptor.required_capabilities & !SB_HOST_CAPABILITIES;
+        if missing != 0 {
+            return Err(CPluginError::MissingCapabilities {
+                plugin: name,
+                missing,
+            });
+        }
+        let classes = if descriptor.class_count == 0 || descriptor.classes.is_null() {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(descriptor.classes, descriptor.class_count) }
+        };
+        let invalid = |index, reason| CPluginError::InvalidClass {
+            plugin: name.clone(),
+            index,
+            reason,
+        };
+        // descriptors of 1.0 plugins end before `config_schemas`
+        let config_schemas = if descriptor.abi_minor < 1 || descriptor.config_schemas.is_null() {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(descriptor.config_schemas, classes.len()) }
+        };
+        let mut checked = Vec::with_capacity(classes.len());
+        for (index, class) in classes.iter().enumerate() {
+            let (Some(class_name), namespace) = (unsafe { class.name.as_str() }, unsafe {
+                class.namespace.as_str()
+            }) else {
+                return Err(invalid(index, "name is not utf-8"));
+            };
+            let instance_type = match class.kind {
+                SB_CLASS_NODE if class.call_node.is_none() => {
+                    return Err(invalid(index, "node without call_node"));
+                }
+                SB_CLASS_NODE => InstanceType::Node,
+                SB_CLASS_FILTER if class.on_request.is_none() => {
+                    return Err(invalid(index, "filter without on_request"));
+                }
+                SB_CLASS_FILTER
+                    if class.flags & SB_CLASS_ON_RESPONSE != 0 && class.on_response.is_none() =>
+                {
+                    return Err(invalid(index, "SB_CLASS_ON_RESPONSE without on_response"));
+                }
+                SB_CLASS_FILTER => InstanceType::Filter,
+                _ => return Err(invalid(index, "unknown kind")),
+            };
+            let config_schema = match config_schemas.get(index).map(|s| unsafe { s.as_bytes() }) {
+                Some(bytes) if !bytes.is_empty() => serde_json::from_slice::<Schema>(bytes)
+                    .map_err(|_| invalid(index, "config schema is not a json schema"))?,
+                _ => Schema::from(true),
+            };
+            let optional = |s: SbStr| {
+                unsafe { s.as_str() }
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+            };
+            checked.push(CClass {
+                id: match namespace.filter(|ns| !ns.is_empty()) {
+                    Some(namespace) => ClassId::new(namespace, class_name),
+                    None => ClassId::std(class_name),
+                },
+                meta: ClassMeta {
+                    version: optional(class.version).unwrap_or_default(),
+                    description: optional(class.description),
+                    author: None,
+                    license: None,
+                    repository: None,
+                    homepage: None,
+                },
+                instance_type,
+                config_schema,
+                flags: class.flags,
+                construct: class.construct,
+                destroy: class.destroy,
+                call_node: class.call_node,
+                on_request: class.on_request,
+                on_response: class.on_response,
+            });
+        }
+        Ok(Self {
+            name,
+            classes: checked,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn class_ids(&self) -> impl Iterator<Item = ClassId> + '_ {
+        self.classes.iter().map(|class| class.id.clone())
+    }
+}
+
+/// An instance built by a plugin, destroyed with the node or filter owning it.
+struct CInstance {
+    class: CClass,
+    ptr: *mut c_void,
+}
+
+// plugins are required to make their instances thread safe
+unsafe impl Send for CInstance {}
+unsafe impl Sync for CInstance {}
+
+impl Drop for CInstance {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.class.destroy {
+            clear_last_error();
+            unsafe { destroy(self.ptr) }
+        }
+    }
+}
+
+fn plugin_error_response(error: CPluginError) -> DynResponse {
+    tracing::error!("{}", error);
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, error, ERR_NATIVE_PLUGIN)
+}
+
+impl SbRequest {
+    /// Take a request apart for a plugin, buffering the body if the class reads it. The body is
+    /// returned if it wasn't.
+    async fn new(
+        req: DynRequest,
+        ctx: &FlowContext,
+        read_body: bool,
+    ) -> Result<(Self, Option<DynBody>), DynResponse> {
+        let (parts, body) = req.into_parts();
+        let (body, stream) = if read_body {
+            let body = read_plugin_body(body, MAX_BODY_BYTES, ERR_NATIVE_PLUGIN).await?;
+            (Some(body), None)
+        } else {
+            (None, Some(body))
+        };
+        let request = Self {
+            uri: parts.uri.to_string(),
+            captures: parts
+                .extensions
+                .get::<Captures>()
+                .map(|captures| captures.captures.clone())
+                .unwrap_or_default(),
+            peer_addr: ctx
+                .connection_info
+                .as_ref()
+                .map(|info| info.peer_addr.to_string())
+                .unwrap_or_default(),
+            parts,
+            body,
+        };
+        Ok((request, stream))
+    }
+
+    /// What `on_response` sees of the request, without its body and extensions.
+    fn snapshot(&self) -> Self {
+        let mut parts = http::Request::new(()).into_parts().0;
+        parts.method = self.parts.method.clone();
+        parts.uri = self.parts.uri.clone();
+        parts.version = self.parts.version;
+        parts.headers = self.parts.headers.clone();
+        Self {
+            parts,
+            uri: self.uri.clone(),
+            body: None,
+            captures: self.captures.clone(),
+            peer_addr: self.peer_addr.clone(),
+        }
+    }
+
+    fn into_request(self, stream: Option<DynBody>) -> DynRequest {
+        let mut parts = self.parts;
+        parts.extensions.insert(Captures {
+            captures: self.captures,
+        });
+        let body = match (self.body, stream) {
+            (Some(body), _) => replace_plugin_body(&mut parts.headers, body),
+            (None, Some(stream)) => stream,
+            (None, None) => bytes_body(Vec::new()),
+        };
+        DynRequest::from_parts(parts, body)
+    }
+}
+
+impl SbResponse {
+    fn new() -> Self {
+        Self {
+            parts: http::Response::new(()).into_parts().0,
+            body: None,
+        }
+    }
+
+    fn into_response(self, stream: Option<DynBody>) -> DynResponse {
+        let mut parts = self.parts;
+        let body = match (self.body, stream) {
+            (Some(body), _) => replace_plugin_body(&mut parts.headers, body),
+            (None, Some(stream)) => stream,
+            (None, None) => bytes_body(Vec::new()),
+        };
+        DynResponse::from_parts(parts, body)
+    }
+}
+
+impl CInstance {
+    fn call_error(&self) -> CPluginError {
+        CPluginError::Call {
+            class: self.class.id.clone(),
+            message: take_last_error(),
+        }
+    }
+
+    async fn call_node(self: Arc<Self>, req: DynRequest, ctx: &mut FlowContext) -> DynResponse {
+        let read_body = self.class.flags & SB_CLASS_REQUEST_BODY != 0;
+        let (mut request, _) = match SbRequest::new(req, ctx, read_body).await {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let mut response = SbResponse::new();
+        let call_node = self.class.call_node.expect("checked when loading");
+        clear_last_error();
+        if unsafe { call_node(self.ptr, &mut request, &mut response) } != SB_OK {
+            return plugin_error_response(self.call_error());
+        }
+        response.into_response(None)
+    }
+
+    async fn call_filter(
+        self: Arc<Self>,
+        req: DynRequest,
+        ctx: &mut FlowContext,
+        next: Next,
+    ) -> DynResponse {
+        let read_body = self.class.flags & SB_CLASS_REQUEST_BODY != 0;
+        let (mut request, stream) = match SbRequest::new(req, ctx, read_body).await {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let mut response = SbResponse::new();
+        let on_request = self.class.on_request.expect("checked when loading");
+        clear_last_error();
+        match unsafe { on_request(self.ptr, &mut request, &mut response) } {
+            SB_NEXT => {}
+            SB_RESPOND => return response.into_response(None),
+            _ => return plugin_error_response(self.call_error()),
+        }
+        let on_response = match self.class.on_response {
+            Some(on_response) if self.class.flags & SB_CLASS_ON_RESPONSE != 0 => on_response,
+            _ => return next.call(request.into_request(stream), ctx).await,
+        };
+        let snapshot = request.snapshot();
+        let (parts, body) = next
+            .call(request.into_request(stream), ctx)
+            .await
+            .into_parts();
+        let (body, stream) = if self.class.flags & SB_CLASS_RESPONSE_BODY != 0 {
+            match read_plugin_body(body, MAX_BODY_BYTES, ERR_NATIVE_PLUGIN).await {
+                Ok(body) => (Some(body), None),
+                Err(response) => return response,
+            }
+        } else {
+            (None, Some(body))
+        };
+        let mut response = SbResponse { parts, body };
+        clear_last_error();
+        if unsafe { on_response(self.ptr, &snapshot, &mut response) } != SB_OK {
+            return plugin_error_response(self.call_error());
+        }
+        response.into_response(stream)
+    }
+}
+
+impl ClassRegistry {
+    /// Register every class of a native plugin, replacing classes with the same id.
+    pub fn register_c_plugin(&mut self, plugin: &CPlugin) {
+        for class in &plugin.classes {
+            let data = ClassData {
+                id: class.id.clone(),
+                meta: class.meta.clone(),
+                instance_type: class.instance_type.clone(),
+                config_schema: class.config_schema.clone(),
+            };
+            let class = class.clone();
+            let constructor = Constructor::new(move |config: &SerdeValue| {
+                let config = serde_json::to_string(config)
+                    .map_err(|e| ConstructError::BuildError(Box::new(e)))?;
+                let mut ptr = std::ptr::null_mut();
+                clear_last_error();
+                if unsafe { (class.construct)(SbStr::new(config.as_bytes()), &mut ptr) } != SB_OK {
+                    return Err(ConstructError::BuildError(Box::new(
+                        CPluginError::Construct {
+                            class: class.id.clone(),
+                            message: take_last_error(),
+                        },
+                    )));
+                }
+                let instance = Arc::new(CInstance {
+                    class: class.clone(),
+                    ptr,
+                });
+                Ok(match class.instance_type {
+                    InstanceType::Filter => InstanceValue::Filter(Filter {
+                        call: Arc::new(move |req, ctx, next| {
+                            Box::pin(instance.clone().call_filter(req, ctx, next))
+                        }),
+                    }),
+                    InstanceType::Node => {
+                        InstanceValue::Node(Node::new(NodeInterface::service(), move |req, ctx| {
+                            Box::pin(instance.clone().call_node(req, ctx))
+                        }))
+                    }
+                })
+            });
+            self.class_data.insert(
+                data.id.clone(),
+                ClassDataWithConstructor { data, constructor },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::flow::Flow;
+
+    fn descriptor(
+        abi_major: u32,
+        abi_minor: u32,
+        required_capabilities: u64,
+        classes: Vec<SbClass>,
+    ) -> *const SbPlugin {
+        let classes = Box::leak(classes.into_boxed_slice());
+        Box::leak(Box::new(SbPlugin {
+            name: SbStr::new(b"test"),
+            abi_major,
+            abi_minor,
+            required_capabilities,
+            class_count: classes.len(),
+            classes: classes.as_ptr(),
+            config_schemas: std::ptr::null(),
+        }))
+    }
+
+    unsafe extern "C" fn construct(_config: SbStr, instance: *mut *mut c_void) -> i32 {
+        unsafe { *instance = std::ptr::null_mut() };
+        SB_OK
+    }
+
+    /// Answers with the `x-echo` header and the body of the request.
+    unsafe extern "C" fn echo(_: *mut c_void, req: *mut SbRequest, res: *mut SbResponse) -> i32 {
+        let api = &HOST_API;
+        unsafe {
+            let value = (api.headers_get)((api.request_headers)(req), SbStr::new(b"x-echo"));
+            (api.headers_set)((api.response_headers)(res), SbStr::new(b"x-echo"), value);
+            (api.response_set_body)(res, (api.request_body)(req));
+        }
+        SB_OK
+    }
+
+    fn echo_class() -> SbClass {
+        SbClass {
+            namespace: SbStr::new(b"test"),
+            name: SbStr::new(b"echo"),
+            version: SbStr::NULL,
+            description: SbStr::NULL,
+            kind: SB_CLASS_NODE,
+            flags: SB_CLASS_REQUEST_BODY,
+            construct,
+            destroy: None,
+            call_node: Some(echo),
+            on_request: None,
+            on_response: None,
+        }
+    }
+
+    unsafe extern "C" fn null_entry(_: *const SbHostApi) -> *const SbPlugin {
+        std::ptr::null()
+    }
+
+    unsafe extern "C" fn next_major_entry(_: *const SbHostApi) -> *const SbPlugin {
+        descriptor(SB_ABI_VERSION_MAJOR + 1, 0, 0, vec![])
+    }
+
+    unsafe extern "C" fn next_minor_entry(_: *const SbHostApi) -> *const SbPlugin {
+        descriptor(SB_ABI_VERSION_MAJOR, SB_ABI_VERSION_MINOR + 1, 0, vec![])
+    }
+
+    unsafe extern "C" fn unknown_capability_entry(_: *const SbHostApi) -> *const SbPlugin {
+        descriptor(SB_ABI_VERSION_MAJOR, 0, SB_CAP_NODE | 1 << 63, vec![])
+    }
+
+    unsafe extern "C" fn invalid_class_entry(_: *const SbHostApi) -> *const SbPlugin {
+        let class = SbClass {
+            call_node: None,
+            ..echo_class()
+        };
+        descriptor(SB_ABI_VERSION_MAJOR, SB_ABI_VERSION_MINOR, 0, vec![class])
+    }
+
+    unsafe extern "C" fn echo_entry(host: *const SbHostApi) -> *const SbPlugin {
+        assert_eq!(unsafe { (*host).abi_major }, SB_ABI_VERSION_MAJOR);
+        // built against 1.0, before config schemas
+        descriptor(
+            SB_ABI_VERSION_MAJOR,
+            0,
+            SB_CAP_NODE | SB_CAP_BODY,
+            vec![echo_class()],
+        )
+    }
+
+    unsafe extern "C" fn failing_construct(_config: SbStr, _instance: *mut *mut c_void) -> i32 {
+        SB_ERROR
+    }
+
+    unsafe extern "C" fn failing_entry(_: *const SbHostApi) -> *const SbPlugin {
+        let class = SbClass {
+            construct: failing_construct,
+            ..echo_class()
+        };
+        descriptor(SB_ABI_VERSION_MAJOR, SB_ABI_VERSION_MINOR, 0, vec![class])
+    }
+
+    #[test]
+    fn test_descriptor_checks() {
+        let load = |entry| unsafe { CPlugin::from_entry(entry) };
+        assert!(matches!(
+            load(null_entry),
+            Err(CPluginError::NullDescriptor)
+        ));
+        assert!(matches!(
+            load(next_major_entry),
+            Err(CPluginError::IncompatibleAbi { plugin_major, .. })
+                if plugin_major == SB_ABI_VERSION_MAJOR + 1
+        ));
+        assert!(matches!(
+            load(next_minor_entry),
+            Err(CPluginError::IncompatibleAbi { plugin_minor, .. })
+                if plugin_minor == SB_ABI_VERSION_MINOR + 1
+        ));
+        assert!(matches!(
+            load(unknown_capability_entry),
+            Err(CPluginError::MissingCapabilities {
+                missing: 0x8000_0000_0000_0000,
+                ..
+            })
+        ));
+        assert!(matches!(
+            load(invalid_class_entry),
+            Err(CPluginError::InvalidClass { index: 0, .. })
+        ));
+        let plugin = load(echo_entry).unwrap();
+        assert_eq!(plugin.name(), "test");
+        assert_eq!(
+            plugin.class_ids().collect::<Vec<_>>(),
+            vec![ClassId::new("test", "echo")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_node_round_trip() {
+        let plugin = unsafe { CPlugin::from_entry(echo_entry) }.unwrap();
+        let mut registry = ClassRegistry::default();
+        registry.register_c_plugin(&plugin);
+        let InstanceValue::Node(node) = registry
+            .construct(ClassId::new("test", "echo"), SerdeValue::Unit)
+            .unwrap()
+        else {
+            panic!("echo is a node");
+        };
+        let flow = Flow {
+            nodes: Arc::new(HashMap::new()),
+            filters: Arc::new(HashMap::new()),
+            entrypoint: "echo".into(),
+        };
+        let mut ctx = FlowContext::new(flow, "echo".into());
+        let request = http::Request::builder()
+            .header("x-echo", "ping")
+            .body(bytes_body(b"hello".to_vec()))
+            .unwrap();
+        let response = (node.call)(request, &mut ctx).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-echo"], "ping");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_replaced_body_drops_content_length() {
+        let (mut parts, _) = http::Request::builder()
+            .header(http::header::CONTENT_LENGTH, "5")
+            .header("x-keep", "1")
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts.uri = "/path".parse().unwrap();
+        let mut request = SbRequest {
+            uri: parts.uri.to_string(),
+            parts,
+            body: Some(b"hello".to_vec()),
+            captures: HashMap::new(),
+            peer_addr: String::new(),
+        };
+        unsafe {
+            (HOST_API.request_set_body)(&mut request, SbStr::new(b"replaced body"));
+            let headers = (HOST_API.request_headers)(&mut request);
+            assert_eq!(
+                (HOST_API.headers_set)(headers, SbStr::new(b"x-plugin"), SbStr::new(b"yes")),
+                SB_OK
+            );
+        }
+        let request = request.into_request(None);
+        assert!(
+            request
+                .headers()
+                .get(http::header::CONTENT_LENGTH)
+                .is_none()
+        );
+        assert_eq!(request.headers()["x-keep"], "1");
+        assert_eq!(request.headers()["x-plugin"], "yes");
+        let body = request.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"replaced body");
+
+        let mut response = SbResponse::new();
+        response
+            .parts
+            .headers
+            .insert(http::header::CONTENT_LENGTH, HeaderValue::from_static("2"));
+        unsafe { (HOST_API.response_set_body)(&mut response, SbStr::new(b"longer")) };
+        let response = response.into_response(None);
+        assert!(
+            response
+                .headers()
+                .get(http::header::CONTENT_LENGTH)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_stale_error_is_cleared() {
+        let plugin = unsafe { CPlugin::from_entry(failing_entry) }.unwrap();
+        let mut registry = ClassRegistry::default();
+        registry.register_c_plugin(&plugin);
+        // an earlier call on this thread set an error, yet succeeded
+        unsafe { (HOST_API.set_error)(SbStr::new(b"stale error")) };
+        let Err(error) = registry.construct(ClassId::new("test", "echo"), SerdeValue::Unit) else {
+            panic!("construct should fail");
+        };
+        let message = error.to_string();
+        assert!(!message.contains("stale error"), "{message}");
+        assert!(message.contains("no error message"), "{message}");
+    }
+}
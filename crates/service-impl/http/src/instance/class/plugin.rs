@@ -1,5 +1,8 @@
 use super::registry::ClassRegistry;
 
+/// Entry of a Rust ABI plugin. The ABI of Rust isn't stable, so these plugins must be built with
+/// the same compiler and `switchboard-http` as the kernel. Prefer the C ABI of
+/// [`cabi`](super::cabi).
 pub type PluginRegisterFn = unsafe extern "Rust" fn(&mut ClassRegistry, api_verison: &'static str);
 pub const API_VERSION: &str = "0.1";
 impl ClassRegistry {
@@ -8,13 +11,296 @@ impl ClassRegistry {
     }
 }
 
+#[cfg(feature = "service-impl")]
+#[derive(Debug, thiserror::Error)]
+pub enum PluginLoadError {
+    #[error("no `switchboard_plugin_v1` or `register` entry: {0}")]
+    MissingEntry(#[from] libloading::Error),
+    #[error(transparent)]
+    CPlugin(#[from] super::cabi::CPluginError),
+    #[error("class `{0}` is already registered by another plugin or the kernel")]
+    ClassConflict(switchboard_model::services::http::ClassId),
+    #[error("{0:?} is a rust abi plugin, which can't be unloaded and is only loaded at startup")]
+    NotUnloadable(std::path::PathBuf),
+}
+
 #[cfg(feature = "service-impl")]
 impl ClassRegistry {
-    pub fn load_dynamic_lib(&mut self, lib: &libloading::Library) -> Result<(), libloading::Error> {
+    /// Register the classes of a loaded library, through its C ABI entry if it has one.
+    pub fn load_dynamic_lib(&mut self, lib: &libloading::Library) -> Result<(), PluginLoadError> {
+        use super::cabi::{CPlugin, SB_PLUGIN_ENTRY_SYMBOL, SbPluginEntryFn};
         unsafe {
+            if let Ok(entry) = lib.get::<SbPluginEntryFn>(SB_PLUGIN_ENTRY_SYMBOL) {
+                let plugin = CPlugin::from_entry(*entry)?;
+                tracing::debug!(plugin = plugin.name(), "register native http plugin");
+                self.register_c_plugin(&plugin);
+                return Ok(());
+            }
             let register_fn = lib.get::<PluginRegisterFn>(b"register\0")?;
             self.register_rust_plugin(*register_fn);
         };
         Ok(())
     }
 }
+
+#[cfg(feature = "service-impl")]
+mod loaded {
+    use std::{
+        mem::ManuallyDrop,
+        path::{Path, PathBuf},
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    use serde::Serialize;
+    use switchboard_model::services::http::ClassId;
+
+    use super::PluginLoadError;
+    use crate::{
+        flow::{filter::Filter, node::Node},
+        instance::{
+            InstanceValue,
+            class::{Constructor, registry::ClassRegistry},
+        },
+    };
+
+    /// A native plugin library. It stays loaded while a class or an instance built from it is
+    /// alive, so a replaced C ABI plugin is unloaded once the last flow using it is dropped.
+    ///
+    /// Rust ABI plugins are never unloaded: their response bodies, errors, spawned tasks and
+    /// tracing callsites can point into the library long after the last call returned. So they
+    /// are only loaded at startup, see [`PluginLibrary::require_unloadable`].
+    pub struct PluginLibrary {
+        library: ManuallyDrop<libloading::Library>,
+        path: PathBuf,
+        remove_on_unload: bool,
+        unloadable: bool,
+    }
+
+    impl std::fmt::Debug for PluginLibrary {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("PluginLibrary")
+                .field("path", &self.path)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl PluginLibrary {
+        /// # Safety
+        /// Loading a library runs its initializers, see [`libloading::Library::new`].
+        pub unsafe fn load(path: impl Into<PathBuf>) -> Result<Self, libloading::Error> {
+            use crate::instance::class::cabi::{SB_PLUGIN_ENTRY_SYMBOL, SbPluginEntryFn};
+            let path = path.into();
+            let library = unsafe { libloading::Library::new(&path)? };
+            // the C ABI copies everything a plugin hands over into host memory
+            let unloadable =
+                unsafe { library.get::<SbPluginEntryFn>(SB_PLUGIN_ENTRY_SYMBOL) }.is_ok();
+            Ok(Self {
+                library: ManuallyDrop::new(library),
+                path,
+                remove_on_unload: false,
+                unloadable,
+            })
+        }
+        /// Delete the library file once it is unloaded, for artifacts pushed by the controller.
+        pub fn remove_on_unload(mut self) -> Self {
+            self.remove_on_unload = true;
+            self
+        }
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+        /// Whether the library is unloaded once unused, only C ABI plugins are.
+        pub fn unloadable(&self) -> bool {
+            self.unloadable
+        }
+        /// Refuse a library which could never be unloaded, for plugins loaded while running.
+        pub fn require_unloadable(mut self) -> Result<Self, PluginLoadError> {
+            if self.unloadable {
+                return Ok(self);
+            }
+            // nothing but its initializers ran yet, so it is still safe to unload
+            self.unloadable = true;
+            Err(PluginLoadError::NotUnloadable(self.path.clone()))
+        }
+    }
+
+    impl Drop for PluginLibrary {
+        fn drop(&mut self) {
+            if self.unloadable {
+                // SAFETY: the library is never touched again, every class and instance of it is
+                // gone and the C ABI leaves no plugin owned data behind
+                unsafe { ManuallyDrop::drop(&mut self.library) };
+                tracing::info!(path = ?self.path, "unloaded http plugin library");
+            } else {
+                tracing::info!(path = ?self.path, "keeping unused rust abi http plugin library loaded");
+            }
+            if self.remove_on_unload {
+                let _ = std::fs::remove_file(&self.path).inspect_err(
+                    |e| tracing::warn!(path = ?self.path, "fail to remove unloaded plugin: {e}"),
+                );
+            }
+        }
+    }
+
+    /// A plugin registered into a [`ClassRegistry`], with the classes it contributes.
+    #[derive(Debug, Clone, Serialize)]
+    pub struct LoadedPlugin {
+        pub name: String,
+        pub path: PathBuf,
+        /// Hex sha256 of the artifact, for plugins pushed by the controller.
+        pub sha256: Option<String>,
+        /// The registry generation the plugin was loaded in.
+        pub generation: u64,
+        pub classes: Vec<ClassId>,
+    }
+
+    impl LoadedPlugin {
+        pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+            Self {
+                name: name.into(),
+                path: path.into(),
+                sha256: None,
+                generation: 0,
+                classes: Vec::new(),
+            }
+        }
+        pub fn with_sha256(mut self, sha256: impl Into<String>) -> Self {
+            self.sha256 = Some(sha256.into());
+            self
+        }
+    }
+
+    /// Keeps `guard` alive behind `inner`, fields drop in order so `inner` goes first.
+    struct Retained<T> {
+        inner: T,
+        guard: Arc<PluginLibrary>,
+    }
+
+    impl<T> Retained<T> {
+        fn get(&self) -> &T {
+            &self.inner
+        }
+    }
+
+    pin_project_lite::pin_project! {
+        /// A call into a plugin, which may still run plugin code after its instance is dropped.
+        struct RetainedFuture<F> {
+            #[pin]
+            inner: F,
+            guard: Arc<PluginLibrary>,
+        }
+    }
+
+    impl<F: Future> Future for RetainedFuture<F> {
+        type Output = F::Output;
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            self.project().inner.poll(cx)
+        }
+    }
+
+    fn retain_instance(instance: InstanceValue, library: &Arc<PluginLibrary>) -> InstanceValue {
+        match instance {
+            InstanceValue::Node(node) => {
+                let call = Retained {
+                    inner: node.call,
+                    guard: library.clone(),
+                };
+                InstanceValue::Node(Node {
+                    interface: node.interface,
+                    call: Arc::new(move |req, ctx| {
+                        Box::pin(RetainedFuture {
+                            inner: (call.get())(req, ctx),
+                            guard: call.guard.clone(),
+                        })
+                    }),
+                })
+            }
+            InstanceValue::Filter(filter) => {
+                let call = Retained {
+                    inner: filter.call,
+                    guard: library.clone(),
+                };
+                InstanceValue::Filter(Filter {
+                    call: Arc::new(move |req, ctx, next| {
+                        Box::pin(RetainedFuture {
+                            inner: (call.get())(req, ctx, next),
+                            guard: call.guard.clone(),
+                        })
+                    }),
+                })
+            }
+        }
+    }
+
+    impl Constructor {
+        /// Keep `library` loaded as long as this constructor or an instance it built is alive.
+        fn retain(self, library: &Arc<PluginLibrary>) -> Self {
+            let constructor = Retained {
+                inner: self,
+                guard: library.clone(),
+            };
+            Constructor::new(move |config| {
+                let instance = constructor.get().construct(config)?;
+                Ok(retain_instance(instance, &constructor.guard))
+            })
+        }
+    }
+
+    impl ClassRegistry {
+        /// Register the classes of a native plugin as `plugin`, replacing an earlier plugin of
+        /// the same name.
+        pub fn register_native_plugin(
+            &mut self,
+            plugin: LoadedPlugin,
+            library: Arc<PluginLibrary>,
+        ) -> Result<(), PluginLoadError> {
+            let mut classes = ClassRegistry::default();
+            classes.load_dynamic_lib(&library.library)?;
+            for class in classes.class_data.values_mut() {
+                class.constructor = class.constructor.clone().retain(&library);
+            }
+            self.register_plugin_classes(plugin, classes)
+        }
+
+        /// Register the wasm plugin as `plugin`, replacing an earlier plugin of the same name.
+        #[cfg(feature = "wasm")]
+        pub fn register_named_wasm_plugin(
+            &mut self,
+            plugin: LoadedPlugin,
+            wasm_plugin: Arc<crate::instance::class::wasm::WasmPlugin>,
+        ) -> Result<(), PluginLoadError> {
+            let mut classes = ClassRegistry::default();
+            classes.register_wasm_plugin(wasm_plugin);
+            self.register_plugin_classes(plugin, classes)
+        }
+
+        fn register_plugin_classes(
+            &mut self,
+            mut plugin: LoadedPlugin,
+            classes: ClassRegistry,
+        ) -> Result<(), PluginLoadError> {
+            let previous = self.plugins.get(&plugin.name);
+            for id in classes.class_data.keys() {
+                let replaced = previous.is_some_and(|previous| previous.classes.contains(id));
+                if !replaced && self.class_data.contains_key(id) {
+                    return Err(PluginLoadError::ClassConflict(id.clone()));
+                }
+            }
+            if let Some(previous) = self.plugins.remove(&plugin.name) {
+                for id in &previous.classes {
+                    self.class_data.remove(id);
+                }
+            }
+            plugin.classes = classes.class_data.keys().cloned().collect();
+            plugin.classes.sort_by_key(|id| id.to_string());
+            plugin.generation = self.generation;
+            self.class_data.extend(classes.class_data);
+            self.plugins.insert(plugin.name.clone(), plugin);
+            Ok(())
+        }
+    }
+}
+#[cfg(feature = "service-impl")]
+pub use loaded::*;
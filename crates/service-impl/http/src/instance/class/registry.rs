@@ -2,7 +2,7 @@ use crate::flow::{
     filter::{AsFilterClass, FilterClass},
     node::{AsNodeClass, NodeClass},
 };
-use crate::instance::{class::*, InstanceValue};
+use crate::instance::{InstanceValue, class::*};
 use std::collections::HashMap;
 use switchboard_service::SerdeValue;
 
@@ -23,6 +23,10 @@ impl std::fmt::Debug for ClassDataWithConstructor {
 #[derive(Debug, Clone, Default)]
 pub struct ClassRegistry {
     pub class_data: HashMap<ClassId, ClassDataWithConstructor>,
+    /// Bumped every time plugins are loaded at runtime.
+    pub generation: u64,
+    #[cfg(feature = "service-impl")]
+    pub plugins: std::collections::BTreeMap<String, super::plugin::LoadedPlugin>,
 }
 #[derive(Debug, thiserror::Error)]
 pub enum ClassRegistryError {
@@ -32,10 +36,22 @@ pub enum ClassRegistryError {
     ClassNotFound { id: ClassId },
 }
 
+/// A value of an instance config that doesn't match the schema of its class.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct ConfigSchemaError {
+    /// Where the value is inside the config, e.g. `["options", "timeout"]`.
+    pub path: Vec<String>,
+    pub message: String,
+}
+
 impl ClassRegistry {
     pub fn const_new() -> Self {
         Self {
             class_data: HashMap::new(),
+            generation: 0,
+            #[cfg(feature = "service-impl")]
+            plugins: std::collections::BTreeMap::new(),
         }
     }
     pub fn construct(
@@ -52,13 +68,45 @@ impl ClassRegistry {
             .construct(&config)
             .map_err(ClassRegistryError::ConstructError)
     }
+    /// Check a config against the schema of its class, returning every mismatch.
+    ///
+    /// Unknown classes and schemas that fail to compile yield nothing, constructing reports them.
+    #[cfg(feature = "service-impl")]
+    pub fn check_config(&self, class_id: &ClassId, config: &SerdeValue) -> Vec<ConfigSchemaError> {
+        let Some(class_data) = self.class_data.get(class_id) else {
+            return Vec::new();
+        };
+        let Ok(config) = serde_json::to_value(config) else {
+            return Vec::new();
+        };
+        let validator = match jsonschema::validator_for(class_data.data.config_schema.as_value()) {
+            Ok(validator) => validator,
+            Err(e) => {
+                tracing::warn!(class = %class_id, "invalid config schema: {e}");
+                return Vec::new();
+            }
+        };
+        validator
+            .iter_errors(&config)
+            .map(|error| ConfigSchemaError {
+                path: error
+                    .instance_path
+                    .as_str()
+                    .split('/')
+                    .skip(1)
+                    .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+                    .collect(),
+                message: error.to_string(),
+            })
+            .collect()
+    }
     pub fn register<C: Class>(&mut self, class: C) {
         let class_id = class.id();
         let class_data = ClassData {
             id: class_id.clone(),
             meta: class.meta(),
             instance_type: class.instance_type(),
-            // config_schema: class.schema(),
+            config_schema: class.schema(),
         };
         self.class_data.insert(
             class_id,
@@ -81,10 +129,15 @@ mod resigter {
     use std::sync::{Arc, OnceLock};
     use tokio::sync::RwLock;
     static GLOBAL_CLASS_REGISTRY: OnceLock<Arc<RwLock<super::ClassRegistry>>> = OnceLock::new();
+    use super::super::plugin::LoadedPlugin;
     use crate::{
+        HttpProvider,
         flow::{
-            balancer::BalancerClass,
+            balancer::{BalancerClass, traffic_split::TrafficSplitClass},
             filter::{
+                api_key_auth::ApiKeyAuthFilterClass, basic_auth::BasicAuthFilterClass,
+                cors::CorsFilterClass, forward_auth::ForwardAuthFilterClass,
+                jwt_auth::JwtAuthFilterClass,
                 request_header_modify::RequestHeaderModifyFilterClass,
                 request_mirror::RequestMirrorFilterClass,
                 request_rate_limit::RequestRateLimitFilterClass,
@@ -92,13 +145,12 @@ mod resigter {
                 response_header_modify::ResponseHeaderModifyFilterClass, timeout::Timeout,
                 url_rewrite::UrlRewriteFilterClass,
             },
-            router::router::RouterRouterClass,
+            router::{matcher::MatchRouterClass, router::RouterRouterClass},
             service::{
                 http_client::HttpClientClass, reverse_proxy::ReverseProxyServiceClass,
                 static_file::StaticFileClass, static_response::StaticResponseServiceClass,
             },
         },
-        HttpProvider,
     };
     impl super::ClassRegistry {
         pub fn register_prelude(&mut self) {
@@ -106,9 +158,11 @@ mod resigter {
             {
                 // balancers
                 self.register_node(BalancerClass);
+                self.register_node(TrafficSplitClass);
 
                 // routers
                 self.register_node(RouterRouterClass);
+                self.register_node(MatchRouterClass);
 
                 // services
                 self.register_node(HttpClientClass);
@@ -125,6 +179,11 @@ mod resigter {
                 self.register_filter(RequestRedirectFilterClass);
                 self.register_filter(ResponseHeaderModifyFilterClass);
                 self.register_filter(Timeout);
+                self.register_filter(CorsFilterClass);
+                self.register_filter(BasicAuthFilterClass);
+                self.register_filter(ApiKeyAuthFilterClass);
+                self.register_filter(JwtAuthFilterClass);
+                self.register_filter(ForwardAuthFilterClass);
             }
         }
         pub fn global(provider: &HttpProvider) -> Arc<RwLock<Self>> {
@@ -134,13 +193,90 @@ mod resigter {
                     registry.register_prelude();
                     // loading dynamic libs
                     for lib in &provider.rust_dyn_libs {
+                        let plugin = LoadedPlugin::new(plugin_name(lib.path()), lib.path());
                         let _ = registry
-                            .load_dynamic_lib(lib)
+                            .register_native_plugin(plugin, lib.clone())
                             .inspect_err(|e| tracing::error!("fail to load dyn lib: {e}"));
                     }
+                    #[cfg(feature = "wasm")]
+                    for plugin in &provider.wasm_plugins {
+                        let loaded = LoadedPlugin::new(plugin_name(plugin.path()), plugin.path());
+                        let _ = registry
+                            .register_named_wasm_plugin(loaded, plugin.clone())
+                            .inspect_err(|e| tracing::error!("fail to load wasm plugin: {e}"));
+                    }
                     Arc::new(RwLock::new(registry))
                 })
                 .clone()
         }
+        /// Replace `registry` with its next generation, built by `update` from a copy of the
+        /// current one. The current generation is kept when `update` fails. Flows keep the
+        /// instances they were built with, so they only see the new classes once rebuilt.
+        pub async fn next_generation<E>(
+            registry: &RwLock<Self>,
+            update: impl FnOnce(&mut Self) -> Result<(), E>,
+        ) -> Result<u64, E> {
+            let mut registry = registry.write().await;
+            let mut next = registry.clone();
+            next.generation += 1;
+            update(&mut next)?;
+            let generation = next.generation;
+            *registry = next;
+            Ok(generation)
+        }
+    }
+
+    /// Plugins given by path are named after their file.
+    fn plugin_name(path: &std::path::Path) -> String {
+        path.file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "service-impl")]
+mod test {
+    use switchboard_model::services::http::consts::STATIC_RESPONSE_CLASS_ID;
+
+    use super::*;
+
+    fn check(class: &ClassId, config: serde_json::Value) -> Vec<Vec<String>> {
+        let mut registry = ClassRegistry::default();
+        registry.register_prelude();
+        registry
+            .check_config(class, &serde_json::from_value(config).unwrap())
+            .into_iter()
+            .map(|error| error.path)
+            .collect()
+    }
+
+    #[test]
+    fn test_check_config() {
+        let static_response = ClassId::std(STATIC_RESPONSE_CLASS_ID);
+        assert!(check(&static_response, serde_json::json!({})).is_empty());
+        assert!(
+            check(
+                &static_response,
+                serde_json::json!({ "status_code": 200, "headers": [["x-a", "b"]] })
+            )
+            .is_empty()
+        );
+        // every mismatch is reported with the path to its field
+        let mut errors = check(
+            &static_response,
+            serde_json::json!({ "status_code": "ok", "headers": [["x-a", 1]] }),
+        );
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                vec!["headers".to_string(), "0".to_string(), "1".to_string()],
+                vec!["status_code".to_string()],
+            ]
+        );
+        // unknown classes are left to the constructor to report
+        assert!(check(&ClassId::std("no-such-class"), serde_json::json!(1)).is_empty());
     }
 }
@@ -0,0 +1,721 @@
+//! WASM plugins, components of the `switchboard:http-plugin` world in `wit/plugin.wit`.
+//!
+//! Unlike Rust plugins they don't depend on the compiler or crate versions the kernel was built
+//! with, and they run sandboxed: WASI without filesystem, network or environment, and every call
+//! gets a fuel budget and a memory cap.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use schemars::Schema;
+use serde::Deserialize;
+use switchboard_model::services::http::{
+    ClassData, ClassId, ClassMeta, InstanceType, NodeInterface,
+};
+use switchboard_service::SerdeValue;
+use wasmtime::{
+    Engine, Store, StoreLimits, StoreLimitsBuilder,
+    component::{Component, Linker, ResourceTable},
+};
+use wasmtime_wasi::{IoView, WasiCtx, WasiCtxBuilder, WasiView};
+
+use crate::{
+    DynBody, DynRequest, DynResponse,
+    consts::ERR_WASM_PLUGIN,
+    extension::captures::Captures,
+    flow::{
+        FlowContext,
+        filter::{Filter, Next},
+        node::Node,
+    },
+    instance::{
+        InstanceValue,
+        class::{
+            ConstructError, Constructor, read_plugin_body,
+            registry::{ClassDataWithConstructor, ClassRegistry},
+            replace_plugin_body,
+        },
+    },
+    utils::error_response,
+};
+
+wasmtime::component::bindgen!({
+    path: "wit",
+    world: "plugin",
+});
+
+use switchboard::http_plugin::{host, types};
+
+/// Constructed instances kept for reuse by each node or filter.
+const MAX_IDLE_INSTANCES: usize = 32;
+
+/// # Example
+/// ```toml
+/// [provider.http.wasm]
+/// fuel = 100000000
+/// memory_bytes = 67108864
+/// max_body_bytes = 4194304
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WasmLimits {
+    /// Fuel of a single call, about one unit per executed instruction.
+    pub fuel: u64,
+    /// Linear memory of an instance.
+    pub memory_bytes: usize,
+    /// Bodies are buffered for the plugin, larger ones are refused with `413`.
+    pub max_body_bytes: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 100_000_000,
+            memory_bytes: 64 << 20,
+            max_body_bytes: 4 << 20,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WasmPluginError {
+    #[error("Failed to set up wasm runtime: {0:#}")]
+    Runtime(wasmtime::Error),
+    #[error("Failed to load wasm plugin {path:?}: {error:#}")]
+    Load {
+        path: PathBuf,
+        error: wasmtime::Error,
+    },
+    #[error("Wasm plugin {path:?} trapped: {error:#}")]
+    Trap {
+        path: PathBuf,
+        error: wasmtime::Error,
+    },
+    #[error("Wasm plugin failed to construct `{class}`: {message}")]
+    Construct { class: ClassId, message: String },
+    #[error("Wasm plugin task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+    #[error("Invalid {0} from wasm plugin")]
+    InvalidMessage(&'static str),
+}
+
+struct HostState {
+    plugin: Arc<Path>,
+    captures: HashMap<Arc<str>, Arc<str>>,
+    peer_addr: Option<SocketAddr>,
+    limits: StoreLimits,
+    table: ResourceTable,
+    wasi: WasiCtx,
+}
+
+impl IoView for HostState {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+impl WasiView for HostState {
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+impl types::Host for HostState {}
+
+impl host::Host for HostState {
+    fn log(&mut self, level: host::LogLevel, message: String) {
+        let plugin = self.plugin.display();
+        match level {
+            host::LogLevel::Trace => tracing::trace!(%plugin, "{message}"),
+            host::LogLevel::Debug => tracing::debug!(%plugin, "{message}"),
+            host::LogLevel::Info => tracing::info!(%plugin, "{message}"),
+            host::LogLevel::Warn => tracing::warn!(%plugin, "{message}"),
+            host::LogLevel::Error => tracing::error!(%plugin, "{message}"),
+        }
+    }
+    fn get_capture(&mut self, name: String) -> Option<String> {
+        self.captures
+            .get(name.as_str())
+            .map(|value| value.to_string())
+    }
+    fn set_capture(&mut self, name: String, value: String) {
+        self.captures.insert(name.into(), value.into());
+    }
+    fn peer_addr(&mut self) -> Option<String> {
+        self.peer_addr.map(|addr| addr.to_string())
+    }
+}
+
+/// Compiles and instantiates wasm plugins, shared by every plugin.
+#[derive(Clone)]
+pub struct WasmRuntime {
+    engine: Engine,
+    linker: Arc<Linker<HostState>>,
+    limits: WasmLimits,
+}
+
+impl WasmRuntime {
+    pub fn new(limits: WasmLimits) -> Result<Self, WasmPluginError> {
+        let mut config = wasmtime::Config::new();
+        config.wasm_component_model(true).consume_fuel(true);
+        let engine = Engine::new(&config).map_err(WasmPluginError::Runtime)?;
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker_sync(&mut linker).map_err(WasmPluginError::Runtime)?;
+        Plugin::add_to_linker(&mut linker, |state: &mut HostState| state)
+            .map_err(WasmPluginError::Runtime)?;
+        Ok(Self {
+            engine,
+            linker: Arc::new(linker),
+            limits,
+        })
+    }
+
+    /// Compile the component at `path` and ask it for its classes.
+    pub fn load(&self, path: impl Into<PathBuf>) -> Result<WasmPlugin, WasmPluginError> {
+        let path: Arc<Path> = path.into().into();
+        let component =
+            Component::from_file(&self.engine, &path).map_err(|error| WasmPluginError::Load {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        let mut plugin = WasmPlugin {
+            runtime: self.clone(),
+            path,
+            component,
+            classes: Vec::new(),
+            config_schemas: Vec::new(),
+        };
+        let mut instance = plugin.instantiate()?;
+        plugin.classes = instance
+            .bindings
+            .call_classes(&mut instance.store)
+            .map_err(|error| plugin.trap(error))?;
+        plugin.config_schemas = plugin
+            .classes
+            .iter()
+            .map(|class| match &class.config_schema {
+                Some(schema) => serde_json::from_str::<Schema>(schema)
+                    .map_err(|_| WasmPluginError::InvalidMessage("config schema")),
+                None => Ok(Schema::from(true)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(plugin)
+    }
+}
+
+pub struct WasmPlugin {
+    runtime: WasmRuntime,
+    path: Arc<Path>,
+    component: Component,
+    classes: Vec<types::ClassInfo>,
+    /// The parsed config schema of each class, a plugin with an invalid one is refused.
+    config_schemas: Vec<Schema>,
+}
+
+struct PluginInstance {
+    store: Store<HostState>,
+    bindings: Plugin,
+}
+
+impl WasmPlugin {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn class_ids(&self) -> impl Iterator<Item = ClassId> + '_ {
+        self.classes
+            .iter()
+            .map(|class| ClassId::new(&class.namespace, &class.name))
+    }
+
+    fn trap(&self, error: wasmtime::Error) -> WasmPluginError {
+        WasmPluginError::Trap {
+            path: self.path.to_path_buf(),
+            error,
+        }
+    }
+
+    fn instantiate(&self) -> Result<PluginInstance, WasmPluginError> {
+        let limits = &self.runtime.limits;
+        let state = HostState {
+            plugin: self.path.clone(),
+            captures: HashMap::new(),
+            peer_addr: None,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(limits.memory_bytes)
+                .build(),
+            table: ResourceTable::new(),
+            wasi: WasiCtxBuilder::new().build(),
+        };
+        let mut store = Store::new(&self.runtime.engine, state);
+        store.limiter(|state| &mut state.limits);
+        store
+            .set_fuel(limits.fuel)
+            .map_err(WasmPluginError::Runtime)?;
+        let bindings = Plugin::instantiate(&mut store, &self.component, &self.runtime.linker)
+            .map_err(|error| WasmPluginError::Load {
+                path: self.path.to_path_buf(),
+                error,
+            })?;
+        Ok(PluginInstance { store, bindings })
+    }
+}
+
+/// Instances of one constructed node or filter. The calls of a wasm instance can't overlap, so
+/// each concurrent call takes its own.
+struct InstancePool {
+    plugin: Arc<WasmPlugin>,
+    class: types::ClassInfo,
+    config: String,
+    idle: Mutex<Vec<(PluginInstance, u32)>>,
+}
+
+/// What a call reads and writes of the flow besides the request.
+struct CallContext {
+    captures: HashMap<Arc<str>, Arc<str>>,
+    peer_addr: Option<SocketAddr>,
+}
+
+impl CallContext {
+    fn new(extensions: &http::Extensions, context: &FlowContext) -> Self {
+        Self {
+            captures: extensions
+                .get::<Captures>()
+                .map(|captures| captures.captures.clone())
+                .unwrap_or_default(),
+            peer_addr: context.connection_info.as_ref().map(|info| info.peer_addr),
+        }
+    }
+}
+
+impl InstancePool {
+    fn class_id(&self) -> ClassId {
+        ClassId::new(&self.class.namespace, &self.class.name)
+    }
+
+    fn checkout(&self) -> Result<(PluginInstance, u32), WasmPluginError> {
+        if let Some(instance) = self.idle.lock().ok().and_then(|mut idle| idle.pop()) {
+            return Ok(instance);
+        }
+        let mut instance = self.plugin.instantiate()?;
+        let handle = instance
+            .bindings
+            .call_construct(&mut instance.store, &self.class.name, &self.config)
+            .map_err(|error| self.plugin.trap(error))?
+            .map_err(|message| WasmPluginError::Construct {
+                class: self.class_id(),
+                message,
+            })?;
+        Ok((instance, handle))
+    }
+
+    fn checkin(&self, instance: (PluginInstance, u32)) {
+        if let Ok(mut idle) = self.idle.lock()
+            && idle.len() < MAX_IDLE_INSTANCES
+        {
+            idle.push(instance);
+        }
+    }
+
+    /// Run `f` on a blocking thread with an instance and a fresh fuel budget, returning its
+    /// result and the captures after the call.
+    ///
+    /// A trapped instance is dropped, its memory may be left inconsistent.
+    async fn call<F, R>(
+        self: &Arc<Self>,
+        context: CallContext,
+        f: F,
+    ) -> Result<(R, HashMap<Arc<str>, Arc<str>>), WasmPluginError>
+    where
+        F: FnOnce(&Plugin, &mut Store<HostState>, u32) -> wasmtime::Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let (mut instance, handle) = pool.checkout()?;
+            let state = instance.store.data_mut();
+            state.captures = context.captures;
+            state.peer_addr = context.peer_addr;
+            instance
+                .store
+                .set_fuel(pool.plugin.runtime.limits.fuel)
+                .map_err(WasmPluginError::Runtime)?;
+            let result = f(&instance.bindings, &mut instance.store, handle)
+                .map_err(|error| pool.plugin.trap(error))?;
+            let captures = std::mem::take(&mut instance.store.data_mut().captures);
+            pool.checkin((instance, handle));
+            Ok((result, captures))
+        })
+        .await?
+    }
+}
+
+fn plugin_error_response(error: WasmPluginError) -> DynResponse {
+    tracing::error!("{}", error);
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, error, ERR_WASM_PLUGIN)
+}
+
+async fn read_body(body: DynBody, limit: usize) -> Result<Vec<u8>, DynResponse> {
+    read_plugin_body(body, limit, ERR_WASM_PLUGIN).await
+}
+
+fn to_wit_headers(headers: &HeaderMap) -> types::Headers {
+    headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+        .collect()
+}
+
+fn from_wit_headers(headers: types::Headers) -> Result<HeaderMap, WasmPluginError> {
+    let mut header_map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| WasmPluginError::InvalidMessage("header name"))?;
+        let value = HeaderValue::from_bytes(&value)
+            .map_err(|_| WasmPluginError::InvalidMessage("header value"))?;
+        header_map.append(name, value);
+    }
+    Ok(header_map)
+}
+
+fn from_wit_response(response: types::Response) -> Result<DynResponse, WasmPluginError> {
+    let status = StatusCode::from_u16(response.status)
+        .map_err(|_| WasmPluginError::InvalidMessage("status code"))?;
+    let mut headers = from_wit_headers(response.headers)?;
+    let mut dyn_response = DynResponse::new(replace_plugin_body(&mut headers, response.body));
+    *dyn_response.status_mut() = status;
+    *dyn_response.headers_mut() = headers;
+    Ok(dyn_response)
+}
+
+/// Apply a request returned by a filter onto the original request parts, keeping the
+/// extensions.
+fn apply_wit_request(
+    parts: &mut http::request::Parts,
+    request: types::Request,
+) -> Result<DynBody, WasmPluginError> {
+    parts.method = request
+        .method
+        .parse()
+        .map_err(|_| WasmPluginError::InvalidMessage("method"))?;
+    parts.uri = request
+        .uri
+        .parse()
+        .map_err(|_| WasmPluginError::InvalidMessage("uri"))?;
+    parts.headers = from_wit_headers(request.headers)?;
+    Ok(replace_plugin_body(&mut parts.headers, request.body))
+}
+
+async fn to_wit_request(
+    parts: &http::request::Parts,
+    body: DynBody,
+    limit: usize,
+) -> Result<types::Request, DynResponse> {
+    Ok(types::Request {
+        method: parts.method.to_string(),
+        uri: parts.uri.to_string(),
+        headers: to_wit_headers(&parts.headers),
+        body: read_body(body, limit).await?,
+    })
+}
+
+impl InstancePool {
+    fn max_body_bytes(&self) -> usize {
+        self.plugin.runtime.limits.max_body_bytes
+    }
+
+    async fn call_node(self: Arc<Self>, req: DynRequest, ctx: &mut FlowContext) -> DynResponse {
+        let (parts, body) = req.into_parts();
+        let request = match to_wit_request(&parts, body, self.max_body_bytes()).await {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let context = CallContext::new(&parts.extensions, ctx);
+        let result = self
+            .call(context, move |bindings, store, handle| {
+                bindings.call_call_node(store, handle, &request)
+            })
+            .await;
+        match result.and_then(|(response, _)| from_wit_response(response)) {
+            Ok(response) => response,
+            Err(e) => plugin_error_response(e),
+        }
+    }
+
+    async fn call_filter(
+        self: Arc<Self>,
+        req: DynRequest,
+        ctx: &mut FlowContext,
+        next: Next,
+    ) -> DynResponse {
+        let (mut parts, body) = req.into_parts();
+        let request = match to_wit_request(&parts, body, self.max_body_bytes()).await {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let context = CallContext::new(&parts.extensions, ctx);
+        let result = self
+            .call(context, move |bindings, store, handle| {
+                bindings.call_on_request(store, handle, &request)
+            })
+            .await;
+        let (action, captures) = match result {
+            Ok(result) => result,
+            Err(e) => return plugin_error_response(e),
+        };
+        let request = match action {
+            types::RequestAction::Next(request) => request,
+            types::RequestAction::Respond(response) => {
+                return from_wit_response(response).unwrap_or_else(plugin_error_response);
+            }
+        };
+        let body = match apply_wit_request(&mut parts, request) {
+            Ok(body) => body,
+            Err(e) => return plugin_error_response(e),
+        };
+        parts.extensions.insert(Captures {
+            captures: captures.clone(),
+        });
+        let peer_addr = ctx.connection_info.as_ref().map(|info| info.peer_addr);
+        let response = next.call(DynRequest::from_parts(parts, body), ctx).await;
+        if !self.class.on_response {
+            return response;
+        }
+        let (parts, body) = response.into_parts();
+        let body = match read_body(body, self.max_body_bytes()).await {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        let response = types::Response {
+            status: parts.status.as_u16(),
+            headers: to_wit_headers(&parts.headers),
+            body,
+        };
+        let context = CallContext {
+            captures,
+            peer_addr,
+        };
+        let result = self
+            .call(context, move |bindings, store, handle| {
+                bindings.call_on_response(store, handle, &response)
+            })
+            .await;
+        match result.and_then(|(response, _)| from_wit_response(response)) {
+            Ok(response) => response,
+            Err(e) => plugin_error_response(e),
+        }
+    }
+}
+
+impl ClassRegistry {
+    /// Register every class of a wasm plugin, replacing classes with the same id.
+    pub fn register_wasm_plugin(&mut self, plugin: Arc<WasmPlugin>) {
+        for (class, config_schema) in plugin.classes.iter().zip(&plugin.config_schemas) {
+            let id = ClassId::new(&class.namespace, &class.name);
+            let instance_type = match class.kind {
+                types::ClassKind::Node => InstanceType::Node,
+                types::ClassKind::Filter => InstanceType::Filter,
+            };
+            let data = ClassData {
+                id: id.clone(),
+                meta: ClassMeta {
+                    version: class.version.clone(),
+                    description: class.description.clone(),
+                    author: None,
+                    license: None,
+                    repository: None,
+                    homepage: None,
+                },
+                instance_type,
+                config_schema: config_schema.clone(),
+            };
+            let plugin = plugin.clone();
+            let class = class.clone();
+            let constructor = Constructor::new(move |config: &SerdeValue| {
+                let config = serde_json::to_string(config)
+                    .map_err(|e| ConstructError::BuildError(Box::new(e)))?;
+                let pool = Arc::new(InstancePool {
+                    plugin: plugin.clone(),
+                    class: class.clone(),
+                    config,
+                    idle: Mutex::new(Vec::new()),
+                });
+                // construct one right away, so a bad config fails the flow build
+                let instance = pool
+                    .checkout()
+                    .map_err(|e| ConstructError::BuildError(Box::new(e)))?;
+                pool.checkin(instance);
+                Ok(match class.kind {
+                    types::ClassKind::Node => {
+                        InstanceValue::Node(Node::new(NodeInterface::service(), move |req, ctx| {
+                            Box::pin(pool.clone().call_node(req, ctx))
+                        }))
+                    }
+                    types::ClassKind::Filter => InstanceValue::Filter(Filter {
+                        call: Arc::new(move |req, ctx, next| {
+                            Box::pin(pool.clone().call_filter(req, ctx, next))
+                        }),
+                    }),
+                })
+            });
+            self.class_data
+                .insert(id, ClassDataWithConstructor { data, constructor });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::bytes_body;
+
+    #[test]
+    fn test_wit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-multi", HeaderValue::from_static("a"));
+        headers.append("x-multi", HeaderValue::from_static("b"));
+        headers.append("x-bytes", HeaderValue::from_bytes(&[0x80, 0xff]).unwrap());
+        let wit = to_wit_headers(&headers);
+        assert_eq!(wit.len(), 3);
+        assert_eq!(from_wit_headers(wit).unwrap(), headers);
+
+        assert!(matches!(
+            from_wit_headers(vec![("bad name".to_string(), b"v".to_vec())]),
+            Err(WasmPluginError::InvalidMessage("header name"))
+        ));
+        assert!(matches!(
+            from_wit_headers(vec![("x-bad".to_string(), b"a\nb".to_vec())]),
+            Err(WasmPluginError::InvalidMessage("header value"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_from_wit_response() {
+        let response = from_wit_response(types::Response {
+            status: 201,
+            headers: vec![
+                ("x-plugin".to_string(), b"yes".to_vec()),
+                ("content-length".to_string(), b"100".to_vec()),
+            ],
+            body: b"hello".to_vec(),
+        })
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["x-plugin"], "yes");
+        assert!(
+            response
+                .headers()
+                .get(http::header::CONTENT_LENGTH)
+                .is_none()
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"hello");
+
+        assert!(matches!(
+            from_wit_response(types::Response {
+                status: 1000,
+                headers: Vec::new(),
+                body: Vec::new(),
+            }),
+            Err(WasmPluginError::InvalidMessage("status code"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_apply_wit_request() {
+        let (mut parts, _) = http::Request::builder()
+            .uri("/old")
+            .header(http::header::CONTENT_LENGTH, "5")
+            .extension(42u32)
+            .body(())
+            .unwrap()
+            .into_parts();
+        let body = apply_wit_request(
+            &mut parts,
+            types::Request {
+                method: "POST".to_string(),
+                uri: "/new?q=1".to_string(),
+                headers: vec![
+                    ("x-plugin".to_string(), b"yes".to_vec()),
+                    ("content-length".to_string(), b"5".to_vec()),
+                ],
+                body: b"replaced".to_vec(),
+            },
+        )
+        .unwrap();
+        assert_eq!(parts.method, http::Method::POST);
+        assert_eq!(parts.uri, "/new?q=1");
+        assert_eq!(parts.headers["x-plugin"], "yes");
+        assert!(parts.headers.get(http::header::CONTENT_LENGTH).is_none());
+        assert_eq!(parts.extensions.get::<u32>(), Some(&42));
+        let body = body.collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"replaced");
+
+        let request = |method: &str, uri: &str| types::Request {
+            method: method.to_string(),
+            uri: uri.to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        };
+        assert!(matches!(
+            apply_wit_request(&mut parts, request("BAD METHOD", "/")),
+            Err(WasmPluginError::InvalidMessage("method"))
+        ));
+        assert!(matches!(
+            apply_wit_request(&mut parts, request("GET", "not a uri")),
+            Err(WasmPluginError::InvalidMessage("uri"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_to_wit_request() {
+        let (parts, _) = http::Request::builder()
+            .method("PUT")
+            .uri("/upload")
+            .header("x-client", "1")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let request = to_wit_request(&parts, bytes_body(b"hello".to_vec()), 5)
+            .await
+            .unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.uri, "/upload");
+        assert_eq!(
+            request.headers,
+            vec![("x-client".to_string(), b"1".to_vec())]
+        );
+        assert_eq!(request.body, b"hello");
+
+        let Err(response) = to_wit_request(&parts, bytes_body(b"hello!".to_vec()), 5).await else {
+            panic!("body over the limit was accepted");
+        };
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_load_invalid_component() {
+        let runtime = WasmRuntime::new(WasmLimits::default()).unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "switchboard-wasm-invalid-{}.wasm",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"not a component").unwrap();
+        let result = runtime.load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            matches!(result, Err(WasmPluginError::Load { path: error_path, .. }) if error_path == path)
+        );
+        assert!(matches!(
+            runtime.load(path.with_extension("missing")),
+            Err(WasmPluginError::Load { .. })
+        ));
+    }
+}
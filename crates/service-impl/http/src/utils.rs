@@ -20,6 +20,7 @@ mod client;
 #[cfg(feature = "service-impl")]
 pub use client::*;
 
+pub mod constant_time;
 pub mod duration_expr;
 pub mod token_bucket;
 
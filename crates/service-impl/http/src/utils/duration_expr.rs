@@ -14,9 +14,44 @@ where
     from_duration_expr(s).map_err(serde::de::Error::custom)
 }
 
+/// Schema of a duration expression like `1h 30m` or `500ms`, for fields (de)serialized with this
+/// module.
+pub struct DurationExpr;
+
+impl schemars::JsonSchema for DurationExpr {
+    fn inline_schema() -> bool {
+        true
+    }
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "DurationExpr".into()
+    }
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "pattern": "^\\s*(\\d+\\s*(h|ms|m|s)\\s*)*\\d*\\s*$",
+            "examples": ["1h 30m", "500ms"],
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Never(());
 
+impl schemars::JsonSchema for Never {
+    fn inline_schema() -> bool {
+        true
+    }
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Never".into()
+    }
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "pattern": "^[Nn][Ee][Vv][Ee][Rr]$",
+        })
+    }
+}
+
 impl serde::Serialize for Never {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -43,7 +78,7 @@ impl<'de> serde::Deserialize<'de> for Never {
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[serde(untagged)]
 /// Represents a timeout duration which can be specified as an expression, milliseconds, or never.
 /// - `Expr`: A human-readable duration expression (e.g., "1h 30m").
@@ -51,7 +86,11 @@ impl<'de> serde::Deserialize<'de> for Never {
 /// - `Never`: Represents an infinite timeout, should be serialized/deserialized as the string "never".
 pub enum TimeoutDuration {
     Never(Never),
-    Expr(#[serde(with = "self")] tokio::time::Duration),
+    Expr(
+        #[serde(with = "self")]
+        #[schemars(with = "DurationExpr")]
+        tokio::time::Duration,
+    ),
     MilliSecond(u32),
 }
 
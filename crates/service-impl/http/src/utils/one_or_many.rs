@@ -1,6 +1,6 @@
 use std::iter::once;
 
-#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, schemars::JsonSchema)]
 #[serde(untagged)]
 pub enum OneOrMany<T> {
     Many(Vec<T>),
@@ -0,0 +1,115 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+
+use http_body_util::BodyExt;
+use switchboard_http::{
+    empty_body,
+    flow::{Flow, FlowContext},
+    instance::{
+        InstanceValue,
+        class::{
+            plugin::{LoadedPlugin, PluginLibrary},
+            registry::ClassRegistry,
+        },
+    },
+};
+use switchboard_model::{SerdeValue, services::http::ClassId};
+
+/// Build the example C plugin into `dir` with `cc`, which is also the linker of the test itself.
+fn build_hello(dir: &Path) -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    std::fs::create_dir_all(dir).unwrap();
+    let output = dir.join("libhello.so");
+    let status = Command::new("cc")
+        .args(["-shared", "-fPIC", "-I"])
+        .arg(manifest_dir.join("include"))
+        .arg("-o")
+        .arg(&output)
+        .arg(manifest_dir.join("../../../examples/example-c-plugin/hello.c"))
+        .status()
+        .expect("a C compiler is needed to build hello.c");
+    assert!(status.success(), "failed to build hello.c");
+    output.canonicalize().unwrap()
+}
+
+fn is_mapped(path: &Path) -> bool {
+    std::fs::read_to_string("/proc/self/maps")
+        .unwrap()
+        .contains(path.to_str().unwrap())
+}
+
+async fn call_hello(registry: &ClassRegistry) -> String {
+    let InstanceValue::Node(node) = registry
+        .construct(ClassId::new("test", "hello-world"), SerdeValue::Unit)
+        .unwrap()
+    else {
+        panic!("hello-world is a node");
+    };
+    let entrypoint = "hello".into();
+    let flow = Flow {
+        nodes: Arc::new(HashMap::new()),
+        filters: Arc::new(HashMap::new()),
+        entrypoint: "hello".into(),
+    };
+    let mut ctx = FlowContext::new(flow, entrypoint);
+    let response = (node.call)(http::Request::new(empty_body()), &mut ctx).await;
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_plugin_load_replace_unload() {
+    let dir =
+        std::env::temp_dir().join(format!("switchboard-plugin-reload-{}", std::process::id()));
+    let first_path = build_hello(&dir.join("first"));
+    let second_path = build_hello(&dir.join("second"));
+
+    // load
+    let mut registry = ClassRegistry::default();
+    let first = Arc::new(
+        unsafe { PluginLibrary::load(&first_path) }
+            .unwrap()
+            .remove_on_unload()
+            .require_unloadable()
+            .unwrap(),
+    );
+    assert!(first.unloadable());
+    let first_weak = Arc::downgrade(&first);
+    registry
+        .register_native_plugin(LoadedPlugin::new("hello", &first_path), first)
+        .unwrap();
+    assert_eq!(call_hello(&registry).await, "hello world");
+    let InstanceValue::Node(old_node) = registry
+        .construct(ClassId::new("test", "hello-world"), SerdeValue::Unit)
+        .unwrap()
+    else {
+        panic!("hello-world is a node");
+    };
+
+    // replace, the node built from the first library keeps it loaded
+    let second = Arc::new(unsafe { PluginLibrary::load(&second_path) }.unwrap());
+    registry
+        .register_native_plugin(LoadedPlugin::new("hello", &second_path), second)
+        .unwrap();
+    assert_eq!(registry.plugins["hello"].path, second_path);
+    assert!(first_weak.upgrade().is_some());
+    assert!(is_mapped(&first_path));
+
+    // unload once the last instance of the first library is gone
+    drop(old_node);
+    assert!(first_weak.upgrade().is_none());
+    assert!(!is_mapped(&first_path));
+    assert!(!first_path.exists());
+    assert!(is_mapped(&second_path));
+    assert_eq!(call_hello(&registry).await, "hello world");
+
+    drop(registry);
+    assert!(!is_mapped(&second_path));
+    let _ = std::fs::remove_dir_all(dir);
+}
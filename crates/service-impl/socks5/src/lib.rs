@@ -7,7 +7,7 @@ use std::{
 
 use switchboard_service::{
     SerdeValue, TcpServiceProvider,
-    tcp::{AsyncStream, TcpService},
+    tcp::{AsyncStream, TcpConnectionStats, TcpService},
 };
 use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
@@ -225,6 +225,7 @@ impl TcpService for Socks5 {
             accepted.stream,
             accepted.context.ct,
             accepted.context.peer_addr,
+            accepted.context.stats,
         ))
     }
 }
@@ -235,16 +236,19 @@ impl Socks5 {
         mut stream: S,
         ct: tokio_util::sync::CancellationToken,
         peer: SocketAddr,
+        stats: Arc<TcpConnectionStats>,
     ) -> io::Result<()>
     where
         S: AsyncStream,
     {
-        self.accept(&mut stream, peer, ct.child_token()).await
+        self.accept(&mut stream, peer, &stats, ct.child_token())
+            .await
     }
     pub async fn accept<S>(
         &self,
         stream: &mut S,
         _peer: SocketAddr,
+        stats: &TcpConnectionStats,
         ct: tokio_util::sync::CancellationToken,
     ) -> io::Result<()>
     where
@@ -286,6 +290,7 @@ impl Socks5 {
         }
 
         let request = read_request(stream).await?;
+        stats.set_handshake_done();
 
         match request {
             Socks5Request::Connect(addr) => {
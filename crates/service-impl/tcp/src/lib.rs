@@ -120,6 +120,7 @@ impl Tcp {
             TlsStrategy::Terminate(outbounds) => {
                 let accepted = accepted.maybe_tls_terminate().await?;
                 let switchboard_service::tcp::TcpAccepted { stream, context } = accepted;
+                context.stats.set_handshake_done();
                 let from = context.peer_addr;
                 let info = TcpConnectionInfo { from };
                 let ct = context.ct.clone();
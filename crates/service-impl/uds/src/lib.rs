@@ -46,6 +46,8 @@ impl TcpService for Uds {
         self: Arc<Self>,
         accepted: switchboard_service::tcp::TcpAccepted,
     ) -> Pin<Box<dyn Future<Output = io::Result<()>> + 'static + Send>> {
+        // forwarding has no handshake of its own
+        accepted.context.stats.set_handshake_done();
         Box::pin(self.serve_inner(
             accepted.stream,
             accepted.context.ct,
@@ -39,6 +39,7 @@ pub struct Webui {
 impl Webui {
     async fn serve_inner(self: Arc<Self>, accepted: TcpAccepted) -> std::io::Result<()> {
         let accepted = accepted.maybe_tls_terminate().await?;
+        accepted.context.stats.set_handshake_done();
         let peer_addr = accepted.context.peer_addr;
         let ct = accepted.context.ct;
         let io = TokioIo::new(accepted.stream);
@@ -58,6 +58,61 @@ pub trait TcpServiceProvider: Send + Sync + 'static {
         &self,
         config: Option<SerdeValue>,
     ) -> impl Future<Output = Result<Self::Service, Self::Error>> + Send + '_;
+    /// Dry-run a config and report every problem found, without keeping the service.
+    ///
+    /// The default implementation constructs the service and reports its error, if any.
+    fn validate(
+        &self,
+        config: Option<SerdeValue>,
+    ) -> impl Future<Output = ValidationReport> + Send + '_ {
+        async move {
+            let mut report = ValidationReport::default();
+            if let Err(e) = self.construct(config).await {
+                report.error(Vec::<String>::new(), e);
+            }
+            report
+        }
+    }
+}
+
+/// A problem found by [`TcpServiceProvider::validate`].
+#[derive(Debug)]
+pub struct ValidationIssue {
+    /// Path segments relative to the service config root.
+    pub path: Vec<String>,
+    pub error: BoxedError,
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn error<S: Into<String>>(
+        &mut self,
+        path: impl IntoIterator<Item = S>,
+        error: impl Into<BoxedError>,
+    ) {
+        self.errors.push(ValidationIssue {
+            path: path.into_iter().map(Into::into).collect(),
+            error: error.into(),
+        });
+    }
+    pub fn warning<S: Into<String>>(
+        &mut self,
+        path: impl IntoIterator<Item = S>,
+        error: impl Into<BoxedError>,
+    ) {
+        self.warnings.push(ValidationIssue {
+            path: path.into_iter().map(Into::into).collect(),
+            error: error.into(),
+        });
+    }
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
 }
 
 pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
@@ -69,6 +124,7 @@ pub trait DynTcpServiceProvider: Send + Sync + 'static {
         &self,
         config: Option<SerdeValue>,
     ) -> BoxFuture<'_, Result<SharedTcpService, BoxedError>>;
+    fn validate(&self, config: Option<SerdeValue>) -> BoxFuture<'_, ValidationReport>;
 }
 
 impl<T: TcpServiceProvider> DynTcpServiceProvider for T {
@@ -90,6 +146,9 @@ impl<T: TcpServiceProvider> DynTcpServiceProvider for T {
             })
             .boxed()
     }
+    fn validate(&self, config: Option<SerdeValue>) -> BoxFuture<'_, ValidationReport> {
+        self.validate(config).boxed()
+    }
 }
 
 pub trait UdpServiceProvider {
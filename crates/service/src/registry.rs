@@ -11,6 +11,7 @@ use crate::{
     BoxedError,
     DynTcpServiceProvider,
     TcpServiceProvider,
+    ValidationReport,
     tcp::SharedTcpService, // tcp::{DynTcpService, tls::TlsService},
 };
 
@@ -37,6 +38,14 @@ impl ServiceProviderRegistry {
         let service = provider.construct(config).await?;
         Ok(service)
     }
+    pub async fn validate_tcp(
+        &self,
+        name: &str,
+        config: Option<SerdeValue>,
+    ) -> Result<ValidationReport, ServiceProviderRegistryError> {
+        let provider = self.get_tcp_provider(name)?;
+        Ok(provider.validate(config).await)
+    }
     pub fn register_tcp_provider<P: TcpServiceProvider>(&mut self, p: P) {
         self.tcp.insert(
             P::NAME.to_string(),
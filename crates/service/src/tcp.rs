@@ -12,7 +12,9 @@ use tokio::{
 pub use tokio_rustls::TlsAcceptor;
 use tokio_util::sync::CancellationToken;
 
+mod stats;
 pub mod tls;
+pub use stats::*;
 pub trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
 pub struct BoxedAsyncStream(Box<dyn AsyncStream>);
 
@@ -80,6 +82,7 @@ pub struct TcpConnectionContext {
     // optional tls acceptor, service will decide to use or not.
     pub tls_acceptor: Option<TlsAcceptor>,
     pub tls_client_hello: Option<tls::OwnedClientHello>,
+    pub stats: Arc<TcpConnectionStats>,
 }
 
 pub trait TcpService: Send + Sync + 'static {
@@ -96,7 +99,10 @@ pub struct TcpListener {
     pub bind: SocketAddr,
 }
 
-pub struct TcpAccepted<S = TcpStream> {
+/// An accepted tcp stream, counting the bytes it carries.
+pub type TcpConnectionStream = CountedStream<TcpStream>;
+
+pub struct TcpAccepted<S = TcpConnectionStream> {
     pub stream: S,
     pub context: TcpConnectionContext,
 }
@@ -116,17 +122,18 @@ impl TcpListener {
         Ok(Self { inner, bind: addr })
     }
     pub async fn accept(&self, ct: &CancellationToken) -> io::Result<TcpAccepted> {
-        self.inner
-            .accept()
-            .await
-            .map(|(tcp_stream, peer_addr)| TcpAccepted {
-                stream: tcp_stream,
+        self.inner.accept().await.map(|(tcp_stream, peer_addr)| {
+            let stats = Arc::new(TcpConnectionStats::default());
+            TcpAccepted {
+                stream: CountedStream::new(tcp_stream, stats.clone()),
                 context: TcpConnectionContext {
                     peer_addr,
                     ct: ct.child_token(),
                     tls_acceptor: None,
                     tls_client_hello: None,
+                    stats,
                 },
-            })
+            }
+        })
     }
 }
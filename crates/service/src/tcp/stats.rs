@@ -0,0 +1,127 @@
+use std::{
+    pin::Pin,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use tokio::io::{self, AsyncRead, AsyncWrite, ReadBuf};
+
+/// What a connection did so far, shared between the service serving it and the kernel.
+#[derive(Debug, Default)]
+pub struct TcpConnectionStats {
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    sni: OnceLock<String>,
+    handshake_done: AtomicBool,
+}
+
+impl TcpConnectionStats {
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+    /// Server name of the TLS ClientHello, once a service read it.
+    pub fn sni(&self) -> Option<&str> {
+        self.sni.get().map(String::as_str)
+    }
+    pub fn set_sni(&self, sni: &str) {
+        let _ = self.sni.set(sni.to_owned());
+    }
+    /// Whether the client finished the handshake of the service's protocol, such as
+    /// sending its TLS ClientHello or its first HTTP request.
+    pub fn handshake_done(&self) -> bool {
+        self.handshake_done.load(Ordering::Relaxed)
+    }
+    pub fn set_handshake_done(&self) {
+        self.handshake_done.store(true, Ordering::Relaxed);
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Counts the raw bytes going through `inner` into [`TcpConnectionStats`].
+    pub struct CountedStream<S> {
+        #[pin]
+        inner: S,
+        stats: Arc<TcpConnectionStats>,
+    }
+}
+
+impl<S> CountedStream<S> {
+    pub fn new(inner: S, stats: Arc<TcpConnectionStats>) -> Self {
+        Self { inner, stats }
+    }
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for CountedStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        let filled = buf.filled().len();
+        let poll = this.inner.poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            let read = buf.filled().len() - filled;
+            this.stats
+                .bytes_received
+                .fetch_add(read as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for CountedStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let poll = this.inner.poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            this.stats
+                .bytes_sent
+                .fetch_add(written as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let poll = this.inner.poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(written)) = poll {
+            this.stats
+                .bytes_sent
+                .fetch_add(written as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
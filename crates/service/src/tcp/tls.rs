@@ -16,6 +16,9 @@ where
         match &context.tls_acceptor {
             Some(tls_acceptor) => {
                 let tls_stream = tls_acceptor.accept(stream).await?;
+                if let Some(sni) = tls_stream.get_ref().1.server_name() {
+                    context.stats.set_sni(sni);
+                }
                 Ok(TcpAccepted {
                     stream: MaybeTlsStream::Tls(Box::new(tls_stream)),
                     context,
@@ -38,6 +41,13 @@ where
             mut context,
         } = self;
         let (client_hello, rewind) = read_hello::read_client_hello(stream).await?;
+        context.stats.set_handshake_done();
+        if let Some(sni) = client_hello
+            .as_ref()
+            .and_then(|hello| hello.server_name.as_deref())
+        {
+            context.stats.set_sni(sni);
+        }
         context.tls_client_hello = client_hello;
         Ok(TcpAccepted {
             stream: rewind,
@@ -0,0 +1,119 @@
+/*
+ * A native http plugin built against the stable C ABI:
+ *
+ *     cc -shared -fPIC -I ../../crates/service-impl/http/include -o libhello.so hello.c
+ *
+ * `test.hello-world` answers every request, `test.add-header` refuses requests without an
+ * `authorization` header and adds `x-plugin` to the other responses.
+ */
+#include <stdlib.h>
+#include <string.h>
+
+#include "switchboard_plugin.h"
+
+static const sb_host_api *host;
+
+typedef struct add_header {
+    char *value;
+} add_header;
+
+static int32_t hello_construct(sb_str config, void **instance) {
+    (void)config;
+    *instance = NULL;
+    return SB_OK;
+}
+
+static int32_t hello_call(void *instance, sb_request *req, sb_response *res) {
+    (void)instance;
+    (void)req;
+    host->headers_set(host->response_headers(res), SB_STR("content-type"), SB_STR("text/plain"));
+    host->response_set_body(res, SB_STR("hello world"));
+    return SB_OK;
+}
+
+static int32_t add_header_construct(sb_str config, void **instance) {
+    /* the config is json, a real plugin would parse it */
+    add_header *filter = malloc(sizeof(add_header));
+    if (filter == NULL) {
+        host->set_error(SB_STR("out of memory"));
+        return SB_ERROR;
+    }
+    filter->value = malloc(config.len + 1);
+    memcpy(filter->value, config.ptr, config.len);
+    filter->value[config.len] = '\0';
+    *instance = filter;
+    return SB_OK;
+}
+
+static void add_header_destroy(void *instance) {
+    add_header *filter = instance;
+    free(filter->value);
+    free(filter);
+}
+
+static int32_t add_header_on_request(void *instance, sb_request *req, sb_response *res) {
+    (void)instance;
+    if (host->headers_get(host->request_headers(req), SB_STR("authorization")).ptr == NULL) {
+        host->response_set_status(res, 401);
+        host->response_set_body(res, SB_STR("unauthorized"));
+        return SB_RESPOND;
+    }
+    return SB_NEXT;
+}
+
+static int32_t add_header_on_response(void *instance, const sb_request *req, sb_response *res) {
+    add_header *filter = instance;
+    (void)req;
+    sb_str value = {(const uint8_t *)filter->value, strlen(filter->value)};
+    if (host->headers_set(host->response_headers(res), SB_STR("x-plugin"), value) != SB_OK) {
+        host->set_error(SB_STR("invalid header value"));
+        return SB_ERROR;
+    }
+    return SB_OK;
+}
+
+static const sb_class classes[] = {
+    {
+        .namespace_ = SB_STR("test"),
+        .name = SB_STR("hello-world"),
+        .version = SB_STR("0.1.0"),
+        .description = SB_STR("answers hello world"),
+        .kind = SB_CLASS_NODE,
+        .flags = 0,
+        .construct = hello_construct,
+        .call_node = hello_call,
+    },
+    {
+        .namespace_ = SB_STR("test"),
+        .name = SB_STR("add-header"),
+        .version = SB_STR("0.1.0"),
+        .description = SB_STR("adds x-plugin to responses"),
+        .kind = SB_CLASS_FILTER,
+        .flags = SB_CLASS_ON_RESPONSE,
+        .construct = add_header_construct,
+        .destroy = add_header_destroy,
+        .on_request = add_header_on_request,
+        .on_response = add_header_on_response,
+    },
+};
+
+/* hello-world takes any config, add-header the header value */
+static const sb_str config_schemas[] = {
+    {NULL, 0},
+    SB_STR("{\"type\":\"string\"}"),
+};
+
+static const sb_plugin plugin = {
+    .name = SB_STR("example-c-plugin"),
+    .abi_major = SB_ABI_VERSION_MAJOR,
+    .abi_minor = SB_ABI_VERSION_MINOR,
+    .required_capabilities = SB_CAP_NODE | SB_CAP_FILTER | SB_CAP_ON_RESPONSE,
+    .class_count = sizeof(classes) / sizeof(classes[0]),
+    .classes = classes,
+    .config_schemas = config_schemas,
+};
+
+const sb_plugin *switchboard_plugin_v1(const sb_host_api *host_api) {
+    host = host_api;
+    return &plugin;
+}
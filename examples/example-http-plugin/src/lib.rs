@@ -7,7 +7,7 @@ use switchboard_http::{
 };
 use switchboard_model::services::http::ClassId;
 struct HelloWorldClass;
-#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 struct HelloWorldClassConfig {}
 impl NodeClass for HelloWorldClass {
     type Node = HelloWorld;
@@ -0,0 +1,106 @@
+//! The `example-http-plugin` hello world node as a WASM plugin, plus a filter setting a header.
+//!
+//! Build it with `cargo build --release --target wasm32-wasip2`, and list
+//! `target/wasm32-wasip2/release/example_wasm_plugin.wasm` in `provider.http.plugins`.
+
+use std::sync::Mutex;
+
+wit_bindgen::generate!({
+    path: "../../crates/service-impl/http/wit",
+    world: "plugin",
+});
+
+use switchboard::http_plugin::{host, types::ClassKind};
+
+const ADD_HEADER_SCHEMA: &str = r#"{
+    "type": "object",
+    "properties": { "name": { "type": "string" }, "value": { "type": "string" } },
+    "required": ["name", "value"]
+}"#;
+
+#[derive(serde::Deserialize)]
+struct AddHeaderConfig {
+    name: String,
+    value: String,
+}
+
+enum Instance {
+    HelloWorld,
+    AddHeader(AddHeaderConfig),
+}
+
+static INSTANCES: Mutex<Vec<Instance>> = Mutex::new(Vec::new());
+
+fn with_instance<R>(handle: u32, f: impl FnOnce(&Instance) -> R) -> R {
+    let instances = INSTANCES.lock().expect("plugin instances poisoned");
+    f(&instances[handle as usize])
+}
+
+struct ExamplePlugin;
+
+impl Guest for ExamplePlugin {
+    fn classes() -> Vec<ClassInfo> {
+        let class = |name: &str, kind, on_response, config_schema: Option<&str>| ClassInfo {
+            namespace: "test".to_string(),
+            name: name.to_string(),
+            kind,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: None,
+            on_response,
+            config_schema: config_schema.map(str::to_string),
+        };
+        vec![
+            class("hello-world", ClassKind::Node, false, None),
+            class(
+                "add-header",
+                ClassKind::Filter,
+                false,
+                Some(ADD_HEADER_SCHEMA),
+            ),
+        ]
+    }
+
+    fn construct(name: String, config: String) -> Result<u32, String> {
+        let instance = match name.as_str() {
+            "hello-world" => Instance::HelloWorld,
+            "add-header" => {
+                Instance::AddHeader(serde_json::from_str(&config).map_err(|e| e.to_string())?)
+            }
+            _ => return Err(format!("unknown class {name}")),
+        };
+        let mut instances = INSTANCES.lock().expect("plugin instances poisoned");
+        instances.push(instance);
+        Ok(instances.len() as u32 - 1)
+    }
+
+    fn call_node(_instance: u32, _request: Request) -> Response {
+        Response {
+            status: 200,
+            headers: vec![("content-type".to_string(), b"text/plain".to_vec())],
+            body: b"hello world".to_vec(),
+        }
+    }
+
+    fn on_request(instance: u32, mut request: Request) -> RequestAction {
+        with_instance(instance, |instance| {
+            if let Instance::AddHeader(config) = instance {
+                if let Some(peer) = host::peer_addr() {
+                    host::log(host::LogLevel::Debug, &format!("add header for {peer}"));
+                }
+                request
+                    .headers
+                    .retain(|(name, _)| !name.eq_ignore_ascii_case(&config.name));
+                request
+                    .headers
+                    .push((config.name.clone(), config.value.clone().into_bytes()));
+            }
+        });
+        RequestAction::Next(request)
+    }
+
+    fn on_response(_instance: u32, response: Response) -> Response {
+        response
+    }
+}
+
+export!(ExamplePlugin);
@@ -1,4 +1,9 @@
-import type { ConfigRolloutReport, KernelConnectionAndState, ResultObject } from '../types';
+import type {
+	ConfigRolloutReport,
+	DeploymentGroup,
+	KernelConnectionAndState,
+	ResultObject
+} from '../types';
 import type { LinkOrValue } from '../types/controller';
 import { fetchJson } from './index';
 
@@ -15,18 +20,31 @@ export type UpdateConfigRequest =
 			config: unknown;
 	  };
 
+export type KernelFilter = {
+	/** Label selector, e.g. `role=edge,region in (eu,us)` */
+	selector?: string;
+	group?: string;
+};
+
 export const kernelManagerApi = {
-	listKernels: () => fetchJson<KernelSummary>('/api/kernel_manager/kernels'),
+	listKernels: (filter: KernelFilter = {}) => {
+		const query = new URLSearchParams();
+		if (filter.selector) query.set('selector', filter.selector);
+		if (filter.group) query.set('group', filter.group);
+		return fetchJson<KernelSummary>('/api/kernel_manager/kernels', {}, query);
+	},
+
+	listGroups: () => fetchJson<DeploymentGroup[]>('/api/kernel_manager/groups'),
 
 	/**
-	 * Update configuration for all kernels
+	 * Update configuration for the kernels of a deployment group, or all kernels
 	 * @returns Transactional rollout report
 	 */
-	updateConfig: (request: UpdateConfigRequest) =>
+	updateConfig: (request: UpdateConfigRequest, group?: string) =>
 		fetchJson<ConfigRolloutReport>('/api/kernel_manager/kernels', {
 			method: 'PUT',
 			headers: { 'Content-Type': 'application/json' },
-			body: JSON.stringify(request)
+			body: JSON.stringify({ ...request, group })
 		}),
 
 	/**
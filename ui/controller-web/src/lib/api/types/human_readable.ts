@@ -1,10 +1,12 @@
 import type { LinkOrValue } from './controller';
+import type { ListenerLimits } from './listener';
 import type { TlsOptions, TlsCertParams } from './tls';
 
 export type FileBind = {
 	bind: string;
 	tls?: string;
 	description?: string;
+	limits?: ListenerLimits;
 };
 
 export type FileTcpServiceConfig = {
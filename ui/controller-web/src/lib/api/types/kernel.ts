@@ -8,6 +8,15 @@ export type KernelInfo = {
 	id: string;
 	description: string | null;
 	meta: KernelMeta;
+	labels: Record<string, string>;
+};
+
+export type DeploymentGroup = {
+	name: string;
+	selector: string;
+	description: string | null;
+	kernels: string[];
+	config_version: string | null;
 };
 
 export type KernelStateKind =
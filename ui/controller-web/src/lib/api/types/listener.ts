@@ -1,4 +1,18 @@
+export type AcceptRate = {
+	per_second: number;
+	burst?: number;
+};
+
+export type ListenerLimits = {
+	max_connections?: number;
+	max_connections_per_ip?: number;
+	accept_rate?: AcceptRate;
+	handshake_timeout_secs?: number;
+	idle_timeout_secs?: number;
+};
+
 export type Listener = {
 	bind: string;
 	description?: string;
+	limits?: ListenerLimits;
 };
@@ -0,0 +1,67 @@
+// Generate `.d.ts` types of the http class configs from the schemas dumped by
+// `sbk <kernel.toml> --dump-class-schemas <schemas.json>`.
+//
+//     tsx scripts/generate-class-types.mts <schemas.json> <output.d.ts>
+import { readFile, writeFile } from 'node:fs/promises';
+import { compile, type JSONSchema } from 'json-schema-to-typescript';
+
+interface ClassInfo {
+    id: string;
+    instance_type: 'Node' | 'Filter';
+    version: string;
+    description?: string | null;
+    config_schema: JSONSchema | boolean;
+}
+
+interface ClassList {
+    generation: number;
+    classes: ClassInfo[];
+}
+
+const [input, output] = process.argv.slice(2);
+if (!input || !output) {
+    console.error('usage: generate-class-types.mts <schemas.json> <output.d.ts>');
+    process.exit(1);
+}
+
+// `std.reverse-proxy` -> `StdReverseProxyConfig`
+function typeName(id: string): string {
+    return id
+        .split(/[^A-Za-z0-9]+/)
+        .filter((part) => part.length > 0)
+        .map((part) => part[0].toUpperCase() + part.slice(1))
+        .join('') + 'Config';
+}
+
+const { classes }: ClassList = JSON.parse(await readFile(input, 'utf8'));
+classes.sort((a, b) => a.id.localeCompare(b.id));
+
+// one schema with a property per class, so definitions shared by classes are declared once
+const defs: Record<string, JSONSchema> = {};
+const properties: Record<string, JSONSchema> = {};
+for (const { id, instance_type, description, config_schema } of classes) {
+    // `true` accepts any config
+    const { $schema, $defs, ...schema }: JSONSchema =
+        typeof config_schema === 'boolean' ? {} : config_schema;
+    Object.assign(defs, $defs);
+    properties[id] = {
+        ...schema,
+        title: typeName(id),
+        description: `${instance_type.toLowerCase()} \`${id}\`${description ? `: ${description}` : ''}`,
+    };
+}
+const root: JSONSchema = {
+    title: 'ClassConfigs',
+    type: 'object',
+    properties,
+    required: Object.keys(properties),
+    additionalProperties: false,
+    $defs: defs,
+};
+const types = await compile(root, 'ClassConfigs', {
+    bannerComment:
+        '// Generated by utils/switchboard-config/scripts/generate-class-types.mts, do not edit.',
+    unreachableDefinitions: true,
+});
+await writeFile(output, types);
+console.log(`${classes.length} class config types written to ${output}`);